@@ -0,0 +1,60 @@
+// Times drawing the DONE panel of long lists through the binary: the cursor walks down the
+// panel with a frame drawn after each step, against loading the same file without keys.
+// Run with `cargo bench`, the time per frame should hardly grow with the list.
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const FRAMES: usize = 200;
+const RUNS: u32 = 5;
+
+fn list(dones: usize) -> String {
+    let mut list = String::from("TODO(*): open\n<--->\n");
+    for i in 0..dones {
+        list.push_str(&format!(
+            "DONE(2024-{:02}-{:02} 10:00 +0000): finished item number {i} +tag\n",
+            i % 12 + 1,
+            i % 28 + 1
+        ));
+    }
+    list
+}
+
+// The mean time of a run of the app on `path` with `keys`.
+fn time(dir: &Path, path: &Path, keys: &str) -> Duration {
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        let status = Command::new(env!("CARGO_BIN_EXE_todors"))
+            .current_dir(dir)
+            .env("TODORS_CONFIG", dir.join("config"))
+            .env("HOME", dir)
+            .args(["-q", "--no-state", "--read-only", "-f"])
+            .arg(path)
+            .args(["--keys", keys])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("todors-bench-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("config"), "").unwrap();
+    let walk = format!("<Tab>{}", "j<Idle>".repeat(FRAMES));
+
+    println!("{:>8} {:>12} {:>12}", "dones", "load", "per frame");
+    for dones in [100, 1_000, 10_000] {
+        let path = dir.join(format!("{dones}.todo"));
+        fs::write(&path, list(dones)).unwrap();
+        let load = time(&dir, &path, "");
+        let walked = time(&dir, &path, &walk);
+        let frame = walked.saturating_sub(load) / FRAMES as u32;
+        println!("{dones:>8} {load:>12.2?} {frame:>12.2?}");
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,65 @@
+// Times inserting at the top of long TODO lists through the binary, where every insert
+// shifts the items after it and copies the list for undo. The new item is cancelled, so
+// the file is left as it was. Past `max_items` (50000 by default) the insert is refused
+// and costs no more than the keys, with the cap raised out of the way it grows with the
+// list. Run with `cargo bench --bench insert`.
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const INSERTS: usize = 20;
+const RUNS: u32 = 3;
+
+fn list(todos: usize) -> String {
+    let mut list = String::new();
+    for i in 0..todos {
+        list.push_str(&format!("TODO(*): open item number {i} +tag\n"));
+    }
+    list.push_str("<--->\n");
+    list
+}
+
+// The mean time of a run of the app on `path` with `keys`.
+fn time(dir: &Path, path: &Path, config: &str, keys: &str) -> Duration {
+    fs::write(dir.join("config"), config).unwrap();
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        let status = Command::new(env!("CARGO_BIN_EXE_todors"))
+            .current_dir(dir)
+            .env("TODORS_CONFIG", dir.join("config"))
+            .env("HOME", dir)
+            .args(["-q", "--no-state", "--no-auto-archive", "-f"])
+            .arg(path)
+            .args(["--keys", keys])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let dir = std::env::temp_dir().join(format!("todors-bench-insert-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("list.todo");
+    let inserts = format!("gg{}", "i<Esc>".repeat(INSERTS));
+    let uncapped = "max_items = 1000000\ninsert_position = top\n";
+    let capped = "insert_position = top\n";
+
+    println!(
+        "{:>8} {:>12} {:>16} {:>16}",
+        "todos", "load", "insert uncapped", "insert capped"
+    );
+    for todos in [1_000, 10_000, 50_000, 100_000] {
+        fs::write(&path, list(todos)).unwrap();
+        let load = time(&dir, &path, capped, "");
+        let per_insert =
+            |config| time(&dir, &path, config, &inserts).saturating_sub(load) / INSERTS as u32;
+        let (free, cap) = (per_insert(uncapped), per_insert(capped));
+        println!("{todos:>8} {load:>12.2?} {free:>16.2?} {cap:>16.2?}");
+    }
+    let _ = fs::remove_dir_all(&dir);
+}
@@ -2,61 +2,157 @@ extern crate regex;
 mod mods;
 
 use chrono::Local;
+use std::cmp::min;
+use std::env;
+use std::fs;
+use std::io::Write;
 use std::path::Path;
+use std::process::exit;
+use std::time::{Duration, Instant};
 
 use ncurses::*;
 
+use mods::backend::*;
+#[cfg(feature = "clipboard")]
+use mods::clipboard;
+use mods::config::*;
+use mods::diff::*;
+use mods::keymap::*;
+use mods::org::Format;
+use mods::query::Query;
+use mods::taskwarrior;
+use mods::text::*;
 use mods::todo::*;
 use mods::ui::*;
 use mods::utils::*;
 
 const TIMEOUT: i32 = 1000; // 1 second
+const CHORD_TIMEOUT: i32 = 1000; // how long a chord waits for its second key
 const FPS: i32 = 30;
 const INDENT_SIZE: usize = 4;
+const DUE_SOON_N: usize = 3;
 
 const SELECTED_PAIR: i16 = 1;
 const UNSELECTED_PAIR: i16 = 2;
 const HIGHLIGHT_PAIR: i16 = 3;
 const UI_PAIR: i16 = 4;
 const HELP_PAIR: i16 = 5;
+const BLOCKED_PAIR: i16 = 6;
+const ADDED_PAIR: i16 = 7;
+const REMOVED_PAIR: i16 = 8;
+// The age gradient takes AGE_STEPS pairs from AGE_PAIR on, fresh to stale.
+const AGE_PAIR: i16 = 9;
+const AGE_STEPS: usize = 6;
 
-const USAGE: &str = "Usage: todors [-f | --file <file>] [-h | --help]";
+const USAGE: &str =
+    "Usage: todors [-f | --file <file>]... [--print-on-exit[=todos|dones|all]] [--keys <script>] [-q | --quiet] [-h | --help] [-v | --version] [--read-only] [--no-state] [--no-auto-archive] [--format todo|org] [--add <text>]... [--migrate] [--export-txt] [add <text> | done <n|text> | list [--done ...] | migrate [--dry-run] | import [--taskwarrior] <file> | export --plain [--width <n>] | stats [--log] | doctor | config init|path|check]";
 const HELP: &str = r#"ToDors - a simple todo list manager in terminal.
 Author: Iurii Kondrakov <deezzir@gmail.com>
 
     Options:
-        -f, --file <file>   The file to use for the todo list.
-        -h, --help          Show this help message.
+        -f, --file <file>                       The file to use for the todo list, a directory to pick one of its files.
+                                                Can be given multiple times to open files as tabs.
+        --print-on-exit[=todos|dones|all]       Print the list to stdout on exit (default: todos).
+        --keys <script>                         Run without a terminal, feeding the keys from <script>
+                                                (special keys as <Enter>, <Esc>, <Tab>, <Up>, ...,
+                                                <C-r> for CTRL+r) and print the final frame.
+        -q, --quiet                             Don't print informational messages, only errors.
+        --read-only                             Open the files without saving them (and without locking them).
+        --no-state                              Don't restore the filter and sorts of the last session, nor keep these.
+        --no-auto-archive                       Don't move the old DONE items to the archive this time.
+        --format todo|org                       Read and write the files in this format instead of the one
+                                                of their extension (.org files are Org-mode ones).
+        --add <text>                            Add an item to the first file and open the app on it, repeat for more.
+                                                Without a terminal, it's the same as 'add <text>'.
+        --migrate                               Upgrade files written in an old format on save
+                                                (the old version is kept as <file>.pre-migrate).
+        --export-txt                            Print the list in the todo.txt format.
+        -h, --help                              Show this help message.
+        -v, --version                           Show the version.
+
+    Commands:
+        add <text>                              Add a new 'Todo' item to the file without opening the UI.
+        done <n|text>                           Complete the n-th 'Todo' item or the first one containing <text>.
+        list                                    Print the 'Todo' items.
+        list --done [--sort date|alpha|recent|pri|due] [--reverse] [--limit <n>]
+                                                Print the 'Done' items, sorted by completion date (oldest
+                                                or most recent first), text, priority or due date, the
+                                                first <n> of them.
+        migrate [--dry-run]                     Upgrade a file written in an old format, or show what would change.
+        import <file>                           Append the items of a todo.txt file.
+        import --taskwarrior [<file>]           Append the tasks of a 'task export' (stdin without <file>),
+                                                skipping the ones already in the list.
+        stats [--log]                           Print the number of items, with --log also the counts
+                                                from the activity log.
+        export --plain [--width <n>]            Print the list wrapped to <n> columns (default: the terminal width).
+        doctor                                  Check the config, the files and the terminal, printing what's wrong.
+        config init|path|check                  Write a commented default config (never overwriting one), print
+                                                its path, or validate it and print the settings in effect.
+
+    Exit codes:
+        0                                       Success.
+        1                                       Item not found or the list is empty.
+        2                                       Usage error.
+        3                                       I/O error.
+        4                                       A 'doctor' or 'config check' check failed.
+        5                                       A file or the config could not be parsed.
+        6                                       A file is opened by another instance.
 
     Controls:
         <k/up>, <j/down>                ~ Move the cursor UP/DOWN
         <K/shift+up>, <J/shift+down>    ~ Drag item UP/DOWN
-        <g>, <G>, <h>                   ~ Jump to the TOP/BOTTOM/HALF of the list
+        <>>, <</shift+tab>              ~ Make current item a subtask of the one above it/Move a subtask out to the level of its parent
+        <g g>, <G>/<g e>, <h>           ~ Jump to the TOP/BOTTOM/HALF of the list
+        <g u>, <g U>                    ~ Change current item to lower/upper case (not the tags)
+        <g f>                           ~ Open the file named in current item in a new tab
         <d>                             ~ Delete 'Done' item/subtask
         <i>                             ~ Insert a new 'Todo' item
         <a>                             ~ Add a subtask to the current 'Todo' item
-        <u>                             ~ Undo last action
+        <u>, <ctrl+r>                   ~ Undo last action/Redo the last undone action
         <r>                             ~ Edit current item
+        <R>                             ~ Edit completion date of current 'Done' item
+        <b>                             ~ Mark current item as blocked by another/Unblock
+        <s>                             ~ Mark current item to swap/Swap it with the marked one
+        <m>, <p>                        ~ Pick items (or a <V> selection)/Move them below the cursor
+        <M>                             ~ Invert the picks: pick the shown items that aren't picked, unpick the others
+        <ctrl+a>, <ctrl+x>              ~ Select every shown item (as with <V>)/Clear the selection and the picks
+        <1>, <2>, <3>                   ~ Jump to the item in the 'Due soon' section
         <t>                             ~ Hide subtasks
+        <F>                             ~ Cycle through the filter presets of the config
+        <C>                             ~ Clear the filter and show the hidden subtasks
         <?>                             ~ Show help
         <space>                         ~ Mark current item as 'Done'
         <enter>                         ~ Transfer item/Save edited item
         <esc>                           ~ Cancel editing/inserting
         <tab>                           ~ Switch between 'Todos'/'Dones'
+        <[>, <]>                        ~ Switch to the PREVIOUS/NEXT file tab
+        <:>                             ~ Enter a command (save, saveas <file>, open <file|dir>, print [todos|dones|all], copy [todos|dones|all], today, sort <order>|off, filter [name], tags, search <words>, retag <from> <to>, dupes, dedupe [<distance>], done-all, undo [<n>|all], swap-panels, group, tag/untag <token>)
+        <ctrl+t>                        ~ Browse the tags ('+project', '@context') with their counts
+        </>                             ~ Search the items of both panels, <enter> jumps to a result
+        <V>                             ~ Select a range to delete/transfer/pick at once, <t> or <:> to tag it
+        <Y>                             ~ Copy the shown list to the clipboard
         <q>, <ctrl+c>                   ~ Quit
 "#;
 
 const FILE_PATH: &str = "TODO.list";
+const SCRIPT_SIZE: Vec2 = Vec2 { x: 80, y: 24 };
 
 #[derive(PartialEq, Clone, Copy)]
 enum Mode {
     Edit,
     Normal,
+    Command,
+    Visual,
 }
 
 enum Display {
     App,
     Help,
+    Tags,
+    Search,
+    Dedupe,
+    Files,
+    Diff,
 }
 
 #[cfg(not(unix))]
@@ -64,161 +160,1838 @@ compile_error! {"Windows platform is not supported."}
 fn main() {
     sig_handler_init();
 
-    let file_path: String = get_args();
-    let file_name: String = Path::new(&file_path)
-        .file_name()
-        .unwrap()
-        .to_str()
-        .unwrap()
-        .to_string();
+    let Args {
+        file_paths,
+        mut print_on_exit,
+        script,
+        command,
+        quiet,
+        migrate,
+        read_only,
+        no_state,
+        no_auto_archive,
+        format,
+        adds,
+        dry_run,
+        log,
+        width,
+    } = get_args().unwrap_or_else(|err| match err {
+        ArgsError::Help => {
+            page(&format!("{HELP}\n{USAGE}\n"));
+            exit(ExitCode::Success as i32);
+        }
+        ArgsError::Version => {
+            println!("todors {}", env!("CARGO_PKG_VERSION"));
+            exit(ExitCode::Success as i32);
+        }
+        ArgsError::Usage(msg) => {
+            eprintln!("[ERROR]: {msg}");
+            eprintln!("{USAGE}");
+            exit(ExitCode::Usage as i32);
+        }
+    });
+
+    if let Some(Command::Doctor) = command {
+        exit(doctor(&file_paths) as i32);
+    }
+    if let Some(Command::Config(command)) = command {
+        exit(config_command(command, quiet) as i32);
+    }
+
+    let config = Config::load();
+    // A directory is a choice of the files in it, made before anything is loaded.
+    let mut ui = None;
+    let mut picked = Vec::new();
+    for path in file_paths {
+        if !Path::new(&path).is_dir() {
+            picked.push(path);
+            continue;
+        }
+        let files = match todo_files(&path) {
+            Ok(files) if files.is_empty() => {
+                eprintln!("[ERROR]: No todo files found in directory '{path}'.");
+                exit(ExitCode::NotFound as i32);
+            }
+            Ok(files) => files,
+            Err(msg) => {
+                eprintln!("[ERROR]: {msg}");
+                exit(ExitCode::Io as i32);
+            }
+        };
+        if command.is_some() {
+            eprintln!("[ERROR]: '{path}' is a directory, pass one of its files to a command.");
+            exit(ExitCode::Usage as i32);
+        }
+        let ui = ui.get_or_insert_with(|| new_ui(&script, &config));
+        let last = if no_state { None } else { last_picked(&path) };
+        let Some(files) = pick_files(ui, &config, &path, &files, last.as_deref()) else {
+            continue;
+        };
+        if let [file] = files.as_slice() {
+            if !no_state && !read_only {
+                if let Err(err) = save_picked(&path, file) {
+                    eprintln!("[WARNING]: Could not keep the file picked in '{path}': {err}");
+                }
+            }
+        }
+        picked.extend(files);
+    }
+    if let Some(ui) = ui.as_mut() {
+        // Left for the messages of the loading, the first refresh of the app comes back.
+        match script {
+            Some(_) if picked.is_empty() => println!("{}", ui.backend().dump()),
+            Some(_) => {}
+            None => drop(endwin()),
+        }
+    }
+    if picked.is_empty() {
+        exit(ExitCode::Success as i32);
+    }
+    let file_paths = picked;
+
+    for path in file_paths.iter() {
+        if let Err(msg) = check_file(path, &config) {
+            eprintln!("[ERROR]: {msg}");
+            exit(ExitCode::Io as i32);
+        }
+    }
+    let writes = command.as_ref().is_none_or(|command| command.writes());
+    let mut tabs: Vec<Tab> = Vec::new();
+    for path in file_paths {
+        let lock = if read_only || !writes {
+            None
+        } else {
+            match FileLock::acquire(&path) {
+                Ok(lock) => Some(lock),
+                Err(LockError::Locked(pid)) => {
+                    eprintln!(
+                        "[ERROR]: {} Pass --read-only to open it without saving.",
+                        locked(&path, pid)
+                    );
+                    exit(ExitCode::Locked as i32);
+                }
+                Err(LockError::Io(err)) => {
+                    eprintln!("[ERROR]: Could not lock '{path}': {err}");
+                    exit(ExitCode::Io as i32);
+                }
+            }
+        };
+        match Tab::new(path, &config, migrate, read_only, format, lock) {
+            Ok(tab) => tabs.push(tab),
+            Err(err) => {
+                // The locks of the tabs opened so far go with them.
+                drop(tabs);
+                eprintln!("[ERROR]: {err}");
+                exit(ExitCode::Parse as i32);
+            }
+        }
+    }
+
+    if let Some(command) = command {
+        let code = run_cli(command, &mut tabs[0], &config, quiet, dry_run, log, width);
+        drop(tabs);
+        exit(code as i32);
+    }
+    // Without a terminal to open the app in, `--add` is the `add` command.
+    if !adds.is_empty() && script.is_none() && !interactive() {
+        for text in adds {
+            let code = run_cli(
+                Command::Add(text),
+                &mut tabs[0],
+                &config,
+                quiet,
+                false,
+                false,
+                None,
+            );
+            if code != ExitCode::Success {
+                exit(code as i32);
+            }
+        }
+        drop(tabs);
+        exit(ExitCode::Success as i32);
+    }
+
+    if !no_auto_archive {
+        for Tab { file_path, app, .. } in tabs.iter_mut() {
+            app.auto_archive(file_path, config.auto_archive_days);
+        }
+    }
+    if !no_state {
+        for Tab { file_path, app, .. } in tabs.iter_mut() {
+            app.load_state(file_path);
+        }
+    }
+    if !adds.is_empty() {
+        if let Err(err) = add_items(&mut tabs[0].app, &adds, &config) {
+            drop(tabs);
+            eprintln!("[ERROR]: {err}");
+            exit(ExitCode::Usage as i32);
+        }
+    }
+    let mut ui = ui.unwrap_or_else(|| new_ui(&script, &config));
+
+    run(&mut ui, &mut tabs, &config, &mut print_on_exit, !no_state);
+
+    match script {
+        Some(_) => println!("{}", ui.backend().dump()),
+        None => drop(endwin()),
+    }
+
+    let mut code = ExitCode::Success;
+    for Tab { file_path, app, .. } in tabs.iter_mut() {
+        if !no_state && !app.is_read_only() {
+            if let Err(err) = app.save_state(file_path) {
+                eprintln!("[WARNING]: Could not keep the filter of '{file_path}': {err}");
+            }
+        }
+        // Printed whether the list gets saved or not, it's what was on the screen.
+        if let Some(kind) = print_on_exit {
+            print!("{}", app.snapshot(kind));
+        }
+        if app.is_read_only() {
+            if !quiet {
+                println!("[INFO]: '{file_path}' was not saved, it is opened read-only.");
+            }
+            continue;
+        }
+        if app.needs_migration() {
+            if !quiet {
+                println!("[INFO]: '{file_path}' was not saved, it uses an old format. Run with --migrate to upgrade it.");
+            }
+            continue;
+        }
+        // Only reached when quitting on a signal, the quit key asks first.
+        if app.external_change(file_path).is_some() {
+            eprintln!("[WARNING]: '{file_path}' was changed on disk, overwriting it.");
+        }
+        if let Err(err) = app.save(file_path) {
+            eprintln!("[ERROR]: Could not save '{file_path}': {err}");
+            code = ExitCode::Io;
+            continue;
+        }
+        if let Some(warning) = app.take_log_warning() {
+            eprintln!("[WARNING]: {warning}");
+        }
+        report_commits(app, quiet);
+
+        if !quiet {
+            println!(
+                "[INFO]: Saved ({})todos and ({})dones to '{file_path}'.",
+                app.get_todos_n(false),
+                app.get_dones_n(false)
+            );
+
+            if cfg!(debug_assertions) {
+                println!("{app:#?}");
+            }
+        }
+    }
+    if !quiet {
+        println!("[INFO]: Bye!");
+    }
+    drop(tabs);
+    exit(code as i32);
+}
+
+// The items of `--add`, added before the app opens with the cursor on the last one. A
+// filter that would hide it is cleared.
+fn add_items(app: &mut TodoApp, adds: &[String], config: &Config) -> Result<(), String> {
+    for text in adds {
+        app.add_todo(text, config.insert_position)?;
+    }
+    let hidden = !app.is_cur_shown();
+    if hidden {
+        app.clear_filter();
+    }
+    app.set_message(&match (adds, hidden) {
+        ([text], false) => format!("Added '{}'.", text.trim()),
+        ([text], true) => format!("Added '{}', the filter hiding it is cleared.", text.trim()),
+        (_, false) => format!("Added {} items.", adds.len()),
+        (_, true) => format!(
+            "Added {} items, the filter hiding them is cleared.",
+            adds.len()
+        ),
+    });
+    Ok(())
+}
+
+fn new_ui(script: &Option<String>, config: &Config) -> UI {
+    let mut ui = match script {
+        Some(script) => UI::new(Box::new(Grid::new(SCRIPT_SIZE, script))),
+        None => {
+            let colors = ncurses_init(config.age_gradient());
+            let mut ui = UI::new(Box::new(Ncurses::default()));
+            ui.set_colors(colors);
+            ui
+        }
+    };
+    ui.set_ellipsis(config.ellipsis);
+    ui.set_rule(match config.rule {
+        rule if rule.is_ascii() || utf8_output() => rule,
+        _ => '-',
+    });
+    ui
+}
+
+// The picker of `-f <dir>`, shown on its own before the app: the file picked, all of
+// them or none.
+fn pick_files(
+    ui: &mut UI,
+    config: &Config,
+    dir: &str,
+    files: &[String],
+    last: Option<&str>,
+) -> Option<Vec<String>> {
+    let mut cur = picked_at(files, last);
+    let mut timeout = 0;
+    while !ctrlc_poll() {
+        if timeout <= 0 {
+            let size = ui.backend().size();
+            ui.backend().clear();
+            ui.begin(Vec2::new(0, 0), LayoutKind::Vert, size);
+            display_files(ui, dir, files, &mut cur, config.selection_style);
+            ui.end();
+            timeout = TIMEOUT;
+        }
+        ui.backend().refresh();
+
+        let key = match ui.backend().poll() {
+            Event::Key(key) => key,
+            Event::Idle => {
+                timeout -= 1000 / FPS;
+                continue;
+            }
+            Event::End => break,
+        };
+        timeout = 0;
+        match pick_file(config.keymap.get(key), &mut cur, files.len()) {
+            Picked::None => {}
+            Picked::One => return Some(vec![files[cur].clone()]),
+            Picked::All => return Some(files.to_vec()),
+            Picked::Cancel => break,
+        }
+    }
+    None
+}
+
+// Where the picker starts: on the file picked last, if it's still there.
+fn picked_at(files: &[String], last: Option<&str>) -> usize {
+    last.and_then(|last| files.iter().position(|file| file_name(file) == last))
+        .unwrap_or(0)
+}
+
+enum Picked {
+    None,
+    One,
+    All,
+    Cancel,
+}
+
+// The keys of the list of files to pick from, for `-f <dir>` and `:open <dir>`.
+fn pick_file(action: Option<KeyAction>, cur: &mut usize, cnt: usize) -> Picked {
+    match action {
+        Some(KeyAction::Up) => *cur = cur.saturating_sub(1),
+        Some(KeyAction::Down) => *cur = min(*cur + 1, cnt.saturating_sub(1)),
+        Some(KeyAction::Top) => *cur = 0,
+        Some(KeyAction::Bottom) => *cur = cnt.saturating_sub(1),
+        Some(KeyAction::Transfer) => return Picked::One,
+        Some(KeyAction::SelectAll) => return Picked::All,
+        Some(KeyAction::Cancel | KeyAction::Quit) => return Picked::Cancel,
+        _ => {}
+    }
+    Picked::None
+}
+
+fn report_commits(app: &mut TodoApp, quiet: bool) {
+    for result in app.wait_commits() {
+        match result {
+            Ok(msg) if !quiet => println!("[INFO]: {msg}"),
+            Ok(_) => {}
+            Err(msg) => eprintln!("[WARNING]: {msg}"),
+        }
+    }
+}
+
+// The startup checks of a file, also run by `doctor`.
+fn check_file(path: &str, config: &Config) -> Result<(), String> {
+    check_dir(path, config.create_dirs).map_err(|msg| format!("Can't save to '{path}': {msg}"))
+}
+
+fn locked(path: &str, pid: i32) -> String {
+    format!("'{path}' is open in another instance (pid {pid}).")
+}
+
+// Runs the checks the startup does and a few more, going on after a failed one, and
+// prints a line for each of them.
+fn doctor(file_paths: &[String]) -> ExitCode {
+    let mut failed = false;
+    let mut report = |result: Result<String, String>| match result {
+        Ok(msg) => println!("[OK]: {msg}"),
+        Err(msg) => {
+            println!("[ERROR]: {msg}");
+            failed = true;
+        }
+    };
+
+    let config = match Config::try_load() {
+        Ok(config) => {
+            report(Ok(match Config::path() {
+                Some(path) if path.exists() => format!("The config '{}' is valid.", path.display()),
+                Some(path) => format!("No config at '{}', using the defaults.", path.display()),
+                None => "No config location ($HOME isn't set), using the defaults.".to_string(),
+            }));
+            for warning in config.warnings.iter() {
+                println!("[WARNING]: {warning}");
+            }
+            config
+        }
+        Err(errors) => {
+            errors.into_iter().for_each(|err| report(Err(err)));
+            Config::default()
+        }
+    };
+
+    // The files of a directory are the ones its picker would show.
+    let mut paths = Vec::new();
+    for path in file_paths {
+        if Path::new(path).is_dir() {
+            match todo_files(path) {
+                Ok(files) if files.is_empty() => {
+                    report(Err(format!("No todo files found in directory '{path}'.")))
+                }
+                Ok(files) => paths.extend(files),
+                Err(msg) => report(Err(msg)),
+            }
+        } else {
+            paths.push(path.clone());
+        }
+    }
+    for path in paths.iter() {
+        let opened = fs::OpenOptions::new().read(true).append(true).open(path);
+        report(match (check_file(path, &config), opened) {
+            (Err(msg), _) => Err(msg),
+            (Ok(()), Ok(_)) => Ok(format!("'{path}' can be read and written.")),
+            (Ok(()), Err(err)) if err.kind() == std::io::ErrorKind::NotFound => Ok(format!(
+                "'{path}' doesn't exist yet, it will be created on save."
+            )),
+            (Ok(()), Err(err)) => Err(format!("Can't open '{path}' for writing: {err}")),
+        });
+
+        let log_path = Path::new(path).with_extension("log");
+        if config.activity_log && log_path.exists() {
+            let opened = fs::OpenOptions::new().append(true).open(&log_path);
+            report(match opened {
+                Ok(_) => Ok(format!(
+                    "The activity log '{}' can be written.",
+                    log_path.display()
+                )),
+                Err(err) => Err(format!(
+                    "Can't write the activity log '{}': {err}",
+                    log_path.display()
+                )),
+            });
+        }
+
+        report(match FileLock::holder(path) {
+            Some(pid) => Err(locked(path, pid)),
+            None => Ok(format!("'{path}' isn't open in another instance.")),
+        });
+    }
+
+    report(if utf8_locale() {
+        Ok("The locale uses UTF-8.".to_string())
+    } else {
+        Err(
+            "The locale isn't UTF-8 (e.g. LANG=en_US.UTF-8), borders and markers won't show right."
+                .to_string(),
+        )
+    });
+    if !cfg!(feature = "wide") {
+        report(Err(
+            "Built without the 'wide' feature (ncursesw), text outside ASCII is drawn as '?'."
+                .to_string(),
+        ));
+    }
+
+    if failed {
+        ExitCode::Check
+    } else {
+        ExitCode::Success
+    }
+}
+
+// `config path` prints where the config is read from, `config init` writes the commented
+// defaults there and `config check` loads it the way the startup does and prints the
+// settings in effect.
+fn config_command(command: ConfigCommand, quiet: bool) -> ExitCode {
+    let path = Config::path();
+    match (command, path) {
+        (ConfigCommand::Check, path) => match Config::try_load() {
+            Ok(config) => {
+                match path {
+                    Some(path) if path.exists() => println!(
+                        "# The config '{}' (from {}):",
+                        path.display(),
+                        Config::path_source()
+                    ),
+                    Some(path) => println!(
+                        "# No config at '{}' (from {}), the defaults:",
+                        path.display(),
+                        Config::path_source()
+                    ),
+                    None => println!("# No config location ($HOME isn't set), the defaults:"),
+                }
+                for warning in config.warnings.iter() {
+                    eprintln!("[WARNING]: {warning}");
+                }
+                print!("{}", config.settings());
+                ExitCode::Success
+            }
+            Err(errors) => {
+                errors
+                    .into_iter()
+                    .for_each(|err| eprintln!("[ERROR]: {err}"));
+                ExitCode::Check
+            }
+        },
+        (_, None) => {
+            eprintln!("[ERROR]: No config location, $HOME isn't set.");
+            ExitCode::Io
+        }
+        (ConfigCommand::Path, Some(path)) => {
+            println!("{}", path.display());
+            ExitCode::Success
+        }
+        (ConfigCommand::Init, Some(path)) => {
+            let written = path
+                .parent()
+                .map_or(Ok(()), fs::create_dir_all)
+                .and_then(|()| {
+                    fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(&path)
+                })
+                .and_then(|mut file| file.write_all(Config::template().as_bytes()));
+            match written {
+                Ok(()) => {
+                    if !quiet {
+                        println!("[INFO]: Wrote the default config to '{}'.", path.display());
+                    }
+                    ExitCode::Success
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {
+                    eprintln!(
+                        "[ERROR]: '{}' already exists, not overwriting it.",
+                        path.display()
+                    );
+                    ExitCode::Io
+                }
+                Err(err) => {
+                    eprintln!("[ERROR]: Could not write '{}': {err}", path.display());
+                    ExitCode::Io
+                }
+            }
+        }
+    }
+}
+
+fn run_cli(
+    command: Command,
+    tab: &mut Tab,
+    config: &Config,
+    quiet: bool,
+    dry_run: bool,
+    log: bool,
+    width: Option<usize>,
+) -> ExitCode {
+    let Tab { file_path, app, .. } = tab;
 
+    if app.needs_migration()
+        && !matches!(
+            command,
+            Command::List
+                | Command::ListDones { .. }
+                | Command::Migrate
+                | Command::ExportTxt
+                | Command::ExportPlain
+                | Command::Stats
+        )
+    {
+        eprintln!("[ERROR]: '{file_path}' uses an old format. Run 'todors migrate' or pass --migrate to upgrade it.");
+        return ExitCode::Usage;
+    }
+
+    let info = match command {
+        Command::Doctor | Command::Config(_) => {
+            unreachable!("doctor and config run before the files are loaded")
+        }
+        Command::Migrate if !app.needs_migration() => {
+            if !quiet {
+                println!("[INFO]: '{file_path}' is up to date.");
+            }
+            return ExitCode::Success;
+        }
+        Command::Migrate if dry_run => {
+            print!("{}", app.migration_diff(file_path));
+            return ExitCode::Success;
+        }
+        Command::Migrate => {
+            app.confirm_migration();
+            format!("Upgraded '{file_path}', the old version is in '{file_path}.pre-migrate'.")
+        }
+        Command::List => {
+            let list = app.snapshot(Snapshot::Todos);
+            if list.is_empty() {
+                return ExitCode::NotFound;
+            }
+            page(&list);
+            return ExitCode::Success;
+        }
+        Command::ListDones {
+            sort,
+            reverse,
+            limit,
+        } => {
+            let list = app.list_dones(sort, reverse, limit);
+            if list.is_empty() {
+                return ExitCode::NotFound;
+            }
+            page(&list);
+            return ExitCode::Success;
+        }
+        Command::ExportPlain => {
+            let width = width.or_else(terminal_width).unwrap_or(80);
+            page(&app.export_plain(width));
+            return ExitCode::Success;
+        }
+        Command::Stats => {
+            println!("Todos: {}", app.get_todos_n(false));
+            println!("Dones: {}", app.get_dones_n(false));
+            if log {
+                match app.log_counts(file_path) {
+                    Ok((added, done, deleted)) => {
+                        println!("Added (log): {added}");
+                        println!("Completed (log): {done}");
+                        println!("Deleted (log): {deleted}");
+                    }
+                    Err(err) => {
+                        eprintln!(
+                            "[ERROR]: Could not read the activity log of '{file_path}': {err}"
+                        );
+                        return ExitCode::Io;
+                    }
+                }
+            }
+            return ExitCode::Success;
+        }
+        Command::ExportTxt => {
+            page(&app.to_todo_txt());
+            return ExitCode::Success;
+        }
+        Command::Add(text) => match app.add_todo(&text, config.insert_position) {
+            Ok(()) => format!("Added '{}'.", text.trim()),
+            Err(err) => {
+                eprintln!("[ERROR]: {err}");
+                return ExitCode::Usage;
+            }
+        },
+        Command::Import(path) => {
+            let content = match fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(err) => {
+                    eprintln!("[ERROR]: Could not read '{path}': {err}");
+                    return ExitCode::Io;
+                }
+            };
+            match app.import_todo_txt(&content) {
+                Ok((todos_n, dones_n)) => {
+                    format!("Imported ({todos_n})todos and ({dones_n})dones from '{path}'.")
+                }
+                Err(err) => {
+                    eprintln!("[ERROR]: Could not import '{path}': {err}");
+                    return ExitCode::Usage;
+                }
+            }
+        }
+        Command::ImportTaskwarrior(path) => {
+            let content = match &path {
+                Some(path) => fs::read_to_string(path),
+                None => std::io::read_to_string(std::io::stdin()),
+            };
+            let name = path.unwrap_or_else(|| "stdin".to_string());
+            let tasks = match content {
+                Ok(content) => match taskwarrior::parse(&content) {
+                    Ok(tasks) => tasks,
+                    Err(err) => {
+                        eprintln!("[ERROR]: Could not read the tasks of '{name}': {err}");
+                        return ExitCode::Parse;
+                    }
+                },
+                Err(err) => {
+                    eprintln!("[ERROR]: Could not read '{name}': {err}");
+                    return ExitCode::Io;
+                }
+            };
+            match app.import_taskwarrior(&tasks) {
+                Ok((todos_n, dones_n, skipped)) => format!(
+                    "Imported ({todos_n})todos and ({dones_n})dones from '{name}', skipped ({skipped}) already in the list."
+                ),
+                Err(err) => {
+                    eprintln!("[ERROR]: Could not import '{name}': {err}");
+                    return ExitCode::Usage;
+                }
+            }
+        }
+        Command::Done(query) => {
+            if !app.find_todo(&query) {
+                eprintln!("[ERROR]: No TODO item matching '{query}'.");
+                return ExitCode::NotFound;
+            }
+            match app.complete_todo(config.view == View::Panels) {
+                Ok(text) => format!("Done: '{text}'."),
+                Err(err) => {
+                    eprintln!("[ERROR]: {err}");
+                    return ExitCode::NotFound;
+                }
+            }
+        }
+    };
+
+    if let Err(err) = app.save(file_path) {
+        eprintln!("[ERROR]: Could not save '{file_path}': {err}");
+        return ExitCode::Io;
+    }
+    if let Some(warning) = app.take_log_warning() {
+        eprintln!("[WARNING]: {warning}");
+    }
+    if let Some(failure) = app.take_hook_failure() {
+        eprintln!("[WARNING]: {failure}.");
+    }
+    report_commits(app, quiet);
+    if !quiet {
+        println!("[INFO]: {info}");
+    }
+    ExitCode::Success
+}
+
+// With `state`, the tabs opened from the app get the filter of their last session.
+fn run(
+    ui: &mut UI,
+    tabs: &mut Vec<Tab>,
+    config: &Config,
+    print_on_exit: &mut Option<Snapshot>,
+    state: bool,
+) {
     let mut editing_cursor: usize = 0;
-    let mut term_size = Vec2::new(0, 0);
+    let mut command = String::new();
     let mut timeout = 0;
+    let mut chord: Option<i32> = None;
+    let mut chord_timeout = 0;
 
     let mut mode: Mode = Mode::Normal;
     let mut disp: Display = Display::App;
-    let mut ui = UI::new();
-
-    let mut app: TodoApp = TodoApp::new();
-    app.parse(&file_path);
+    let mut cur_tab: usize = 0;
+    let mut cur_tag: usize = 0;
+    let mut search = String::new();
+    let mut cur_found: usize = 0;
+    let mut panel_order = config.panel_order;
+    let mut group_dones = config.group_dones;
+    let mut diff: Vec<Diff> = Vec::new();
+    let mut diff_scroll: usize = 0;
+    let mut files_dir = String::new();
+    let mut files: Vec<String> = Vec::new();
+    let mut cur_file: usize = 0;
+    let mut last_autosave = Instant::now();
 
-    ncurses_init();
-
-    while !ctrlc_poll() {
-        getmaxyx(stdscr(), &mut term_size.y, &mut term_size.x);
+    'main: while !ctrlc_poll() {
+        for (i, Tab { app, .. }) in tabs.iter_mut().enumerate() {
+            if let Some(result) = app.poll_commit() {
+                app.set_message(&result.unwrap_or_else(|err| err));
+                timeout = 0;
+            }
+            // The prompt of an edit isn't replaced, the day is checked once it's done.
+            if (i != cur_tab || mode != Mode::Edit) && app.check_day_change() {
+                timeout = 0;
+            }
+        }
+        // Checked once a second at most, and never halfway through an edit.
+        let every = match config.autosave {
+            Autosave::Seconds(secs) => Duration::from_secs(secs),
+            _ => Duration::from_secs(1),
+        };
+        if config.autosave != Autosave::Off
+            && mode != Mode::Edit
+            && last_autosave.elapsed() >= every
+        {
+            last_autosave = Instant::now();
+            for Tab { file_path, app, .. } in tabs.iter_mut() {
+                if autosave(app, file_path, config.autosave) {
+                    timeout = 0;
+                }
+            }
+        }
+        let term_size = ui.backend().size();
+        let Tab { file_name, app, .. } = &mut tabs[cur_tab];
 
         if timeout <= 0 {
-            erase();
+            ui.backend().clear();
             let date = Local::now().format("%Y %a %b %d %H:%M:%S");
 
-            ui.begin(Vec2::new(0, 0), LayoutKind::Vert, term_size);
+            // The hint bar takes the bottom line, the rest of the frame shrinks above it.
+            let mut frame = term_size;
+            if matches!(disp, Display::App) && config.hint_bar && term_size.y > 2 {
+                frame.y -= 1;
+                let hints = match mode {
+                    Mode::Normal if app.is_confirming() => "y:yes  n:no".to_string(),
+                    _ => hint_bar(&config.keymap, key_hints(mode), term_size.x as usize),
+                };
+                ui.begin(
+                    Vec2::new(0, frame.y),
+                    LayoutKind::Vert,
+                    Vec2::new(frame.x, 1),
+                );
+                ui.label_styled(&hints, Style::Ui, None);
+                ui.end();
+            }
+            ui.begin(Vec2::new(0, 0), LayoutKind::Vert, frame);
             {
-                ui.begin_layout(LayoutKind::Horz);
+                ui.begin_split_layout(LayoutKind::Horz, 2);
                 {
                     ui.begin_layout(LayoutKind::Vert);
                     {
-                        ui.label_styled(
-                            &format!(
-                                "[CONTENT]: ({})todos and ({})dones",
-                                app.get_todos_n(false),
-                                app.get_dones_n(false)
-                            ),
-                            UI_PAIR,
-                            Some(A_BOLD()),
-                        );
-                        ui.label_styled(
-                            &format!("[MESSAGE]: {}", app.get_message()),
-                            UI_PAIR,
-                            Some(A_BOLD()),
+                        let estimate = app.total_open_estimate();
+                        let remaining = if estimate > chrono::Duration::zero() {
+                            format!(" ~{} remaining", format_duration(estimate))
+                        } else {
+                            String::new()
+                        };
+                        let content = format!(
+                            "[CONTENT]: {}{remaining}",
+                            content_summary(&config.content_format, app)
                         );
+                        ui.label_styled(&content, Style::Ui, Some(A_BOLD()));
+                        if mode == Mode::Command {
+                            ui.label_styled(
+                                &format!("[COMMAND]: :{command}"),
+                                Style::Ui,
+                                Some(A_BOLD()),
+                            );
+                        } else if let Some(first) = chord {
+                            ui.label_styled(
+                                &format!("[MESSAGE]: {}-", key_name(first)),
+                                Style::Ui,
+                                Some(A_BOLD()),
+                            );
+                        } else if mode == Mode::Visual && app.get_message().is_empty() {
+                            ui.label_styled("[MESSAGE]: -- VISUAL --", Style::Ui, Some(A_BOLD()));
+                        } else if let Some(len) = app
+                            .get_edit_len()
+                            .filter(|&len| len * 5 >= app.get_max_len() * 4)
+                        {
+                            ui.label_styled(
+                                &format!(
+                                    "[MESSAGE]: {} ({len}/{})",
+                                    app.get_message(),
+                                    app.get_max_len()
+                                ),
+                                Style::Ui,
+                                Some(A_BOLD()),
+                            );
+                        } else if config.undo_depth
+                            && app.get_message().is_empty()
+                            && mode != Mode::Edit
+                        {
+                            ui.label_styled(
+                                &format!(
+                                    "[MESSAGE]: undo: {} available / redo: {} available",
+                                    app.undo_depth(),
+                                    app.redo_depth()
+                                ),
+                                Style::Ui,
+                                Some(A_BOLD()),
+                            );
+                        } else {
+                            ui.label_styled(
+                                &format!("[MESSAGE]: {}", app.get_message()),
+                                Style::Ui,
+                                Some(A_BOLD()),
+                            );
+                        }
                     }
                     ui.end_layout();
 
                     ui.begin_layout(LayoutKind::Vert);
                     {
-                        ui.label_styled(&format!("[DATE]: {date}"), UI_PAIR, Some(A_BOLD()));
-                        ui.label_styled(&format!("[FILE]: {file_name}"), UI_PAIR, Some(A_BOLD()));
+                        ui.label_styled(&format!("[DATE]: {date}"), Style::Ui, Some(A_BOLD()));
+                        let dirty = if app.is_dirty() { "*" } else { "" };
+                        let read_only = if app.is_read_only() {
+                            " (read-only)"
+                        } else {
+                            ""
+                        };
+                        ui.label_styled(
+                            &format!("[FILE]: {file_name}{dirty}{read_only}"),
+                            Style::Ui,
+                            Some(A_BOLD()),
+                        );
                     }
                     ui.end_layout();
                 }
                 ui.end_layout();
 
                 ui.hl();
-                ui.br();
+                // A filter is shown in the line under the header, so items it hides aren't
+                // forgotten.
+                let badge = tabs[cur_tab]
+                    .app
+                    .active_filter_summary()
+                    .map_or(String::new(), |summary| format!("[{summary}]"));
+                if tabs.len() > 1 {
+                    display_tabs(ui, tabs, cur_tab, &badge);
+                } else if !badge.is_empty() {
+                    ui.label_styled(&badge, Style::Ui, Some(A_BOLD()));
+                } else {
+                    ui.br();
+                }
 
+                let Tab { file_name, app, .. } = &mut tabs[cur_tab];
                 match disp {
-                    Display::App => display_app(&mut ui, &mut app, mode, editing_cursor),
-                    Display::Help => display_help(&mut ui),
+                    Display::App => display_app(
+                        ui,
+                        app,
+                        config,
+                        panel_order,
+                        group_dones,
+                        mode,
+                        editing_cursor,
+                    ),
+                    Display::Help => display_help(ui, &config.keymap),
+                    Display::Diff => display_diff(ui, file_name, &diff, &mut diff_scroll),
+                    Display::Tags => display_tags(ui, app, &mut cur_tag, config.selection_style),
+                    Display::Search => {
+                        display_search(ui, app, &search, &mut cur_found, config.selection_style)
+                    }
+                    Display::Dedupe => display_dedupe(ui, app),
+                    Display::Files => display_files(
+                        ui,
+                        &files_dir,
+                        &files,
+                        &mut cur_file,
+                        config.selection_style,
+                    ),
                 }
             }
             ui.end();
+            tabs[cur_tab].app.clear_flash();
 
             timeout = TIMEOUT;
         }
 
-        refresh();
+        ui.backend().refresh();
+
+        let mut key = match ui.backend().poll() {
+            Event::Key(key) => key,
+            Event::Idle => {
+                timeout -= 1000 / FPS;
+                if chord.is_some() {
+                    chord_timeout -= 1000 / FPS;
+                    if chord_timeout <= 0 {
+                        chord = None;
+                        timeout = 0;
+                    }
+                }
+                continue;
+            }
+            Event::End => break,
+        };
 
-        let key = getch();
-        if key != ERR {
+        // Keys that are already waiting are all handled before the next render, so
+        // holding down a key doesn't leave the screen lagging behind the cursor.
+        loop {
+            let Tab { file_path, app, .. } = &mut tabs[cur_tab];
+            // A key left out of the help, for bug reports: the frame on screen as text.
+            if key == KEY_F12 {
+                app.set_message(&dump_frame(ui));
+                key = match ui.backend().poll_pending() {
+                    Event::Key(key) => key,
+                    Event::Idle | Event::End => break,
+                };
+                continue;
+            }
+            // A key starting a chord waits for the next one, which either completes the
+            // chord or cancels it (as Esc does). Keys typed as text don't start chords.
+            let chords = match disp {
+                Display::App => matches!(mode, Mode::Normal | Mode::Visual) && !app.is_confirming(),
+                Display::Help | Display::Dedupe => false,
+                Display::Diff | Display::Tags | Display::Search | Display::Files => true,
+            };
+            let action = match chord.take() {
+                Some(first) if chords => config.keymap.chord([first, key]).map(Some),
+                _ if chords && config.keymap.is_prefix(key) => {
+                    chord = Some(key);
+                    chord_timeout = CHORD_TIMEOUT;
+                    None
+                }
+                _ => Some(config.keymap.get(key)),
+            };
+            let Some(action) = action else {
+                key = match ui.backend().poll_pending() {
+                    Event::Key(key) => key,
+                    Event::Idle | Event::End => break,
+                };
+                continue;
+            };
             match disp {
                 Display::App => {
                     match mode {
+                        // Only an answer closes the question, other keys are ignored.
+                        Mode::Normal if app.is_confirming() => match char::from_u32(key as u32) {
+                            Some('y' | 'Y') => app.confirm(true),
+                            Some('n' | 'N' | '\u{1b}') => app.confirm(false),
+                            _ => {}
+                        },
                         Mode::Normal => {
                             app.clear_message();
-                            match char::from_u32(key as u32).unwrap() {
-                                'k' | '\u{103}' => app.go_up(),     // 'k' or 'up'
-                                'j' | '\u{102}' => app.go_down(),   // 'j' or 'down'
-                                'K' | '\u{151}' => app.drag_up(),   // 'K' or 'shift+up'
-                                'J' | '\u{150}' => app.drag_down(), // 'J' or 'shift+down'
-                                'g' => app.go_top(),
-                                'G' => app.go_bottom(),
-                                'h' => app.go_half(),
-                                ' ' => app.mark_item(),
-                                '\n' => app.transfer_item(),
-                                'd' => app.delete_item(),
-                                'u' => app.undo(),
-                                '\t' => app.toggle_panel(),
-                                't' => app.toggle_subtasks(),
-                                '?' => disp = Display::Help,
-                                'i' => {
-                                    if let Some(cur) = app.insert_item() {
+                            match action {
+                                Some(
+                                    KeyAction::Mark
+                                    | KeyAction::Transfer
+                                    | KeyAction::Delete
+                                    | KeyAction::DragUp
+                                    | KeyAction::DragDown
+                                    | KeyAction::Indent
+                                    | KeyAction::Outdent
+                                    | KeyAction::Block
+                                    | KeyAction::Swap
+                                    | KeyAction::Pick
+                                    | KeyAction::Gather
+                                    | KeyAction::Append
+                                    | KeyAction::Edit
+                                    | KeyAction::EditDate
+                                    | KeyAction::Visual,
+                                ) if !app.is_cur_shown() => {
+                                    app.set_message("No item is shown by the filter.")
+                                }
+                                Some(KeyAction::Up) => app.go_up(),
+                                Some(KeyAction::Down) => app.go_down(),
+                                Some(KeyAction::DragUp) => app.drag_up(),
+                                Some(KeyAction::DragDown) => app.drag_down(),
+                                Some(KeyAction::Indent) => app.indent_current(),
+                                Some(KeyAction::Outdent) => app.outdent_current(),
+                                Some(KeyAction::Top) => app.go_top(),
+                                Some(KeyAction::Bottom) => app.go_bottom(),
+                                Some(KeyAction::Half) => app.go_half(),
+                                Some(KeyAction::Mark) => app.mark_item(),
+                                Some(KeyAction::Transfer | KeyAction::TogglePanel)
+                                    if config.view == View::Single =>
+                                {
+                                    app.set_message("Not available in the single list view.")
+                                }
+                                Some(KeyAction::Transfer) => app.transfer_item(),
+                                Some(KeyAction::Delete) => app.delete_item(),
+                                Some(KeyAction::Undo) => app.undo(),
+                                Some(KeyAction::Redo) => app.redo(),
+                                Some(KeyAction::TogglePanel) => app.toggle_panel(),
+                                Some(KeyAction::ToggleSubtasks) => app.toggle_subtasks(),
+                                Some(KeyAction::FilterToday) => app.toggle_today(),
+                                Some(KeyAction::NextFilter) => next_preset(app, &config.filters),
+                                Some(KeyAction::ClearFilters) => app.clear_filters(),
+                                Some(KeyAction::Block) => app.block_item(),
+                                Some(KeyAction::JumpDue(n)) if config.due_soon => {
+                                    app.go_to_due(n, DUE_SOON_N)
+                                }
+                                Some(KeyAction::Swap) => app.swap_item(),
+                                Some(KeyAction::Pick) => app.pick_item(),
+                                Some(KeyAction::Gather) => app.gather_selected_to_cursor(),
+                                Some(KeyAction::InvertPicks) => app.invert_selection(),
+                                Some(KeyAction::SelectAll) => {
+                                    mode = if app.select_all() {
+                                        Mode::Visual
+                                    } else {
+                                        Mode::Normal
+                                    }
+                                }
+                                Some(KeyAction::ClearSelection) => app.clear_selection(),
+                                Some(KeyAction::Lowercase) => app.change_case(false),
+                                Some(KeyAction::Uppercase) => app.change_case(true),
+                                Some(KeyAction::OpenFile) => {
+                                    open_file(tabs, &mut cur_tab, config, state)
+                                }
+                                Some(KeyAction::CopyList) => {
+                                    copy_list(app, default_copy(config.view))
+                                }
+                                Some(KeyAction::Cancel) => {
+                                    app.cancel_block();
+                                    app.cancel_swap();
+                                    app.cancel_picks();
+                                }
+                                Some(KeyAction::Help) => disp = Display::Help,
+                                Some(KeyAction::Tags) => disp = Display::Tags,
+                                Some(KeyAction::Search) => {
+                                    command.push_str("search ");
+                                    mode = Mode::Command;
+                                }
+                                Some(KeyAction::Command) => mode = Mode::Command,
+                                Some(KeyAction::Visual) => {
+                                    app.start_selection();
+                                    mode = Mode::Visual;
+                                }
+                                Some(KeyAction::NextTab) => cur_tab = (cur_tab + 1) % tabs.len(),
+                                Some(KeyAction::PrevTab) => {
+                                    cur_tab = (cur_tab + tabs.len() - 1) % tabs.len()
+                                }
+                                Some(KeyAction::Insert) => {
+                                    if let Some(cur) = app.insert_item(config.insert_position) {
                                         editing_cursor = cur;
                                         mode = Mode::Edit;
                                     }
                                 }
-                                'a' => {
+                                Some(KeyAction::Append) => {
                                     if let Some(cur) = app.append_item() {
                                         editing_cursor = cur;
                                         mode = Mode::Edit;
                                     }
                                 }
-                                'r' => {
-                                    if let Some(cur) = app.edit_item() {
+                                Some(KeyAction::Edit) => {
+                                    if let Some(cur) = app.edit_item(config.edit_start) {
+                                        editing_cursor = cur;
+                                        mode = Mode::Edit;
+                                    }
+                                }
+                                Some(KeyAction::EditDate) => {
+                                    if let Some(cur) = app.edit_date() {
                                         editing_cursor = cur;
                                         mode = Mode::Edit;
                                     }
                                 }
-                                'q' => break,
+                                Some(KeyAction::Quit) => match find_conflict(tabs) {
+                                    Some((tab, lines)) => {
+                                        (cur_tab, diff, diff_scroll) = (tab, lines, 0);
+                                        disp = Display::Diff;
+                                    }
+                                    None => break 'main,
+                                },
                                 _ => {}
                             }
                         }
-                        Mode::Edit => {
-                            match key as u8 as char {
-                                '\n' | '\u{1b}' => {
-                                    // Enter or Esc
-                                    mode = if app.finish_edit() {
-                                        editing_cursor = 0;
+                        Mode::Visual => {
+                            app.clear_message();
+                            match action {
+                                Some(KeyAction::Up) => app.go_up(),
+                                Some(KeyAction::Down) => app.go_down(),
+                                Some(KeyAction::Top) => app.go_top(),
+                                Some(KeyAction::Bottom) => app.go_bottom(),
+                                Some(KeyAction::Half) => app.go_half(),
+                                Some(KeyAction::Delete) => {
+                                    app.delete_selection();
+                                    mode = Mode::Normal;
+                                }
+                                Some(KeyAction::Transfer) if config.view == View::Single => {
+                                    app.cancel_selection();
+                                    app.set_message("Not available in the single list view.");
+                                    mode = Mode::Normal;
+                                }
+                                Some(KeyAction::Transfer) => {
+                                    mode = if app.transfer_selection() {
                                         Mode::Normal
                                     } else {
-                                        Mode::Edit
+                                        Mode::Visual
                                     };
                                 }
-                                _ => app.edit_item_with(&mut editing_cursor, key),
+                                Some(KeyAction::Pick) => {
+                                    app.pick_selection();
+                                    mode = Mode::Normal;
+                                }
+                                Some(KeyAction::SelectAll) => drop(app.select_all()),
+                                Some(KeyAction::ClearSelection) => {
+                                    app.clear_selection();
+                                    mode = Mode::Normal;
+                                }
+                                // The selection stays for a `:tag`/`:untag` command.
+                                Some(KeyAction::Command) => mode = Mode::Command,
+                                Some(KeyAction::ToggleSubtasks) => {
+                                    command.push_str("tag ");
+                                    mode = Mode::Command;
+                                }
+                                Some(KeyAction::Cancel | KeyAction::Visual) => {
+                                    app.cancel_selection();
+                                    mode = Mode::Normal;
+                                }
+                                _ => {}
                             }
                         }
+                        // Only Enter and Esc leave edit mode, every other key (including
+                        // the ones bound to actions in normal mode) goes to the editor.
+                        Mode::Edit => match key {
+                            10 | KEY_ENTER => {
+                                // Enter
+                                mode = if app.finish_edit() {
+                                    editing_cursor = 0;
+                                    Mode::Normal
+                                } else {
+                                    Mode::Edit
+                                };
+                            }
+                            27 => {
+                                // Esc
+                                app.cancel_edit();
+                                editing_cursor = 0;
+                                mode = Mode::Normal;
+                            }
+                            _ => app.edit_item_with(&mut editing_cursor, key),
+                        },
+                        Mode::Command => match key {
+                            10 if command.trim() == "tags" => {
+                                // Enter
+                                disp = Display::Tags;
+                                command.clear();
+                                app.cancel_selection();
+                                mode = Mode::Normal;
+                            }
+                            10 if command.trim() == "swap-panels" => {
+                                // Enter
+                                panel_order = panel_order.reversed();
+                                command.clear();
+                                app.cancel_selection();
+                                mode = Mode::Normal;
+                            }
+                            10 if command.trim() == "group" => {
+                                // Enter
+                                group_dones = !group_dones;
+                                command.clear();
+                                app.cancel_selection();
+                                mode = Mode::Normal;
+                            }
+                            10 if command.split_whitespace().next() == Some("search") => {
+                                // Enter
+                                app.cancel_selection();
+                                mode = Mode::Normal;
+                                let words = command.trim()["search".len()..].trim().to_string();
+                                command.clear();
+                                if words.is_empty() {
+                                    app.set_message("Usage: search <words>.");
+                                } else {
+                                    (search, cur_found) = (words, 0);
+                                    disp = Display::Search;
+                                }
+                            }
+                            10 if command.split_whitespace().next() == Some("dedupe") => {
+                                // Enter
+                                app.cancel_selection();
+                                mode = Mode::Normal;
+                                let distance = match command.trim()["dedupe".len()..].trim() {
+                                    "" => Some(0),
+                                    distance => distance.parse::<usize>().ok(),
+                                };
+                                command.clear();
+                                match distance {
+                                    Some(distance) => {
+                                        if app.start_dedupe(distance) {
+                                            disp = Display::Dedupe;
+                                        }
+                                    }
+                                    None => app.set_message("Usage: dedupe [<distance>]."),
+                                }
+                            }
+                            10 if command.split_whitespace().next() == Some("open") => {
+                                // Enter
+                                app.cancel_selection();
+                                mode = Mode::Normal;
+                                let path = command.trim()["open".len()..].trim().to_string();
+                                command.clear();
+                                if path.is_empty() {
+                                    app.set_message("Usage: open <file|dir>.");
+                                } else if !Path::new(&path).is_dir() {
+                                    open_path(tabs, &mut cur_tab, config, &path, state);
+                                } else {
+                                    match todo_files(&path) {
+                                        Ok(found) if found.is_empty() => app.set_message(&format!(
+                                            "No todo files found in directory '{path}'."
+                                        )),
+                                        Ok(found) => {
+                                            let last = last_picked(&path).filter(|_| state);
+                                            cur_file = picked_at(&found, last.as_deref());
+                                            (files_dir, files) = (path, found);
+                                            disp = Display::Files;
+                                        }
+                                        Err(msg) => app.set_message(&msg),
+                                    }
+                                }
+                            }
+                            10 if command.split_whitespace().next() == Some("saveas") => {
+                                // Enter
+                                app.cancel_selection();
+                                mode = Mode::Normal;
+                                let path = command.trim()["saveas".len()..].trim().to_string();
+                                command.clear();
+                                if path.is_empty() {
+                                    app.set_message("Usage: saveas <file>.");
+                                } else {
+                                    tabs[cur_tab].save_as(&path, config);
+                                }
+                            }
+                            10 => {
+                                // Enter
+                                let save = matches!(command.trim(), "w" | "save");
+                                match conflict(app, file_path).filter(|_| save) {
+                                    Some(lines) => {
+                                        (diff, diff_scroll) = (lines, 0);
+                                        disp = Display::Diff;
+                                    }
+                                    None => {
+                                        run_command(&command, app, file_path, config, print_on_exit)
+                                    }
+                                }
+                                command.clear();
+                                app.cancel_selection();
+                                mode = Mode::Normal;
+                            }
+                            27 => {
+                                // Esc
+                                command.clear();
+                                app.cancel_selection();
+                                mode = Mode::Normal;
+                            }
+                            KEY_BACKSPACE | 127 if command.is_empty() => {
+                                app.cancel_selection();
+                                mode = Mode::Normal;
+                            }
+                            KEY_BACKSPACE | 127 => drop(command.pop()),
+                            key => command.extend(key_char(key)),
+                        },
                     }
                 }
-                Display::Help => match char::from_u32(key as u32).unwrap() {
-                    ' ' => disp = Display::App,
-                    'q' => break,
+                Display::Help => match key {
+                    key if key == ' ' as i32 => disp = Display::App,
+                    key if key == 'q' as i32 => match find_conflict(tabs) {
+                        Some((tab, lines)) => {
+                            (cur_tab, diff, diff_scroll) = (tab, lines, 0);
+                            disp = Display::Diff;
+                        }
+                        None => break 'main,
+                    },
+                    // Resizes, function keys and codes that aren't characters do nothing.
                     _ => {}
                 },
+                Display::Diff => match key {
+                    key if key == 'o' as i32 => {
+                        run_command("w", app, file_path, config, print_on_exit);
+                        disp = Display::App;
+                    }
+                    key if key == 'r' as i32 => {
+                        tabs[cur_tab].reload();
+                        disp = Display::App;
+                    }
+                    key if key == 's' as i32 => {
+                        command = String::from("saveas ");
+                        mode = Mode::Command;
+                        disp = Display::App;
+                    }
+                    _ => match action {
+                        Some(KeyAction::Up) => diff_scroll = diff_scroll.saturating_sub(1),
+                        Some(KeyAction::Down) => diff_scroll += 1,
+                        Some(KeyAction::Top) => diff_scroll = 0,
+                        Some(KeyAction::Bottom) => diff_scroll = diff.len(),
+                        Some(KeyAction::Cancel | KeyAction::Quit) => {
+                            app.set_message("Save cancelled.");
+                            disp = Display::App;
+                        }
+                        _ => {}
+                    },
+                },
+                Display::Tags => {
+                    app.clear_message();
+                    let tag = app.tags().get(cur_tag).map(|(tag, _, _)| tag.clone());
+                    match (action, tag) {
+                        (Some(KeyAction::Up), _) => cur_tag = cur_tag.saturating_sub(1),
+                        (Some(KeyAction::Down), _) => {
+                            cur_tag = min(cur_tag + 1, app.tags().len().saturating_sub(1))
+                        }
+                        (Some(KeyAction::Transfer), Some(tag)) => {
+                            app.filter_tag(&tag);
+                            disp = Display::App;
+                        }
+                        (Some(KeyAction::Delete), Some(tag)) => {
+                            app.remove_tag(&tag);
+                            disp = Display::App;
+                        }
+                        (Some(KeyAction::Edit), Some(tag)) => {
+                            command = format!("retag {tag} ");
+                            mode = Mode::Command;
+                            disp = Display::App;
+                        }
+                        (Some(KeyAction::Cancel | KeyAction::Quit | KeyAction::Tags), _) => {
+                            disp = Display::App
+                        }
+                        _ => {}
+                    }
+                }
+                // The results are looked for again on every frame, so they follow the
+                // changes to the lists.
+                Display::Search => {
+                    app.clear_message();
+                    let found: Vec<bool> =
+                        app.search(&search).iter().map(|&(todo, _)| todo).collect();
+                    match (action, found.get(cur_found)) {
+                        (Some(KeyAction::Up), _) => cur_found = cur_found.saturating_sub(1),
+                        (Some(KeyAction::Down), _) => {
+                            cur_found = min(cur_found + 1, found.len().saturating_sub(1))
+                        }
+                        (Some(KeyAction::Top), _) => cur_found = 0,
+                        (Some(KeyAction::Bottom), _) => cur_found = found.len().saturating_sub(1),
+                        (Some(KeyAction::Transfer), Some(false)) if config.view == View::Single => {
+                            app.set_message("Not available in the single list view.")
+                        }
+                        (Some(KeyAction::Transfer), Some(_)) => {
+                            app.go_to_found(&search, cur_found);
+                            disp = Display::App;
+                        }
+                        (Some(KeyAction::Search), _) => {
+                            command.push_str("search ");
+                            mode = Mode::Command;
+                            disp = Display::App;
+                        }
+                        (Some(KeyAction::Cancel | KeyAction::Quit), _) => disp = Display::App,
+                        _ => {}
+                    }
+                }
+                Display::Files => {
+                    app.clear_message();
+                    let read_only = app.is_read_only();
+                    match pick_file(action, &mut cur_file, files.len()) {
+                        Picked::None => {}
+                        Picked::One => {
+                            let file = files[cur_file].clone();
+                            if state && !read_only {
+                                if let Err(err) = save_picked(&files_dir, &file) {
+                                    app.set_message(&format!(
+                                        "Could not keep the file picked in '{files_dir}': {err}"
+                                    ));
+                                }
+                            }
+                            open_path(tabs, &mut cur_tab, config, &file, state);
+                            disp = Display::App;
+                        }
+                        Picked::All => {
+                            for file in files.iter() {
+                                open_path(tabs, &mut cur_tab, config, file, state);
+                            }
+                            disp = Display::App;
+                        }
+                        Picked::Cancel => disp = Display::App,
+                    }
+                }
+                // Keys of its own, like the confirmations: the choices are shown with them.
+                Display::Dedupe => {
+                    app.clear_message();
+                    let resolve = match char::from_u32(key as u32) {
+                        Some('1') => Some(Resolve::KeepFirst),
+                        Some('2') => Some(Resolve::KeepSecond),
+                        Some('b') => Some(Resolve::KeepBoth),
+                        Some('m') => Some(Resolve::Merge),
+                        _ => None,
+                    };
+                    match resolve {
+                        Some(resolve) => app.resolve_duplicate(resolve),
+                        None if key == 27 || key == 'q' as i32 => app.finish_dedupe(),
+                        None => {}
+                    }
+                    if !app.is_deduping() {
+                        disp = Display::App;
+                    }
+                }
+            }
+            key = match ui.backend().poll_pending() {
+                Event::Key(key) => key,
+                Event::Idle | Event::End => break,
+            };
+        }
+        let app = &mut tabs[cur_tab].app;
+        if let Some(failure) = app.take_hook_failure() {
+            let message = format!("{} {failure}.", app.get_message());
+            app.set_message(message.trim_start());
+        }
+        timeout = 0;
+    }
+
+    // Quitting (CTRL+c) in the middle of an edit keeps the text typed so far, unless it
+    // can't be saved as is: then the edit is cancelled and an inserted item dropped.
+    if mode == Mode::Edit {
+        let app = &mut tabs[cur_tab].app;
+        if !app.finish_edit() {
+            app.cancel_edit();
+        }
+    }
+}
+
+struct Tab {
+    file_path: String,
+    file_name: String,
+    app: TodoApp,
+    // Released when the tab is dropped, before exiting.
+    _lock: Option<FileLock>,
+}
+
+impl Tab {
+    fn new(
+        file_path: String,
+        config: &Config,
+        migrate: bool,
+        read_only: bool,
+        format: Option<Format>,
+        lock: Option<FileLock>,
+    ) -> Result<Self, String> {
+        let mut app = load(&file_path, config, read_only, format)?;
+        if migrate {
+            app.confirm_migration();
+        }
+
+        Ok(Self {
+            file_name: file_name(&file_path),
+            file_path,
+            app,
+            _lock: lock,
+        })
+    }
+
+    // Loads the file again, keeping the unsaved changes on top of the ones made on disk.
+    fn reload(&mut self) {
+        let result = fs::read_to_string(&self.file_path)
+            .map_err(|err| err.to_string())
+            .and_then(|disk| self.app.reload_merged(&self.file_path, disk));
+        if let Err(err) = result {
+            self.app
+                .set_message(&format!("Could not reload '{}': {err}", self.file_path));
+        }
+    }
+
+    // Saves to another file and switches the tab to it, locking it in place of the old one.
+    fn save_as(&mut self, path: &str, config: &Config) {
+        if let Err(msg) = check_dir(path, config.create_dirs) {
+            self.app
+                .set_message(&format!("Can't save to '{path}': {msg}"));
+            return;
+        }
+        let lock = match FileLock::acquire(path) {
+            Ok(lock) => lock,
+            Err(LockError::Locked(pid)) => {
+                self.app.set_message(&format!(
+                    "'{path}' is open in another instance (pid {pid})."
+                ));
+                return;
+            }
+            Err(LockError::Io(err)) => {
+                self.app
+                    .set_message(&format!("Could not lock '{path}': {err}"));
+                return;
+            }
+        };
+        match self.app.save(path) {
+            Ok(()) => {
+                self.app.set_message(&format!("Saved to '{path}'."));
+                self.file_name = file_name(path);
+                self.file_path = path.to_string();
+                self._lock = Some(lock);
+            }
+            Err(err) => self
+                .app
+                .set_message(&format!("Could not save '{path}': {err}")),
+        }
+    }
+}
+
+// Saves a changed list for the autosave, returns whether the screen changed. Lists that
+// can't be saved without asking are left for the exit or :w: read-only ones, those
+// waiting for a migration and those changed on disk since they were loaded.
+fn autosave(app: &mut TodoApp, file_path: &str, autosave: Autosave) -> bool {
+    let due = match autosave {
+        Autosave::Off => false,
+        Autosave::Seconds(_) => app.is_dirty(),
+        Autosave::Changes(n) => app.is_dirty() && app.unsaved_changes() >= n,
+    };
+    if !due
+        || app.is_read_only()
+        || app.needs_migration()
+        || app.external_change(file_path).is_some()
+    {
+        return false;
+    }
+    match app.save(file_path) {
+        Ok(()) => {
+            if let Some(warning) = app.take_log_warning() {
+                app.set_message(&format!("{warning}."));
             }
-            timeout = 0;
-        } else {
-            timeout -= 1000 / FPS;
         }
+        Err(err) => app.set_message(&format!("Could not autosave '{file_path}': {err}")),
     }
+    true
+}
 
-    endwin();
-    app.save(&file_path).unwrap();
+// Opens the first word of the current item naming a file, taken from the working
+// directory or the one of the list, in a new tab or the tab it's already open in.
+fn open_file(tabs: &mut Vec<Tab>, cur_tab: &mut usize, config: &Config, state: bool) {
+    let Tab { file_path, app, .. } = &mut tabs[*cur_tab];
+    let dir = Path::new(file_path.as_str())
+        .parent()
+        .unwrap_or(Path::new(""));
+    let found = app.get_cur_text().and_then(|text| {
+        text.split_whitespace()
+            .map(|word| {
+                word.trim_start_matches(['"', '\'', '(', '<', '['])
+                    .trim_end_matches(['"', '\'', ')', '>', ']', '.', ',', ';', ':', '!', '?'])
+            })
+            .filter(|word| !word.is_empty())
+            .flat_map(|word| [Path::new(word).to_path_buf(), dir.join(word)])
+            .find(|path| path.is_file())
+    });
+    let Some(path) = found.map(|path| path.to_string_lossy().to_string()) else {
+        app.set_message("No file is named in the item.");
+        return;
+    };
+    open_path(tabs, cur_tab, config, &path, state);
+}
 
-    println!("[INFO]: Saved to '{file_path}', Bye!");
+// Opens the file in a new tab, or goes to the tab it's already open in.
+fn open_path(tabs: &mut Vec<Tab>, cur_tab: &mut usize, config: &Config, path: &str, state: bool) {
+    let canonical = fs::canonicalize(path).ok();
+    if let Some(i) = tabs
+        .iter()
+        .position(|tab| fs::canonicalize(&tab.file_path).ok() == canonical)
+    {
+        *cur_tab = i;
+        return;
+    }
+    let app = &mut tabs[*cur_tab].app;
+    if let Err(msg) = check_lines(path) {
+        app.set_message(&format!("Can't open '{path}': {msg}."));
+        return;
+    }
+    let read_only = app.is_read_only();
+    let lock = if read_only {
+        None
+    } else {
+        match FileLock::acquire(path) {
+            Ok(lock) => Some(lock),
+            Err(LockError::Locked(pid)) => {
+                app.set_message(&locked(path, pid));
+                return;
+            }
+            Err(LockError::Io(err)) => {
+                app.set_message(&format!("Could not lock '{path}': {err}"));
+                return;
+            }
+        }
+    };
+    match Tab::new(path.to_string(), config, false, read_only, None, lock) {
+        Ok(tab) => tabs.push(tab),
+        Err(err) => {
+            app.set_message(&format!("Can't open '{path}': {err}."));
+            return;
+        }
+    }
+    *cur_tab = tabs.len() - 1;
+    let app = &mut tabs[*cur_tab].app;
+    app.set_message(&format!("Opened '{path}'."));
+    if state {
+        app.load_state(path);
+    }
+}
 
-    if cfg!(debug_assertions) {
-        println!("{app:#?}");
+// The diff from the file on disk to what saving would write, if the file was changed
+// since it was loaded or saved.
+fn conflict(app: &TodoApp, file_path: &str) -> Option<Vec<Diff>> {
+    if app.is_read_only() || app.needs_migration() {
+        return None;
     }
+    let on_disk = app.external_change(file_path)?;
+    Some(diff_lines(&on_disk, &app.contents()))
+}
+
+// The first tab whose file was changed since it was loaded, checked before quitting.
+fn find_conflict(tabs: &[Tab]) -> Option<(usize, Vec<Diff>)> {
+    tabs.iter()
+        .enumerate()
+        .find_map(|(i, tab)| conflict(&tab.app, &tab.file_path).map(|diff| (i, diff)))
+}
+
+fn load(
+    file_path: &str,
+    config: &Config,
+    read_only: bool,
+    format: Option<Format>,
+) -> Result<TodoApp, String> {
+    let mut app = TodoApp::new();
+    app.set_max_len(config.max_item_len);
+    app.set_max_items(config.max_items);
+    app.set_drag_wrap(config.drag_wrap);
+    app.set_activity_log(config.activity_log);
+    app.set_create_dirs(config.create_dirs);
+    app.set_hooks(config.hooks.clone());
+    app.set_date_format(&config.date_format);
+    app.set_git_commit(Some(config.git_message.clone()).filter(|_| config.git_commit));
+    app.set_read_only(read_only);
+    app.set_format(format.unwrap_or_else(|| Format::of_path(file_path)));
+    app.parse(file_path)?;
+    Ok(app)
+}
+
+fn file_name(file_path: &str) -> String {
+    Path::new(file_path)
+        .file_name()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+fn run_command(
+    command: &str,
+    app: &mut TodoApp,
+    file_path: &str,
+    config: &Config,
+    print_on_exit: &mut Option<Snapshot>,
+) {
+    let mut args = command.split_whitespace();
+
+    match args.next() {
+        Some("w" | "save") => match app.save(file_path) {
+            Ok(()) => match app.take_log_warning() {
+                Some(warning) => app.set_message(&format!("Saved to '{file_path}'. {warning}.")),
+                None => app.set_message(&format!("Saved to '{file_path}'.")),
+            },
+            Err(err) => app.set_message(&format!("Could not save '{file_path}': {err}")),
+        },
+        Some("print") => {
+            let kind = args.next().unwrap_or("todos");
+            match Snapshot::from_str(kind) {
+                Some(kind) => {
+                    *print_on_exit = Some(kind);
+                    app.set_message(&format!("The list will be printed on exit ({kind:?})."));
+                }
+                None => app.set_message(&format!("Unknown snapshot kind: '{kind}'.")),
+            }
+        }
+        Some("copy") => match args
+            .next()
+            .map_or(Some(default_copy(config.view)), Snapshot::from_str)
+        {
+            Some(kind) => copy_list(app, kind),
+            None => app.set_message("Usage: copy [todos|dones|all]."),
+        },
+        Some("today") => app.toggle_today(),
+        Some("sort") => match args.next() {
+            Some("off") => app.sort_panel(None),
+            Some(name) if Sort::from_str(name).is_some() => app.sort_panel(Sort::from_str(name)),
+            _ => app.set_message("Usage: sort date|alpha|recent|pri|due|off."),
+        },
+        Some("filter") => match args.next() {
+            Some("clear") => app.clear_filter(),
+            Some(name) => match config.filters.iter().find(|(n, _)| n == name) {
+                Some((name, query)) => app.filter_preset(name, query),
+                None => app.set_message(&format!("Unknown filter: '{name}'.")),
+            },
+            None => app.clear_filter(),
+        },
+        Some("dupes") => app.pick_duplicates(config.duplicate_policy),
+        Some("done-all") => app.mark_all_shown(),
+        Some("undo") => match (args.next(), args.next()) {
+            (None, _) => app.undo_many(Some(1)),
+            (Some("all"), None) => app.undo_many(None),
+            (Some(n), None) if n.parse::<usize>().is_ok_and(|n| n > 0) => {
+                app.undo_many(n.parse().ok())
+            }
+            _ => app.set_message("Usage: undo [<n>|all]."),
+        },
+        Some("retag") => match (args.next(), args.next()) {
+            (Some(from), Some(to)) => app.rename_tag(from, to),
+            _ => app.set_message("Usage: retag <from> <to>."),
+        },
+        Some(cmd @ ("tag" | "untag")) => match (args.next(), args.next()) {
+            (Some(token), None) => app.tag_selection(token, cmd == "untag"),
+            _ => app.set_message(&format!("Usage: {cmd} <token>.")),
+        },
+        Some(cmd) => app.set_message(&format!("Unknown command: '{cmd}'.")),
+        None => {}
+    }
+}
+
+// The content line from its template. Under a filter the counts are of the shown items
+// out of all of them, the filter itself is named in the badge under the header.
+fn content_summary(template: &str, app: &TodoApp) -> String {
+    let (todos, dones, full) = match app.get_filter() {
+        Some(_) => {
+            let (todos, dones) = app.get_filtered_n();
+            (
+                todos,
+                dones,
+                Some((app.get_todos_n(true), app.get_dones_n(true))),
+            )
+        }
+        None => (app.get_todos_n(false), app.get_dones_n(false), None),
+    };
+    let total = todos + dones;
+    let percent = (dones * 100).checked_div(total).unwrap_or(0);
+    let (todos, dones, total) = match full {
+        Some((all_todos, all_dones)) => (
+            format!("{todos}/{all_todos}"),
+            format!("{dones}/{all_dones}"),
+            format!("{total}/{}", all_todos + all_dones),
+        ),
+        None => (todos.to_string(), dones.to_string(), total.to_string()),
+    };
+    template
+        .replace("{todos}", &todos)
+        .replace("{dones}", &dones)
+        .replace("{total}", &total)
+        .replace("{percent}", &format!("{percent}%"))
+}
+
+// Cycles through the filter presets of the config, then back to no filter.
+fn next_preset(app: &mut TodoApp, presets: &[(String, Query)]) {
+    if presets.is_empty() {
+        app.set_message("No filter presets in the config.");
+        return;
+    }
+    let next = match app.get_filter() {
+        Some(Filter::Preset(name, _)) => presets
+            .iter()
+            .position(|(n, _)| n == name)
+            .map_or(0, |i| i + 1),
+        _ => 0,
+    };
+    match presets.get(next) {
+        Some((name, query)) => app.filter_preset(name, query),
+        None => app.clear_filter(),
+    }
+}
+
+// What is copied without a kind: the single list view only shows the TODO items.
+fn default_copy(view: View) -> Snapshot {
+    match view {
+        View::Single => Snapshot::Todos,
+        View::Panels => Snapshot::All,
+    }
+}
+
+// Copies the items on screen to the clipboard in the `- [ ]` format of `:print`.
+#[cfg(feature = "clipboard")]
+fn copy_list(app: &mut TodoApp, kind: Snapshot) {
+    let (text, cnt) = app.snapshot_shown(kind);
+    if cnt == 0 {
+        app.set_message("Nothing to copy.");
+        return;
+    }
+    match clipboard::copy(&text) {
+        Ok(tool) => app.set_message(&format!("Copied {cnt} line(s) to the clipboard ({tool}).")),
+        Err(err) => app.set_message(&format!("Could not copy: {err}.")),
+    }
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn copy_list(app: &mut TodoApp, _kind: Snapshot) {
+    app.set_message("Copying needs the 'clipboard' feature, todors was built without it.");
 }
 
 fn prefix(subs_hidden: bool, has_children: bool, active: bool) -> &'static str {
@@ -230,124 +2003,567 @@ fn prefix(subs_hidden: bool, has_children: bool, active: bool) -> &'static str {
     }
 }
 
-fn display_app(ui: &mut UI, app: &mut TodoApp, mode: Mode, editing_cursor: usize) {
-    ui.begin_layout(LayoutKind::Horz);
-    {
+// The glyph of the first marker matching the item, padded to the widest one so the
+// checkboxes stay in one column. Empty without any marker configured.
+fn gutter(markers: &[Marker], utf8: bool, text: &str) -> String {
+    let Some(width) = markers.iter().map(|m| display_width(m.glyph(utf8))).max() else {
+        return String::new();
+    };
+    let glyph = markers
+        .iter()
+        .find(|m| m.matches(text))
+        .map_or("", |m| m.glyph(utf8));
+    format!("{} ", pad_to_width(glyph, width))
+}
+
+// Besides the colors, the selection styles mark the selected row and the focused panel
+// with an attribute or an arrow.
+fn selected_label(ui: &mut UI, text: &str, right: &str, style: SelectionStyle) {
+    ui.cursor_here();
+    match style {
+        SelectionStyle::Color => ui.label_split_styled(text, right, Style::Selected, None),
+        SelectionStyle::Reverse => {
+            ui.label_split_styled(text, right, Style::Selected, Some(A_REVERSE()))
+        }
+        SelectionStyle::Arrow => {
+            ui.label_split_styled(&format!("▶ {text}"), right, Style::Selected, None)
+        }
+    }
+}
+
+fn panel_header(ui: &mut UI, name: &str, focused: bool, style: SelectionStyle) {
+    match (style, focused) {
+        (SelectionStyle::Color, true) => {
+            ui.label_styled(&format!("[{name}]"), Style::Highlight, None)
+        }
+        (SelectionStyle::Reverse, true) => {
+            ui.label_styled(&format!("[{name}]"), Style::Highlight, Some(A_REVERSE()))
+        }
+        (SelectionStyle::Arrow, true) => {
+            ui.label_styled(&format!("▶ {name} "), Style::Highlight, None)
+        }
+        (SelectionStyle::Arrow, false) => {
+            ui.label_styled(&format!("  {name} "), Style::Unselected, None)
+        }
+        (_, false) => ui.label_styled(&format!(" {name} "), Style::Unselected, None),
+    }
+}
+
+// The keys worth a reminder in each mode, with a word for what they do there.
+fn key_hints(mode: Mode) -> &'static [(KeyAction, &'static str)] {
+    match mode {
+        Mode::Normal => &[
+            (KeyAction::Insert, "add"),
+            (KeyAction::Mark, "done"),
+            (KeyAction::Delete, "del"),
+            (KeyAction::Search, "search"),
+            (KeyAction::Help, "help"),
+            (KeyAction::Quit, "quit"),
+        ],
+        Mode::Visual => &[
+            (KeyAction::Delete, "del"),
+            (KeyAction::Transfer, "transfer"),
+            (KeyAction::Pick, "pick"),
+            (KeyAction::Cancel, "cancel"),
+        ],
+        Mode::Edit => &[(KeyAction::Transfer, "save"), (KeyAction::Cancel, "cancel")],
+        Mode::Command => &[(KeyAction::Transfer, "run"), (KeyAction::Cancel, "cancel")],
+    }
+}
+
+// The hints as `key:word` with the keys they're bound to, as many whole ones as fit in
+// `width`. Named keys are written in lower case, `enter` rather than `ENTER`.
+fn hint_bar(keymap: &KeyMap, hints: &[(KeyAction, &str)], width: usize) -> String {
+    let mut bar = String::new();
+    for (action, word) in hints {
+        let Some(key) = keymap.key_for(*action) else {
+            continue;
+        };
+        let name = key_name(key);
+        let name = match name.chars().count() {
+            1 => name,
+            _ => name.to_lowercase(),
+        };
+        let hint = format!("{name}:{word}");
+        let sep = if bar.is_empty() { "" } else { "  " };
+        if display_width(&bar) + sep.len() + display_width(&hint) > width {
+            break;
+        }
+        bar.push_str(sep);
+        bar.push_str(&hint);
+    }
+    bar
+}
+
+fn begin_panel(ui: &mut UI, config: &Config) {
+    if config.borders {
+        ui.begin_bordered_layout(LayoutKind::Vert);
+    } else {
         ui.begin_layout(LayoutKind::Vert);
-        {
-            if app.is_in_todos() {
-                ui.label_styled("[TODO]", HIGHLIGHT_PAIR, None);
-            } else {
-                ui.label_styled(" TODO ", UNSELECTED_PAIR, None);
+    }
+}
+
+fn display_app(
+    ui: &mut UI,
+    app: &mut TodoApp,
+    config: &Config,
+    panel_order: PanelOrder,
+    group_dones: bool,
+    mode: Mode,
+    editing_cursor: usize,
+) {
+    let panels = match config.view {
+        View::Panels => 2,
+        View::Single => 1,
+    };
+    ui.begin_split_layout(LayoutKind::Horz, panels);
+    {
+        match (config.view, panel_order) {
+            (View::Single, _) => display_todos(ui, app, config, mode, editing_cursor),
+            (View::Panels, PanelOrder::TodoFirst) => {
+                display_todos(ui, app, config, mode, editing_cursor);
+                display_dones(ui, app, config, group_dones, mode, editing_cursor);
+            }
+            (View::Panels, PanelOrder::DoneFirst) => {
+                display_dones(ui, app, config, group_dones, mode, editing_cursor);
+                display_todos(ui, app, config, mode, editing_cursor);
+            }
+        }
+    }
+    ui.end_layout();
+}
+
+fn display_todos(
+    ui: &mut UI,
+    app: &mut TodoApp,
+    config: &Config,
+    mode: Mode,
+    editing_cursor: usize,
+) {
+    begin_panel(ui, config);
+    {
+        panel_header(ui, "TODO", app.is_in_todos(), config.selection_style);
+        ui.hl();
+
+        let due_soon = app.due_soon(DUE_SOON_N);
+        if config.due_soon && !due_soon.is_empty() {
+            ui.label_styled("Due soon:", Style::Ui, Some(A_BOLD()));
+            for (i, item) in due_soon.iter().enumerate() {
+                let due = item.get_due().unwrap().format("%Y-%m-%d");
+                ui.label(&format!("{}. ({due}) {}", i + 1, item.get_text()));
             }
             ui.hl();
+        }
 
-            for (todo, level) in app.iter_todos() {
-                let indent = " ".repeat(level * INDENT_SIZE);
-                let prefix = prefix(app.is_subs_hidden(), todo.has_children(), todo.is_active());
-                let text = todo.get_text();
-                let todo_disp = format!("{indent}{prefix} {text}",);
+        let height = ui.remaining_height() as usize;
+        let utf8 = utf8_output();
+        app.scroll_todos(height, config.scroll_margin);
+        let mut rows = 0;
+        for (todo, level) in app.visible_todos(height) {
+            if todo.has_gap() && rows > 0 {
+                ui.br();
+                rows += 1;
+            }
+            if rows >= height {
+                break;
+            }
+            rows += 1;
 
-                if app.is_cur_todo(todo) {
-                    if app.is_in_todos() {
-                        if mode == Mode::Edit {
-                            ui.edit_label(text, editing_cursor, format!("{indent}{prefix} "));
-                        } else {
-                            ui.label_styled(&todo_disp, SELECTED_PAIR, None);
-                        }
+            let indent = " ".repeat(level * INDENT_SIZE);
+            let prefix = prefix(app.is_subs_hidden(), todo.has_children(), todo.is_active());
+            let blocked = app.is_blocked(todo);
+            let marker = if blocked { "⊘ " } else { "" };
+            let text = todo.get_text();
+            let gutter = gutter(&config.markers, utf8, text);
+            let todo_disp = format!("{gutter}{indent}{prefix} {marker}{text}",);
+            // The estimate and the subtasks done out of all, updated as they are marked.
+            let estimate = todo
+                .get_estimate()
+                .map(|est| format!("~{}", format_duration(est)));
+            let subtasks = app
+                .progress(todo)
+                .map(|(done, all)| format!("({done}/{all})"));
+            let progress = [estimate, subtasks]
+                .into_iter()
+                .flatten()
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            if config.completion_flash && app.is_flashing(todo) {
+                ui.label_split_styled(
+                    &format!("{todo_disp} ✓"),
+                    &progress,
+                    Style::Highlight,
+                    Some(A_BOLD()),
+                );
+            } else if app.is_cur_todo(todo) {
+                if app.is_in_todos() {
+                    // The text about to be replaced is shown selected.
+                    if mode == Mode::Edit && app.is_replacing() {
+                        selected_label(ui, &todo_disp, "", SelectionStyle::Reverse);
+                    } else if mode == Mode::Edit {
+                        ui.edit_label(
+                            text,
+                            editing_cursor,
+                            format!("{gutter}{indent}{prefix} {marker}"),
+                            config.cursor_style,
+                            config.cursor_blink,
+                        );
                     } else {
-                        ui.label_styled(&todo_disp, UNSELECTED_PAIR, None);
+                        selected_label(ui, &todo_disp, &progress, config.selection_style);
                     }
                 } else {
-                    ui.label(&todo_disp);
+                    ui.label_split_styled(&todo_disp, &progress, Style::Unselected, None);
                 }
+            } else if app.is_selected(todo)
+                || app.is_blocking(todo)
+                || app.is_swapping(todo)
+                || app.is_picked(todo)
+            {
+                ui.label_split_styled(&todo_disp, &progress, Style::Highlight, None);
+            } else if blocked {
+                ui.label_split_styled(&todo_disp, &progress, Style::Blocked, Some(A_DIM()));
+            } else if let Some(style) = age_style(config, todo) {
+                ui.label_split_styled(&todo_disp, &progress, style, None);
+            } else {
+                ui.label_split(&todo_disp, &progress);
             }
         }
-        ui.end_layout();
+    }
+    ui.end_layout();
+}
 
-        ui.begin_layout(LayoutKind::Vert);
-        {
-            if app.is_in_dones() {
-                ui.label_styled("[DONE]", HIGHLIGHT_PAIR, None);
-            } else {
-                ui.label_styled(" DONE ", UNSELECTED_PAIR, None);
+// The step of the age gradient an item is drawn in, from its created: date. Items older
+// than age_days have the stale color.
+fn age_style(config: &Config, item: &Item) -> Option<Style> {
+    config.age_colors.as_ref()?;
+    let age = (Local::now().date_naive() - item.get_created()?).num_days();
+    let age = (age.max(0) as usize).min(config.age_days);
+    Some(Style::Age(age * (AGE_STEPS - 1) / config.age_days))
+}
+
+fn display_dones(
+    ui: &mut UI,
+    app: &mut TodoApp,
+    config: &Config,
+    group_dones: bool,
+    mode: Mode,
+    editing_cursor: usize,
+) {
+    begin_panel(ui, config);
+    {
+        panel_header(ui, "DONE", app.is_in_dones(), config.selection_style);
+        ui.hl();
+
+        let height = ui.remaining_height() as usize;
+        let utf8 = utf8_output();
+        // Scrolling leaves room for the group headers so the cursor stays in view.
+        let headers = if group_dones { 4 } else { 0 };
+        app.scroll_dones(height.saturating_sub(headers), config.scroll_margin);
+        let mut group = None;
+        let mut rows = 0;
+        for (done, level) in app.visible_dones(height) {
+            let date_group = done.is_root().then(|| done.get_date_group());
+            if let Some(date_group) = date_group.filter(|&g| group_dones && group != Some(g)) {
+                // The header is only worth a row if an item fits below it.
+                if rows + 1 >= height {
+                    break;
+                }
+                ui.label_styled(
+                    &format!("{}:", date_group.name()),
+                    Style::Ui,
+                    Some(A_BOLD()),
+                );
+                group = Some(date_group);
+                rows += 1;
+            } else if done.has_gap() && rows > 0 {
+                ui.br();
+                rows += 1;
             }
-            ui.hl();
+            if rows >= height {
+                break;
+            }
+            rows += 1;
 
-            for (done, level) in app.iter_dones() {
-                let indent = " ".repeat(level * INDENT_SIZE);
-                let prefix = prefix(app.is_subs_hidden(), done.has_children(), done.is_active());
-                let text = done.get_text();
-                let date = if !done.is_root() {
-                    String::new()
-                } else {
-                    format!("({})", done.get_date())
-                };
-                let done_disp = format!("{indent}{prefix}{date} {text}",);
+            let indent = " ".repeat(level * INDENT_SIZE);
+            let prefix = prefix(app.is_subs_hidden(), done.has_children(), done.is_active());
+            let text = done.get_text();
+            let gutter = gutter(&config.markers, utf8, text);
+            let date = if !done.is_root() {
+                String::new()
+            } else {
+                format!("({})", done.get_date())
+            };
+            let done_disp = format!("{gutter}{indent}{prefix}{date} {text}",);
 
-                if app.is_cur_done(done) {
-                    if app.is_in_dones() {
-                        if mode == Mode::Edit {
-                            ui.edit_label(text, editing_cursor, format!("{indent}{prefix} "));
-                        } else {
-                            ui.label_styled(&done_disp, SELECTED_PAIR, None);
+            if config.completion_flash && app.is_flashing(done) {
+                ui.label_styled(&format!("{done_disp} ✓"), Style::Highlight, Some(A_BOLD()));
+            } else if app.is_cur_done(done) {
+                if app.is_in_dones() {
+                    if mode == Mode::Edit && app.is_replacing() {
+                        selected_label(ui, &done_disp, "", SelectionStyle::Reverse);
+                    } else if mode == Mode::Edit {
+                        match app.get_date_input() {
+                            Some(date) => ui.edit_label_styled(
+                                date,
+                                editing_cursor,
+                                format!("{gutter}{indent}{prefix}[DATE]: "),
+                                Some(if app.is_date_input_valid() {
+                                    Style::Valid
+                                } else {
+                                    Style::Invalid
+                                }),
+                                config.cursor_style,
+                                config.cursor_blink,
+                            ),
+                            None => ui.edit_label(
+                                text,
+                                editing_cursor,
+                                format!("{gutter}{indent}{prefix} "),
+                                config.cursor_style,
+                                config.cursor_blink,
+                            ),
                         }
                     } else {
-                        ui.label_styled(&done_disp, UNSELECTED_PAIR, None);
+                        selected_label(ui, &done_disp, "", config.selection_style);
                     }
                 } else {
-                    ui.label(&done_disp);
+                    ui.label_styled(&done_disp, Style::Unselected, None);
                 }
+            } else if app.is_selected(done) || app.is_swapping(done) || app.is_picked(done) {
+                ui.label_styled(&done_disp, Style::Highlight, None);
+            } else {
+                ui.label(&done_disp);
             }
         }
-        ui.end_layout();
     }
     ui.end_layout();
 }
 
-fn display_help(ui: &mut UI) {
-    ui.label_styled("CONTROLS", UNSELECTED_PAIR, None);
+fn display_tabs(ui: &mut UI, tabs: &[Tab], cur_tab: usize, badge: &str) {
+    let tab_bar = tabs
+        .iter()
+        .enumerate()
+        .map(|(i, tab)| {
+            if i == cur_tab {
+                format!("[{}]", tab.file_name)
+            } else {
+                format!(" {} ", tab.file_name)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ");
+
+    ui.label_split_styled(
+        &format!("[TABS]: {tab_bar}"),
+        badge,
+        Style::Ui,
+        Some(A_BOLD()),
+    );
+    ui.br();
+}
+
+fn display_tags(
+    ui: &mut UI,
+    app: &mut TodoApp,
+    cur_tag: &mut usize,
+    selection_style: SelectionStyle,
+) {
+    ui.label_styled("TAGS", Style::Unselected, None);
+    ui.hl();
+
+    let tags = app.tags();
+    if tags.is_empty() {
+        ui.label("No tags found. Tags are words starting with '+' or '@'.");
+    } else {
+        *cur_tag = min(*cur_tag, tags.len() - 1);
+        let height = (ui.remaining_height() as usize).saturating_sub(3).max(1);
+        let skip = (*cur_tag + 1).saturating_sub(height);
+        let width = tags
+            .iter()
+            .map(|(tag, _, _)| display_width(tag))
+            .max()
+            .unwrap_or(0);
+
+        for (i, (tag, open, done)) in tags.iter().enumerate().skip(skip).take(height) {
+            let line = format!("{}  ({open})open ({done})done", pad_to_width(tag, width));
+            if i == *cur_tag {
+                selected_label(ui, &line, "", selection_style);
+            } else {
+                ui.label(&line);
+            }
+        }
+    }
+
+    ui.br();
+    ui.hl();
+    ui.label("ENTER to filter by the tag, d to remove it, r to rename it, ESC to go back...");
+}
+
+// Writes the frame on screen as plain text to a file in the temporary directory, to be
+// attached to a bug report in place of a screenshot.
+fn dump_frame(ui: &mut UI) -> String {
+    let size = ui.backend().size();
+    let path = env::temp_dir().join(format!(
+        "todors-frame-{}.txt",
+        Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let frame = format!(
+        "todors {} {}x{}\n{}\n",
+        env!("CARGO_PKG_VERSION"),
+        size.x,
+        size.y,
+        ui.backend().dump()
+    );
+    match fs::write(&path, frame) {
+        Ok(()) => format!("Frame written to '{}'.", path.display()),
+        Err(err) => format!("Could not write the frame to '{}': {err}", path.display()),
+    }
+}
+
+fn display_search(
+    ui: &mut UI,
+    app: &TodoApp,
+    search: &str,
+    cur_found: &mut usize,
+    selection_style: SelectionStyle,
+) {
+    let found = app.search(search);
+    ui.label_split_styled(
+        &format!("SEARCH: {search}"),
+        &format!("({}) found", found.len()),
+        Style::Unselected,
+        None,
+    );
+    ui.hl();
+
+    if found.is_empty() {
+        ui.label(&format!("No item has '{search}' in it."));
+    } else {
+        *cur_found = min(*cur_found, found.len() - 1);
+        let height = (ui.remaining_height() as usize).saturating_sub(3).max(1);
+        let skip = (*cur_found + 1).saturating_sub(height);
+
+        for (i, (todo, item)) in found.iter().enumerate().skip(skip).take(height) {
+            let line = if *todo {
+                format!("[T] {}", item.get_text())
+            } else {
+                format!("[D] ({}) {}", item.get_date(), item.get_text())
+            };
+            if i == *cur_found {
+                selected_label(ui, &line, "", selection_style);
+            } else {
+                ui.label(&line);
+            }
+        }
+    }
+
+    ui.br();
+    ui.hl();
+    ui.label("ENTER to jump to the item, / to search again, ESC to go back...");
+}
+
+fn display_files(
+    ui: &mut UI,
+    dir: &str,
+    files: &[String],
+    cur: &mut usize,
+    selection_style: SelectionStyle,
+) {
+    ui.label_split_styled(
+        &format!("OPEN A FILE OF: {dir}"),
+        &format!("({}) files", files.len()),
+        Style::Unselected,
+        None,
+    );
+    ui.hl();
+
+    *cur = min(*cur, files.len().saturating_sub(1));
+    let height = (ui.remaining_height() as usize).saturating_sub(3).max(1);
+    let skip = (*cur + 1).saturating_sub(height);
+    for (i, file) in files.iter().enumerate().skip(skip).take(height) {
+        if i == *cur {
+            selected_label(ui, &file_name(file), "", selection_style);
+        } else {
+            ui.label(&file_name(file));
+        }
+    }
+
+    ui.br();
+    ui.hl();
+    ui.label("ENTER to open the file, CTRL+a to open them all in tabs, ESC to go back...");
+}
+
+fn display_dedupe(ui: &mut UI, app: &TodoApp) {
+    let Some((first, second, n, cnt)) = app.dedupe_pair() else {
+        return;
+    };
+    ui.label_split_styled(
+        "DUPLICATES",
+        &format!("({n}/{cnt})"),
+        Style::Unselected,
+        None,
+    );
+    ui.hl();
+    ui.label_styled(&format!("1: {first}"), Style::Help, None);
+    ui.label_styled(&format!("2: {second}"), Style::Help, None);
+    ui.br();
+    ui.hl();
+    ui.label("(1) keep the first, (2) keep the second, (b) keep both, (m) merge its tags into the first, ESC to stop...");
+}
+
+// The changes saving would make to a file that was changed since it was loaded. The scroll
+// offset is clamped here, where the height is known.
+fn display_diff(ui: &mut UI, file_name: &str, diff: &[Diff], scroll: &mut usize) {
+    ui.label_styled(
+        &format!("'{file_name}' WAS CHANGED ON DISK"),
+        Style::Unselected,
+        None,
+    );
+    ui.label("(o)verwrite it, (r)eload it merging your changes in, (s)ave as another file, <esc> to cancel.");
+    ui.hl();
+
+    let height = ui.remaining_height() as usize;
+    *scroll = min(*scroll, diff.len().saturating_sub(height));
+    for line in diff.iter().skip(*scroll).take(height) {
+        match line {
+            Diff::Same(text) => ui.label(&format!("  {text}")),
+            Diff::Removed(text) => ui.label_styled(&format!("- {text}"), Style::Removed, None),
+            Diff::Added(text) => ui.label_styled(&format!("+ {text}"), Style::Added, None),
+        }
+    }
+}
+
+fn display_help(ui: &mut UI, keymap: &KeyMap) {
+    let controls = keymap.describe();
+
+    ui.label_styled("CONTROLS", Style::Unselected, None);
     ui.hl();
 
     ui.begin_layout(LayoutKind::Horz);
     {
         ui.begin_layout(LayoutKind::Vert);
         {
-            ui.label_styled("k/↑, j/↓", HELP_PAIR, None);
-            ui.label("K/SHIFT+↑, J/SHIFT+↓");
-            ui.label_styled("g, G, h", HELP_PAIR, None);
-            ui.label("d");
-            ui.label_styled("i", HELP_PAIR, None);
-            ui.label("a");
-            ui.label_styled("u", HELP_PAIR, None);
-            ui.label("r");
-            ui.label_styled("t", HELP_PAIR, None);
-            ui.label("?");
-            ui.label_styled("SPACE", HELP_PAIR, None);
-            ui.label("ENTER");
-            ui.label_styled("ESC", HELP_PAIR, None);
-            ui.label("TAB");
-            ui.label_styled("q/CTRL+c", HELP_PAIR, None);
+            for (i, (_, keys)) in controls.iter().enumerate() {
+                let keys = keys.join(", ");
+                if i % 2 == 0 {
+                    ui.label_styled(&keys, Style::Help, None);
+                } else {
+                    ui.label(&keys);
+                }
+            }
         }
         ui.end_layout();
 
         ui.begin_layout(LayoutKind::Vert);
         {
-            ui.label_styled("Move the cursor UP/DOWN", HELP_PAIR, None);
-            ui.label("Drag item UP/DOWN");
-            ui.label_styled("Jump to the TOP/BOTTOM/HALF of the list", HELP_PAIR, None);
-            ui.label("Delete 'Done' item/subtask");
-            ui.label_styled("Insert a new 'Todo' item", HELP_PAIR, None);
-            ui.label("Add a subtask to the current 'Todo' item");
-            ui.label_styled("Undo last action", HELP_PAIR, None);
-            ui.label("Edit current item");
-            ui.label_styled("Hide subtasks", HELP_PAIR, None);
-            ui.label("Show this help");
-            ui.label_styled("Mark current item as 'Done'", HELP_PAIR, None);
-            ui.label("Transfer item/Save edited item");
-            ui.label_styled("Cancel editing/inserting", HELP_PAIR, None);
-            ui.label("Switch between 'Todos'/'Dones'");
-            ui.label_styled("Quit", HELP_PAIR, None);
+            for (i, (desc, _)) in controls.iter().enumerate() {
+                if i % 2 == 0 {
+                    ui.label_styled(desc, Style::Help, None);
+                } else {
+                    ui.label(desc);
+                }
+            }
         }
         ui.end_layout();
     }
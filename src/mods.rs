@@ -1,3 +1,15 @@
+pub mod backend;
+#[cfg(feature = "clipboard")]
+pub mod clipboard;
+pub mod color;
+pub mod config;
+pub mod diff;
+pub mod git;
+pub mod keymap;
+pub mod org;
+pub mod query;
+pub mod taskwarrior;
+pub mod text;
 pub mod todo;
 pub mod ui;
 pub mod utils;
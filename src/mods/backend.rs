@@ -0,0 +1,387 @@
+use ncurses::*;
+
+use super::text::char_width;
+use super::ui::Vec2;
+use crate::FPS;
+
+// Stands for CTRL+c in a script, it raises SIGINT instead of being read as a key.
+const SIGINT_KEY: i32 = -2;
+// Stands for a pause in a script: the keys before it are handled and the frame is drawn
+// before the next one is read.
+const IDLE_KEY: i32 = -3;
+// The cell right of a wide character in a Grid, covered by it.
+const WIDE_TAIL: char = '\0';
+// Characters past ASCII are read as keys above every ncurses KEY_* code, which they would
+// collide with otherwise.
+const CHAR_KEY: i32 = 0x20_0000;
+
+// The key a character typed as text is read as.
+pub fn char_key(ch: char) -> i32 {
+    if ch.is_ascii() {
+        ch as i32
+    } else {
+        CHAR_KEY + ch as i32
+    }
+}
+
+// The character a key types into the text being edited, if any. Past ASCII only with
+// ncursesw, plain ncurses couldn't draw it.
+pub fn key_char(key: i32) -> Option<char> {
+    match key {
+        32..=126 => Some(key as u8 as char),
+        _ if cfg!(feature = "wide") && key > CHAR_KEY => {
+            char::from_u32((key - CHAR_KEY) as u32).filter(|ch| !ch.is_control())
+        }
+        _ => None,
+    }
+}
+
+pub enum Event {
+    Key(i32),
+    Idle,
+    End,
+}
+
+pub trait Backend {
+    fn size(&self) -> Vec2;
+    fn clear(&mut self);
+    fn put(&mut self, pos: Vec2, text: &str);
+    fn attr_on(&mut self, attr: attr_t);
+    fn attr_off(&mut self, attr: attr_t);
+    fn refresh(&mut self);
+    // Leaves the terminal's cursor at `pos`, where screen readers follow it, shown or not.
+    fn set_cursor(&mut self, pos: Vec2, visibility: CURSOR_VISIBILITY);
+    fn poll(&mut self) -> Event;
+    // Like poll(), but doesn't wait for a key that isn't there yet.
+    fn poll_pending(&mut self) -> Event;
+    // The text of the frame drawn last, row by row, without the attributes.
+    fn dump(&self) -> String;
+}
+
+// The characters of a frame, kept to be dumped as text.
+struct Cells(Vec<Vec<char>>);
+
+impl Cells {
+    fn new(size: Vec2) -> Self {
+        Self(vec![
+            vec![' '; size.x.max(0) as usize];
+            size.y.max(0) as usize
+        ])
+    }
+
+    fn clear(&mut self) {
+        for row in self.0.iter_mut() {
+            row.fill(' ');
+        }
+    }
+
+    // Like a terminal, a wide character takes two cells and one that doesn't fit at the
+    // end of the row is left out. Writing over half of a wide character blanks the other
+    // half. Combining and zero-width characters are dropped.
+    fn put(&mut self, pos: Vec2, text: &str) {
+        let Some(row) = self.0.get_mut(pos.y as usize) else {
+            return;
+        };
+        let mut x = pos.x.max(0) as usize;
+        for ch in text.chars() {
+            let width = char_width(ch);
+            if width == 0 {
+                continue;
+            }
+            if x + width > row.len() {
+                break;
+            }
+            if row[x] == WIDE_TAIL && x > 0 {
+                row[x - 1] = ' ';
+            }
+            if row.get(x + width) == Some(&WIDE_TAIL) {
+                row[x + width] = ' ';
+            }
+            row[x] = ch;
+            if width == 2 {
+                row[x + 1] = WIDE_TAIL;
+            }
+            x += width;
+        }
+    }
+
+    fn dump(&self) -> String {
+        self.0
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .filter(|&&ch| ch != WIDE_TAIL)
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+// The terminal, with a copy of what is drawn on it for dump().
+pub struct Ncurses {
+    cells: Cells,
+}
+
+impl Default for Ncurses {
+    fn default() -> Self {
+        let mut ncurses = Self {
+            cells: Cells::new(Vec2::default()),
+        };
+        ncurses.cells = Cells::new(ncurses.size());
+        ncurses
+    }
+}
+
+impl Backend for Ncurses {
+    fn size(&self) -> Vec2 {
+        let mut size = Vec2::default();
+        getmaxyx(stdscr(), &mut size.y, &mut size.x);
+        size
+    }
+
+    // The copy follows the terminal when it's resized.
+    fn clear(&mut self) {
+        erase();
+        let size = self.size();
+        let rows = self.cells.0.len() as i32;
+        let cols = self.cells.0.first().map_or(0, |row| row.len()) as i32;
+        if (cols, rows) == (size.x, size.y) {
+            self.cells.clear();
+        } else {
+            self.cells = Cells::new(size);
+        }
+    }
+
+    fn put(&mut self, pos: Vec2, text: &str) {
+        self.cells.put(pos, text);
+        mv(pos.y, pos.x);
+        if cfg!(feature = "wide") {
+            addstr(text);
+            return;
+        }
+        // Plain ncurses takes every byte for a character, the rest is drawn as `?` over
+        // the columns it would take.
+        let text: String = text
+            .chars()
+            .flat_map(|c| {
+                if c.is_ascii() {
+                    vec![c]
+                } else {
+                    vec!['?'; char_width(c)]
+                }
+            })
+            .collect();
+        addstr(&text);
+    }
+
+    fn attr_on(&mut self, attr: attr_t) {
+        attr_on(attr);
+    }
+
+    fn attr_off(&mut self, attr: attr_t) {
+        attr_off(attr);
+    }
+
+    fn refresh(&mut self) {
+        refresh();
+    }
+
+    fn set_cursor(&mut self, pos: Vec2, visibility: CURSOR_VISIBILITY) {
+        mv(pos.y, pos.x);
+        curs_set(visibility);
+    }
+
+    #[cfg(feature = "wide")]
+    fn poll(&mut self) -> Event {
+        match get_wch() {
+            None => Event::Idle,
+            Some(WchResult::KeyCode(key)) => Event::Key(key),
+            Some(WchResult::Char(ch)) => Event::Key(char::from_u32(ch).map_or(ERR, char_key)),
+        }
+    }
+
+    // Plain ncurses reads text byte by byte, only ASCII gets through as characters.
+    #[cfg(not(feature = "wide"))]
+    fn poll(&mut self) -> Event {
+        match getch() {
+            ERR => Event::Idle,
+            key => Event::Key(key),
+        }
+    }
+
+    fn poll_pending(&mut self) -> Event {
+        timeout(0);
+        let event = self.poll();
+        timeout(1000 / FPS);
+        event
+    }
+
+    fn dump(&self) -> String {
+        self.cells.dump()
+    }
+}
+
+// Renders into an in-memory grid of characters and reads keys from a script,
+// so the app can be driven without a terminal.
+pub struct Grid {
+    size: Vec2,
+    cells: Cells,
+    keys: Vec<i32>,
+}
+
+impl Grid {
+    pub fn new(size: Vec2, script: &str) -> Self {
+        Self {
+            size,
+            cells: Cells::new(size),
+            keys: parse_keys(script).into_iter().rev().collect(),
+        }
+    }
+}
+
+impl Backend for Grid {
+    fn size(&self) -> Vec2 {
+        self.size
+    }
+
+    fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    fn put(&mut self, pos: Vec2, text: &str) {
+        self.cells.put(pos, text);
+    }
+
+    fn attr_on(&mut self, _attr: attr_t) {}
+
+    fn attr_off(&mut self, _attr: attr_t) {}
+
+    fn refresh(&mut self) {}
+
+    fn set_cursor(&mut self, _pos: Vec2, _visibility: CURSOR_VISIBILITY) {}
+
+    fn poll(&mut self) -> Event {
+        match self.keys.pop() {
+            Some(SIGINT_KEY) => {
+                unsafe { libc::raise(libc::SIGINT) };
+                Event::Idle
+            }
+            Some(IDLE_KEY) => Event::Idle,
+            Some(key) => Event::Key(key),
+            None => Event::End,
+        }
+    }
+
+    fn poll_pending(&mut self) -> Event {
+        self.poll()
+    }
+
+    fn dump(&self) -> String {
+        self.cells.dump()
+    }
+}
+
+// Keys are given literally, special keys are written in angle brackets,
+// e.g. "jj<Space><Tab>i<Esc>", <C-c> sends SIGINT, <Idle> draws a frame and <F1> to
+// <F12> are function keys.
+fn parse_keys(script: &str) -> Vec<i32> {
+    let mut keys = Vec::new();
+    let mut chars = script.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '<' {
+            keys.push(char_key(ch));
+            continue;
+        }
+
+        let name: String = chars.by_ref().take_while(|&ch| ch != '>').collect();
+        let key = match name.to_lowercase().as_str() {
+            "cr" | "enter" => '\n' as i32,
+            "esc" => 27,
+            "tab" => '\t' as i32,
+            "space" => ' ' as i32,
+            "bs" => KEY_BACKSPACE,
+            "del" => KEY_DC,
+            "up" => KEY_UP,
+            "down" => KEY_DOWN,
+            "left" => KEY_LEFT,
+            "right" => KEY_RIGHT,
+            "s-up" => KEY_SR,
+            "s-down" => KEY_SF,
+            "s-tab" => KEY_BTAB,
+            "home" => KEY_HOME,
+            "end" => KEY_END,
+            "lt" => '<' as i32,
+            f if f.len() > 1
+                && f.starts_with('f')
+                && f[1..].parse::<u8>().is_ok_and(|n| n <= 12) =>
+            {
+                KEY_F(f[1..].parse().unwrap())
+            }
+            "c-c" => SIGINT_KEY,
+            "idle" => IDLE_KEY,
+            ctrl if ctrl.len() == 3
+                && ctrl.starts_with("c-")
+                && ctrl.as_bytes()[2].is_ascii_lowercase() =>
+            {
+                (ctrl.as_bytes()[2] & 0x1f) as i32
+            }
+            _ => {
+                eprintln!("[ERROR]: Unknown key in script: '<{name}>'.");
+                continue;
+            }
+        };
+        keys.push(key);
+    }
+
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(width: i32, puts: &[(i32, &str)]) -> String {
+        let mut grid = Grid::new(Vec2::new(width, 1), "");
+        for &(x, text) in puts {
+            grid.put(Vec2::new(x, 0), text);
+        }
+        grid.dump()
+    }
+
+    #[test]
+    fn wide_characters_take_two_cells() {
+        assert_eq!(grid(10, &[(0, "日本語"), (6, "ab")]), "日本語ab");
+        assert_eq!(grid(10, &[(1, "中文"), (0, "x")]), "x中文");
+        // The one that doesn't fit at the end of the row is left out.
+        assert_eq!(grid(5, &[(0, "日本語")]), "日本");
+        assert_eq!(grid(5, &[(0, "a日本語")]), "a日本");
+        // Combining marks are dropped, emoji are wide.
+        assert_eq!(grid(10, &[(0, "e\u{301}🎉!")]), "e🎉!");
+    }
+
+    #[test]
+    fn writing_over_half_of_a_wide_character() {
+        // The other half is blanked, on either side.
+        assert_eq!(grid(6, &[(0, "日本語"), (1, "x")]), " x本語");
+        assert_eq!(grid(6, &[(0, "日本語"), (2, "y")]), "日y 語");
+        assert_eq!(grid(6, &[(0, "abcdef"), (1, "한")]), "a한def");
+    }
+
+    #[test]
+    fn script_characters_past_ascii() {
+        let keys = parse_keys("aé<Enter>東🎉");
+        assert_eq!(keys[0], 'a' as i32);
+        assert_eq!(keys[2], '\n' as i32);
+        assert!(keys.iter().all(|key| !(KEY_MIN..=KEY_MAX).contains(key)));
+        let typed: Vec<Option<char>> = keys.iter().map(|&key| key_char(key)).collect();
+        if cfg!(feature = "wide") {
+            assert_eq!(typed, [Some('a'), Some('é'), None, Some('東'), Some('🎉')]);
+        } else {
+            assert_eq!(typed, [Some('a'), None, None, None, None]);
+        }
+    }
+}
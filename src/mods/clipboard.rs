@@ -0,0 +1,37 @@
+// Copying text to the system clipboard through the first clipboard tool found on the
+// system, without linking to a display server.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+const TOOLS: [(&str, &[&str]); 4] = [
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+    ("pbcopy", &[]),
+];
+
+// Pipes `text` to the first tool that runs, returning its name.
+pub fn copy(text: &str) -> Result<&'static str, String> {
+    for (tool, args) in TOOLS {
+        let Ok(mut child) = Command::new(tool)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        let written = child.stdin.take().unwrap().write_all(text.as_bytes());
+        let status = child
+            .wait()
+            .map_err(|err| format!("{tool} failed: {err}"))?;
+        return match (written, status.success()) {
+            (Ok(()), true) => Ok(tool),
+            (Err(err), _) => Err(format!("{tool} failed: {err}")),
+            (Ok(()), false) => Err(format!("{tool} failed ({status})")),
+        };
+    }
+    Err("no clipboard tool found (wl-copy, xclip, xsel or pbcopy)".to_string())
+}
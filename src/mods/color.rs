@@ -0,0 +1,87 @@
+// Colors of the age gradient. The ends come from the config as a name, an index of the
+// 256-color palette or `#rrggbb`, the steps between them are mixed in RGB and drawn with
+// the closest color the terminal has.
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+const NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+// The first 16 colors as xterm draws them by default, terminals differ a little.
+const BASIC: [Rgb; 16] = [
+    Rgb(0, 0, 0),
+    Rgb(205, 0, 0),
+    Rgb(0, 205, 0),
+    Rgb(205, 205, 0),
+    Rgb(0, 0, 238),
+    Rgb(205, 0, 205),
+    Rgb(0, 205, 205),
+    Rgb(229, 229, 229),
+    Rgb(127, 127, 127),
+    Rgb(255, 0, 0),
+    Rgb(0, 255, 0),
+    Rgb(255, 255, 0),
+    Rgb(92, 92, 255),
+    Rgb(255, 0, 255),
+    Rgb(0, 255, 255),
+    Rgb(255, 255, 255),
+];
+
+// Entry `idx` of the 256-color palette: the basic colors, a 6x6x6 cube and a gray ramp.
+fn palette(idx: u8) -> Rgb {
+    let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+    match idx {
+        0..=15 => BASIC[idx as usize],
+        16..=231 => {
+            let i = idx - 16;
+            Rgb(level(i / 36), level(i / 6 % 6), level(i % 6))
+        }
+        _ => {
+            let v = 8 + (idx - 232) * 10;
+            Rgb(v, v, v)
+        }
+    }
+}
+
+pub fn parse(value: &str) -> Option<Rgb> {
+    let value = value.to_lowercase();
+    if let Some(idx) = NAMES.iter().position(|&name| name == value) {
+        return Some(palette(idx as u8));
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+        return match hex.len() {
+            6 => Some(Rgb(channel(0)?, channel(2)?, channel(4)?)),
+            _ => None,
+        };
+    }
+    value.parse::<u8>().ok().map(palette)
+}
+
+// `steps` colors from `from` to `to`, both included.
+pub fn gradient(from: Rgb, to: Rgb, steps: usize) -> Vec<Rgb> {
+    let mix = |a: u8, b: u8, t: f64| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    (0..steps)
+        .map(|i| {
+            let t = i as f64 / steps.saturating_sub(1).max(1) as f64;
+            Rgb(
+                mix(from.0, to.0, t),
+                mix(from.1, to.1, t),
+                mix(from.2, to.2, t),
+            )
+        })
+        .collect()
+}
+
+// The palette entry closest to `rgb` among the first `colors` the terminal has.
+pub fn nearest(rgb: Rgb, colors: i32) -> i16 {
+    let distance = |other: Rgb| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(rgb.0, other.0) + d(rgb.1, other.1) + d(rgb.2, other.2)
+    };
+    (0..colors.clamp(1, 256))
+        .min_by_key(|&idx| distance(palette(idx as u8)))
+        .unwrap_or(0) as i16
+}
@@ -0,0 +1,840 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process::exit;
+
+use chrono::format::{Item, StrftimeItems};
+use chrono::{Local, NaiveDate, NaiveDateTime};
+
+use super::color::{self, Rgb};
+use super::keymap::{KeyAction, KeyMap};
+use super::query::Query;
+use super::text::char_width;
+use super::todo::DATE_INPUT_FMT;
+use super::utils::ExitCode;
+
+const CONFIG_DIR: &str = "todors";
+const CONFIG_FILE: &str = "config";
+
+// The summary after `[CONTENT]:`, the fields are filled in from the list.
+pub const CONTENT_FORMAT: &str = "({todos})todos and ({dones})dones";
+pub const CONTENT_FIELDS: [&str; 4] = ["todos", "dones", "total", "percent"];
+
+// Every option with what it does, and an example value for the ones without a default,
+// in the order `config init` writes them and `config check` prints them.
+const OPTIONS: [(&str, &str, &str); 35] = [
+    ("cursor_style", "Style of the cursor in edit mode: terminal|reverse|underline|block.", ""),
+    ("cursor_blink", "Blink the cursor in edit mode.", ""),
+    ("borders", "Draw borders around the panels.", ""),
+    ("due_soon", "Show the items with the nearest due: dates at the top of the TODO panel.", ""),
+    ("group_dones", "Group the DONE items under Today/Yesterday/This week/Earlier headers.", ""),
+    ("content_format", "The summary on the [CONTENT] line, with {todos}, {dones}, {total} and {percent}.", ""),
+    ("filter", "A filter preset for F and :filter, '<name>: <query>', can be repeated.", "work: +work and not @someday"),
+    ("date_format", "The strftime format completion dates are edited in.", ""),
+    ("age_colors", "Color the TODO items by the age of their created: date, '<fresh> <stale>' as names, 0-255 or #rrggbb.", "green red"),
+    ("age_days", "Age in days at which an item has the stale color of age_colors.", ""),
+    ("marker", "A glyph before the items with a tag or priority, '<token> <glyph> [ascii]', can be repeated.", "+urgent 🔥 !"),
+    ("insert_position", "Where i puts a new item: top|bottom|above-cursor|below-cursor.", ""),
+    ("edit_start", "How r starts an edit: end (the cursor after the text) or replace (the first key typed replaces it).", ""),
+    ("max_item_len", "Longest item text accepted when editing.", ""),
+    ("max_items", "Most items a panel takes from i, a, transfers, add and import, a bigger file can still be browsed.", ""),
+    ("completion_flash", "Briefly highlight a transferred item.", ""),
+    ("ellipsis", "End text cut at the panel width with an ellipsis.", ""),
+    ("rule", "The character of the lines under the headers, - where UTF-8 can't be drawn.", ""),
+    ("view", "panels, or single to show only the TODO list.", ""),
+    ("panel_order", "Which panel is on the left: todo-first|done-first.", ""),
+    ("selection_style", "How the selected row is marked besides the colors: color|reverse|arrow.", ""),
+    ("duplicate_policy", "When :dupes takes items as duplicates: exact|ignore-case|ignore-tags.", ""),
+    ("undo_depth", "Show how many actions can be undone and redone when there's no message.", ""),
+    ("hint_bar", "Show the main keys of what's being done in the bottom line.", ""),
+    ("scroll_margin", "Items kept visible above and below the cursor when scrolling.", ""),
+    ("drag_wrap", "Drag an item past the last of its siblings around to the first and back.", ""),
+    ("create_dirs", "Create the missing directories of the file when it is opened.", ""),
+    ("autosave", "Save the changed files every <N>s seconds or after <N> changes as well as on exit, off to only save on exit and :w. A file without unsaved changes is left alone.", ""),
+    ("hook_add", "Shell command run when an item is added, with the text as $1.", "notify-send todors"),
+    ("hook_done", "Shell command run when an item is completed, with the text as $1.", "notify-send todors"),
+    ("hook_delete", "Shell command run when an item is deleted, with the text as $1.", "notify-send todors"),
+    ("git_commit", "Commit the file to its git repository after each save that changed it.", ""),
+    ("git_message", "The message of these commits, with {file}, {todos} and {dones}.", ""),
+    ("activity_log", "Append every added, completed and deleted item to <file>.log on save.", ""),
+    ("auto_archive_days", "Move the DONE items completed more days ago than this to <file>.archive on startup, 0 never does.", ""),
+];
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum CursorStyle {
+    Terminal,
+    Reverse,
+    Underline,
+    Block,
+}
+
+impl CursorStyle {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "terminal" => Some(CursorStyle::Terminal),
+            "reverse" => Some(CursorStyle::Reverse),
+            "underline" => Some(CursorStyle::Underline),
+            "block" => Some(CursorStyle::Block),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            CursorStyle::Terminal => "terminal",
+            CursorStyle::Reverse => "reverse",
+            CursorStyle::Underline => "underline",
+            CursorStyle::Block => "block",
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum InsertPosition {
+    Top,
+    Bottom,
+    AboveCursor,
+    BelowCursor,
+}
+
+impl InsertPosition {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "top" => Some(InsertPosition::Top),
+            "bottom" => Some(InsertPosition::Bottom),
+            "above-cursor" => Some(InsertPosition::AboveCursor),
+            "below-cursor" => Some(InsertPosition::BelowCursor),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            InsertPosition::Top => "top",
+            InsertPosition::Bottom => "bottom",
+            InsertPosition::AboveCursor => "above-cursor",
+            InsertPosition::BelowCursor => "below-cursor",
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum EditStart {
+    End,
+    Replace,
+}
+
+impl EditStart {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "end" => Some(EditStart::End),
+            "replace" => Some(EditStart::Replace),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            EditStart::End => "end",
+            EditStart::Replace => "replace",
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum SelectionStyle {
+    Color,
+    Reverse,
+    Arrow,
+}
+
+impl SelectionStyle {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "color" => Some(SelectionStyle::Color),
+            "reverse" => Some(SelectionStyle::Reverse),
+            "arrow" => Some(SelectionStyle::Arrow),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            SelectionStyle::Color => "color",
+            SelectionStyle::Reverse => "reverse",
+            SelectionStyle::Arrow => "arrow",
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DuplicatePolicy {
+    Exact,
+    IgnoreCase,
+    IgnoreTags,
+}
+
+impl DuplicatePolicy {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "exact" => Some(DuplicatePolicy::Exact),
+            "ignore-case" => Some(DuplicatePolicy::IgnoreCase),
+            "ignore-tags" => Some(DuplicatePolicy::IgnoreTags),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            DuplicatePolicy::Exact => "exact",
+            DuplicatePolicy::IgnoreCase => "ignore-case",
+            DuplicatePolicy::IgnoreTags => "ignore-tags",
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PanelOrder {
+    TodoFirst,
+    DoneFirst,
+}
+
+impl PanelOrder {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "todo-first" => Some(PanelOrder::TodoFirst),
+            "done-first" => Some(PanelOrder::DoneFirst),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            PanelOrder::TodoFirst => "todo-first",
+            PanelOrder::DoneFirst => "done-first",
+        }
+    }
+
+    pub fn reversed(self) -> Self {
+        match self {
+            PanelOrder::TodoFirst => PanelOrder::DoneFirst,
+            PanelOrder::DoneFirst => PanelOrder::TodoFirst,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum View {
+    Panels,
+    Single,
+}
+
+impl View {
+    fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "panels" => Some(View::Panels),
+            "single" => Some(View::Single),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            View::Panels => "panels",
+            View::Single => "single",
+        }
+    }
+}
+
+// When the app saves the changed lists by itself, besides on exit.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Autosave {
+    Off,
+    // Every that many seconds.
+    Seconds(u64),
+    // Once that many changes are made since the last save.
+    Changes(usize),
+}
+
+impl Autosave {
+    fn from_str(s: &str) -> Option<Self> {
+        if s == "off" || s == "0" {
+            return Some(Autosave::Off);
+        }
+        if let Some(changes) = s.strip_suffix("changes").or(s.strip_suffix("change")) {
+            let changes = changes.trim_end().parse().ok();
+            return changes.filter(|&n| n > 0).map(Autosave::Changes);
+        }
+        let secs = s.strip_suffix('s')?.parse().ok();
+        secs.filter(|&n| n > 0).map(Autosave::Seconds)
+    }
+
+    fn as_value(self) -> String {
+        match self {
+            Autosave::Off => "off".to_string(),
+            Autosave::Seconds(secs) => format!("{secs}s"),
+            Autosave::Changes(n) => format!("{n} changes"),
+        }
+    }
+}
+
+// Shell commands run when an item is added, completed or deleted.
+#[derive(Default, Clone, Debug)]
+pub struct Hooks {
+    pub add: Option<String>,
+    pub done: Option<String>,
+    pub delete: Option<String>,
+}
+
+// A glyph shown before the items with a tag or a `pri:X` token, `ascii` instead of it
+// when the locale isn't UTF-8. Only drawn, nothing is written to the file.
+#[derive(Clone, Debug)]
+pub struct Marker {
+    token: String,
+    glyph: String,
+    ascii: String,
+}
+
+impl Marker {
+    fn from_str(s: &str) -> Option<Self> {
+        let mut words = s.split_whitespace();
+        let token = words.next()?;
+        let glyph = words.next()?;
+        let ascii = words.next().unwrap_or("*");
+        if words.next().is_some() || !ascii.is_ascii() {
+            return None;
+        }
+
+        let tag = token.len() > 1 && (token.starts_with('+') || token.starts_with('@'));
+        let priority = token
+            .strip_prefix("pri:")
+            .is_some_and(|p| p.len() == 1 && p.chars().all(|c| c.is_ascii_uppercase()));
+        (tag || priority).then(|| Self {
+            token: token.to_string(),
+            glyph: glyph.to_string(),
+            ascii: ascii.to_string(),
+        })
+    }
+
+    fn as_value(&self) -> String {
+        format!("{} {} {}", self.token, self.glyph, self.ascii)
+    }
+
+    pub fn matches(&self, text: &str) -> bool {
+        text.split_whitespace().any(|word| word == self.token)
+    }
+
+    pub fn glyph(&self, utf8: bool) -> &str {
+        if utf8 {
+            &self.glyph
+        } else {
+            &self.ascii
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub cursor_style: CursorStyle,
+    pub cursor_blink: bool,
+    pub borders: bool,
+    pub due_soon: bool,
+    pub insert_position: InsertPosition,
+    pub edit_start: EditStart,
+    pub max_item_len: usize,
+    pub max_items: usize,
+    pub completion_flash: bool,
+    pub ellipsis: bool,
+    pub rule: char,
+    pub view: View,
+    pub panel_order: PanelOrder,
+    pub group_dones: bool,
+    pub selection_style: SelectionStyle,
+    pub scroll_margin: usize,
+    pub drag_wrap: bool,
+    pub duplicate_policy: DuplicatePolicy,
+    pub undo_depth: bool,
+    pub hint_bar: bool,
+    pub activity_log: bool,
+    pub auto_archive_days: usize,
+    pub create_dirs: bool,
+    pub autosave: Autosave,
+    pub hooks: Hooks,
+    pub git_commit: bool,
+    pub git_message: String,
+    pub date_format: String,
+    pub content_format: String,
+    pub markers: Vec<Marker>,
+    // The fresh and stale ends of the age gradient, as written.
+    pub age_colors: Option<(String, String)>,
+    pub age_days: usize,
+    // Named filters, in the order `F` cycles through them.
+    pub filters: Vec<(String, Query)>,
+    pub keymap: KeyMap,
+    // The lines read with a value that was replaced by the default, as `<path>:<line>: ...`.
+    pub warnings: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            cursor_style: CursorStyle::Terminal,
+            cursor_blink: false,
+            borders: false,
+            due_soon: true,
+            insert_position: InsertPosition::AboveCursor,
+            edit_start: EditStart::End,
+            max_item_len: 1000,
+            max_items: 50000,
+            completion_flash: true,
+            ellipsis: true,
+            rule: '‾',
+            view: View::Panels,
+            panel_order: PanelOrder::TodoFirst,
+            group_dones: false,
+            selection_style: SelectionStyle::Color,
+            scroll_margin: 0,
+            drag_wrap: false,
+            duplicate_policy: DuplicatePolicy::IgnoreCase,
+            undo_depth: false,
+            hint_bar: true,
+            activity_log: false,
+            auto_archive_days: 0,
+            create_dirs: true,
+            autosave: Autosave::Seconds(30),
+            hooks: Hooks::default(),
+            git_commit: false,
+            git_message: String::from("Update {file}"),
+            date_format: DATE_INPUT_FMT.to_string(),
+            content_format: CONTENT_FORMAT.to_string(),
+            markers: Vec::new(),
+            age_colors: None,
+            age_days: 30,
+            filters: Vec::new(),
+            keymap: KeyMap::default(),
+            warnings: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        if let Ok(path) = env::var("TODORS_CONFIG") {
+            return Some(PathBuf::from(path));
+        }
+
+        let base = match env::var("XDG_CONFIG_HOME") {
+            Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var("HOME").ok()?).join(".config"),
+        };
+        Some(base.join(CONFIG_DIR).join(CONFIG_FILE))
+    }
+
+    // Which variable the config path comes from, for `config check`.
+    pub fn path_source() -> &'static str {
+        match env::var("XDG_CONFIG_HOME") {
+            _ if env::var("TODORS_CONFIG").is_ok() => "$TODORS_CONFIG",
+            Ok(dir) if !dir.is_empty() => "$XDG_CONFIG_HOME",
+            _ => "$HOME",
+        }
+    }
+
+    pub fn load() -> Self {
+        let config = Self::try_load().unwrap_or_else(|errors| {
+            for err in errors {
+                eprintln!("[ERROR]: {err}");
+            }
+            exit(ExitCode::Parse as i32);
+        });
+        for warning in config.warnings.iter() {
+            eprintln!("[WARNING]: {warning}");
+        }
+        config
+    }
+
+    // The config from the file, the defaults without one. Every invalid line is reported,
+    // `doctor` lists them all and the startup refuses to go on with any. A bad
+    // `content_format` only is a warning, the default summary is shown instead.
+    pub fn try_load() -> Result<Self, Vec<String>> {
+        let mut config = Self::default();
+
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Ok(config),
+        };
+
+        match fs::read_to_string(&path) {
+            Ok(content) => {
+                let errors = config.parse(&content);
+                if !errors.is_empty() {
+                    return Err(errors
+                        .into_iter()
+                        .map(|(line, err)| format!("{}:{}: {}", path.display(), line, err))
+                        .collect());
+                }
+                for warning in config.warnings.iter_mut() {
+                    *warning = format!("{}:{warning}", path.display());
+                }
+            }
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(vec![format!(
+                    "Could not read config '{}': {err}",
+                    path.display()
+                )])
+            }
+        }
+
+        Ok(config)
+    }
+
+    // A config file with every option commented out at its default, or at an example for
+    // the ones without one, for `config init`.
+    pub fn template() -> String {
+        let defaults = Self::default();
+        let mut out = String::from(
+            "# ToDors config, each line is a `key = value` pair. Uncomment an option to change it.\n",
+        );
+        for (key, description, example) in OPTIONS {
+            let value = defaults.values(key).pop().filter(|value| !value.is_empty());
+            let value = value.unwrap_or(example.to_string());
+            out.push_str(&format!("\n# {description}\n# {}", setting(key, &value)));
+        }
+        out.push_str(
+            "\n# Two keys pressed one after the other run an action, none unbinds the chord.\n",
+        );
+        for (keys, action) in defaults.keymap.chord_settings() {
+            out.push_str(&format!("# \"{keys}\" = {action}\n"));
+        }
+        out
+    }
+
+    // The ends of the age gradient, checked when the config was read.
+    pub fn age_gradient(&self) -> Option<(Rgb, Rgb)> {
+        let (fresh, stale) = self.age_colors.as_ref()?;
+        Some((color::parse(fresh)?, color::parse(stale)?))
+    }
+
+    // The settings in effect, one `key = value` line per value, as `config check` prints
+    // them. Unset hooks are left empty, repeatable options without a value are left out.
+    pub fn settings(&self) -> String {
+        let chords = self
+            .keymap
+            .chord_settings()
+            .into_iter()
+            .map(|(keys, action)| format!("\"{keys}\" = {action}\n"));
+        OPTIONS
+            .iter()
+            .flat_map(|(key, ..)| {
+                self.values(key)
+                    .into_iter()
+                    .map(move |value| setting(key, &value))
+            })
+            .chain(chords)
+            .collect()
+    }
+
+    fn values(&self, key: &str) -> Vec<String> {
+        let value = match key {
+            "cursor_style" => self.cursor_style.as_str().to_string(),
+            "cursor_blink" => self.cursor_blink.to_string(),
+            "borders" => self.borders.to_string(),
+            "due_soon" => self.due_soon.to_string(),
+            "group_dones" => self.group_dones.to_string(),
+            "content_format" => self.content_format.clone(),
+            "date_format" => self.date_format.clone(),
+            "insert_position" => self.insert_position.as_str().to_string(),
+            "edit_start" => self.edit_start.as_str().to_string(),
+            "max_item_len" => self.max_item_len.to_string(),
+            "max_items" => self.max_items.to_string(),
+            "completion_flash" => self.completion_flash.to_string(),
+            "ellipsis" => self.ellipsis.to_string(),
+            "rule" => self.rule.to_string(),
+            "view" => self.view.as_str().to_string(),
+            "panel_order" => self.panel_order.as_str().to_string(),
+            "selection_style" => self.selection_style.as_str().to_string(),
+            "duplicate_policy" => self.duplicate_policy.as_str().to_string(),
+            "undo_depth" => self.undo_depth.to_string(),
+            "hint_bar" => self.hint_bar.to_string(),
+            "scroll_margin" => self.scroll_margin.to_string(),
+            "drag_wrap" => self.drag_wrap.to_string(),
+            "create_dirs" => self.create_dirs.to_string(),
+            "autosave" => self.autosave.as_value(),
+            "hook_add" => self.hooks.add.clone().unwrap_or_default(),
+            "hook_done" => self.hooks.done.clone().unwrap_or_default(),
+            "hook_delete" => self.hooks.delete.clone().unwrap_or_default(),
+            "git_commit" => self.git_commit.to_string(),
+            "git_message" => self.git_message.clone(),
+            "activity_log" => self.activity_log.to_string(),
+            "auto_archive_days" => self.auto_archive_days.to_string(),
+            "age_colors" => self
+                .age_colors
+                .as_ref()
+                .map(|(fresh, stale)| format!("{fresh} {stale}"))
+                .unwrap_or_default(),
+            "age_days" => self.age_days.to_string(),
+            "marker" => return self.markers.iter().map(Marker::as_value).collect(),
+            "filter" => {
+                return self
+                    .filters
+                    .iter()
+                    .map(|(name, query)| format!("{name}: {query}"))
+                    .collect()
+            }
+            _ => unreachable!("'{key}' isn't in OPTIONS"),
+        };
+        vec![value]
+    }
+
+    fn parse(&mut self, content: &str) -> Vec<(usize, String)> {
+        let mut errors = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let warned = self.warnings.len();
+            let result = match line.split_once('=') {
+                Some((key, value)) => self.set(key.trim(), value.trim().trim_matches('"')),
+                None => Err(format!("expected 'key = value', got '{line}'")),
+            };
+            if let Err(err) = result {
+                errors.push((i + 1, err));
+            }
+            for warning in self.warnings[warned..].iter_mut() {
+                *warning = format!("{}: {warning}", i + 1);
+            }
+        }
+        errors
+    }
+
+    fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "cursor_style" => {
+                self.cursor_style = CursorStyle::from_str(value).ok_or(format!(
+                    "invalid cursor_style '{value}', expected terminal|reverse|underline|block"
+                ))?
+            }
+            "cursor_blink" => self.cursor_blink = parse_bool(key, value)?,
+            "borders" => self.borders = parse_bool(key, value)?,
+            "due_soon" => self.due_soon = parse_bool(key, value)?,
+            "group_dones" => self.group_dones = parse_bool(key, value)?,
+            "completion_flash" => self.completion_flash = parse_bool(key, value)?,
+            "ellipsis" => self.ellipsis = parse_bool(key, value)?,
+            "rule" => {
+                let mut chars = value.chars();
+                self.rule = match (chars.next(), chars.next()) {
+                    (Some(c), None) if char_width(c) == 1 => c,
+                    _ => {
+                        return Err(format!(
+                            "invalid rule '{value}', expected a single character one column wide"
+                        ))
+                    }
+                }
+            }
+            "undo_depth" => self.undo_depth = parse_bool(key, value)?,
+            "hint_bar" => self.hint_bar = parse_bool(key, value)?,
+            "drag_wrap" => self.drag_wrap = parse_bool(key, value)?,
+            "activity_log" => self.activity_log = parse_bool(key, value)?,
+            "create_dirs" => self.create_dirs = parse_bool(key, value)?,
+            "autosave" => {
+                self.autosave = Autosave::from_str(value).ok_or(format!(
+                    "invalid autosave '{value}', expected off, <N>s or <N> changes"
+                ))?
+            }
+            "hook_add" => self.hooks.add = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "hook_done" => self.hooks.done = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "hook_delete" => self.hooks.delete = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "git_commit" => self.git_commit = parse_bool(key, value)?,
+            "git_message" => self.git_message = value.to_string(),
+            "date_format" => self.date_format = parse_date_format(value)?,
+            "content_format" => {
+                self.content_format = parse_content_format(value).unwrap_or_else(|err| {
+                    self.warnings.push(format!("{err}, using the default"));
+                    CONTENT_FORMAT.to_string()
+                })
+            }
+            "marker" => self.markers.push(Marker::from_str(value).ok_or(format!(
+                "invalid marker '{value}', expected '<+tag|@tag|pri:X> <glyph> [ascii]'"
+            ))?),
+            "age_colors" => self.age_colors = parse_age_colors(value)?,
+            "age_days" => {
+                self.age_days = match value.parse::<usize>() {
+                    Ok(days) if days > 0 => days,
+                    _ => {
+                        return Err(format!(
+                            "invalid age_days '{value}', expected a positive number"
+                        ))
+                    }
+                }
+            }
+            "filter" => {
+                let (name, query) = parse_filter(value)?;
+                if self.filters.iter().any(|(n, _)| *n == name) {
+                    return Err(format!("filter '{name}' is defined twice"));
+                }
+                self.filters.push((name, query));
+            }
+            "insert_position" => {
+                self.insert_position = InsertPosition::from_str(value).ok_or(format!(
+                "invalid insert_position '{value}', expected top|bottom|above-cursor|below-cursor"
+            ))?
+            }
+            "edit_start" => {
+                self.edit_start = EditStart::from_str(value).ok_or(format!(
+                    "invalid edit_start '{value}', expected end|replace"
+                ))?
+            }
+            "view" => {
+                self.view = View::from_str(value)
+                    .ok_or(format!("invalid view '{value}', expected panels|single"))?
+            }
+            "panel_order" => {
+                self.panel_order = PanelOrder::from_str(value).ok_or(format!(
+                    "invalid panel_order '{value}', expected todo-first|done-first"
+                ))?
+            }
+            "selection_style" => {
+                self.selection_style = SelectionStyle::from_str(value).ok_or(format!(
+                    "invalid selection_style '{value}', expected color|reverse|arrow"
+                ))?
+            }
+            "auto_archive_days" => {
+                self.auto_archive_days = value.parse().map_err(|_| {
+                    format!("invalid auto_archive_days '{value}', expected a number")
+                })?
+            }
+            "scroll_margin" => {
+                self.scroll_margin = value
+                    .parse()
+                    .map_err(|_| format!("invalid scroll_margin '{value}', expected a number"))?
+            }
+            "duplicate_policy" => {
+                self.duplicate_policy = DuplicatePolicy::from_str(value).ok_or(format!(
+                    "invalid duplicate_policy '{value}', expected exact|ignore-case|ignore-tags"
+                ))?
+            }
+            "max_item_len" => {
+                self.max_item_len = match value.parse::<usize>() {
+                    Ok(len) if len > 0 => len,
+                    _ => {
+                        return Err(format!(
+                            "invalid max_item_len '{value}', expected a positive number"
+                        ))
+                    }
+                }
+            }
+            "max_items" => {
+                self.max_items = match value.parse::<usize>() {
+                    Ok(max) if max > 0 => max,
+                    _ => {
+                        return Err(format!(
+                            "invalid max_items '{value}', expected a positive number"
+                        ))
+                    }
+                }
+            }
+            // A chord, `"g g" = go_top`, or `none` to unbind it.
+            _ => match key.strip_prefix('"').and_then(|key| key.strip_suffix('"')) {
+                Some(keys) => {
+                    let action = match value {
+                        "none" => None,
+                        name => Some(
+                            KeyAction::from_name(name)
+                                .ok_or(format!("unknown action '{name}' for the chord '{keys}'"))?,
+                        ),
+                    };
+                    self.keymap.bind_chord(keys, action)?
+                }
+                None => return Err(format!("unknown option '{key}'")),
+            },
+        }
+        Ok(())
+    }
+}
+
+// Two colors, or nothing to leave the items uncolored.
+fn parse_age_colors(value: &str) -> Result<Option<(String, String)>, String> {
+    let words: Vec<&str> = value.split_whitespace().collect();
+    match words.as_slice() {
+        [] => Ok(None),
+        [fresh, stale] if color::parse(fresh).is_some() && color::parse(stale).is_some() => {
+            Ok(Some((fresh.to_string(), stale.to_string())))
+        }
+        _ => Err(format!(
+            "invalid age_colors '{value}', expected '<fresh> <stale>' as color names, 0-255 or #rrggbb"
+        )),
+    }
+}
+
+// A strftime format dates are entered in. Prompts are prefilled with the date in it, so
+// it has to read back as a date.
+fn parse_date_format(value: &str) -> Result<String, String> {
+    if StrftimeItems::new(value).any(|item| item == Item::Error) {
+        return Err(format!("invalid date_format '{value}'"));
+    }
+    let now = Local::now().format(value).to_string();
+    if NaiveDateTime::parse_from_str(&now, value).is_err()
+        && NaiveDate::parse_from_str(&now, value).is_err()
+    {
+        return Err(format!(
+            "invalid date_format '{value}', it doesn't read back as a date"
+        ));
+    }
+    Ok(value.to_string())
+}
+
+// A named filter is written as `<name>: <query>`.
+fn parse_filter(value: &str) -> Result<(String, Query), String> {
+    let (name, query) = value
+        .split_once(':')
+        .filter(|(name, _)| !name.trim().is_empty() && !name.trim().contains(' '))
+        .ok_or(format!(
+            "invalid filter '{value}', expected '<name>: <query>'"
+        ))?;
+    let query = Query::parse(query.trim())
+        .map_err(|err| format!("invalid filter '{}': {err}", name.trim()))?;
+    Ok((name.trim().to_string(), query))
+}
+
+// Every `{...}` in the template has to be one of the CONTENT_FIELDS.
+fn parse_content_format(value: &str) -> Result<String, String> {
+    let mut rest = value;
+    while let Some(start) = rest.find(['{', '}']) {
+        let field = rest[start..]
+            .strip_prefix('{')
+            .and_then(|after| after.split_once('}'))
+            .map(|(field, _)| field)
+            .ok_or(format!("invalid content_format '{value}', unmatched brace"))?;
+        if !CONTENT_FIELDS.contains(&field) {
+            return Err(format!(
+                "invalid content_format '{value}', unknown field '{{{field}}}', expected {}",
+                CONTENT_FIELDS.map(|f| format!("{{{f}}}")).join("|")
+            ));
+        }
+        rest = &rest[start + field.len() + 2..];
+    }
+    Ok(value.to_string())
+}
+
+// A `key = value` line. Values are trimmed when read, the ones with spaces around them
+// are written in quotes.
+fn setting(key: &str, value: &str) -> String {
+    match value {
+        "" => format!("{key} =\n"),
+        _ if value.trim() != value => format!("{key} = \"{value}\"\n"),
+        _ => format!("{key} = {value}\n"),
+    }
+}
+
+fn parse_bool(key: &str, value: &str) -> Result<bool, String> {
+    match value {
+        "true" | "yes" | "on" => Ok(true),
+        "false" | "no" | "off" => Ok(false),
+        _ => Err(format!(
+            "invalid value '{value}' for '{key}', expected true|false"
+        )),
+    }
+}
@@ -0,0 +1,418 @@
+// A line-level diff of two texts, from the longest common subsequence of their lines.
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Diff {
+    Same(String),
+    Removed(String),
+    Added(String),
+}
+
+// The lines of `old` and `new` in order, each kept, removed from `old` or added in `new`.
+// The common head and tail are split off first, the table is only built for the rest.
+pub fn diff_lines(old: &str, new: &str) -> Vec<Diff> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    let head = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let tail = old[head..]
+        .iter()
+        .rev()
+        .zip(new[head..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (&old[head..old.len() - tail], &new[head..new.len() - tail]);
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..].
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut diff: Vec<Diff> = old[..head]
+        .iter()
+        .map(|line| Diff::Same(line.to_string()))
+        .collect();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            diff.push(Diff::Same(a[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            diff.push(Diff::Removed(a[i].to_string()));
+            i += 1;
+        } else {
+            diff.push(Diff::Added(b[j].to_string()));
+            j += 1;
+        }
+    }
+    diff.extend(a[i..].iter().map(|line| Diff::Removed(line.to_string())));
+    diff.extend(b[j..].iter().map(|line| Diff::Added(line.to_string())));
+    diff.extend(
+        old[old.len() - tail..]
+            .iter()
+            .map(|line| Diff::Same(line.to_string())),
+    );
+    diff
+}
+
+// Lines of the merged file with the side they come from, to tell items moved on disk
+// from new ones.
+#[derive(PartialEq, Clone, Copy, Debug)]
+enum Side {
+    Base,
+    Local,
+    Disk,
+}
+
+// Changes made on both sides to the same lines, the local ones are kept.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Conflict {
+    pub local: Vec<String>,
+    pub disk: Vec<String>,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Merge {
+    pub contents: String,
+    pub conflicts: Vec<Conflict>,
+}
+
+// `base[start..end]` replaced by `lines`.
+struct Hunk {
+    start: usize,
+    end: usize,
+    lines: Vec<String>,
+}
+
+fn hunks(base: &str, new: &str) -> Vec<Hunk> {
+    let mut hunks: Vec<Hunk> = Vec::new();
+    let mut pos = 0;
+    let mut open = false;
+    for diff in diff_lines(base, new) {
+        if let Diff::Same(_) = diff {
+            pos += 1;
+            open = false;
+            continue;
+        }
+        if !open {
+            hunks.push(Hunk {
+                start: pos,
+                end: pos,
+                lines: Vec::new(),
+            });
+            open = true;
+        }
+        let hunk = hunks.last_mut().unwrap();
+        match diff {
+            Diff::Removed(_) => {
+                pos += 1;
+                hunk.end = pos;
+            }
+            Diff::Added(line) => hunk.lines.push(line),
+            Diff::Same(_) => unreachable!(),
+        }
+    }
+    // As many lines replaced as added are items edited in place, one by one, so an edit
+    // next to one made on the other side isn't taken for a conflict.
+    hunks
+        .into_iter()
+        .flat_map(|hunk| {
+            if hunk.end - hunk.start == hunk.lines.len() {
+                (hunk.start..hunk.end)
+                    .zip(hunk.lines)
+                    .map(|(start, line)| Hunk {
+                        start,
+                        end: start + 1,
+                        lines: vec![line],
+                    })
+                    .collect()
+            } else {
+                vec![hunk]
+            }
+        })
+        .collect()
+}
+
+// `base[start..end]` with the hunks (all within it) applied.
+fn apply(base: &[&str], start: usize, end: usize, hunks: &[Hunk]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut pos = start;
+    for hunk in hunks {
+        lines.extend(base[pos..hunk.start].iter().map(|line| line.to_string()));
+        lines.extend(hunk.lines.iter().cloned());
+        pos = hunk.end;
+    }
+    lines.extend(base[pos..end].iter().map(|line| line.to_string()));
+    lines
+}
+
+// The `id:N` token of an item line, it stays the same when the item is edited.
+fn line_id(line: &str) -> Option<&str> {
+    line.split_whitespace().find(|word| {
+        word.strip_prefix("id:")
+            .is_some_and(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_digit()))
+    })
+}
+
+// A three-way merge of the lines changed in `local` and on `disk` since `base`.
+// Changes made on one side only are taken, lines added on both sides at the same place
+// are all kept (once if they're the same). Where both sides changed the same lines
+// differently, the local version is kept and the disk one reported as a conflict: a line
+// deleted on disk but edited here stays. Lines moved on disk that were deleted or edited
+// here, or with the `id:` of a line edited here, aren't brought back.
+pub fn merge_lines(base: &str, local: &str, disk: &str) -> Merge {
+    let lines: Vec<&str> = base.lines().collect();
+    let mut mine = hunks(base, local).into_iter().peekable();
+    let mut theirs = hunks(base, disk).into_iter().peekable();
+
+    let mut merged: Vec<(String, Side)> = Vec::new();
+    let mut conflicts = Vec::new();
+    let mut pos = 0;
+    loop {
+        // Lines added where the other side changes the lines after come first.
+        let (mut ours, mut others) = (Vec::new(), Vec::new());
+        let local_first = match (mine.peek(), theirs.peek()) {
+            (None, None) => break,
+            (Some(a), Some(b)) => (a.start, a.end) <= (b.start, b.end),
+            (a, _) => a.is_some(),
+        };
+        let first = if local_first {
+            mine.next().unwrap()
+        } else {
+            theirs.next().unwrap()
+        };
+        let (start, mut end) = (first.start, first.end);
+        merged.extend(
+            lines[pos..start]
+                .iter()
+                .map(|l| (l.to_string(), Side::Base)),
+        );
+        if local_first {
+            ours.push(first);
+        } else {
+            others.push(first);
+        }
+
+        // The hunks of both sides overlapping the region, and the lines added at the same
+        // place as the ones it adds.
+        loop {
+            let joins = |h: &Hunk| {
+                (h.start < end && start < h.end)
+                    || (start == end && h.start == start && h.end == start)
+            };
+            if let Some(hunk) = mine.next_if(joins) {
+                end = end.max(hunk.end);
+                ours.push(hunk);
+            } else if let Some(hunk) = theirs.next_if(joins) {
+                end = end.max(hunk.end);
+                others.push(hunk);
+            } else {
+                break;
+            }
+        }
+        pos = end;
+
+        let local = apply(&lines, start, end, &ours);
+        let disk = apply(&lines, start, end, &others);
+        if others.is_empty() || local == disk {
+            merged.extend(local.into_iter().map(|l| (l, Side::Local)));
+        } else if ours.is_empty() {
+            merged.extend(disk.into_iter().map(|l| (l, Side::Disk)));
+        } else if start == end {
+            // Added on both sides, the lines in both once.
+            merged.extend(
+                diff_lines(&local.join("\n"), &disk.join("\n"))
+                    .into_iter()
+                    .map(|diff| match diff {
+                        Diff::Same(line) | Diff::Removed(line) => (line, Side::Local),
+                        Diff::Added(line) => (line, Side::Disk),
+                    }),
+            );
+        } else {
+            merged.extend(local.iter().map(|l| (l.clone(), Side::Local)));
+            conflicts.push(Conflict {
+                disk: disk
+                    .into_iter()
+                    .filter(|line| !local.contains(line))
+                    .collect(),
+                local,
+            });
+        }
+    }
+    merged.extend(lines[pos..].iter().map(|l| (l.to_string(), Side::Base)));
+
+    // Taken back out: lines brought in from disk that are in the base but not kept here,
+    // and the ones with the id of a line that is, those are conflicts.
+    let local_lines: Vec<&str> = local.lines().collect();
+    let kept: Vec<String> = merged
+        .iter()
+        .filter(|(line, side)| *side != Side::Disk && line_id(line).is_some())
+        .map(|(line, _)| line.clone())
+        .collect();
+    merged.retain(|(line, side)| {
+        if *side != Side::Disk || line.trim().is_empty() {
+            return true;
+        }
+        if lines.contains(&line.as_str()) && !local_lines.contains(&line.as_str()) {
+            return false;
+        }
+        let Some(same) = kept.iter().find(|kept| line_id(kept) == line_id(line)) else {
+            return true;
+        };
+        match conflicts.iter_mut().find(|c| c.local.contains(same)) {
+            Some(conflict) => conflict.disk.push(line.clone()),
+            None => conflicts.push(Conflict {
+                local: vec![same.clone()],
+                disk: vec![line.clone()],
+            }),
+        }
+        false
+    });
+
+    let mut contents = merged
+        .into_iter()
+        .map(|(line, _)| line)
+        .collect::<Vec<String>>()
+        .join("\n");
+    if !contents.is_empty() {
+        contents.push('\n');
+    }
+    Merge {
+        contents,
+        conflicts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE: &str = "a\nb\nc\nd\n";
+
+    fn merge(local: &str, disk: &str) -> (String, Vec<Conflict>) {
+        let merge = merge_lines(BASE, local, disk);
+        (merge.contents, merge.conflicts)
+    }
+
+    fn conflict(local: &[&str], disk: &[&str]) -> Conflict {
+        Conflict {
+            local: local.iter().map(|l| l.to_string()).collect(),
+            disk: disk.iter().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn changes_on_one_side_are_taken() {
+        assert_eq!(merge(BASE, BASE), (BASE.to_string(), vec![]));
+        assert_eq!(merge("a\nb!\nc\nd\n", BASE).0, "a\nb!\nc\nd\n");
+        assert_eq!(merge(BASE, "a\nc\nd\n").0, "a\nc\nd\n");
+        // Edits next to each other are of different items.
+        assert_eq!(
+            merge("a!\nb\nc\nd\n", "a\nb?\nc\nd\n"),
+            ("a!\nb?\nc\nd\n".to_string(), vec![])
+        );
+        assert_eq!(
+            merge("a\nb\nc\nd\ne\n", "z\na\nb\nc\nd\n").0,
+            "z\na\nb\nc\nd\ne\n"
+        );
+    }
+
+    #[test]
+    fn add_add() {
+        // At the same place both are kept, the local ones first, and the same line once.
+        assert_eq!(
+            merge("a\nb\nx\nc\nd\n", "a\nb\ny\nc\nd\n"),
+            ("a\nb\nx\ny\nc\nd\n".to_string(), vec![])
+        );
+        assert_eq!(
+            merge("a\nb\nx\nc\nd\n", "a\nb\nx\nc\nd\n").0,
+            "a\nb\nx\nc\nd\n"
+        );
+        assert_eq!(
+            merge("a\nb\nc\nd\nx\ny\n", "a\nb\nc\nd\ny\nz\n").0,
+            "a\nb\nc\nd\nx\ny\nz\n"
+        );
+        // Without a base everything is an addition.
+        let merge = merge_lines("", "a\n", "b\n");
+        assert_eq!(merge.contents, "a\nb\n");
+        assert!(merge.conflicts.is_empty());
+    }
+
+    #[test]
+    fn edit_delete() {
+        // An item edited here and deleted on disk stays.
+        assert_eq!(
+            merge("a\nb!\nc\nd\n", "a\nc\nd\n"),
+            ("a\nb!\nc\nd\n".to_string(), vec![conflict(&["b!"], &[])])
+        );
+        // Deleted here and edited on disk, the deletion wins and the edit is reported.
+        assert_eq!(
+            merge("a\nc\nd\n", "a\nb?\nc\nd\n"),
+            ("a\nc\nd\n".to_string(), vec![conflict(&[], &["b?"])])
+        );
+        // Edited on both sides, the local version is kept.
+        assert_eq!(
+            merge("a\nb!\nc\nd\n", "a\nb?\nc\nd\n"),
+            (
+                "a\nb!\nc\nd\n".to_string(),
+                vec![conflict(&["b!"], &["b?"])]
+            )
+        );
+        // Deleted on both sides.
+        assert_eq!(
+            merge("a\nc\nd\n", "a\nc\nd\n"),
+            ("a\nc\nd\n".to_string(), vec![])
+        );
+    }
+
+    #[test]
+    fn reorder() {
+        // Moved on one side, kept where it was moved to.
+        assert_eq!(merge(BASE, "b\nc\na\nd\n").0, "b\nc\na\nd\n");
+        assert_eq!(merge("a\nc\nb\nd\n", BASE).0, "a\nc\nb\nd\n");
+        // Moved on disk while edited here: not brought back as a second copy.
+        let (contents, _) = merge("a!\nb\nc\nd\n", "b\nc\nd\na\n");
+        assert_eq!(contents.matches('a').count(), 1, "{contents}");
+        assert!(contents.contains("a!\n"), "{contents}");
+        // Moved on disk while deleted here: stays deleted.
+        let (contents, _) = merge("b\nc\nd\n", "b\nc\nd\na\n");
+        assert_eq!(contents, "b\nc\nd\n");
+    }
+
+    #[test]
+    fn items_are_matched_by_id() {
+        let base = "x id:1\ny\nz\n";
+        // Edited here and edited and moved on disk: one item, the local text.
+        let merge = merge_lines(base, "x! id:1\ny\nz\n", "y\nz\nx? id:1\n");
+        assert_eq!(merge.contents, "x! id:1\ny\nz\n");
+        assert_eq!(merge.conflicts, [conflict(&["x! id:1"], &["x? id:1"])]);
+        // An id alone isn't enough, `id:` needs a number.
+        assert_eq!(line_id("a id:12 b"), Some("id:12"));
+        assert_eq!(line_id("a id: b id:x"), None);
+    }
+
+    #[test]
+    fn diff_of_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc\n", "a\nx\nc\n"),
+            [
+                Diff::Same("a".to_string()),
+                Diff::Removed("b".to_string()),
+                Diff::Added("x".to_string()),
+                Diff::Same("c".to_string()),
+            ]
+        );
+        assert!(diff_lines("", "").is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+// Committing a saved file to the git repository it is in.
+
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// Commits the file alone with `message`. Ok(false) when there is nothing to commit: the
+// file isn't in a git work tree or it has no changes.
+pub fn commit(file_path: &str, message: &str) -> Result<bool, String> {
+    let path = Path::new(file_path);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or(format!("invalid file name '{file_path}'"))?;
+
+    if git(dir, &["rev-parse", "--is-inside-work-tree"]).is_err() {
+        return Ok(false);
+    }
+    if git(dir, &["status", "--porcelain", "--", name])?.is_empty() {
+        return Ok(false);
+    }
+    git(dir, &["add", "--", name])?;
+    git(dir, &["commit", "--quiet", "-m", message, "--", name])?;
+    Ok(true)
+}
+
+// The output of git run in `dir`, or the first line it printed on failure.
+fn git(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|err| format!("could not run git: {err}"))?;
+
+    if output.status.success() {
+        return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    Err(stderr
+        .lines()
+        .chain(stdout.lines())
+        .find(|line| !line.trim().is_empty())
+        .map(|line| line.trim().to_string())
+        .unwrap_or(format!("git {} failed ({})", args[0], output.status)))
+}
@@ -0,0 +1,366 @@
+use ncurses::constants::*;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum KeyAction {
+    Up,
+    Down,
+    DragUp,
+    DragDown,
+    Indent,
+    Outdent,
+    Top,
+    Bottom,
+    Half,
+    Delete,
+    Insert,
+    Append,
+    Undo,
+    Redo,
+    Edit,
+    EditDate,
+    Block,
+    Swap,
+    Pick,
+    Gather,
+    InvertPicks,
+    SelectAll,
+    ClearSelection,
+    JumpDue(usize),
+    ToggleSubtasks,
+    FilterToday,
+    Tags,
+    Search,
+    Help,
+    Mark,
+    Transfer,
+    Cancel,
+    TogglePanel,
+    PrevTab,
+    NextTab,
+    Command,
+    Visual,
+    Lowercase,
+    Uppercase,
+    OpenFile,
+    CopyList,
+    NextFilter,
+    ClearFilters,
+    Quit,
+}
+
+// The names the actions are bound by in the config, e.g. `"g g" = go_top`.
+const NAMES: [(&str, KeyAction); 46] = [
+    ("go_up", KeyAction::Up),
+    ("go_down", KeyAction::Down),
+    ("drag_up", KeyAction::DragUp),
+    ("drag_down", KeyAction::DragDown),
+    ("indent", KeyAction::Indent),
+    ("outdent", KeyAction::Outdent),
+    ("go_top", KeyAction::Top),
+    ("go_bottom", KeyAction::Bottom),
+    ("go_half", KeyAction::Half),
+    ("delete", KeyAction::Delete),
+    ("insert", KeyAction::Insert),
+    ("append", KeyAction::Append),
+    ("undo", KeyAction::Undo),
+    ("redo", KeyAction::Redo),
+    ("edit", KeyAction::Edit),
+    ("edit_date", KeyAction::EditDate),
+    ("block", KeyAction::Block),
+    ("swap", KeyAction::Swap),
+    ("pick", KeyAction::Pick),
+    ("gather", KeyAction::Gather),
+    ("invert_picks", KeyAction::InvertPicks),
+    ("select_all", KeyAction::SelectAll),
+    ("clear_selection", KeyAction::ClearSelection),
+    ("jump_due_1", KeyAction::JumpDue(0)),
+    ("jump_due_2", KeyAction::JumpDue(1)),
+    ("jump_due_3", KeyAction::JumpDue(2)),
+    ("toggle_subtasks", KeyAction::ToggleSubtasks),
+    ("filter_today", KeyAction::FilterToday),
+    ("tags", KeyAction::Tags),
+    ("search", KeyAction::Search),
+    ("help", KeyAction::Help),
+    ("mark", KeyAction::Mark),
+    ("transfer", KeyAction::Transfer),
+    ("cancel", KeyAction::Cancel),
+    ("toggle_panel", KeyAction::TogglePanel),
+    ("prev_tab", KeyAction::PrevTab),
+    ("next_tab", KeyAction::NextTab),
+    ("command", KeyAction::Command),
+    ("visual", KeyAction::Visual),
+    ("lowercase", KeyAction::Lowercase),
+    ("uppercase", KeyAction::Uppercase),
+    ("open_file", KeyAction::OpenFile),
+    ("copy_list", KeyAction::CopyList),
+    ("next_filter", KeyAction::NextFilter),
+    ("clear_filters", KeyAction::ClearFilters),
+    ("quit", KeyAction::Quit),
+];
+
+impl KeyAction {
+    pub fn from_name(name: &str) -> Option<Self> {
+        NAMES
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, action)| *action)
+    }
+
+    pub fn name(&self) -> &'static str {
+        NAMES
+            .iter()
+            .find(|(_, action)| action == self)
+            .map(|(name, _)| *name)
+            .expect("every action has a name")
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            KeyAction::Up => "Move the cursor UP",
+            KeyAction::Down => "Move the cursor DOWN",
+            KeyAction::DragUp => "Drag item UP",
+            KeyAction::DragDown => "Drag item DOWN",
+            KeyAction::Indent => "Make current item a subtask of the one above it",
+            KeyAction::Outdent => "Move current subtask out to the level of its parent",
+            KeyAction::Top => "Jump to the TOP of the list",
+            KeyAction::Bottom => "Jump to the BOTTOM of the list",
+            KeyAction::Half => "Jump to the HALF of the list",
+            KeyAction::Delete => "Delete 'Done' item/subtask",
+            KeyAction::Insert => "Insert a new 'Todo' item",
+            KeyAction::Append => "Add a subtask to the current 'Todo' item",
+            KeyAction::Undo => "Undo last action",
+            KeyAction::Redo => "Redo the last undone action",
+            KeyAction::Edit => "Edit current item",
+            KeyAction::EditDate => "Edit completion date of current 'Done' item",
+            KeyAction::Block => "Mark current item as blocked by another/Unblock",
+            KeyAction::Swap => "Mark current item to swap/Swap it with the marked one",
+            KeyAction::Pick => "Pick current item (or the selection) to move/Unpick",
+            KeyAction::Gather => "Move the picked items below the cursor",
+            KeyAction::InvertPicks => "Pick the shown items that aren't picked, unpick the others",
+            KeyAction::SelectAll => "Select every shown item to delete/transfer/pick/tag at once",
+            KeyAction::ClearSelection => "Clear the selection and the picks",
+            KeyAction::JumpDue(_) => "Jump to the item in the 'Due soon' section",
+            KeyAction::ToggleSubtasks => "Hide subtasks",
+            KeyAction::FilterToday => "Show only items added/completed today",
+            KeyAction::Tags => "Browse the tags (also :tags)",
+            KeyAction::Search => "Search both panels (also :search <words>)",
+            KeyAction::Help => "Show this help",
+            KeyAction::Mark => "Mark current item as 'Done'",
+            KeyAction::Transfer => "Transfer item/Save edited item",
+            KeyAction::Cancel => "Cancel editing/inserting/blocking/swapping/picking",
+            KeyAction::TogglePanel => "Switch between 'Todos'/'Dones'",
+            KeyAction::PrevTab => "Switch to the PREVIOUS file tab",
+            KeyAction::NextTab => "Switch to the NEXT file tab",
+            KeyAction::Command => {
+                "Enter a command (save, saveas, open, print, copy, today, filter, search, tags, \
+                 retag, tag, untag, dupes, dedupe, done-all, undo, swap-panels, group)"
+            }
+            KeyAction::Visual => "Select a range to delete/transfer/pick/tag at once",
+            KeyAction::Lowercase => "Change current item to lower case (not tags)",
+            KeyAction::Uppercase => "Change current item to upper case (not tags)",
+            KeyAction::OpenFile => "Open the file named in current item in a new tab",
+            KeyAction::NextFilter => {
+                "Cycle through the filter presets of the config (also :filter)"
+            }
+            KeyAction::ClearFilters => "Clear the filter and show the hidden subtasks",
+            KeyAction::CopyList => "Copy the shown list to the clipboard (also :copy)",
+            KeyAction::Quit => "Quit (also CTRL+c)",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct KeyMap {
+    bindings: Vec<(i32, KeyAction)>,
+    // Two keys pressed one after the other, the first one does nothing on its own.
+    chords: Vec<([i32; 2], KeyAction)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let bindings = vec![
+            ('k' as i32, KeyAction::Up),
+            (KEY_UP, KeyAction::Up),
+            ('j' as i32, KeyAction::Down),
+            (KEY_DOWN, KeyAction::Down),
+            ('K' as i32, KeyAction::DragUp),
+            (KEY_SR, KeyAction::DragUp),
+            ('J' as i32, KeyAction::DragDown),
+            (KEY_SF, KeyAction::DragDown),
+            ('>' as i32, KeyAction::Indent),
+            ('<' as i32, KeyAction::Outdent),
+            (KEY_BTAB, KeyAction::Outdent),
+            ('G' as i32, KeyAction::Bottom),
+            ('h' as i32, KeyAction::Half),
+            ('d' as i32, KeyAction::Delete),
+            ('i' as i32, KeyAction::Insert),
+            ('a' as i32, KeyAction::Append),
+            ('u' as i32, KeyAction::Undo),
+            (18, KeyAction::Redo), // CTRL+r
+            ('r' as i32, KeyAction::Edit),
+            ('R' as i32, KeyAction::EditDate),
+            ('b' as i32, KeyAction::Block),
+            ('s' as i32, KeyAction::Swap),
+            ('m' as i32, KeyAction::Pick),
+            ('p' as i32, KeyAction::Gather),
+            ('M' as i32, KeyAction::InvertPicks),
+            (1, KeyAction::SelectAll),       // CTRL+a
+            (24, KeyAction::ClearSelection), // CTRL+x
+            ('1' as i32, KeyAction::JumpDue(0)),
+            ('2' as i32, KeyAction::JumpDue(1)),
+            ('3' as i32, KeyAction::JumpDue(2)),
+            ('t' as i32, KeyAction::ToggleSubtasks),
+            ('T' as i32, KeyAction::FilterToday),
+            ('F' as i32, KeyAction::NextFilter),
+            ('C' as i32, KeyAction::ClearFilters),
+            (20, KeyAction::Tags), // CTRL+t
+            ('/' as i32, KeyAction::Search),
+            ('?' as i32, KeyAction::Help),
+            (' ' as i32, KeyAction::Mark),
+            ('\n' as i32, KeyAction::Transfer),
+            (27, KeyAction::Cancel),
+            ('\t' as i32, KeyAction::TogglePanel),
+            ('[' as i32, KeyAction::PrevTab),
+            (']' as i32, KeyAction::NextTab),
+            (':' as i32, KeyAction::Command),
+            ('V' as i32, KeyAction::Visual),
+            ('Y' as i32, KeyAction::CopyList),
+            ('q' as i32, KeyAction::Quit),
+        ];
+        let chords = vec![
+            (['g' as i32, 'g' as i32], KeyAction::Top),
+            (['g' as i32, 'e' as i32], KeyAction::Bottom),
+            (['g' as i32, 'u' as i32], KeyAction::Lowercase),
+            (['g' as i32, 'U' as i32], KeyAction::Uppercase),
+            (['g' as i32, 'f' as i32], KeyAction::OpenFile),
+        ];
+
+        Self { bindings, chords }
+    }
+}
+
+impl KeyMap {
+    pub fn get(&self, key: i32) -> Option<KeyAction> {
+        self.bindings
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, action)| *action)
+    }
+
+    // The first key bound to the action, chords aside.
+    pub fn key_for(&self, action: KeyAction) -> Option<i32> {
+        self.bindings
+            .iter()
+            .find(|(_, a)| *a == action)
+            .map(|(key, _)| *key)
+    }
+
+    // Whether `key` starts a chord, the action then waits for the second key.
+    pub fn is_prefix(&self, key: i32) -> bool {
+        self.chords.iter().any(|([first, _], _)| *first == key)
+    }
+
+    pub fn chord(&self, keys: [i32; 2]) -> Option<KeyAction> {
+        self.chords
+            .iter()
+            .find(|(k, _)| *k == keys)
+            .map(|(_, action)| *action)
+    }
+
+    // Binds the chord written as two characters apart, e.g. `g g`, in place of the action
+    // it had, or unbinds it without an action. A key bound on its own can't start a chord,
+    // its action would never be reached.
+    pub fn bind_chord(&mut self, keys: &str, action: Option<KeyAction>) -> Result<(), String> {
+        let parsed: Option<Vec<i32>> = keys
+            .split_whitespace()
+            .map(|key| {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) if ch.is_ascii_graphic() => Some(ch as i32),
+                    _ => None,
+                }
+            })
+            .collect();
+        let Some(&[first, second]) = parsed.as_deref() else {
+            return Err(format!(
+                "invalid chord '{keys}', expected two characters apart like 'g g'"
+            ));
+        };
+        if let Some(bound) = self.get(first) {
+            return Err(format!(
+                "invalid chord '{keys}', '{}' is bound to {} on its own",
+                key_name(first),
+                bound.name()
+            ));
+        }
+
+        self.chords.retain(|(k, _)| *k != [first, second]);
+        if let Some(action) = action {
+            self.chords.push(([first, second], action));
+        }
+        Ok(())
+    }
+
+    // The chords as they are written in the config, `"g g" = go_top`.
+    pub fn chord_settings(&self) -> Vec<(String, &'static str)> {
+        self.chords
+            .iter()
+            .map(|([first, second], action)| {
+                (
+                    format!("{} {}", key_name(*first), key_name(*second)),
+                    action.name(),
+                )
+            })
+            .collect()
+    }
+
+    pub fn describe(&self) -> Vec<(&'static str, Vec<String>)> {
+        let mut described: Vec<(&'static str, Vec<String>)> = Vec::new();
+
+        let chords = self.chords.iter().map(|([first, second], action)| {
+            (
+                format!("{} {}", key_name(*first), key_name(*second)),
+                action,
+            )
+        });
+        for (name, action) in self
+            .bindings
+            .iter()
+            .map(|(key, action)| (key_name(*key), action))
+            .chain(chords)
+        {
+            let desc = action.description();
+            match described.iter_mut().find(|(d, _)| *d == desc) {
+                Some((_, keys)) => keys.push(name),
+                None => described.push((desc, vec![name])),
+            }
+        }
+
+        described
+    }
+}
+
+pub fn key_name(key: i32) -> String {
+    match key {
+        KEY_UP => "↑".to_string(),
+        KEY_DOWN => "↓".to_string(),
+        KEY_LEFT => "←".to_string(),
+        KEY_RIGHT => "→".to_string(),
+        KEY_SR => "SHIFT+↑".to_string(),
+        KEY_SF => "SHIFT+↓".to_string(),
+        KEY_BTAB => "SHIFT+TAB".to_string(),
+        KEY_HOME => "HOME".to_string(),
+        KEY_END => "END".to_string(),
+        KEY_DC => "DEL".to_string(),
+        KEY_BACKSPACE | 127 => "BACKSPACE".to_string(),
+        9 => "TAB".to_string(),
+        10 => "ENTER".to_string(),
+        27 => "ESC".to_string(),
+        32 => "SPACE".to_string(),
+        1..=26 => format!("CTRL+{}", (key as u8 + b'a' - 1) as char),
+        _ => match char::from_u32(key as u32) {
+            Some(ch) if !ch.is_control() => ch.to_string(),
+            _ => format!("<{key}>"),
+        },
+    }
+}
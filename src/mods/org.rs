@@ -0,0 +1,109 @@
+// Org-mode list files: `* TODO` and `* DONE` headings are the items, the headings under
+// them their subtasks. CLOSED and DEADLINE of the planning line right below a heading
+// are its completion and due dates. Every other line is kept as it is, see parse_org().
+use std::path::Path;
+use std::sync::OnceLock;
+
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Format {
+    Todo,
+    Org,
+}
+
+impl Format {
+    pub fn from_str(value: &str) -> Option<Self> {
+        match value {
+            "todo" => Some(Format::Todo),
+            "org" => Some(Format::Org),
+            _ => None,
+        }
+    }
+
+    // The format of a file no `--format` was given for, by its extension.
+    pub fn of_path(path: &str) -> Self {
+        if Path::new(path).extension().is_some_and(|ext| ext == "org") {
+            Format::Org
+        } else {
+            Format::Todo
+        }
+    }
+}
+
+pub struct Heading<'a> {
+    pub stars: usize,
+    // Whether the keyword is DONE, None for a heading without TODO or DONE.
+    pub done: Option<bool>,
+    pub text: &'a str,
+}
+
+pub fn heading(line: &str) -> Option<Heading<'_>> {
+    static RE_HEADING: OnceLock<Regex> = OnceLock::new();
+    let re_heading = RE_HEADING
+        .get_or_init(|| Regex::new(r"^(\*+)[ \t]+(?:(TODO|DONE)(?:[ \t]+|$))?(.*)$").unwrap());
+
+    let caps = re_heading.captures(line)?;
+    Some(Heading {
+        stars: caps[1].len(),
+        done: caps.get(2).map(|keyword| keyword.as_str() == "DONE"),
+        text: caps.get(3).map_or("", |text| text.as_str()),
+    })
+}
+
+pub struct Planning {
+    pub closed: Option<NaiveDateTime>,
+    pub deadline: Option<NaiveDate>,
+    // `SCHEDULED: <...>` as written, there is no such date to change it with.
+    pub scheduled: Option<String>,
+}
+
+// A line of CLOSED, DEADLINE and SCHEDULED timestamps. None when there is anything else
+// on it or a date doesn't read, the line is then kept like any other.
+pub fn planning(line: &str) -> Option<Planning> {
+    static RE_LINE: OnceLock<Regex> = OnceLock::new();
+    static RE_ENTRY: OnceLock<Regex> = OnceLock::new();
+    let re_line = RE_LINE.get_or_init(|| {
+        Regex::new(r"^\s*(?:(?:CLOSED|DEADLINE|SCHEDULED):\s*(?:<[^>]*>|\[[^\]]*\])\s*)+$").unwrap()
+    });
+    let re_entry = RE_ENTRY.get_or_init(|| {
+        Regex::new(r"(CLOSED|DEADLINE|SCHEDULED):\s*(?:<([^>]*)>|\[([^\]]*)\])").unwrap()
+    });
+
+    if !re_line.is_match(line) {
+        return None;
+    }
+    let mut planning = Planning {
+        closed: None,
+        deadline: None,
+        scheduled: None,
+    };
+    for caps in re_entry.captures_iter(line) {
+        let stamp = caps.get(2).or(caps.get(3))?.as_str();
+        match &caps[1] {
+            "CLOSED" => planning.closed = Some(timestamp(stamp)?),
+            "DEADLINE" => planning.deadline = Some(timestamp(stamp)?.date()),
+            _ => planning.scheduled = Some(caps[0].to_string()),
+        }
+    }
+    Some(planning)
+}
+
+// `2026-10-16 Fri 14:30` without the brackets, the day name and the time are optional.
+fn timestamp(stamp: &str) -> Option<NaiveDateTime> {
+    let mut words = stamp.split_whitespace();
+    let date = NaiveDate::parse_from_str(words.next()?, "%Y-%m-%d").ok()?;
+    let time = words
+        .find_map(|word| NaiveTime::parse_from_str(word, "%H:%M").ok())
+        .unwrap_or_default();
+    Some(date.and_time(time))
+}
+
+pub fn closed_stamp(closed: NaiveDateTime) -> String {
+    format!("CLOSED: [{}]", closed.format("%Y-%m-%d %a %H:%M"))
+}
+
+pub fn deadline_stamp(deadline: NaiveDate) -> String {
+    format!("DEADLINE: <{}>", deadline.format("%Y-%m-%d %a"))
+}
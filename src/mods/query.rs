@@ -0,0 +1,418 @@
+// The small language of the filter presets in the config, e.g. `+work and not @someday`
+// or `pri<=B or overdue`. An item's text is all a query looks at.
+//
+//   query := and ("or" and)*
+//   and   := not ("and" not)*
+//   not   := "not" not | "(" query ")" | term
+//   term  := +tag | @tag | pri<op><A-Z> | due<op><date> | overdue | word
+//
+// `<op>` is one of `:`, `<`, `<=`, `>`, `>=` (`:` is equality), a `<date>` is
+// `YYYY-MM-DD`, `today`, `tomorrow`, `yesterday` or `today+N`/`today-N` days. Priorities
+// compare as letters, so `pri<=B` is A or B. Items without a priority or due date don't
+// match the terms on them. Any other word is looked for in the text, ignoring case.
+
+use std::fmt;
+
+use chrono::{Duration, NaiveDate};
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Cmp {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Cmp {
+    fn holds<T: PartialOrd>(self, lhs: T, rhs: T) -> bool {
+        match self {
+            Cmp::Eq => lhs == rhs,
+            Cmp::Lt => lhs < rhs,
+            Cmp::Le => lhs <= rhs,
+            Cmp::Gt => lhs > rhs,
+            Cmp::Ge => lhs >= rhs,
+        }
+    }
+}
+
+impl fmt::Display for Cmp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            Cmp::Eq => ":",
+            Cmp::Lt => "<",
+            Cmp::Le => "<=",
+            Cmp::Gt => ">",
+            Cmp::Ge => ">=",
+        };
+        write!(f, "{op}")
+    }
+}
+
+// Dates relative to today are resolved when the query runs, so a preset kept open over
+// midnight stays right.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Day {
+    Today(i64),
+    On(NaiveDate),
+}
+
+impl Day {
+    fn resolve(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            Day::Today(offset) => today + Duration::days(offset),
+            Day::On(date) => date,
+        }
+    }
+}
+
+impl fmt::Display for Day {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Day::Today(0) => write!(f, "today"),
+            Day::Today(offset) => write!(f, "today{offset:+}"),
+            Day::On(date) => write!(f, "{}", date.format("%Y-%m-%d")),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Query {
+    Tag(String),
+    Word(String),
+    Priority(Cmp, char),
+    Due(Cmp, Day),
+    Not(Box<Query>),
+    And(Box<Query>, Box<Query>),
+    Or(Box<Query>, Box<Query>),
+}
+
+impl Query {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let tokens = tokenize(input);
+        let mut parser = Parser { tokens, pos: 0 };
+        let query = parser.query()?;
+        match parser.peek() {
+            None => Ok(query),
+            Some((col, ")")) => Err(format!("column {col}: unmatched ')'")),
+            Some((col, word)) => Err(format!(
+                "column {col}: expected 'and' or 'or' before '{word}'"
+            )),
+        }
+    }
+
+    pub fn matches(&self, text: &str, today: NaiveDate) -> bool {
+        match self {
+            Query::Tag(tag) => text.split_whitespace().any(|word| word == tag),
+            Query::Word(word) => text.to_lowercase().contains(word),
+            Query::Priority(cmp, letter) => {
+                token_value(text, "pri:").is_some_and(|p| match p.as_bytes() {
+                    [p] if p.is_ascii_uppercase() => cmp.holds(*p as char, *letter),
+                    _ => false,
+                })
+            }
+            Query::Due(cmp, day) => token_value(text, "due:")
+                .and_then(|due| NaiveDate::parse_from_str(due, "%Y-%m-%d").ok())
+                .is_some_and(|due| cmp.holds(due, day.resolve(today))),
+            Query::Not(query) => !query.matches(text, today),
+            Query::And(lhs, rhs) => lhs.matches(text, today) && rhs.matches(text, today),
+            Query::Or(lhs, rhs) => lhs.matches(text, today) || rhs.matches(text, today),
+        }
+    }
+}
+
+// Written back in the syntax it's parsed from, with the parentheses the precedence needs,
+// so `config check` can show a preset the way it's understood.
+impl fmt::Display for Query {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let grouped = |query: &Query, wrap: bool| {
+            if wrap {
+                format!("({query})")
+            } else {
+                query.to_string()
+            }
+        };
+        match self {
+            Query::Tag(tag) => write!(f, "{tag}"),
+            Query::Word(word) => write!(f, "{word}"),
+            Query::Priority(cmp, letter) => write!(f, "pri{cmp}{letter}"),
+            Query::Due(cmp, day) => write!(f, "due{cmp}{day}"),
+            Query::Not(query) => {
+                let wrap = matches!(**query, Query::And(..) | Query::Or(..));
+                write!(f, "not {}", grouped(query, wrap))
+            }
+            Query::And(lhs, rhs) => write!(
+                f,
+                "{} and {}",
+                grouped(lhs, matches!(**lhs, Query::Or(..))),
+                grouped(rhs, matches!(**rhs, Query::And(..) | Query::Or(..)))
+            ),
+            Query::Or(lhs, rhs) => write!(
+                f,
+                "{} or {}",
+                lhs,
+                grouped(rhs, matches!(**rhs, Query::Or(..)))
+            ),
+        }
+    }
+}
+
+fn token_value<'a>(text: &'a str, key: &str) -> Option<&'a str> {
+    text.split_whitespace()
+        .find_map(|word| word.strip_prefix(key))
+}
+
+// Words with the column (from 1) they start at, parentheses are words of their own.
+fn tokenize(input: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (idx, c) in input.char_indices() {
+        if c.is_whitespace() || c == '(' || c == ')' {
+            if let Some(s) = start.take() {
+                tokens.push((s, &input[s..idx]));
+            }
+            if !c.is_whitespace() {
+                tokens.push((idx, &input[idx..idx + 1]));
+            }
+        } else if start.is_none() {
+            start = Some(idx);
+        }
+    }
+    if let Some(s) = start {
+        tokens.push((s, &input[s..]));
+    }
+    tokens
+        .into_iter()
+        .map(|(idx, word)| (input[..idx].chars().count() + 1, word))
+        .collect()
+}
+
+struct Parser<'a> {
+    tokens: Vec<(usize, &'a str)>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<(usize, &'a str)> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    // Takes the next word if it's the keyword, in any case.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self
+            .peek()
+            .is_some_and(|(_, word)| word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn query(&mut self) -> Result<Query, String> {
+        let mut query = self.and()?;
+        while self.keyword("or") {
+            query = Query::Or(Box::new(query), Box::new(self.and()?));
+        }
+        Ok(query)
+    }
+
+    fn and(&mut self) -> Result<Query, String> {
+        let mut query = self.not()?;
+        while self.keyword("and") {
+            query = Query::And(Box::new(query), Box::new(self.not()?));
+        }
+        Ok(query)
+    }
+
+    fn not(&mut self) -> Result<Query, String> {
+        if self.keyword("not") {
+            return Ok(Query::Not(Box::new(self.not()?)));
+        }
+        match self.next() {
+            None => Err("unexpected end, expected a term".to_string()),
+            Some((col, "(")) => {
+                let query = self.query()?;
+                match self.next() {
+                    Some((_, ")")) => Ok(query),
+                    Some((at, word)) => Err(format!(
+                        "column {at}: expected ')' for the '(' at column {col}, found '{word}'"
+                    )),
+                    None => Err(format!("column {col}: '(' is never closed")),
+                }
+            }
+            Some((col, word)) => term(word).map_err(|err| format!("column {col}: {err}")),
+        }
+    }
+}
+
+fn term(word: &str) -> Result<Query, String> {
+    let lower = word.to_lowercase();
+    match lower.as_str() {
+        ")" => return Err("unexpected ')', expected a term".to_string()),
+        "and" | "or" => return Err(format!("unexpected '{word}', expected a term")),
+        "overdue" => return Ok(Query::Due(Cmp::Lt, Day::Today(0))),
+        _ => {}
+    }
+    if word.len() > 1 && (word.starts_with('+') || word.starts_with('@')) {
+        return Ok(Query::Tag(word.to_string()));
+    }
+    if let Some(rest) = word.strip_prefix("pri") {
+        if let Some((cmp, value)) = comparison(rest) {
+            return match value.as_bytes() {
+                [p] if p.is_ascii_alphabetic() => {
+                    Ok(Query::Priority(cmp, p.to_ascii_uppercase() as char))
+                }
+                _ => Err(format!("invalid priority '{value}', expected a letter")),
+            };
+        }
+    }
+    if let Some(rest) = word.strip_prefix("due") {
+        if let Some((cmp, value)) = comparison(rest) {
+            return day(value).map(|day| Query::Due(cmp, day)).ok_or(format!(
+                "invalid date '{value}', expected YYYY-MM-DD, today, tomorrow, yesterday or today±N"
+            ));
+        }
+    }
+    Ok(Query::Word(lower))
+}
+
+fn comparison(s: &str) -> Option<(Cmp, &str)> {
+    [
+        ("<=", Cmp::Le),
+        (">=", Cmp::Ge),
+        ("<", Cmp::Lt),
+        (">", Cmp::Gt),
+        (":", Cmp::Eq),
+        ("=", Cmp::Eq),
+    ]
+    .into_iter()
+    .find_map(|(op, cmp)| s.strip_prefix(op).map(|value| (cmp, value)))
+}
+
+fn day(value: &str) -> Option<Day> {
+    match value.to_lowercase().as_str() {
+        "today" => return Some(Day::Today(0)),
+        "tomorrow" => return Some(Day::Today(1)),
+        "yesterday" => return Some(Day::Today(-1)),
+        _ => {}
+    }
+    if let Some(offset) = value.strip_prefix("today") {
+        let days = match offset.as_bytes().first() {
+            Some(b'+') => offset[1..].parse::<u32>().ok()? as i64,
+            Some(b'-') => -(offset[1..].parse::<u32>().ok()? as i64),
+            _ => return None,
+        };
+        return Some(Day::Today(days));
+    }
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(Day::On)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn matches(query: &str, text: &str) -> bool {
+        Query::parse(query)
+            .unwrap()
+            .matches(text, date("2026-05-10"))
+    }
+
+    #[test]
+    fn parse_follows_the_precedence() {
+        // `and` binds tighter than `or`, `not` tighter than both.
+        for (input, shown) in [
+            ("+a or +b and +c", "+a or +b and +c"),
+            ("(+a or +b) and +c", "(+a or +b) and +c"),
+            ("not +a and +b", "not +a and +b"),
+            ("not (+a and +b)", "not (+a and +b)"),
+            ("+a or (+b or +c)", "+a or (+b or +c)"),
+            ("((+a))", "+a"),
+            ("NOT +a AND pri=b", "not +a and pri:B"),
+            ("due<=today+7 or overdue", "due<=today+7 or due<today"),
+            ("Word due:2026-01-02", ""),
+        ] {
+            match Query::parse(input) {
+                Ok(query) => assert_eq!(query.to_string(), shown, "{input}"),
+                Err(_) => assert_eq!(shown, "", "{input}"),
+            }
+        }
+        // What's shown reads back as the same query.
+        let query = Query::parse("not (+a or @b) and (pri>=C or due>yesterday)").unwrap();
+        assert_eq!(Query::parse(&query.to_string()).unwrap(), query);
+    }
+
+    #[test]
+    fn parse_errors_name_the_column() {
+        for (input, err) in [
+            ("", "unexpected end, expected a term"),
+            ("+a and", "unexpected end, expected a term"),
+            ("+a +b", "column 4: expected 'and' or 'or' before '+b'"),
+            ("+a)", "column 3: unmatched ')'"),
+            ("(+a", "column 1: '(' is never closed"),
+            (
+                "(+a +b)",
+                "column 5: expected ')' for the '(' at column 1, found '+b'",
+            ),
+            ("or +a", "column 1: unexpected 'or', expected a term"),
+            (
+                "pri:AB",
+                "column 1: invalid priority 'AB', expected a letter",
+            ),
+            ("+é and due<soon", "column 8: invalid date 'soon'"),
+        ] {
+            let got = Query::parse(input).unwrap_err();
+            assert!(got.starts_with(err), "{input}: {got}");
+        }
+    }
+
+    #[test]
+    fn terms_match_the_text() {
+        assert!(matches("+work", "call +work"));
+        assert!(!matches("+work", "call +workshop"));
+        assert!(matches("@home", "@home fix the tap"));
+        assert!(matches("TAP", "@home fix the tap"));
+
+        assert!(matches("pri<=B", "x pri:A"));
+        assert!(matches("pri<=B", "x pri:B"));
+        assert!(!matches("pri<=B", "x pri:C"));
+        // Without a priority, or with an odd one, neither a term nor its opposite holds.
+        assert!(!matches("pri>=A", "x"));
+        assert!(!matches("pri<Z", "x pri:ab"));
+        assert!(matches("not pri:A", "x"));
+
+        assert!(matches("overdue", "pay due:2026-05-09"));
+        assert!(!matches("overdue", "pay due:2026-05-10"));
+        assert!(matches("due:today", "pay due:2026-05-10"));
+        assert!(matches("due<=today+7", "pay due:2026-05-17"));
+        assert!(!matches("due<=today+7", "pay due:2026-05-18"));
+        assert!(matches("due>=yesterday and due<tomorrow", "due:2026-05-09"));
+        assert!(matches("due:2026-05-10", "due:2026-05-10"));
+        assert!(!matches("due<today", "due:someday"));
+
+        assert!(matches("+work and not @someday", "+work now"));
+        assert!(!matches("+work and not @someday", "+work @someday"));
+        assert!(matches("pri:A or overdue", "due:2026-01-01"));
+    }
+
+    #[test]
+    fn relative_days_follow_today() {
+        let query = Query::parse("due:today").unwrap();
+        assert!(query.matches("due:2026-05-10", date("2026-05-10")));
+        assert!(query.matches("due:2026-05-11", date("2026-05-11")));
+        assert_eq!(day("today-3"), Some(Day::Today(-3)));
+        assert_eq!(day("today3"), None);
+        assert_eq!(day("today+"), None);
+    }
+}
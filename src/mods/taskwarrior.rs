@@ -0,0 +1,415 @@
+// Tasks of a Taskwarrior `task export`, read once to move them into a list: the JSON is
+// an array of task objects (or one object per line in old versions), only the fields
+// the list has a place for are read.
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Utc};
+
+enum Json {
+    // Numbers, booleans and null: no field read from a task is one.
+    Scalar,
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.bump();
+        }
+    }
+
+    fn expect(&mut self, word: &str) -> Result<(), String> {
+        if self.text[self.pos..].starts_with(word) {
+            self.pos += word.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected '{word}'")))
+        }
+    }
+
+    fn error(&self, msg: &str) -> String {
+        let line = self.text[..self.pos].lines().count().max(1);
+        format!("line {line}: {msg}")
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_ws();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Json::String),
+            Some('t') => self.expect("true").map(|_| Json::Scalar),
+            Some('f') => self.expect("false").map(|_| Json::Scalar),
+            Some('n') => self.expect("null").map(|_| Json::Scalar),
+            Some(ch) if ch == '-' || ch.is_ascii_digit() => self.number(),
+            Some(ch) => Err(self.error(&format!("unexpected '{ch}'"))),
+            None => Err(self.error("unexpected end")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.bump();
+        let mut fields = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_ws();
+            if self.peek() != Some('"') {
+                return Err(self.error("expected a key"));
+            }
+            let key = self.string()?;
+            self.skip_ws();
+            self.expect(":")?;
+            fields.push((key, self.value()?));
+            self.skip_ws();
+            match self.bump() {
+                Some(',') => continue,
+                Some('}') => return Ok(Json::Object(fields)),
+                _ => return Err(self.error("expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.bump();
+        let mut values = Vec::new();
+        self.skip_ws();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_ws();
+            match self.bump() {
+                Some(',') => continue,
+                Some(']') => return Ok(Json::Array(values)),
+                _ => return Err(self.error("expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.bump();
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.bump() {
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('u') => s.push(self.unicode()?),
+                    Some(ch @ ('"' | '\\' | '/')) => s.push(ch),
+                    _ => return Err(self.error("invalid escape")),
+                },
+                Some(ch) => s.push(ch),
+                None => return Err(self.error("unterminated string")),
+            }
+        }
+    }
+
+    // The code of a `\u` escape, two of them for characters past the BMP. A lone
+    // surrogate isn't a character, `char::from_u32` refuses the low one.
+    fn unicode(&mut self) -> Result<char, String> {
+        let high = self.hex()?;
+        let code = match high {
+            0xD800..=0xDBFF => {
+                self.expect("\\u")?;
+                match self.hex()? {
+                    low @ 0xDC00..=0xDFFF => 0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00),
+                    _ => return Err(self.error("invalid surrogate pair")),
+                }
+            }
+            _ => high,
+        };
+        char::from_u32(code).ok_or(self.error("invalid \\u escape"))
+    }
+
+    // Exactly four hex digits, `from_str_radix` alone would take a sign too.
+    fn hex(&mut self) -> Result<u32, String> {
+        let digits = self.text.get(self.pos..self.pos + 4).unwrap_or("");
+        if digits.len() != 4 || !digits.chars().all(|ch| ch.is_ascii_hexdigit()) {
+            return Err(self.error("invalid \\u escape"));
+        }
+        self.pos += 4;
+        Ok(u32::from_str_radix(digits, 16).unwrap())
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|ch| ch.is_ascii_digit() || "+-.eE".contains(ch))
+        {
+            self.bump();
+        }
+        self.text[start..self.pos]
+            .parse::<f64>()
+            .map(|_| Json::Scalar)
+            .map_err(|_| self.error("invalid number"))
+    }
+}
+
+pub struct Task {
+    pub uuid: Option<String>,
+    pub status: String,
+    pub description: String,
+    pub project: Option<String>,
+    pub tags: Vec<String>,
+    pub due: Option<NaiveDate>,
+    pub wait: Option<NaiveDate>,
+    pub priority: Option<String>,
+    pub entry: Option<DateTime<Local>>,
+    pub end: Option<DateTime<Local>>,
+}
+
+impl Task {
+    fn from_json(json: &Json) -> Option<Self> {
+        let string = |key: &str| json.get(key).and_then(Json::as_str).map(String::from);
+        let date = |key: &str| json.get(key).and_then(Json::as_str).and_then(timestamp);
+        Some(Self {
+            uuid: string("uuid"),
+            status: string("status").unwrap_or_else(|| "pending".to_string()),
+            description: string("description")?
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" "),
+            project: string("project"),
+            tags: match json.get("tags") {
+                Some(Json::Array(tags)) => tags
+                    .iter()
+                    .filter_map(Json::as_str)
+                    .map(String::from)
+                    .collect(),
+                _ => Vec::new(),
+            },
+            due: date("due").map(|due| due.date_naive()),
+            wait: date("wait").map(|wait| wait.date_naive()),
+            priority: string("priority"),
+            entry: date("entry"),
+            end: date("end"),
+        })
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.status == "completed"
+    }
+
+    // Deleted tasks and the templates of recurring ones aren't things to do, the
+    // instances of a recurring task are pending tasks of their own.
+    pub fn is_kept(&self) -> bool {
+        matches!(self.status.as_str(), "pending" | "waiting" | "completed")
+    }
+
+    // The item text: the project as a `+project` tag, the tags as `@tags`, the priority
+    // as `pri:A` to `pri:C` like the todo.txt import, and `due:`, `wait:` and `tw:` tokens.
+    pub fn text(&self) -> String {
+        let mut text = self.description.clone();
+        if let Some(project) = &self.project {
+            text.push_str(&format!(" +{project}"));
+        }
+        for tag in self.tags.iter() {
+            text.push_str(&format!(" @{tag}"));
+        }
+        let priority = match self.priority.as_deref() {
+            Some("H") => Some("A"),
+            Some("M") => Some("B"),
+            Some("L") => Some("C"),
+            _ => None,
+        };
+        if let Some(priority) = priority {
+            text.push_str(&format!(" pri:{priority}"));
+        }
+        if let Some(due) = self.due {
+            text.push_str(&format!(" due:{}", due.format("%Y-%m-%d")));
+        }
+        if let Some(wait) = self.wait {
+            text.push_str(&format!(" wait:{}", wait.format("%Y-%m-%d")));
+        }
+        // The whole UUID: the 8 digits Taskwarrior shows can be shared by two tasks.
+        if let Some(uuid) = &self.uuid {
+            text.push_str(&format!(" tw:{uuid}"));
+        }
+        text
+    }
+}
+
+// Taskwarrior writes its dates in UTC as `20261016T093000Z`.
+fn timestamp(value: &str) -> Option<DateTime<Local>> {
+    let naive = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ").ok()?;
+    Some(DateTime::<Utc>::from_naive_utc_and_offset(naive, Utc).with_timezone(&Local))
+}
+
+pub fn parse(content: &str) -> Result<Vec<Task>, String> {
+    let mut parser = Parser {
+        text: content,
+        pos: 0,
+    };
+    let mut values = Vec::new();
+    loop {
+        parser.skip_ws();
+        match parser.peek() {
+            None => break,
+            // Old versions write the objects one per line, with commas between them.
+            Some(',') => drop(parser.bump()),
+            Some(_) => match parser.value()? {
+                Json::Array(tasks) => values.extend(tasks),
+                task => values.push(task),
+            },
+        }
+    }
+    values
+        .iter()
+        .map(|value| Task::from_json(value).ok_or("a task without a description".to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descriptions(content: &str) -> Vec<String> {
+        parse(content)
+            .unwrap()
+            .into_iter()
+            .map(|task| task.description)
+            .collect()
+    }
+
+    fn error(content: &str) -> String {
+        parse(content).err().unwrap()
+    }
+
+    #[test]
+    fn array_and_one_object_per_line() {
+        let array = r#"[
+{"id":1,"description":"one","status":"pending","urgency":1.8,"tags":["a","b"]},
+{"id":0,"description":"two","status":"completed","end":"20261016T093000Z"}
+]"#;
+        let lines = "{\"description\":\"one\"},\n{\"description\":\"two\"}\n";
+        assert_eq!(descriptions(array), ["one", "two"]);
+        assert_eq!(descriptions(lines), ["one", "two"]);
+        assert_eq!(descriptions(&lines.replace(",\n", "\n")), ["one", "two"]);
+        assert!(descriptions("[]\n").is_empty());
+        assert!(descriptions("").is_empty());
+
+        let tasks = parse(array).unwrap();
+        assert_eq!(tasks[0].tags, ["a", "b"]);
+        assert!(tasks[1].is_done());
+        assert_eq!(
+            tasks[1].end.unwrap().with_timezone(&Utc).to_rfc3339(),
+            "2026-10-16T09:30:00+00:00"
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            descriptions(r#"{"description":"a \"b\" c\\d e\/f"}"#),
+            [r#"a "b" c\d e/f"#]
+        );
+        // Control characters are whitespace, joined to one space like line breaks.
+        assert_eq!(
+            descriptions(r#"{"description":"line\nnext\ttab\r\bx\fy"}"#),
+            ["line next tab \u{8}x y"]
+        );
+        assert_eq!(
+            descriptions(r#"{"description":"café 日本"}"#),
+            ["café 日本"]
+        );
+    }
+
+    #[test]
+    fn surrogate_pairs() {
+        assert_eq!(descriptions(r#"{"description":"🎉 𝄞"}"#), ["🎉 𝄞"]);
+        for bad in [
+            r#"{"description":"\ud83c"}"#,
+            r#"{"description":"\ud83c x"}"#,
+            r#"{"description":"\ud83cA"}"#,
+            r#"{"description":"\ud83c\ud83c"}"#,
+            r#"{"description":"\udf89"}"#,
+        ] {
+            assert!(parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn bad_input() {
+        for (bad, msg) in [
+            (r#"{"description":"\u+041"}"#, "line 1: invalid \\u escape"),
+            (r#"{"description":"\u04"}"#, "line 1: invalid \\u escape"),
+            (r#"{"description":"\x"}"#, "line 1: invalid escape"),
+            ("{\"description\":\"open", "line 1: unterminated string"),
+            (
+                "[{\"description\":\"a\"}\n{\"description\":\"b\"}]",
+                "line 2: expected ',' or ']'",
+            ),
+            ("{\"description\" \"a\"}", "line 1: expected ':'"),
+            ("{description:\"a\"}", "line 1: expected a key"),
+            ("{\"description\":\"a\",}", "line 1: expected a key"),
+            ("{\"description\":\"a\"", "line 1: expected ',' or '}'"),
+            ("[{\"description\":\"a\"}", "line 1: expected ',' or ']'"),
+            (
+                "{\"description\":\"a\",\"x\":tru}",
+                "line 1: expected 'true'",
+            ),
+            (
+                "{\"description\":\"a\",\"x\":1.2.3}",
+                "line 1: invalid number",
+            ),
+            ("Error: no matches", "line 1: unexpected 'E'"),
+            ("{\"status\":\"pending\"}", "a task without a description"),
+        ] {
+            assert_eq!(error(bad), msg, "{bad}");
+        }
+    }
+
+    #[test]
+    fn full_uuid_in_the_text() {
+        let tasks = parse(
+            r#"{"description":"a","uuid":"5b2ce1a8-6f1c-4bb2-9a4a-8d7c0c0d6e11","project":"home","tags":["x"],"priority":"H","due":"20261020T120000Z"}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            tasks[0].text(),
+            "a +home @x pri:A due:2026-10-20 tw:5b2ce1a8-6f1c-4bb2-9a4a-8d7c0c0d6e11"
+        );
+    }
+}
@@ -0,0 +1,185 @@
+// Measuring and cutting text by the columns it takes on a terminal rather than by bytes
+// or characters, so wide and combining characters line up.
+
+// Columns taken by a character on a terminal: wide East Asian characters and emoji,
+// with the symbols drawn as emoji by default (⚡, ❗), take two, combining marks and
+// zero-width characters none.
+pub fn char_width(c: char) -> usize {
+    match c as u32 {
+        0x0300..=0x036F | 0x200B..=0x200F | 0x20D0..=0x20FF | 0xFE00..=0xFE0F => 0,
+        0x1100..=0x115F
+        | 0x231A..=0x231B
+        | 0x23E9..=0x23EC
+        | 0x23F0
+        | 0x23F3
+        | 0x25FD..=0x25FE
+        | 0x2614..=0x2615
+        | 0x2648..=0x2653
+        | 0x267F
+        | 0x2693
+        | 0x26A1
+        | 0x26AA..=0x26AB
+        | 0x26BD..=0x26BE
+        | 0x26C4..=0x26C5
+        | 0x26CE
+        | 0x26D4
+        | 0x26EA
+        | 0x26F2..=0x26F3
+        | 0x26F5
+        | 0x26FA
+        | 0x26FD
+        | 0x2705
+        | 0x270A..=0x270B
+        | 0x2728
+        | 0x274C
+        | 0x274E
+        | 0x2753..=0x2755
+        | 0x2757
+        | 0x2795..=0x2797
+        | 0x27B0
+        | 0x27BF
+        | 0x2B1B..=0x2B1C
+        | 0x2B50
+        | 0x2B55
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F680..=0x1F6FF
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+pub fn display_width(s: &str) -> usize {
+    s.chars().map(char_width).sum()
+}
+
+// The longest prefix of `s` within `width` columns, a wide character that would be cut
+// in half is left out. With `ellipsis` a cut is marked with `…`, still within `width`.
+pub fn truncate_to_width(s: &str, width: usize, ellipsis: bool) -> String {
+    if display_width(s) <= width {
+        return s.to_string();
+    }
+    if ellipsis && width > 0 {
+        return format!("{}…", prefix_within(s, width - 1));
+    }
+    prefix_within(s, width).to_string()
+}
+
+fn prefix_within(s: &str, width: usize) -> &str {
+    let mut cols = 0;
+    for (idx, c) in s.char_indices() {
+        cols += char_width(c);
+        if cols > width {
+            return &s[..idx];
+        }
+    }
+    s
+}
+
+// The rest of `s` from the first character starting at column `cols` or later, with the
+// column it starts at.
+pub fn skip_width(s: &str, cols: usize) -> (&str, usize) {
+    let mut start = 0;
+    for (idx, c) in s.char_indices() {
+        if start >= cols {
+            return (&s[idx..], start);
+        }
+        start += char_width(c);
+    }
+    ("", start)
+}
+
+// Fills `s` with spaces up to `width` columns.
+pub fn pad_to_width(s: &str, width: usize) -> String {
+    let fill = width.saturating_sub(display_width(s));
+    format!("{s}{}", " ".repeat(fill))
+}
+
+// Hard-wraps `text` into lines of at most `width` columns (at least one character per
+// line). Words are only split when they don't fit on a line alone.
+pub fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines: Vec<String> = Vec::new();
+    let mut line = String::new();
+    let mut line_width = 0;
+
+    for word in text.split_whitespace() {
+        let word_width = display_width(word);
+        if line_width > 0 && line_width + 1 + word_width <= width {
+            line.push(' ');
+            line.push_str(word);
+            line_width += 1 + word_width;
+            continue;
+        }
+        if line_width > 0 {
+            lines.push(std::mem::take(&mut line));
+            line_width = 0;
+        }
+        for c in word.chars() {
+            let w = char_width(c);
+            if line_width > 0 && line_width + w > width {
+                lines.push(std::mem::take(&mut line));
+                line_width = 0;
+            }
+            line.push(c);
+            line_width += w;
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+// Control characters would move the terminal cursor, tabs become a space and the
+// others a `?`.
+pub fn sanitize_controls(s: &str) -> String {
+    s.chars()
+        .map(|c| match c {
+            '\t' => ' ',
+            c if c.is_control() => '?',
+            c => c,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_a_long_word() {
+        let word = "x".repeat(500);
+        assert_eq!(truncate_to_width(&word, 36, false), "x".repeat(36));
+        assert_eq!(
+            truncate_to_width(&word, 36, true),
+            format!("{}…", "x".repeat(35))
+        );
+        assert_eq!(display_width(&truncate_to_width(&word, 36, true)), 36);
+        assert_eq!(truncate_to_width(&word, 0, true), "");
+        assert_eq!(truncate_to_width(&word, 500, true), word);
+    }
+
+    #[test]
+    fn wrap_a_long_word() {
+        let word = "x".repeat(500);
+        let lines = wrap(&word, 30);
+        assert_eq!(lines.len(), 17);
+        assert!(lines[..16].iter().all(|line| *line == "x".repeat(30)));
+        assert_eq!(lines[16], "x".repeat(20));
+
+        // The word starts a line of its own, the next one follows it on its last line.
+        let lines = wrap(&format!("see {word} now"), 30);
+        assert_eq!(lines[0], "see");
+        assert_eq!(lines[17], format!("{} now", "x".repeat(20)));
+        assert!(lines.iter().all(|line| display_width(line) <= 30));
+    }
+}
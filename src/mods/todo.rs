@@ -1,17 +1,64 @@
-use std::cmp::{min, Ordering};
+use std::cmp::{min, Ordering, Reverse};
 use std::fmt;
-use std::fs::File;
-use std::io::{self, BufRead, Write};
-use std::process::exit;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{self, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::OnceLock;
+use std::thread;
 
-use chrono::{DateTime, Local};
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
 
 use ncurses::constants;
 use regex::Regex;
 
+use super::backend::key_char;
+use super::config::{DuplicatePolicy, EditStart, Hooks, InsertPosition};
+use super::diff::merge_lines;
+use super::git;
+use super::org::{self, Format};
+use super::query::Query;
+use super::taskwarrior::Task;
+use super::text::{display_width, wrap};
 use crate::INDENT_SIZE;
 const SEP: &str = "<--->";
 const DATE_FMT: &str = "%Y-%m-%d %H:%M %z";
+pub const DATE_INPUT_FMT: &str = "%Y-%m-%d %H:%M";
+
+// The headers completed items are grouped under in the DONE panel.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum DateGroup {
+    Today,
+    Yesterday,
+    ThisWeek,
+    Earlier,
+}
+
+impl DateGroup {
+    // Weeks are ISO weeks, starting on Monday. A date in the future counts as today.
+    fn of(date: DateTime<Local>, now: DateTime<Local>) -> Self {
+        let (day, today) = (date.date_naive(), now.date_naive());
+        if day >= today {
+            DateGroup::Today
+        } else if today.pred_opt() == Some(day) {
+            DateGroup::Yesterday
+        } else if day.iso_week() == today.iso_week() {
+            DateGroup::ThisWeek
+        } else {
+            DateGroup::Earlier
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DateGroup::Today => "Today",
+            DateGroup::Yesterday => "Yesterday",
+            DateGroup::ThisWeek => "This week",
+            DateGroup::Earlier => "Earlier",
+        }
+    }
+}
 
 #[derive(PartialEq, Clone, Copy, Debug)]
 enum Panel {
@@ -26,6 +73,237 @@ impl Panel {
             Panel::Done => Panel::Todo,
         }
     }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Panel::Todo => "TODO",
+            Panel::Done => "DONE",
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum Filter {
+    Today,
+    Tag(String),
+    Preset(String, Query),
+}
+
+impl Filter {
+    fn matches(&self, item: &Item, panel: Panel) -> bool {
+        let today = Local::now().date_naive();
+        match (self, panel) {
+            (Filter::Today, Panel::Todo) => item.created == Some(today),
+            (Filter::Today, Panel::Done) => item.date.date_naive() == today,
+            (Filter::Tag(tag), _) => tags_of(&item.text).any(|t| t == tag),
+            (Filter::Preset(_, query), _) => query.matches(&item.text, today),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            Filter::Today => "today",
+            Filter::Tag(tag) => tag,
+            Filter::Preset(name, _) => name,
+        }
+    }
+
+    // How the filter is kept in the state file: `today`, `tag +work` or `preset work:
+    // +work and not @someday`. A preset keeps its query, it may be gone from the config.
+    fn to_state(&self) -> String {
+        match self {
+            Filter::Today => "today".to_string(),
+            Filter::Tag(tag) => format!("tag {tag}"),
+            Filter::Preset(name, query) => format!("preset {name}: {query}"),
+        }
+    }
+
+    fn from_state(value: &str) -> Option<Self> {
+        match value.split_once(' ') {
+            None if value == "today" => Some(Filter::Today),
+            Some(("tag", tag)) if is_tag(tag) => Some(Filter::Tag(tag.to_string())),
+            Some(("preset", preset)) => {
+                let (name, query) = preset.split_once(':')?;
+                let query = Query::parse(query).ok()?;
+                Some(Filter::Preset(name.trim().to_string(), query))
+            }
+            _ => None,
+        }
+    }
+}
+
+// Tags are the todo.txt `+project` and `@context` words of an item.
+fn tags_of(text: &str) -> impl Iterator<Item = &str> {
+    text.split_whitespace()
+        .filter(|word| word.len() > 1 && (word.starts_with('+') || word.starts_with('@')))
+}
+
+fn is_tag(s: &str) -> bool {
+    tags_of(s).next() == Some(s)
+}
+
+// The form of an item's text two items are duplicates by. `ignore-case` also trims the
+// text, `ignore-tags` drops the tags and `key:value` tokens and collapses whitespace.
+fn duplicate_key(text: &str, policy: DuplicatePolicy) -> String {
+    match policy {
+        DuplicatePolicy::Exact => text.to_string(),
+        DuplicatePolicy::IgnoreCase => text.trim().to_lowercase(),
+        DuplicatePolicy::IgnoreTags => text
+            .split_whitespace()
+            .filter(|word| !is_tag(word) && !word.contains(':'))
+            .collect::<Vec<&str>>()
+            .join(" ")
+            .to_lowercase(),
+    }
+}
+
+// Whether two texts are at most `max` single-character edits apart. Only the cells of
+// the table within `max` of the diagonal can be that close, the others count as too far,
+// and the comparison stops as soon as a row is all over `max`.
+fn within_distance(a: &str, b: &str, max: usize) -> bool {
+    let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+    if a.len().abs_diff(b.len()) > max {
+        return false;
+    }
+    let far = max + 1;
+    let mut prev: Vec<usize> = (0..=b.len()).map(|j| j.min(far)).collect();
+    let mut row = vec![far; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        let (from, to) = ((i + 1).saturating_sub(max), min(b.len(), i + 1 + max));
+        row.fill(far);
+        row[0] = min(i + 1, far);
+        for j in from.max(1)..=to {
+            row[j] = (prev[j - 1] + usize::from(*ca != b[j - 1]))
+                .min(prev[j] + 1)
+                .min(row[j - 1] + 1)
+                .min(far);
+        }
+        if row[from..=to].iter().all(|&d| d > max) {
+            return false;
+        }
+        std::mem::swap(&mut prev, &mut row);
+    }
+    prev[b.len()] <= max
+}
+
+// How many of the next keys in the sorted order each key is compared with for near
+// duplicates.
+const DEDUPE_WINDOW: usize = 8;
+
+// The pairs of `(index, key)` entries that are duplicates: the same key, or with
+// `distance` above 0, keys that many edits apart. Sorting puts equal keys next to each
+// other. Near ones are looked for only among the few keys after each one, in the order of
+// the keys and of the keys read backwards (for an edit at the start), so it's no slower
+// than the sort on long lists. Each pair is in the list order.
+fn duplicate_pairs(mut keys: Vec<(usize, String)>, distance: usize) -> Vec<(usize, usize)> {
+    keys.sort_by(|(a_idx, a), (b_idx, b)| a.cmp(b).then(a_idx.cmp(b_idx)));
+
+    let mut pairs = Vec::new();
+    let mut firsts: Vec<(usize, &str)> = Vec::new();
+    for group in keys.chunk_by(|(_, a), (_, b)| a == b) {
+        pairs.extend(group[1..].iter().map(|(idx, _)| (group[0].0, *idx)));
+        firsts.push((group[0].0, &group[0].1));
+    }
+    if distance > 0 {
+        let mut near = Vec::new();
+        for reversed in [false, true] {
+            if reversed {
+                firsts.sort_by_cached_key(|(_, key)| key.chars().rev().collect::<String>());
+            }
+            for (n, &(a_idx, a)) in firsts.iter().enumerate() {
+                for &(b_idx, b) in firsts.iter().skip(n + 1).take(DEDUPE_WINDOW) {
+                    if within_distance(a, b, distance) {
+                        near.push((a_idx.min(b_idx), a_idx.max(b_idx)));
+                    }
+                }
+            }
+        }
+        near.sort_unstable();
+        near.dedup();
+        pairs.extend(near);
+    }
+    pairs.sort_unstable();
+    pairs
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Snapshot {
+    Todos,
+    Dones,
+    All,
+}
+
+impl Snapshot {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "todos" => Some(Snapshot::Todos),
+            "dones" => Some(Snapshot::Dones),
+            "all" => Some(Snapshot::All),
+            _ => None,
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Sort {
+    Date,
+    Alpha,
+    Recent,
+    Priority,
+    Due,
+}
+
+impl Sort {
+    pub fn from_str(s: &str) -> Option<Self> {
+        match s {
+            "date" => Some(Sort::Date),
+            "alpha" => Some(Sort::Alpha),
+            "recent" => Some(Sort::Recent),
+            "pri" => Some(Sort::Priority),
+            "due" => Some(Sort::Due),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sort::Date => "date",
+            Sort::Alpha => "alpha",
+            Sort::Recent => "recent",
+            Sort::Priority => "pri",
+            Sort::Due => "due",
+        }
+    }
+
+    // Items without a priority or a due date go after the ones with it.
+    fn compare(self, a: &Item, b: &Item) -> Ordering {
+        match self {
+            Sort::Date => a.date.cmp(&b.date),
+            Sort::Recent => b.date.cmp(&a.date),
+            Sort::Alpha => a.text.to_lowercase().cmp(&b.text.to_lowercase()),
+            Sort::Priority => {
+                let key = |item: &Item| (item.get_priority().is_none(), item.get_priority());
+                key(a).cmp(&key(b))
+            }
+            Sort::Due => {
+                let key = |item: &Item| (item.get_due().is_none(), item.get_due());
+                key(a).cmp(&key(b))
+            }
+        }
+    }
+
+    // The date of a TODO item is the day it was created, the ones without a `created:`
+    // token go last either way.
+    fn compare_in(self, panel: Panel, a: &Item, b: &Item) -> Ordering {
+        match (self, panel) {
+            (Sort::Date, Panel::Todo) => {
+                (a.created.is_none(), a.created).cmp(&(b.created.is_none(), b.created))
+            }
+            (Sort::Recent, Panel::Todo) => (a.created.is_none(), Reverse(a.created))
+                .cmp(&(b.created.is_none(), Reverse(b.created))),
+            _ => self.compare(a, b),
+        }
+    }
 }
 
 #[derive(PartialEq, Clone, Copy, Debug)]
@@ -33,11 +311,23 @@ enum Action {
     Delete,
     DragUp,
     DragDown,
+    Indent,
+    Outdent,
     Transfer,
     Mark,
     Insert,
     Append,
     Edit,
+    EditDate,
+    Block,
+    Swap,
+    Gather,
+    Retag,
+    Tag,
+    Untag,
+    Case,
+    Dedupe,
+    Sort,
     InEdit,
 }
 
@@ -47,25 +337,102 @@ impl fmt::Display for Action {
             Action::Delete => write!(f, "Delete"),
             Action::DragUp => write!(f, "Drag up"),
             Action::DragDown => write!(f, "Drag down"),
+            Action::Indent => write!(f, "Indent"),
+            Action::Outdent => write!(f, "Outdent"),
             Action::Transfer => write!(f, "Transfer"),
             Action::Insert => write!(f, "Insert"),
             Action::Mark => write!(f, "Mark"),
             Action::Append => write!(f, "Append"),
             Action::Edit => write!(f, "Edit"),
+            Action::EditDate => write!(f, "Edit date"),
+            Action::Block => write!(f, "Block"),
+            Action::Swap => write!(f, "Swap"),
+            Action::Gather => write!(f, "Gather"),
+            Action::Dedupe => write!(f, "Dedupe"),
+            Action::Retag => write!(f, "Retag"),
+            Action::Tag => write!(f, "Tag"),
+            Action::Untag => write!(f, "Untag"),
+            Action::Case => write!(f, "Change case"),
+            Action::Sort => write!(f, "Sort"),
             Action::InEdit => write!(f, ""),
         }
     }
 }
 
+#[derive(PartialEq, Clone, Debug)]
+enum Confirm {
+    MarkBlocked,
+    Migrate,
+    RemoveTag(String),
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Resolve {
+    KeepFirst,
+    KeepSecond,
+    KeepBoth,
+    Merge,
+}
+
+// A `:dedupe` run: the pairs of TODO root items it found and what was decided so far.
+// Nothing changes in the list until the run ends, then it all goes in as one change.
+#[derive(Debug)]
+struct Dedupe {
+    pairs: Vec<(usize, usize)>,
+    cur: usize,
+    // Each removed item with the one kept in its place.
+    removed: Vec<(usize, usize)>,
+    texts: Vec<(usize, String)>,
+}
+
+impl Dedupe {
+    fn text<'a>(&'a self, list: &'a List, idx: usize) -> &'a str {
+        self.texts
+            .iter()
+            .find(|(i, _)| *i == idx)
+            .map_or(&list.list[idx].text, |(_, text)| text)
+    }
+
+    fn kept(&self, mut idx: usize) -> usize {
+        while let Some(&(_, kept)) = self.removed.iter().find(|(removed, _)| *removed == idx) {
+            idx = kept;
+        }
+        idx
+    }
+
+    // The pairs left compare the items kept in place of the removed ones, a pair that
+    // became the same item twice is passed.
+    fn next(&mut self) {
+        self.cur += 1;
+        while let Some(&(first, second)) = self.pairs.get(self.cur) {
+            let (first, second) = (self.kept(first), self.kept(second));
+            if first != second {
+                self.pairs[self.cur] = (first.min(second), first.max(second));
+                break;
+            }
+            self.cur += 1;
+        }
+    }
+}
+
 #[derive(Debug)]
 struct Operation {
     action: Action,
     panel: Panel,
+    // Where the cursor of the panel goes on undo and on redo: the affected item, the
+    // first one of a batch.
+    undo_at: usize,
+    redo_at: usize,
 }
 
 impl Operation {
-    fn new(action: Action, panel: Panel) -> Self {
-        Self { action, panel }
+    fn new(action: Action, panel: Panel, undo_at: usize, redo_at: usize) -> Self {
+        Self {
+            action,
+            panel,
+            undo_at,
+            redo_at,
+        }
     }
 }
 
@@ -76,6 +443,30 @@ pub struct Item {
     parent: Option<usize>,
     children: Vec<usize>,
     act_cnt: usize,
+    id: Option<usize>,
+    blocked_by: Option<usize>,
+    created: Option<NaiveDate>,
+    // Blank lines before the item in the file, shown as an empty row above it.
+    gap: usize,
+    org: Option<Box<OrgItem>>,
+}
+
+// What an item of an Org-mode file had besides its heading, written back as it was.
+#[derive(Clone, PartialEq, Debug)]
+struct OrgItem {
+    stars: usize,
+    // Where the top heading of the item was among the others of the file, open or done.
+    // Saving keeps them in that order, the panels would put the open ones first.
+    order: usize,
+    // The planning line is written as is while the dates it gave stay the same.
+    planning: Option<String>,
+    closed: Option<DateTime<Local>>,
+    deadline: Option<NaiveDate>,
+    scheduled: Option<String>,
+    // The heading had the due date as a `due:` token, not only as a DEADLINE.
+    due_in_text: bool,
+    // The lines up to the next item heading.
+    body: Vec<String>,
 }
 
 impl Item {
@@ -86,7 +477,94 @@ impl Item {
             parent,
             children: Vec::new(),
             act_cnt,
+            id: None,
+            blocked_by: None,
+            created: None,
+            gap: 0,
+            org: None,
+        }
+    }
+
+    pub fn has_gap(&self) -> bool {
+        self.gap > 0
+    }
+
+    fn with_tokens(mut self) -> Self {
+        static RE_TOKEN: OnceLock<Regex> = OnceLock::new();
+        let re_token =
+            RE_TOKEN.get_or_init(|| Regex::new(r"\s*\b(id|blocked|created):(\d[\d-]*)\b").unwrap());
+
+        for caps in re_token.captures_iter(&self.text) {
+            match &caps[1] {
+                "id" => self.id = caps[2].parse().ok(),
+                "blocked" => self.blocked_by = caps[2].parse().ok(),
+                "created" => self.created = NaiveDate::parse_from_str(&caps[2], "%Y-%m-%d").ok(),
+                _ => unreachable!(),
+            }
+        }
+        self.text = re_token.replace_all(&self.text, "").trim().to_string();
+
+        self
+    }
+
+    // Estimates are written as `est:2h`, `est:45m` or `est:1h30m` anywhere in the text.
+    pub fn get_estimate(&self) -> Option<Duration> {
+        static RE_EST: OnceLock<Regex> = OnceLock::new();
+        let re_est = RE_EST.get_or_init(|| Regex::new(r"\best:(?:(\d+)h)?(?:(\d+)m)?\b").unwrap());
+
+        let caps = re_est.captures(&self.text)?;
+        let hours = caps.get(1).and_then(|h| h.as_str().parse().ok());
+        let minutes = caps.get(2).and_then(|m| m.as_str().parse().ok());
+        match (hours, minutes) {
+            (None, None) => None,
+            (h, m) => Some(Duration::hours(h.unwrap_or(0)) + Duration::minutes(m.unwrap_or(0))),
+        }
+    }
+
+    // An `est:` word that isn't an estimate, e.g. `est:soon` or `est:2d`.
+    fn bad_estimate(&self) -> Option<&str> {
+        static RE_VALID: OnceLock<Regex> = OnceLock::new();
+        let re_valid = RE_VALID.get_or_init(|| Regex::new(r"^est:(\d+h\d+m|\d+h|\d+m)$").unwrap());
+
+        self.text
+            .split_whitespace()
+            .filter(|word| word.starts_with("est:"))
+            .map(|word| word.trim_end_matches(|c: char| !c.is_alphanumeric()))
+            .find(|word| !re_valid.is_match(word))
+    }
+
+    // The letter of a `pri:A` token.
+    fn get_priority(&self) -> Option<char> {
+        self.text
+            .split_whitespace()
+            .find_map(|word| word.strip_prefix("pri:"))
+            .and_then(|pri| match pri.as_bytes() {
+                [pri] if pri.is_ascii_uppercase() => Some(*pri as char),
+                _ => None,
+            })
+    }
+
+    pub fn get_due(&self) -> Option<NaiveDate> {
+        static RE_DUE: OnceLock<Regex> = OnceLock::new();
+        let re_due = RE_DUE.get_or_init(|| Regex::new(r"\bdue:(\d{4}-\d{2}-\d{2})\b").unwrap());
+
+        re_due
+            .captures(&self.text)
+            .and_then(|caps| NaiveDate::parse_from_str(&caps[1], "%Y-%m-%d").ok())
+    }
+
+    fn tokens(&self) -> String {
+        let mut tokens = String::new();
+        if let Some(id) = self.id {
+            tokens.push_str(&format!(" id:{id}"));
+        }
+        if let Some(blocker) = self.blocked_by {
+            tokens.push_str(&format!(" blocked:{blocker}"));
         }
+        if let Some(created) = self.created {
+            tokens.push_str(&format!(" created:{}", created.format("%Y-%m-%d")));
+        }
+        tokens
     }
 
     pub fn get_text(&self) -> &String {
@@ -97,6 +575,14 @@ impl Item {
         self.date.format("%y-%m-%d").to_string()
     }
 
+    pub fn get_created(&self) -> Option<NaiveDate> {
+        self.created
+    }
+
+    pub fn get_date_group(&self) -> DateGroup {
+        DateGroup::of(self.date, Local::now())
+    }
+
     pub fn is_active(&self) -> bool {
         self.act_cnt > 0
     }
@@ -117,8 +603,14 @@ impl Item {
 #[derive(Debug)]
 struct List {
     state_stack: Vec<(Vec<Item>, usize)>,
+    redo_stack: Vec<(Vec<Item>, usize)>,
     cur: usize,
+    scroll: usize,
+    // Index in `list` of the item on the first row shown, where drawing starts.
+    top: usize,
     list: Vec<Item>,
+    // Blank lines after the last item, only kept for the file.
+    tail_gap: usize,
 }
 
 pub struct ListIter<'a> {
@@ -157,8 +649,24 @@ impl List {
     fn new() -> Self {
         Self {
             state_stack: Vec::new(),
+            redo_stack: Vec::new(),
             cur: 0,
+            scroll: 0,
+            top: 0,
             list: Vec::new(),
+            tail_gap: 0,
+        }
+    }
+
+    fn iter(&self, skip_children: bool) -> ListIter<'_> {
+        self.iter_from(0, skip_children)
+    }
+
+    fn iter_from(&self, start: usize, skip_children: bool) -> ListIter<'_> {
+        ListIter {
+            obj: self,
+            cur: start,
+            skip_children,
         }
     }
 
@@ -204,6 +712,19 @@ impl List {
         }
     }
 
+    // Like revert_state(), but the current state can be brought back with redo_state().
+    fn undo_state(&mut self) {
+        self.redo_stack.push((self.list.to_owned(), self.cur));
+        self.revert_state().unwrap();
+    }
+
+    fn redo_state(&mut self) {
+        if let Some((list, cur)) = self.redo_stack.pop() {
+            self.record_state();
+            (self.list, self.cur) = (list, cur);
+        }
+    }
+
     fn up(&mut self, full: bool) {
         if self.cur > 0 && !self.list.is_empty() {
             if full {
@@ -219,21 +740,6 @@ impl List {
         }
     }
 
-    fn down(&mut self, full: bool) {
-        if !self.list.is_empty() {
-            if full {
-                self.cur = min(self.cur + 1, self.list.len() - 1)
-            } else {
-                if let Some(next) = self.list[self.cur + 1..]
-                    .iter()
-                    .position(|item| item.is_root())
-                {
-                    self.cur += next + 1;
-                }
-            }
-        }
-    }
-
     fn drag_up(&mut self) -> Result<(), &'static str> {
         if let Some(item) = self.list.get_mut(self.cur) {
             let parent = item.parent;
@@ -321,40 +827,206 @@ impl List {
         }
     }
 
-    fn first(&mut self) {
-        self.cur = 0;
+    // Drags the item past its next sibling, or its previous one with `up`. With `wrap`
+    // an item already past the last of its siblings goes around to be the first of them,
+    // and the other way round.
+    fn drag(&mut self, up: bool, wrap: bool) -> Result<(), &'static str> {
+        let step = |list: &mut Self, up: bool| if up { list.drag_up() } else { list.drag_down() };
+        match step(self, up) {
+            Err(err) if wrap => {
+                // An item without siblings has nowhere to go either way.
+                step(self, !up).map_err(|_| err)?;
+                while step(self, !up).is_ok() {}
+                Ok(())
+            }
+            result => result,
+        }
     }
 
-    fn half(&mut self, full: bool) {
-        if !self.list.is_empty() {
-            if full {
-                self.cur = self.list.len() / 2;
-            } else {
-                let num_roots = self.list.iter().filter(|item| item.is_root()).count();
-                self.cur = self
-                    .list
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, item)| item.is_root())
-                    .nth(num_roots / 2)
-                    .map(|(i, _)| i)
-                    .unwrap_or(self.cur);
+    // Swaps two siblings with their subtasks by dragging: the lower one up above the
+    // upper one, then the upper one down into its place. The cursor stays on the item at `i`.
+    fn swap(&mut self, i: usize, j: usize) -> Result<(), &'static str> {
+        let (top, bottom) = (min(i, j), i.max(j));
+        if bottom >= self.list.len() {
+            return Err("Can't swap. Item no longer exists.");
+        }
+        if top == bottom {
+            return Err("Can't swap an item with itself.");
+        }
+        let parent = self.list[top].parent;
+        if self.list[bottom].parent != parent {
+            return Err("Only items with the same parent can be swapped.");
+        }
+
+        let between = self.list[top + 1..bottom]
+            .iter()
+            .filter(|item| item.parent == parent)
+            .count();
+
+        self.cur = bottom;
+        for _ in 0..=between {
+            self.drag_up()?;
+        }
+        self.cur = top + self.children_cnt(top) + 1;
+        for _ in 0..between {
+            self.drag_down()?;
+        }
+
+        if i != top {
+            self.cur = top;
+        }
+        Ok(())
+    }
+
+    // Moves the picked root items with their subtasks right below the item under the
+    // cursor, keeping their order. The list is rebuilt from its root blocks and the
+    // parent/children indices remapped.
+    fn gather(&mut self, picked: &[usize]) -> Result<(), &'static str> {
+        let mut target = self.cur;
+        while let Some(parent) = self.list.get(target).and_then(|item| item.parent) {
+            target = parent;
+        }
+        if target >= self.list.len() {
+            return Err("Can't gather. List is empty.");
+        }
+        if picked.contains(&target) {
+            return Err("Can't gather picked items below one of them.");
+        }
+
+        let block = |root: usize| root..root + self.children_cnt(root) + 1;
+        let mut order = Vec::new();
+        for root in (0..self.list.len()).filter(|&i| self.list[i].is_root()) {
+            if picked.contains(&root) {
+                continue;
+            }
+            order.extend(block(root));
+            if root == target {
+                for &root in picked {
+                    order.extend(block(root));
+                }
+            }
+        }
+
+        self.reorder(&order);
+        Ok(())
+    }
+
+    // The root items in the order of `sort`, the list order without one. Ties keep the
+    // list order, the reverse one for `recent`: items are added and completed in it.
+    fn sorted_roots(&self, sort: Option<Sort>, panel: Panel) -> Vec<usize> {
+        let mut roots: Vec<usize> = (0..self.list.len())
+            .filter(|&idx| self.list[idx].is_root())
+            .collect();
+        if let Some(sort) = sort {
+            if sort == Sort::Recent {
+                roots.reverse();
             }
+            roots.sort_by(|&a, &b| sort.compare_in(panel, &self.list[a], &self.list[b]));
         }
+        roots
     }
 
-    fn last(&mut self, full: bool) {
-        if !self.list.is_empty() {
-            if full {
-                self.cur = self.list.len() - 1;
-            } else {
-                self.cur = self
-                    .list
-                    .iter()
-                    .rposition(|item| item.is_root())
-                    .unwrap_or(0);
+    // Puts the root items with their subtasks in the order of `sort`. False when they
+    // already were in it.
+    fn sort(&mut self, sort: Sort, panel: Panel) -> bool {
+        let order: Vec<usize> = self
+            .sorted_roots(Some(sort), panel)
+            .into_iter()
+            .flat_map(|root| root..root + self.children_cnt(root) + 1)
+            .collect();
+        if order.iter().enumerate().all(|(new, &old)| new == old) {
+            return false;
+        }
+        self.reorder(&order);
+        true
+    }
+
+    // Puts the items in `order`, given as their current indices, and remaps the
+    // parent/children indices and the cursor to match.
+    fn reorder(&mut self, order: &[usize]) {
+        let mut new_idx = vec![0; self.list.len()];
+        for (new, &old) in order.iter().enumerate() {
+            new_idx[old] = new;
+        }
+        let mut list: Vec<Item> = order.iter().map(|&old| self.list[old].clone()).collect();
+        for item in list.iter_mut() {
+            item.parent = item.parent.map(|p| new_idx[p]);
+            for child in item.children.iter_mut() {
+                *child = new_idx[*child];
+            }
+        }
+
+        self.list = list;
+        self.cur = new_idx[self.cur];
+    }
+
+    // Makes the item a subtask of the sibling above it, as the last of its subtasks. The
+    // items in between are that sibling's subtasks, so nothing moves in the list.
+    fn indent(&mut self) -> Result<(), &'static str> {
+        let Some(item) = self.get_cur_item() else {
+            return Err("Can't indent. List is empty.");
+        };
+        let (parent, active) = (item.parent, item.is_active());
+        let pier = self.list[..self.cur]
+            .iter()
+            .rposition(|item| item.parent == parent)
+            .ok_or("Can't indent. There's no item above it at the same level.")?;
+
+        if let Some(parent) = parent {
+            self.list[parent].children.retain(|&x| x != self.cur);
+            if active {
+                self.list[parent].act_cnt -= 1;
             }
         }
+        self.list[self.cur].parent = Some(pier);
+        self.list[pier].children.push(self.cur);
+        if active {
+            self.unmark_parents(Some(pier));
+        }
+        Ok(())
+    }
+
+    // Makes a subtask a sibling of its parent, right below the parent and its other
+    // subtasks. The subtasks of the item go with it.
+    fn outdent(&mut self) -> Result<(), &'static str> {
+        let Some(item) = self.get_cur_item() else {
+            return Err("Can't outdent. List is empty.");
+        };
+        let active = item.is_active();
+        let parent = item
+            .parent
+            .ok_or("Can't outdent. Item is already at the top level.")?;
+        let grandparent = self.list[parent].parent;
+
+        let (cur, cnt) = (self.cur, self.children_cnt(self.cur) + 1);
+        let end = parent + self.children_cnt(parent) + 1;
+        let block = cur..cur + cnt;
+        let order: Vec<usize> = (0..end)
+            .filter(|i| !block.contains(i))
+            .chain(block.clone())
+            .chain(end..self.list.len())
+            .collect();
+
+        self.list[parent].children.retain(|&x| x != cur);
+        if active {
+            self.list[parent].act_cnt -= 1;
+        }
+        self.list[cur].parent = grandparent;
+        if let Some(grandparent) = grandparent {
+            self.add_child_to(grandparent, cur, active);
+        }
+        self.reorder(&order);
+        Ok(())
+    }
+
+    fn subtree_ids(&self) -> Vec<usize> {
+        let child_cnt = self.children_cnt(self.cur) + 1;
+        self.list
+            .iter()
+            .skip(self.cur)
+            .take(child_cnt)
+            .filter_map(|item| item.id)
+            .collect()
     }
 
     fn children_cnt(&self, parent: usize) -> usize {
@@ -393,31 +1065,52 @@ impl List {
         }
     }
 
-    fn insert(&mut self) -> Result<(), &'static str> {
-        if let Some(item) = self.get_cur_item() {
-            if item.parent.is_some() {
-                return Err("Can't insert item. Current item is a subtask.");
-            }
+    fn root_of(&self, mut idx: usize) -> usize {
+        while let Some(parent) = self.list[idx].parent {
+            idx = parent;
         }
+        idx
+    }
 
-        let item = Item::new(String::new(), Local::now(), None, 1);
+    fn insert(&mut self, position: InsertPosition) -> Result<(), &'static str> {
+        let at = match position {
+            InsertPosition::Top => 0,
+            InsertPosition::Bottom => self.list.len(),
+            InsertPosition::AboveCursor => match self.get_cur_item() {
+                Some(item) if item.parent.is_some() => {
+                    return Err("Can't insert item. Current item is a subtask.")
+                }
+                _ => self.cur,
+            },
+            InsertPosition::BelowCursor if self.list.is_empty() => 0,
+            InsertPosition::BelowCursor => {
+                let root = self.root_of(self.cur);
+                root + self.children_cnt(root) + 1
+            }
+        };
 
-        self.shift_indices(1, self.cur, None, None);
-        self.list.insert(self.cur, item);
+        let mut item = Item::new(String::new(), Local::now(), None, 1);
+        item.created = Some(Local::now().date_naive());
+
+        self.shift_indices(1, at, None, None);
+        self.list.insert(at, item);
+        self.cur = at;
 
         Ok(())
     }
 
     fn append(&mut self) -> Result<(), &'static str> {
         if self.get_cur_item().is_some() {
-            let item = Item::new(String::new(), Local::now(), Some(self.cur), 1);
+            let mut item = Item::new(String::new(), Local::now(), Some(self.cur), 1);
+            item.created = Some(Local::now().date_naive());
+            let at = self.cur + self.children_cnt(self.cur) + 1;
 
             self.unmark_parents(Some(self.cur));
-            self.shift_indices(1, 0, None, Some(self.cur));
+            self.shift_indices(1, 0, None, Some(at - 1));
 
-            self.list[self.cur].children.push(self.cur + 1);
-            self.list.insert(self.cur + 1, item);
-            self.cur += 1;
+            self.list[self.cur].children.push(at);
+            self.list.insert(at, item);
+            self.cur = at;
 
             Ok(())
         } else {
@@ -441,7 +1134,7 @@ impl List {
     fn delete(&mut self) -> Result<(), &'static str> {
         if let Some(item) = self.get_cur_item() {
             let child_cnt = self.children_cnt(self.cur) + 1;
-            let parent = item.parent;
+            let (parent, gap) = (item.parent, item.gap);
 
             if let Some(parent) = parent {
                 self.list[parent].children.retain(|&x| x != self.cur);
@@ -460,6 +1153,7 @@ impl List {
             self.shift_indices(-(child_cnt as isize), self.cur + child_cnt, None, parent);
 
             self.list.splice(self.cur..self.cur + child_cnt, vec![]);
+            self.pass_gap(self.cur, gap);
             if !self.list.is_empty() {
                 self.cur = min(self.cur, self.list.len() - 1);
             } else {
@@ -515,6 +1209,7 @@ impl List {
 
             let mut to_transfer: Vec<Item> =
                 self.list.drain(self.cur..self.cur + child_cnt).collect();
+            self.pass_gap(self.cur, std::mem::take(&mut to_transfer[0].gap));
             rhs.list.append(&mut to_transfer);
 
             rhs.shift_indices(-(self.cur as isize) + move_to as isize, move_to, None, None);
@@ -530,460 +1225,3655 @@ impl List {
         }
     }
 
-    fn edit(&mut self, cur: &mut usize, key: i32) {
-        if let Some(item) = self.get_cur_item_mut() {
-            *cur = min(*cur, item.text.len());
+    // The gap of an item taken out passes to the one taking its place, so the group it
+    // started stays apart. The first item has nothing to be apart from.
+    fn pass_gap(&mut self, at: usize, gap: usize) {
+        if let Some(item) = self.list.get_mut(at).filter(|_| at > 0) {
+            item.gap = item.gap.max(gap);
+        }
+    }
 
-            match key {
-                32..=126 => {
-                    if *cur > item.text.len() {
-                        item.text.push(key as u8 as char);
-                    } else {
-                        item.text.insert(*cur, key as u8 as char);
-                    }
-                    *cur += 1;
-                }
-                constants::KEY_LEFT => {
-                    if *cur > 0 {
-                        *cur -= 1;
-                    }
-                }
-                constants::KEY_RIGHT => {
-                    if *cur < item.text.len() {
-                        *cur += 1;
-                    }
-                }
-                constants::KEY_BACKSPACE | 127 => {
-                    // 127 is backspace
-                    if *cur > 0 {
-                        *cur -= 1;
-                        if *cur < item.text.len() {
-                            item.text.remove(*cur);
-                        }
-                    }
-                }
-                constants::KEY_DC => {
-                    if *cur < item.text.len() {
-                        item.text.remove(*cur);
-                    }
+    // Keeps the row of the cursor within the `height` rows shown from `scroll` on,
+    // with at least `margin` rows around it unless the list ends there. Without a filter
+    // or hidden children every item is a row and nothing needs counting. The item at
+    // `keep` is shown even if the filter doesn't match it.
+    fn scroll_to_cur(
+        &mut self,
+        height: usize,
+        margin: usize,
+        skip_children: bool,
+        filter: Option<&Filter>,
+        panel: Panel,
+        keep: Option<usize>,
+    ) {
+        let every_row = filter.is_none() && !skip_children;
+        let cur = match self.list.get(self.cur) {
+            Some(_) if skip_children => self.root_of(self.cur),
+            Some(_) => self.cur,
+            None => {
+                self.scroll = 0;
+                self.top = 0;
+                return;
+            }
+        };
+        let (mut rows, mut row) = (self.list.len(), cur);
+        if !every_row {
+            rows = 0;
+            for idx in self.rows(0, skip_children, filter, panel, keep) {
+                if idx == cur {
+                    row = rows;
                 }
-                constants::KEY_HOME | 1 => *cur = 0, // 1 is ctrl + a
-                constants::KEY_END | 5 => *cur = item.text.len(), // 5 is ctrl + e
-                _ => {}
+                rows += 1;
             }
         }
-    }
-}
 
-#[derive(Debug)]
-pub struct TodoApp {
-    message: String,
-    panel: Panel,
-    hide_subs: bool,
-    operation_stack: Vec<Operation>,
-    todos: List,
-    dones: List,
-}
+        let margin = min(margin, height.saturating_sub(1) / 2);
+        if row < self.scroll + margin {
+            self.scroll = row.saturating_sub(margin);
+        } else if height > 0 && row + margin >= self.scroll + height {
+            self.scroll = row + margin + 1 - height;
+        }
+        self.scroll = min(self.scroll, rows.saturating_sub(height));
+        self.top = if every_row {
+            self.scroll
+        } else {
+            let top = self
+                .rows(0, skip_children, filter, panel, keep)
+                .nth(self.scroll);
+            top.unwrap_or(self.list.len())
+        };
 
-impl TodoApp {
-    pub fn new() -> Self {
-        Self {
-            message: String::new(),
-            panel: Panel::Todo,
-            hide_subs: false,
-            operation_stack: Vec::new(),
-            todos: List::new(),
-            dones: List::new(),
+        // The empty rows of the gaps can push the cursor past the bottom, then the list
+        // scrolls on until it fits.
+        while self.top < cur
+            && self.rows_used(cur, margin, skip_children, filter, panel, keep) > height
+        {
+            let next = self
+                .rows(self.top + 1, skip_children, filter, panel, keep)
+                .next();
+            self.top = next.unwrap_or(cur);
+            self.scroll += 1;
         }
     }
 
-    pub fn is_in_todos(&self) -> bool {
-        self.panel == Panel::Todo
-    }
-
-    pub fn is_subs_hidden(&self) -> bool {
-        self.hide_subs
+    // Rows taken from `top` down to `margin` items past `cur`, an item with a gap takes
+    // one more unless it is the first. None are needed when `cur` isn't shown.
+    fn rows_used(
+        &self,
+        cur: usize,
+        margin: usize,
+        skip_children: bool,
+        filter: Option<&Filter>,
+        panel: Panel,
+        keep: Option<usize>,
+    ) -> usize {
+        let mut used = 0;
+        let mut after = None;
+        for idx in self.rows(self.top, skip_children, filter, panel, keep) {
+            if idx > cur && after.is_none() {
+                return 0;
+            }
+            used += if idx != self.top && self.list[idx].gap > 0 {
+                2
+            } else {
+                1
+            };
+            after = after.map(|n| n + 1).or((idx == cur).then_some(0));
+            if after == Some(margin) {
+                break;
+            }
+        }
+        used
     }
 
-    pub fn is_in_dones(&self) -> bool {
-        self.panel == Panel::Done
+    // Indices of the items from `from` on shown as rows, without working out their levels.
+    fn rows<'a>(
+        &'a self,
+        from: usize,
+        skip_children: bool,
+        filter: Option<&'a Filter>,
+        panel: Panel,
+        keep: Option<usize>,
+    ) -> impl Iterator<Item = usize> + 'a {
+        (from..self.list.len()).filter(move |&idx| {
+            let item = &self.list[idx];
+            !(skip_children && item.parent.is_some())
+                && (keep == Some(idx) || filter.is_none_or(|f| f.matches(item, panel)))
+        })
     }
 
-    pub fn is_cur_todo(&self, todo: &Item) -> bool {
-        self.todos.get_cur_item().map_or(false, |t| t == todo)
+    fn range(&self, anchor: usize) -> Option<(usize, usize)> {
+        if self.list.is_empty() {
+            return None;
+        }
+        let lo = min(anchor, self.cur);
+        let hi = anchor.max(self.cur);
+        Some((lo, hi + self.children_cnt(hi)))
     }
 
-    pub fn is_cur_done(&self, done: &Item) -> bool {
-        self.dones.get_cur_item().map_or(false, |d| d == done)
+    fn edit(&mut self, cur: &mut usize, key: i32) {
+        if let Some(item) = self.get_cur_item_mut() {
+            edit_text(&mut item.text, cur, key);
+        }
     }
+}
 
-    pub fn get_message(&self) -> &String {
-        &self.message
+// `cur` counts characters, not bytes.
+fn edit_text(text: &mut String, cur: &mut usize, key: i32) {
+    let len = text.chars().count();
+    *cur = min(*cur, len);
+    if let Some(ch) = key_char(key) {
+        text.insert(byte_at(text, *cur), ch);
+        *cur += 1;
+        return;
     }
 
-    pub fn iter_todos(&self) -> ListIter {
-        ListIter {
-            obj: &self.todos,
-            cur: 0,
-            skip_children: self.hide_subs,
+    match key {
+        constants::KEY_LEFT if *cur > 0 => *cur -= 1,
+        constants::KEY_RIGHT if *cur < len => *cur += 1,
+        constants::KEY_BACKSPACE | 127 if *cur > 0 => {
+            // 127 is backspace
+            *cur -= 1;
+            text.remove(byte_at(text, *cur));
         }
+        constants::KEY_DC if *cur < len => {
+            text.remove(byte_at(text, *cur));
+        }
+        constants::KEY_HOME | 1 => *cur = 0,  // 1 is ctrl + a
+        constants::KEY_END | 5 => *cur = len, // 5 is ctrl + e
+        // Items are single line, so there is nowhere to move vertically,
+        // and tabs would break the indentation of the saved file.
+        constants::KEY_UP | constants::KEY_DOWN | 9 => {} // 9 is tab
+        _ => {}
     }
+}
 
-    pub fn get_todos_n(&self, full: bool) -> usize {
-        if full {
-            self.todos.list.len()
-        } else {
-            self.todos
-                .list
-                .iter()
-                .filter(|item| item.parent.is_none())
-                .count()
+// The byte offset of the character at `cur`, the end of the text past the last one.
+fn byte_at(text: &str, cur: usize) -> usize {
+    text.char_indices().nth(cur).map_or(text.len(), |(i, _)| i)
+}
+
+// Rewrites a line written by an older version (or another tool) into the current shape:
+// `TODO( )` for a finished todo and DONE dates without time or timezone.
+fn upgrade_line(line: &str, panel: Panel) -> String {
+    static RE_DONE_DATE: OnceLock<Regex> = OnceLock::new();
+
+    let mut line = line.to_string();
+
+    match panel {
+        Panel::Todo => line = line.replacen("TODO( ): ", "TODO(): ", 1),
+        Panel::Done => {
+            let re_done_date =
+                RE_DONE_DATE.get_or_init(|| Regex::new(r"^(\s*)DONE\(([^)]*)\): ").unwrap());
+            let date = re_done_date
+                .captures(&line)
+                .filter(|caps| DateTime::parse_from_str(&caps[2], DATE_FMT).is_err())
+                .and_then(|caps| {
+                    let date = NaiveDateTime::parse_from_str(&caps[2], DATE_INPUT_FMT)
+                        .ok()
+                        .or_else(|| {
+                            NaiveDate::parse_from_str(&caps[2], "%Y-%m-%d")
+                                .ok()
+                                .and_then(|date| date.and_hms_opt(0, 0, 0))
+                        })?;
+                    let date = Local.from_local_datetime(&date).single()?;
+                    Some((
+                        caps[0].to_string(),
+                        format!("{}DONE({}): ", &caps[1], date.format(DATE_FMT)),
+                    ))
+                });
+            if let Some((old, new)) = date {
+                line = line.replacen(&old, &new, 1);
+            }
         }
     }
+    line
+}
 
-    pub fn iter_dones(&self) -> ListIter {
-        ListIter {
-            obj: &self.dones,
-            cur: 0,
-            skip_children: self.hide_subs,
-        }
+// Splits the leading whitespace off a line, learning the unit of indentation from the first
+// indented line of the file. Returns the level, or None if the line doesn't use that unit.
+fn indent_level(line: &str, unit: &mut Option<String>) -> Option<usize> {
+    let body = line.trim_start_matches([' ', '\t']);
+    let ws = &line[..line.len() - body.len()];
+    if ws.is_empty() {
+        return Some(0);
     }
 
-    pub fn get_dones_n(&self, full: bool) -> usize {
-        if full {
-            self.dones.list.len()
+    let unit = unit.get_or_insert_with(|| {
+        if ws.starts_with('\t') {
+            "\t".to_string()
         } else {
-            self.dones
-                .list
-                .iter()
-                .filter(|item| item.parent.is_none())
-                .count()
+            ws.to_string()
         }
+    });
+    if ws.len().is_multiple_of(unit.len()) && ws == unit.repeat(ws.len() / unit.len()) {
+        Some(ws.len() / unit.len())
+    } else {
+        None
     }
+}
 
-    pub fn clear_message(&mut self) {
-        self.message.clear();
+// Parses a single line of the list file into an item of the given panel.
+// Indentation is accepted but ignored, the place in the tree is up to the caller.
+fn parse_line(line: &str, panel: Panel) -> Result<Item, &'static str> {
+    static RE_TODO: OnceLock<Regex> = OnceLock::new();
+    static RE_DONE: OnceLock<Regex> = OnceLock::new();
+
+    match panel {
+        Panel::Todo => {
+            let re_todo =
+                RE_TODO.get_or_init(|| Regex::new(r"^\s*TODO\((\*|)\):(?: (.*))?$").unwrap());
+            let caps = re_todo
+                .captures(line)
+                .ok_or("invalid format for a TODO item")?;
+            let act_cnt = if caps[1].is_empty() { 0 } else { 1 };
+            let text = caps.get(2).map_or("", |text| text.as_str().trim());
+            Ok(Item::new(text.to_string(), Local::now(), None, act_cnt).with_tokens())
+        }
+        Panel::Done => {
+            let re_done =
+                RE_DONE.get_or_init(|| Regex::new(r"^\s*DONE\((.*?)\):(?: (.*))?$").unwrap());
+            let caps = re_done
+                .captures(line)
+                .ok_or("invalid format for a DONE item")?;
+            let date = DateTime::parse_from_str(&caps[1], DATE_FMT)
+                .map_err(|_| "invalid date format for a DONE item")?;
+            let text = caps.get(2).map_or("", |text| text.as_str().trim());
+            Ok(Item::new(text.to_string(), date.into(), None, 0).with_tokens())
+        }
     }
+}
 
-    pub fn parse(&mut self, file_path: &str) {
-        let file = File::open(file_path);
-
-        let sep = SEP;
-        let re_indent = Regex::new(r"^((\s{4})*)\S+").unwrap();
-        let mut panel = Panel::Todo;
-
-        let mut stack = Vec::new();
-        let mut cnt_todos = 0;
-        let mut cur_indent = 0;
-
-        match file {
-            Ok(file) => {
-                for (i, line) in io::BufReader::new(file).lines().enumerate() {
-                    let line = line.unwrap();
-                    let parent: Option<usize>;
-
-                    if line == sep {
-                        if panel == Panel::Todo {
-                            cur_indent = 0;
-                            stack.clear();
-                            panel = Panel::Done;
-                        } else {
-                            eprintln!("[ERROR]: {}:{}: invalid separator", file_path, i + 1);
-                            exit(1);
-                        }
-                        continue;
-                    }
+// Whether the file at `file_path` loads, or the first line `parse` would stop on. Run
+// before opening a file from the app, where `parse` exiting would lose the other tabs.
+pub fn check_lines(file_path: &str) -> Result<(), String> {
+    let text = fs::read_to_string(file_path).map_err(|err| err.to_string())?;
+    match Format::of_path(file_path) {
+        Format::Todo => check_contents(&text),
+        // Any line an Org-mode file has is either an item or kept.
+        Format::Org => Ok(()),
+    }
+}
 
-                    if let Some(m) = re_indent.captures(&line) {
-                        let indent = m[1].len() / INDENT_SIZE;
-                        match indent.cmp(&cur_indent) {
-                            Ordering::Less => {
-                                (0..(cur_indent - indent + 1)).map(|_| stack.pop()).last();
-                                cur_indent = indent;
-                            }
-                            Ordering::Equal => drop(stack.pop()),
-                            Ordering::Greater => cur_indent = indent,
-                        }
-                        parent = stack.last().copied();
-                        stack.push(i);
-                    } else {
-                        eprintln!("[ERROR]: {}:{}: invalid indentation", file_path, i + 1);
-                        exit(1);
-                    }
+fn check_contents(text: &str) -> Result<(), String> {
+    let mut panel = Panel::Todo;
+    let mut unit = None;
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line = upgrade_line(line, panel);
+        if line == SEP {
+            if panel == Panel::Done {
+                return Err(format!("{}: invalid separator", i + 1));
+            }
+            panel = Panel::Done;
+            continue;
+        }
+        parse_line(&line, panel).map_err(|err| format!("{}: {err}", i + 1))?;
+        indent_level(&line, &mut unit).ok_or(format!("{}: invalid indentation", i + 1))?;
+    }
+    Ok(())
+}
 
-                    match panel {
-                        Panel::Todo => match self.parse_todo(&line, parent) {
-                            Err(e) => {
-                                eprintln!("[ERROR]: {}:{}: {}", file_path, i + 1, e);
-                                exit(1);
-                            }
-                            Ok(todo) => {
-                                let active = todo.is_active();
-                                cnt_todos += 1;
+// The inverse of parse_line(), `level` is the depth of the item in the tree.
+// `indent` is one level of indentation as used by the file.
+fn serialize_item(item: &Item, panel: Panel, level: usize, indent: &str) -> String {
+    let indent = indent.repeat(level);
+    match panel {
+        Panel::Todo => {
+            let act = if item.is_active() { "*" } else { "" };
+            format!("{indent}TODO({act}): {}{}", item.text, item.tokens())
+        }
+        Panel::Done => {
+            let date = item.date.format(DATE_FMT);
+            format!("{indent}DONE({date}): {}{}", item.text, item.tokens())
+        }
+    }
+}
 
-                                self.todos.add_item(todo);
-                                if let Some(parent) = parent {
-                                    self.todos.add_child_to(parent, i, active);
-                                }
-                            }
-                        },
-                        Panel::Done => {
-                            let parent = parent.map(|p| p - cnt_todos - 1);
-                            match self.parse_done(&line, parent) {
-                                Err(e) => {
-                                    eprintln!("[ERROR]: {}:{}: {}", file_path, i + 1, e);
-                                    exit(1);
-                                }
-                                Ok(done) => {
-                                    let i = i - cnt_todos - 1;
-                                    self.dones.add_item(done);
-                                    if let Some(parent) = parent {
-                                        self.dones.add_child_to(parent, i, false);
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                self.message = format!("Loaded '{file_path}' file.")
+// The inverse of parse_org() for one item: its heading at `stars`, the planning line and
+// the body. The planning line is written anew once the dates differ from the ones it gave.
+fn serialize_org(item: &Item, done: bool, stars: usize) -> String {
+    static RE_DUE: OnceLock<Regex> = OnceLock::new();
+    let re_due = RE_DUE.get_or_init(|| Regex::new(r"\s*\bdue:\d{4}-\d{2}-\d{2}\b").unwrap());
+
+    let org = item.org.as_deref();
+    let due = item.get_due();
+    let closed = done.then_some(item.date);
+    let kept = org.filter(|org| org.closed == closed && org.deadline == due);
+
+    let text = if kept.is_some_and(|org| org.due_in_text) {
+        item.text.clone()
+    } else {
+        re_due.replace(&item.text, "").trim().to_string()
+    };
+    let keyword = if done { "DONE" } else { "TODO" };
+    let mut lines = format!("{} {keyword} {text}{}\n", "*".repeat(stars), item.tokens());
+
+    let planning = match kept {
+        Some(org) => org.planning.clone(),
+        None => {
+            let mut stamps = Vec::new();
+            if let Some(closed) = closed {
+                stamps.push(org::closed_stamp(closed.naive_local()));
             }
-            Err(err) => {
-                if err.kind() == io::ErrorKind::NotFound {
-                    self.message = format!("File '{file_path}' not found. Creating a new one.");
-                } else {
-                    self.message =
-                        format!("Error occured while opening the file '{file_path}': {err:?}");
-                }
+            if let Some(due) = due {
+                stamps.push(org::deadline_stamp(due));
             }
+            if let Some(scheduled) = org.and_then(|org| org.scheduled.clone()) {
+                stamps.push(scheduled);
+            }
+            let indent = org
+                .and_then(|org| org.planning.as_deref())
+                .map_or("", |line| &line[..line.len() - line.trim_start().len()]);
+            (!stamps.is_empty()).then(|| format!("{indent}{}", stamps.join(" ")))
         }
+    };
+    let body = org.map_or(&[][..], |org| org.body.as_slice());
+    for line in planning.iter().chain(body) {
+        lines.push_str(line);
+        lines.push('\n');
     }
+    lines
+}
 
-    fn parse_todo(&mut self, line: &str, parent: Option<usize>) -> Result<Item, &'static str> {
-        let re_todo = Regex::new(r"^(\s{4})*TODO\((\*|)\): (.*)$").unwrap();
+// Adds DONE lines to the end of an archive, a list file of its own: a new one starts
+// with the separator so the lines load into its DONE panel.
+fn append_archive(path: &str, lines: &str) -> io::Result<()> {
+    let existing = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+    let mut prefix = String::new();
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        prefix.push('\n');
+    }
+    if !existing.lines().any(|line| line == SEP) {
+        prefix.push_str(SEP);
+        prefix.push('\n');
+    }
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    file.write_all((prefix + lines).as_bytes())?;
+    file.sync_all()
+}
 
-        if let Some(caps) = re_todo.captures(line) {
-            let act_cnt = if caps[2].is_empty() { 0 } else { 1 };
-            Ok(Item::new(
-                caps[3].trim().to_string(),
-                Local::now(),
-                parent,
-                act_cnt,
-            ))
-        } else {
-            Err("invalid format for a TODO item")
+// Replaces the file at `path` by writing a temporary file next to it and renaming it over
+// the original, so a crash or a full disk never leaves it half written. A symlink is
+// followed and the file it points to replaced, with its permissions kept.
+fn write_atomic(path: &str, contents: &str) -> io::Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| Path::new(path).to_path_buf());
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{name}.{}.tmp", process::id()));
+
+    let write = || -> io::Result<()> {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(contents.as_bytes())?;
+        if let Ok(meta) = fs::metadata(&path) {
+            file.set_permissions(meta.permissions())?;
+        }
+        file.sync_all()?;
+        fs::rename(&tmp, &path)
+    };
+    write().inspect_err(|_| drop(fs::remove_file(&tmp)))
+}
+
+// Maps a todo.txt line onto an item:
+//   `x <completion date> [creation date] text` is a DONE item completed on that date,
+//   `x text` is a finished TODO item, there is no date to put it in the DONE list with,
+//   `[(A)] [creation date] text` is a TODO item,
+//   the priority is kept as a `pri:A` token and the creation date as `created:`.
+// Projects (`+project`), contexts (`@context`) and other `key:value` metadata
+// (e.g. `due:`) stay in the text as they are.
+fn parse_todo_txt(line: &str) -> Option<(Panel, Item)> {
+    static RE_PRI: OnceLock<Regex> = OnceLock::new();
+    let re_pri = RE_PRI.get_or_init(|| Regex::new(r"^\(([A-Z])\) +(.*)$").unwrap());
+
+    fn take_date(line: &str) -> (Option<NaiveDate>, &str) {
+        let (word, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+            Ok(date) => (Some(date), rest.trim_start()),
+            Err(_) => (None, line),
         }
     }
 
-    fn parse_done(&mut self, line: &str, parent: Option<usize>) -> Result<Item, &str> {
-        let re_done = Regex::new(r"^(\s{4})*DONE\((.*)\): (.*)$").unwrap();
+    let line = line.trim();
+    let (done, line) = match line.strip_prefix("x ") {
+        Some(rest) => (true, rest.trim_start()),
+        None => (false, line),
+    };
+    let (priority, line) = match re_pri.captures(line) {
+        Some(caps) => (
+            Some(caps.get(1).unwrap().as_str()),
+            caps.get(2).unwrap().as_str(),
+        ),
+        None => (None, line),
+    };
+    let (completed, line) = if done { take_date(line) } else { (None, line) };
+    let (created, line) = take_date(line);
+
+    if line.is_empty() {
+        return None;
+    }
 
-        if let Some(caps) = re_done.captures(line) {
-            let date = DateTime::parse_from_str(&caps[2], DATE_FMT);
-            if let Ok(d) = date {
-                Ok(Item::new(caps[3].trim().to_string(), d.into(), parent, 0))
-            } else {
-                Err("invalid date format for a DONE item")
-            }
-        } else {
-            Err("invalid format for a DONE item")
-        }
+    let mut text = line.to_string();
+    if let Some(priority) = priority {
+        text.push_str(&format!(" pri:{priority}"));
     }
 
-    pub fn save(&mut self, file_path: &str) -> std::io::Result<()> {
-        let sep = SEP;
-        self.hide_subs = false;
+    let completed = completed.and_then(|date| Local.from_local_datetime(&date.into()).single());
+    let (panel, date, act_cnt) = match (done, completed) {
+        (true, Some(date)) => (Panel::Done, date, 0),
+        (true, None) => (Panel::Todo, Local::now(), 0),
+        (false, _) => (Panel::Todo, Local::now(), 1),
+    };
 
-        let mut file = File::create(file_path)?;
-        for (todo, level) in self.iter_todos() {
-            let indent = " ".repeat(level * INDENT_SIZE);
-            let act = if todo.is_active() { "*" } else { "" };
-            writeln!(file, "{indent}TODO({act}): {}", todo.text).unwrap();
-        }
+    let mut item = Item::new(text, date, None, act_cnt).with_tokens();
+    if created.is_some() {
+        item.created = created;
+    }
+    Some((panel, item))
+}
 
-        writeln!(file, "{sep}").unwrap();
+// A line copied from a Markdown checklist, `- [ ] text` or `- [x] text` (the list marker
+// is optional): the text and whether the box is checked.
+fn strip_checkbox(text: &str) -> Option<(&str, bool)> {
+    static RE_CHECKBOX: OnceLock<Regex> = OnceLock::new();
+    let re_checkbox =
+        RE_CHECKBOX.get_or_init(|| Regex::new(r"^(?:[-*+]\s+)?\[([ xX])\]\s+(\S.*)$").unwrap());
 
-        for (done, level) in self.iter_dones() {
-            let indent = " ".repeat(level * INDENT_SIZE);
-            let date = done.date.format(DATE_FMT);
-            writeln!(file, "{indent}DONE({date}): {}", done.text).unwrap();
-        }
+    let caps = re_checkbox.captures(text)?;
+    Some((caps.get(2).unwrap().as_str(), &caps[1] != " "))
+}
 
-        Ok(())
+// A date written in `format`, with or without a time, or in one of the fixed forms.
+fn parse_date(input: &str, format: &str) -> Option<DateTime<Local>> {
+    let input = input.trim();
+    if let Ok(date) = NaiveDateTime::parse_from_str(input, format) {
+        return Local.from_local_datetime(&date).single();
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(input, format) {
+        return Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .single();
     }
 
-    pub fn toggle_panel(&mut self) {
-        assert!(!self.is_in_edit(), "Can't toggle panel while in edit mode.");
+    let input = input.to_lowercase();
+    let now = Local::now();
 
-        self.panel = self.panel.togle();
+    match input.as_str() {
+        "now" | "today" => return Some(now),
+        "yesterday" => return Some(now - Duration::days(1)),
+        _ => {}
     }
 
-    pub fn toggle_subtasks(&mut self) {
-        assert!(
-            !self.is_in_edit(),
-            "Can't toggle subtasks while in edit mode."
-        );
+    if let Some(days) = input
+        .strip_suffix(" days ago")
+        .or_else(|| input.strip_suffix(" day ago"))
+        .or_else(|| input.strip_suffix("d ago"))
+    {
+        return days
+            .trim()
+            .parse::<i64>()
+            .ok()
+            .map(|days| now - Duration::days(days));
+    }
 
-        self.hide_subs = !self.hide_subs;
+    if let Ok(date) = DateTime::parse_from_str(&input, DATE_FMT) {
+        return Some(date.into());
+    }
+    if let Ok(date) = NaiveDateTime::parse_from_str(&input, DATE_INPUT_FMT) {
+        return Local.from_local_datetime(&date).single();
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(&input, "%Y-%m-%d") {
+        return Local
+            .from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+            .single();
+    }
 
-        if self.hide_subs {
-            if let Some(cur_todo) = self.todos.get_cur_item() {
-                if !cur_todo.is_root() {
-                    self.todos.up(!self.hide_subs);
-                }
-            }
-            if let Some(cur_done) = self.dones.get_cur_item() {
-                if !cur_done.is_root() {
-                    self.dones.up(!self.hide_subs);
-                }
-            }
+    None
+}
+
+// The date format as a hint for the prompt, e.g. `YYYY-MM-DD hh:mm`.
+fn date_hint(format: &str) -> String {
+    [
+        ("%Y", "YYYY"),
+        ("%m", "MM"),
+        ("%d", "DD"),
+        ("%H", "hh"),
+        ("%M", "mm"),
+    ]
+    .iter()
+    .fold(format.to_string(), |hint, (spec, text)| {
+        hint.replace(spec, text)
+    })
+}
+
+#[derive(Debug)]
+pub struct TodoApp {
+    message: String,
+    date_input: Option<String>,
+    date_format: String,
+    blocking: Option<Item>,
+    swapping: Option<(Panel, Item)>,
+    picked: Vec<usize>,
+    anchor: Option<usize>,
+    max_len: usize,
+    max_items: usize,
+    drag_wrap: bool,
+    // The edit started with `EditStart::Replace` and no key was typed yet.
+    replacing: bool,
+    flash: Option<(Panel, usize)>,
+    filter: Option<Filter>,
+    // The order each panel is kept in, sorted again when the file is opened next.
+    todo_sort: Option<Sort>,
+    done_sort: Option<Sort>,
+    tags: Option<Vec<(String, usize, usize)>>,
+    activity_log: bool,
+    create_dirs: bool,
+    read_only: bool,
+    log: Vec<String>,
+    log_warned: bool,
+    log_warning: Option<String>,
+    hooks: Hooks,
+    hook_failure: Option<String>,
+    // Saved files are committed on another thread, one commit at a time: a file saved
+    // while one runs waits in `git_pending`.
+    git_message: Option<String>,
+    git_job: Option<Receiver<Result<String, String>>>,
+    git_pending: Option<(String, String)>,
+    migration: Vec<(usize, String, String)>,
+    migrate: bool,
+    // The upgrade was declined: the lines of `migration` are written back as they were.
+    keep_format: bool,
+    format: Format,
+    // The lines of an Org-mode file before its first item.
+    preamble: Vec<String>,
+    // The file as last loaded or saved, to notice changes made to it in the meantime.
+    on_disk: Option<String>,
+    // The day due dates were last looked at, to notice the app running past midnight.
+    evaluated: NaiveDate,
+    indent: String,
+    confirm: Option<Confirm>,
+    dedupe: Option<Dedupe>,
+    panel: Panel,
+    hide_subs: bool,
+    dirty: bool,
+    // Changes made since the list was loaded or saved, counted for the autosave.
+    changes: usize,
+    operation_stack: Vec<Operation>,
+    redo_stack: Vec<Operation>,
+    todos: List,
+    dones: List,
+}
+
+impl TodoApp {
+    pub fn new() -> Self {
+        Self {
+            message: String::new(),
+            date_input: None,
+            date_format: DATE_INPUT_FMT.to_string(),
+            blocking: None,
+            swapping: None,
+            picked: Vec::new(),
+            anchor: None,
+            max_len: usize::MAX,
+            max_items: usize::MAX,
+            drag_wrap: false,
+            replacing: false,
+            flash: None,
+            filter: None,
+            todo_sort: None,
+            done_sort: None,
+            tags: None,
+            activity_log: false,
+            create_dirs: false,
+            read_only: false,
+            log: Vec::new(),
+            log_warned: false,
+            log_warning: None,
+            hooks: Hooks::default(),
+            hook_failure: None,
+            git_message: None,
+            git_job: None,
+            git_pending: None,
+            migration: Vec::new(),
+            migrate: false,
+            keep_format: false,
+            format: Format::Todo,
+            preamble: Vec::new(),
+            on_disk: None,
+            evaluated: Local::now().date_naive(),
+            indent: " ".repeat(INDENT_SIZE),
+            confirm: None,
+            dedupe: None,
+            panel: Panel::Todo,
+            hide_subs: false,
+            dirty: false,
+            changes: 0,
+            operation_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            todos: List::new(),
+            dones: List::new(),
         }
     }
 
-    pub fn go_up(&mut self) {
-        assert!(!self.is_in_edit(), "Can't go up while in edit mode.");
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len;
+    }
 
-        match self.panel {
-            Panel::Todo => self.todos.up(!self.hide_subs),
-            Panel::Done => self.dones.up(!self.hide_subs),
-        }
+    pub fn set_max_items(&mut self, max_items: usize) {
+        self.max_items = max_items;
     }
 
-    pub fn go_down(&mut self) {
-        assert!(!self.is_in_edit(), "Can't go down while in edit mode.");
-        match self.panel {
-            Panel::Todo => self.todos.down(!self.hide_subs),
-            Panel::Done => self.dones.down(!self.hide_subs),
+    // Every item added to a panel shifts the ones after it and each action copies the
+    // list for undo, so past `max_items` the panel stops growing. Loading, browsing and
+    // taking items out still work.
+    fn room(&self, panel: Panel, adding: usize) -> Result<(), String> {
+        let (len, name) = match panel {
+            Panel::Todo => (self.todos.list.len(), "TODO"),
+            Panel::Done => (self.dones.list.len(), "DONE"),
+        };
+        if len.saturating_add(adding) <= self.max_items {
+            return Ok(());
         }
+        let full = if len >= self.max_items {
+            format!("The {name} panel is full ({len} items")
+        } else {
+            format!("The {name} panel has no room for {adding} more items ({len} items")
+        };
+        Err(format!(
+            "{full}, max_items is {}). Move old items to an archive file or raise max_items.",
+            self.max_items
+        ))
     }
 
-    pub fn go_top(&mut self) {
-        assert!(!self.is_in_edit(), "Can't go top while in edit mode.");
-        match self.panel {
-            Panel::Todo => self.todos.first(),
-            Panel::Done => self.dones.first(),
+    // Like room(), with the reason in the message.
+    fn is_full(&mut self, panel: Panel, adding: usize) -> bool {
+        match self.room(panel, adding) {
+            Ok(()) => false,
+            Err(err) => {
+                self.message.push_str(&err);
+                true
+            }
         }
     }
 
-    pub fn go_half(&mut self) {
-        assert!(!self.is_in_edit(), "Can't go half while in edit mode.");
-        match self.panel {
-            Panel::Todo => self.todos.half(!self.hide_subs),
-            Panel::Done => self.dones.half(!self.hide_subs),
-        }
+    pub fn set_drag_wrap(&mut self, wrap: bool) {
+        self.drag_wrap = wrap;
     }
 
-    pub fn go_bottom(&mut self) {
-        assert!(!self.is_in_edit(), "Can't go bottom while in edit mode.");
-        match self.panel {
-            Panel::Todo => self.todos.last(!self.hide_subs),
-            Panel::Done => self.dones.last(!self.hide_subs),
-        }
+    pub fn set_activity_log(&mut self, enabled: bool) {
+        self.activity_log = enabled;
     }
 
-    pub fn drag_up(&mut self) {
-        assert!(!self.is_in_edit(), "Can't drag up while in edit mode.");
-        match self.panel {
-            Panel::Todo => {
-                self.todos.record_state();
-                match self.todos.drag_up() {
-                    Ok(()) => self
-                        .operation_stack
-                        .push(Operation::new(Action::DragUp, Panel::Todo)),
-                    Err(err) => {
-                        self.message.push_str(err);
-                        self.todos.revert_state().unwrap();
-                    }
-                };
-            }
-            Panel::Done => {
-                self.dones.record_state();
-                match self.dones.drag_up() {
-                    Ok(()) => self
-                        .operation_stack
-                        .push(Operation::new(Action::DragUp, Panel::Done)),
-                    Err(err) => {
-                        self.message.push_str(err);
-                        self.dones.revert_state().unwrap();
-                    }
-                };
-            }
+    pub fn set_hooks(&mut self, hooks: Hooks) {
+        self.hooks = hooks;
+    }
+
+    pub fn set_date_format(&mut self, format: &str) {
+        self.date_format = format.to_string();
+    }
+
+    // The message template of the commit made on save, none to not commit.
+    pub fn set_git_commit(&mut self, message: Option<String>) {
+        self.git_message = message;
+    }
+
+    fn commit_file(&mut self, file_path: &str) {
+        let Some(template) = self.git_message.as_ref() else {
+            return;
+        };
+        let name = Path::new(file_path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let message = template
+            .replace("{file}", &name)
+            .replace("{todos}", &self.get_todos_n(false).to_string())
+            .replace("{dones}", &self.get_dones_n(false).to_string());
+        self.git_pending = Some((file_path.to_string(), message));
+        self.start_commit();
+    }
+
+    fn start_commit(&mut self) {
+        if self.git_job.is_some() {
+            return;
         }
+        let Some((file_path, message)) = self.git_pending.take() else {
+            return;
+        };
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let result = match git::commit(&file_path, &message) {
+                Ok(true) => Ok(format!("Committed '{file_path}'.")),
+                Ok(false) => Ok(String::new()),
+                Err(err) => Err(format!("Could not commit '{file_path}': {err}")),
+            };
+            let _ = tx.send(result);
+        });
+        self.git_job = Some(rx);
     }
 
-    pub fn drag_down(&mut self) {
-        assert!(!self.is_in_edit(), "Can't drag down while in edit mode.");
+    // The outcome of a finished commit, unless nothing was committed.
+    pub fn poll_commit(&mut self) -> Option<Result<String, String>> {
+        let result = match self.git_job.as_ref()?.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return None,
+            Err(TryRecvError::Disconnected) => Ok(String::new()),
+        };
+        self.git_job = None;
+        self.start_commit();
+        Some(result).filter(|result| !matches!(result, Ok(msg) if msg.is_empty()))
+    }
 
-        match self.panel {
-            Panel::Todo => {
-                self.todos.record_state();
-                match self.todos.drag_down() {
-                    Ok(()) => self
-                        .operation_stack
-                        .push(Operation::new(Action::DragDown, Panel::Todo)),
-                    Err(err) => {
-                        self.message.push_str(err);
-                        self.todos.revert_state().unwrap();
-                    }
-                };
-            }
-            Panel::Done => {
-                self.dones.record_state();
-                match self.dones.drag_down() {
-                    Ok(()) => self
-                        .operation_stack
-                        .push(Operation::new(Action::DragDown, Panel::Done)),
-                    Err(err) => {
-                        self.message.push_str(err);
-                        self.dones.revert_state().unwrap();
-                    }
-                };
+    // Waits for the commits still to be made, before exiting.
+    pub fn wait_commits(&mut self) -> Vec<Result<String, String>> {
+        let mut results = Vec::new();
+        while let Some(job) = self.git_job.take() {
+            if let Ok(result) = job.recv() {
+                results.push(result);
             }
+            self.start_commit();
         }
+        results.retain(|result| !matches!(result, Ok(msg) if msg.is_empty()));
+        results
     }
 
-    pub fn mark_item(&mut self) {
-        assert!(!self.is_in_edit(), "Can't mark item while in edit mode");
+    pub fn set_create_dirs(&mut self, enabled: bool) {
+        self.create_dirs = enabled;
+    }
 
-        match self.panel {
-            Panel::Todo => {
-                self.todos.record_state();
-                match self.todos.mark() {
-                    Ok(()) => {
-                        self.operation_stack
-                            .push(Operation::new(Action::Mark, Panel::Todo));
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.read_only = read_only;
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // Events are kept until the next save, which appends them to the log next to the file.
+    // The hook of the event runs right away.
+    fn log_event(&mut self, event: &str, text: &str) {
+        if self.activity_log {
+            let date = Local::now().format("%Y-%m-%dT%H:%M:%S");
+            self.log.push(format!("{date} {event} {text:?}"));
+        }
+        self.run_hook(event, text);
+    }
+
+    // The hook gets the item text as `$1` and in TODORS_TEXT, the event (ADD, DONE or
+    // DELETE) in TODORS_EVENT. Its output is discarded and a failure is only reported.
+    fn run_hook(&mut self, event: &str, text: &str) {
+        let hook = match event {
+            "ADD" => &self.hooks.add,
+            "DONE" => &self.hooks.done,
+            "DELETE" => &self.hooks.delete,
+            _ => &None,
+        };
+        let Some(hook) = hook else {
+            return;
+        };
+
+        let status = process::Command::new("sh")
+            .arg("-c")
+            .arg(hook)
+            .arg("todors")
+            .arg(text)
+            .env("TODORS_EVENT", event)
+            .env("TODORS_TEXT", text)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        let failure = match status {
+            Ok(status) if status.success() => return,
+            Ok(status) => status.to_string(),
+            Err(err) => err.to_string(),
+        };
+        self.hook_failure = Some(format!(
+            "The {} hook failed: {failure}",
+            event.to_lowercase()
+        ));
+    }
+
+    pub fn take_hook_failure(&mut self) -> Option<String> {
+        self.hook_failure.take()
+    }
+
+    fn write_log(&mut self, file_path: &str) {
+        if self.log.is_empty() {
+            return;
+        }
+        let path = Path::new(file_path).with_extension("log");
+        let result = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .and_then(|mut file| file.write_all((self.log.join("\n") + "\n").as_bytes()));
+        match result {
+            Ok(()) => self.log.clear(),
+            // Kept for the next save, the list itself is saved either way.
+            Err(err) if !self.log_warned => {
+                self.log_warned = true;
+                self.log_warning = Some(format!(
+                    "Could not write the activity log '{}': {err}",
+                    path.display()
+                ));
+            }
+            Err(_) => {}
+        }
+    }
+
+    // Counts of the (ADD, DONE, DELETE) events in the activity log next to the file.
+    pub fn log_counts(&self, file_path: &str) -> io::Result<(usize, usize, usize)> {
+        let content = fs::read_to_string(Path::new(file_path).with_extension("log"))?;
+        let (mut added, mut done, mut deleted) = (0, 0, 0);
+        for line in content.lines() {
+            match line.split(' ').nth(1) {
+                Some("ADD") => added += 1,
+                Some("DONE") => done += 1,
+                Some("DELETE") => deleted += 1,
+                _ => {}
+            }
+        }
+        Ok((added, done, deleted))
+    }
+
+    // A failed write of the activity log is only reported once.
+    pub fn take_log_warning(&mut self) -> Option<String> {
+        self.log_warning.take()
+    }
+
+    pub fn get_max_len(&self) -> usize {
+        self.max_len
+    }
+
+    pub fn get_edit_len(&self) -> Option<usize> {
+        if !self.is_in_edit() || self.date_input.is_some() {
+            return None;
+        }
+        let item = match self.panel {
+            Panel::Todo => self.todos.get_cur_item(),
+            Panel::Done => self.dones.get_cur_item(),
+        };
+        item.map(|item| item.text.chars().count())
+    }
+
+    pub fn is_in_todos(&self) -> bool {
+        self.panel == Panel::Todo
+    }
+
+    pub fn is_subs_hidden(&self) -> bool {
+        self.hide_subs
+    }
+
+    pub fn is_in_dones(&self) -> bool {
+        self.panel == Panel::Done
+    }
+
+    pub fn is_cur_todo(&self, todo: &Item) -> bool {
+        self.todos.get_cur_item() == Some(todo)
+    }
+
+    pub fn is_cur_done(&self, done: &Item) -> bool {
+        self.dones.get_cur_item() == Some(done)
+    }
+
+    pub fn get_message(&self) -> &String {
+        &self.message
+    }
+
+    // How many of the subtasks right under a 'Todo' item are done, out of how many, `None`
+    // without subtasks.
+    pub fn progress(&self, todo: &Item) -> Option<(usize, usize)> {
+        let children = &todo.children;
+        let done = children
+            .iter()
+            .filter_map(|&child| self.todos.list.get(child))
+            .filter(|child| !child.is_active())
+            .count();
+        (!children.is_empty()).then_some((done, children.len()))
+    }
+
+    pub fn is_blocked(&self, item: &Item) -> bool {
+        self.get_blocker(item).is_some()
+    }
+
+    pub fn is_blocking(&self, item: &Item) -> bool {
+        self.blocking.as_ref() == Some(item)
+    }
+
+    pub fn is_swapping(&self, item: &Item) -> bool {
+        self.swapping
+            .as_ref()
+            .is_some_and(|(_, swapping)| swapping == item)
+    }
+
+    pub fn is_confirming(&self) -> bool {
+        self.confirm.is_some()
+    }
+
+    fn get_blocker(&self, item: &Item) -> Option<&Item> {
+        let blocker = item.blocked_by?;
+        self.todos
+            .list
+            .iter()
+            .find(|todo| todo.id == Some(blocker) && todo.is_active())
+    }
+
+    fn find_by_id(&self, id: usize) -> Option<&Item> {
+        self.todos
+            .list
+            .iter()
+            .chain(self.dones.list.iter())
+            .find(|item| item.id == Some(id))
+    }
+
+    // A file written by hand can give two items the same id, or block items in a circle
+    // nothing could ever unblock. The later duplicates lose their id and each circle loses
+    // the reference that closes it, both counted in the message.
+    fn fix_blocking(&mut self) {
+        let mut ids = Vec::new();
+        let mut dupes = 0;
+        for item in self.todos.list.iter_mut().chain(self.dones.list.iter_mut()) {
+            match item.id {
+                Some(id) if ids.contains(&id) => {
+                    item.id = None;
+                    dupes += 1;
+                }
+                Some(id) => ids.push(id),
+                None => {}
+            }
+        }
+
+        let mut cycles = 0;
+        for idx in 0..self.todos.list.len() {
+            let Some(start) = self.todos.list[idx].id else {
+                continue;
+            };
+            let mut seen = Vec::new();
+            let mut chain = self.todos.list[idx].blocked_by;
+            while let Some(id) = chain.filter(|id| !seen.contains(id)) {
+                if id == start {
+                    self.todos.list[idx].blocked_by = None;
+                    cycles += 1;
+                    break;
+                }
+                seen.push(id);
+                chain = self.find_by_id(id).and_then(|item| item.blocked_by);
+            }
+        }
+
+        if dupes > 0 {
+            self.message
+                .push_str(&format!(" Dropped {dupes} duplicate id(s)."));
+        }
+        if cycles > 0 {
+            self.message
+                .push_str(&format!(" Broke {cycles} blocking cycle(s)."));
+        }
+        if dupes + cycles > 0 {
+            self.dirty = true;
+        }
+    }
+
+    fn next_id(&self) -> usize {
+        self.todos
+            .list
+            .iter()
+            .chain(self.dones.list.iter())
+            .filter_map(|item| item.id)
+            .max()
+            .map_or(1, |id| id + 1)
+    }
+
+    fn clear_blocked_refs(&mut self, ids: &[usize]) -> usize {
+        let mut cnt = 0;
+        for item in self.todos.list.iter_mut().chain(self.dones.list.iter_mut()) {
+            if item.blocked_by.is_some_and(|id| ids.contains(&id)) {
+                item.blocked_by = None;
+                cnt += 1;
+            }
+        }
+        cnt
+    }
+
+    // Drops the `blocked:` references of the TODO items whose blocker is done now and says
+    // how many were freed. References to ids that don't exist are left alone, they don't
+    // block anything.
+    fn unblock_finished(&mut self) {
+        let finished: Vec<usize> = self
+            .todos
+            .list
+            .iter()
+            .filter(|item| !item.is_active())
+            .chain(self.dones.list.iter())
+            .filter_map(|item| item.id)
+            .collect();
+        let mut cnt = 0;
+        for item in self.todos.list.iter_mut() {
+            if item.blocked_by.is_some_and(|id| finished.contains(&id)) {
+                item.blocked_by = None;
+                cnt += 1;
+            }
+        }
+        if cnt > 0 {
+            let sep = if self.message.is_empty() { "" } else { " " };
+            self.message
+                .push_str(&format!("{sep}Unblocked {cnt} item(s)."));
+        }
+    }
+
+    fn is_deleted(&self, ids: &[usize]) -> bool {
+        !ids.is_empty() && ids.iter().all(|&id| self.find_by_id(id).is_none())
+    }
+
+    pub fn due_soon(&self, n: usize) -> Vec<&Item> {
+        let mut due: Vec<&Item> = self
+            .todos
+            .list
+            .iter()
+            .filter(|item| item.is_active() && (!self.hide_subs || item.is_root()))
+            .filter(|item| item.get_due().is_some())
+            .collect();
+        due.sort_by_key(|item| item.get_due());
+        due.truncate(n);
+        due
+    }
+
+    // On the first check of a new day, reports the open items that fell due since the
+    // last one. The day only moves forward: a clock set back and forth again (suspend,
+    // NTP) doesn't report the same items twice.
+    pub fn check_day_change(&mut self) -> bool {
+        let today = Local::now().date_naive();
+        if today <= self.evaluated {
+            return false;
+        }
+        let last = std::mem::replace(&mut self.evaluated, today);
+
+        let cnt = self
+            .todos
+            .list
+            .iter()
+            .filter(|item| item.is_active())
+            .filter_map(|item| item.get_due())
+            .filter(|&due| last < due && due <= today)
+            .count();
+        if cnt == 0 {
+            return false;
+        }
+        self.message = format!("{cnt} item(s) became due.");
+        true
+    }
+
+    pub fn go_to_due(&mut self, n: usize, max: usize) {
+        assert!(!self.is_in_edit(), "Can't jump while in edit mode.");
+
+        let target = self.due_soon(max).get(n).map(|&item| item.clone());
+        match target.and_then(|item| self.todos.list.iter().position(|todo| *todo == item)) {
+            Some(idx) => {
+                self.set_panel(Panel::Todo);
+                self.todos.cur = idx;
+            }
+            None => self.message.push_str("No such item due soon."),
+        }
+    }
+
+    // The items of both panels with every word of `words` in their text, ignoring case,
+    // the 'Todo' ones first. Each comes with whether it's a 'Todo' item.
+    pub fn search(&self, words: &str) -> Vec<(bool, &Item)> {
+        self.search_at(words)
+            .into_iter()
+            .map(|(panel, idx)| match panel {
+                Panel::Todo => (true, &self.todos.list[idx]),
+                Panel::Done => (false, &self.dones.list[idx]),
+            })
+            .collect()
+    }
+
+    fn search_at(&self, words: &str) -> Vec<(Panel, usize)> {
+        let words: Vec<String> = words.split_whitespace().map(str::to_lowercase).collect();
+        let found = |text: &str| {
+            let text = text.to_lowercase();
+            words.iter().all(|word| text.contains(word))
+        };
+        let todos = self
+            .todos
+            .list
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| (Panel::Todo, idx, item));
+        let dones = self
+            .dones
+            .list
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| (Panel::Done, idx, item));
+        todos
+            .chain(dones)
+            .filter(|(_, _, item)| found(&item.text))
+            .map(|(panel, idx, _)| (panel, idx))
+            .collect()
+    }
+
+    // Moves to the `n`th item `search` finds, in its panel. A filter hiding it is cleared
+    // and hidden subtasks are shown again.
+    pub fn go_to_found(&mut self, words: &str, n: usize) {
+        assert!(!self.is_in_edit(), "Can't jump while in edit mode.");
+
+        let Some(&(panel, idx)) = self.search_at(words).get(n) else {
+            self.message.push_str("No such item found.");
+            return;
+        };
+        self.set_panel(panel);
+        let list = match panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        list.cur = idx;
+        let root = list.list[idx].is_root();
+        if self.hide_subs && !root {
+            self.hide_subs = false;
+        }
+        if !self.is_cur_shown() {
+            self.filter = None;
+            self.message.push_str("Filter cleared to show the item.");
+        }
+    }
+
+    pub fn is_replacing(&self) -> bool {
+        self.replacing
+    }
+
+    pub fn get_date_input(&self) -> Option<&String> {
+        self.date_input.as_ref()
+    }
+
+    // Whether the date typed so far can be confirmed, checked again on every key.
+    pub fn is_date_input_valid(&self) -> bool {
+        self.date_input
+            .as_ref()
+            .is_some_and(|input| parse_date(input, &self.date_format).is_some())
+    }
+
+    // Each panel keeps its own scroll offset, the same way it keeps its own cursor.
+    pub fn scroll_todos(&mut self, height: usize, margin: usize) {
+        let keep = self.editing(Panel::Todo);
+        self.todos.scroll_to_cur(
+            height,
+            margin,
+            self.hide_subs,
+            self.filter.as_ref(),
+            Panel::Todo,
+            keep,
+        );
+    }
+
+    pub fn visible_todos(&self, height: usize) -> impl Iterator<Item = (&Item, usize)> {
+        self.todos
+            .iter_from(self.todos.top, self.hide_subs)
+            .filter(|(item, _)| self.is_shown(item, Panel::Todo))
+            .take(height)
+    }
+
+    // Only the items a filter shows count, e.g. what's left for today under a `due:today`
+    // preset.
+    pub fn total_open_estimate(&self) -> Duration {
+        self.todos
+            .list
+            .iter()
+            .filter(|item| item.is_active() && self.is_shown(item, Panel::Todo))
+            .filter_map(|item| item.get_estimate())
+            .fold(Duration::zero(), |total, est| total + est)
+    }
+
+    pub fn get_todos_n(&self, full: bool) -> usize {
+        if full {
+            self.todos.list.len()
+        } else {
+            self.todos
+                .list
+                .iter()
+                .filter(|item| item.parent.is_none())
+                .count()
+        }
+    }
+
+    pub fn scroll_dones(&mut self, height: usize, margin: usize) {
+        let keep = self.editing(Panel::Done);
+        self.dones.scroll_to_cur(
+            height,
+            margin,
+            self.hide_subs,
+            self.filter.as_ref(),
+            Panel::Done,
+            keep,
+        );
+    }
+
+    pub fn visible_dones(&self, height: usize) -> impl Iterator<Item = (&Item, usize)> {
+        self.dones
+            .iter_from(self.dones.top, self.hide_subs)
+            .filter(|(item, _)| self.is_shown(item, Panel::Done))
+            .take(height)
+    }
+
+    pub fn get_dones_n(&self, full: bool) -> usize {
+        if full {
+            self.dones.list.len()
+        } else {
+            self.dones
+                .list
+                .iter()
+                .filter(|item| item.parent.is_none())
+                .count()
+        }
+    }
+
+    pub fn clear_message(&mut self) {
+        self.message.clear();
+    }
+
+    pub fn set_message(&mut self, message: &str) {
+        self.message = message.to_string();
+    }
+
+    // A line that doesn't load is an error naming the file and the line, the lists are
+    // left half built then and the app is dropped.
+    pub fn parse(&mut self, file_path: &str) -> Result<(), String> {
+        match fs::read_to_string(file_path) {
+            Ok(content) => {
+                self.parse_contents(file_path, &content)?;
+                self.on_disk = Some(content);
+            }
+            Err(err) => {
+                if err.kind() == io::ErrorKind::NotFound {
+                    self.message = format!("File '{file_path}' not found. Creating a new one.");
+                } else {
+                    self.message =
+                        format!("Error occured while opening the file '{file_path}': {err:?}");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Loads the file again from `disk` with the changes made since it was loaded or saved
+    // merged in, see merge_lines(). The settings, the filter and the events not logged yet
+    // stay, the undo history goes. Nothing changes when either list doesn't load.
+    pub fn reload_merged(&mut self, file_path: &str, disk: String) -> Result<(), String> {
+        let base = self.on_disk.clone().unwrap_or_default();
+        let merge = merge_lines(&base, &self.contents(), &disk);
+        if self.format == Format::Todo {
+            check_contents(&disk).map_err(|err| format!("{file_path}:{err}"))?;
+            check_contents(&merge.contents)
+                .map_err(|err| format!("the merged list doesn't load, line {err}"))?;
+        }
+
+        let (todo_cur, done_cur) = (self.todos.cur, self.dones.cur);
+        self.todos = List::new();
+        self.dones = List::new();
+        self.operation_stack.clear();
+        self.redo_stack.clear();
+        self.picked.clear();
+        self.anchor = None;
+        self.blocking = None;
+        self.swapping = None;
+        self.flash = None;
+        self.tags = None;
+        self.confirm = None;
+        self.migration.clear();
+        self.indent = " ".repeat(INDENT_SIZE);
+        self.preamble.clear();
+        self.parse_contents(file_path, &merge.contents)?;
+        self.todos.cur = todo_cur.min(self.todos.list.len().saturating_sub(1));
+        self.dones.cur = done_cur.min(self.dones.list.len().saturating_sub(1));
+        self.settle(Panel::Todo, true);
+        self.settle(Panel::Done, true);
+        self.dirty = merge.contents != disk;
+        self.on_disk = Some(disk);
+
+        self.message = if self.dirty {
+            format!("Reloaded '{file_path}' with the unsaved changes merged in.")
+        } else {
+            format!("Reloaded '{file_path}'.")
+        };
+        if merge.conflicts.is_empty() {
+            return Ok(());
+        }
+        self.message.push_str(&format!(
+            " Kept the local version of {} change(s) also made on disk",
+            merge.conflicts.len()
+        ));
+        let lost: Vec<String> = merge
+            .conflicts
+            .into_iter()
+            .flat_map(|conflict| conflict.disk)
+            .map(|line| line.trim().to_string())
+            .collect();
+        match lost.first() {
+            None => self.message.push('.'),
+            Some(_) if self.activity_log => {
+                self.message
+                    .push_str(", the disk version is in the activity log.");
+                lost.iter()
+                    .for_each(|line| self.log_event("CONFLICT", line));
+            }
+            Some(first) => self.message.push_str(&format!(", the disk had '{first}'.")),
+        }
+        Ok(())
+    }
+
+    // Builds the lists from the lines of the file, `file_path` is only used in messages.
+    // The error is the first line that doesn't load, the caller decides whether to exit so
+    // that the lock on the file is released first.
+    fn parse_contents(&mut self, file_path: &str, content: &str) -> Result<(), String> {
+        if self.format == Format::Org {
+            self.parse_org(file_path, content);
+            return Ok(());
+        }
+        let sep = SEP;
+        let mut unit = None;
+        let mut panel = Panel::Todo;
+
+        let mut stack = Vec::new();
+        let mut cur_indent = 0;
+        let mut gap = 0;
+        // Over-long items are kept as is, only reported once loaded. Items without any
+        // text are dropped, their subtasks go to the parent above them.
+        let mut too_long = Vec::new();
+        let mut empty = 0;
+
+        for (i, line) in content.lines().map(String::from).enumerate() {
+            let parent: Option<usize>;
+
+            // Blank lines separate groups of items, kept as the gap of the next one.
+            if line.trim().is_empty() {
+                gap += 1;
+                continue;
+            }
+
+            // Lines in an older shape are upgraded in memory only, the file is
+            // rewritten once the migration is confirmed.
+            let upgraded = upgrade_line(&line, panel);
+            let line = if upgraded != line {
+                self.migration.push((i + 1, line, upgraded.clone()));
+                upgraded
+            } else {
+                line
+            };
+
+            if line == sep {
+                if panel == Panel::Todo {
+                    cur_indent = 0;
+                    stack.clear();
+                    self.todos.tail_gap = std::mem::take(&mut gap);
+                    panel = Panel::Done;
+                } else {
+                    return Err(format!("{}:{}: invalid separator", file_path, i + 1));
+                }
+                continue;
+            }
+
+            let mut item = match parse_line(&line, panel) {
+                Ok(item) => item,
+                Err(e) => return Err(format!("{}:{}: {}", file_path, i + 1, e)),
+            };
+            if item.text.is_empty() {
+                empty += 1;
+                continue;
+            }
+
+            let idx = match panel {
+                Panel::Todo => self.todos.list.len(),
+                Panel::Done => self.dones.list.len(),
+            };
+            if let Some(indent) = indent_level(&line, &mut unit) {
+                match indent.cmp(&cur_indent) {
+                    Ordering::Less => {
+                        (0..(cur_indent - indent + 1)).map(|_| stack.pop()).last();
+                        cur_indent = indent;
+                    }
+                    Ordering::Equal => drop(stack.pop()),
+                    Ordering::Greater => cur_indent = indent,
+                }
+                parent = stack.last().copied();
+                stack.push(idx);
+            } else {
+                return Err(format!("{}:{}: invalid indentation", file_path, i + 1));
+            }
+
+            item.parent = parent;
+            item.gap = std::mem::take(&mut gap);
+            if item.text.chars().count() > self.max_len {
+                too_long.push((i + 1).to_string());
+            }
+            let active = item.is_active();
+            let list = match panel {
+                Panel::Todo => &mut self.todos,
+                Panel::Done => &mut self.dones,
+            };
+            list.add_item(item);
+            if let Some(parent) = parent {
+                list.add_child_to(parent, idx, active);
+            }
+        }
+        match panel {
+            Panel::Todo => self.todos.tail_gap = gap,
+            Panel::Done => self.dones.tail_gap = gap,
+        }
+        // Keep writing the file with the indentation it was written with.
+        if let Some(unit) = unit {
+            self.indent = unit;
+        }
+        self.message = format!("Loaded '{file_path}' file.");
+        if self.needs_migration() {
+            self.message.push_str(&format!(
+                " {} line(s) use an old format. Upgrade the file on save? (y/n)",
+                self.migration.len()
+            ));
+            self.confirm = Some(Confirm::Migrate);
+        }
+        if !too_long.is_empty() {
+            self.message.push_str(&format!(
+                " Line(s) {} exceed {} characters.",
+                too_long.join(", "),
+                self.max_len
+            ));
+        }
+        if empty > 0 {
+            self.message
+                .push_str(&format!(" Dropped {empty} empty item(s)."));
+            self.dirty = true;
+        }
+        self.fix_blocking();
+        Ok(())
+    }
+
+    // Builds the lists from an Org-mode file. A TODO or DONE heading is an item, a deeper
+    // one right under it a subtask, in the panel of the top one. Every other line, the
+    // headings without a keyword too, is kept with the item above it or in `preamble`.
+    fn parse_org(&mut self, file_path: &str, content: &str) {
+        // The item headings a deeper one would be nested under: the stars, panel and
+        // index of each.
+        let mut stack: Vec<(usize, Panel, usize)> = Vec::new();
+        let mut last = None;
+        let mut too_long = Vec::new();
+        // Open headings under DONE ones, the 'Done' panel has no place for them.
+        let mut closed = 0;
+        let mut roots = 0;
+
+        for (i, line) in content.lines().enumerate() {
+            let heading = org::heading(line);
+            if let Some(heading) = heading.as_ref() {
+                // A heading ends the ones at its level and deeper.
+                while stack
+                    .last()
+                    .is_some_and(|&(stars, _, _)| stars >= heading.stars)
+                {
+                    stack.pop();
+                }
+            }
+            let (stars, done, text) = match heading {
+                Some(org::Heading {
+                    stars,
+                    done: Some(done),
+                    text,
+                }) if !text.trim().is_empty() => (stars, done, text.trim()),
+                _ => {
+                    self.org_line(last, line);
+                    continue;
+                }
+            };
+
+            let parent = stack.last().map(|&(_, panel, idx)| (panel, idx));
+            let panel = match parent {
+                Some((panel, _)) => panel,
+                None if done => Panel::Done,
+                None => Panel::Todo,
+            };
+            if panel == Panel::Done && !done {
+                closed += 1;
+            }
+            let act_cnt = usize::from(panel == Panel::Todo && !done);
+            let parent_idx = parent.map(|(_, idx)| idx);
+            let mut item =
+                Item::new(text.to_string(), Local::now(), parent_idx, act_cnt).with_tokens();
+            if item.text.chars().count() > self.max_len {
+                too_long.push((i + 1).to_string());
+            }
+            let due = item.get_due();
+            let order = match parent {
+                Some((panel, idx)) => match panel {
+                    Panel::Todo => &self.todos,
+                    Panel::Done => &self.dones,
+                }
+                .list[idx]
+                    .org
+                    .as_ref()
+                    .map_or(0, |org| org.order),
+                None => {
+                    roots += 1;
+                    roots
+                }
+            };
+            item.org = Some(Box::new(OrgItem {
+                stars,
+                order,
+                planning: None,
+                closed: (!item.is_active()).then_some(item.date),
+                deadline: due,
+                scheduled: None,
+                due_in_text: due.is_some(),
+                body: Vec::new(),
+            }));
+
+            let active = item.is_active();
+            let list = match panel {
+                Panel::Todo => &mut self.todos,
+                Panel::Done => &mut self.dones,
+            };
+            let idx = list.list.len();
+            list.add_item(item);
+            if let Some(parent) = parent_idx {
+                list.list[parent].children.push(idx);
+                if active {
+                    list.unmark_parents(Some(parent));
+                }
+            }
+            stack.push((stars, panel, idx));
+            last = Some((panel, idx));
+        }
+
+        self.message = format!("Loaded '{file_path}' file.");
+        if closed > 0 {
+            self.message.push_str(&format!(
+                " {closed} open heading(s) under DONE ones were loaded as done."
+            ));
+        }
+        if !too_long.is_empty() {
+            self.message.push_str(&format!(
+                " Line(s) {} exceed {} characters.",
+                too_long.join(", "),
+                self.max_len
+            ));
+        }
+        self.fix_blocking();
+    }
+
+    // A line of an Org-mode file that isn't an item heading: the planning line of the
+    // item right above it, or a line kept with that item.
+    fn org_line(&mut self, last: Option<(Panel, usize)>, line: &str) {
+        let Some((panel, idx)) = last else {
+            self.preamble.push(line.to_string());
+            return;
+        };
+        let item = match panel {
+            Panel::Todo => &mut self.todos.list[idx],
+            Panel::Done => &mut self.dones.list[idx],
+        };
+        let has_due = item.get_due().is_some();
+        let done = !item.is_active();
+        let Item {
+            org: Some(org),
+            date,
+            text,
+            ..
+        } = item
+        else {
+            return;
+        };
+
+        let first = org.planning.is_none() && org.body.is_empty();
+        match org::planning(line).filter(|_| first) {
+            Some(planning) => {
+                if let (Some(closed), true) = (planning.closed, done) {
+                    *date = Local.from_local_datetime(&closed).single().unwrap_or(*date);
+                    org.closed = Some(*date);
+                }
+                if let (Some(deadline), false) = (planning.deadline, has_due) {
+                    text.push_str(&format!(" due:{}", deadline.format("%Y-%m-%d")));
+                    org.deadline = Some(deadline);
+                }
+                org.scheduled = planning.scheduled;
+                org.planning = Some(line.to_string());
+            }
+            None => org.body.push(line.to_string()),
+        }
+    }
+
+    pub fn needs_migration(&self) -> bool {
+        !self.migration.is_empty() && !self.migrate && !self.keep_format
+    }
+
+    pub fn confirm_migration(&mut self) {
+        if self.confirm == Some(Confirm::Migrate) {
+            self.confirm = None;
+        }
+        self.migrate = true;
+    }
+
+    pub fn migration_diff(&self, file_path: &str) -> String {
+        let mut diff = String::new();
+        for (line, old, new) in self.migration.iter() {
+            diff.push_str(&format!("{file_path}:{line}:\n-{old}\n+{new}\n"));
+        }
+        diff
+    }
+
+    pub fn save(&mut self, file_path: &str) -> std::io::Result<()> {
+        if self.read_only {
+            return Err(io::Error::other("the file is opened read-only"));
+        }
+        if self.needs_migration() {
+            return Err(io::Error::other(
+                "the file uses an old format, run with --migrate to upgrade it",
+            ));
+        }
+        if self.migrate && !self.migration.is_empty() {
+            fs::copy(file_path, format!("{file_path}.pre-migrate"))?;
+            self.migration.clear();
+        }
+
+        if self.create_dirs {
+            if let Some(dir) = Path::new(file_path).parent() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+
+        let contents = self.contents();
+        let changed = self.on_disk.as_ref() != Some(&contents);
+        write_atomic(file_path, &contents)?;
+        self.on_disk = Some(contents);
+        if changed {
+            self.commit_file(file_path);
+        }
+
+        self.dirty = false;
+        self.changes = 0;
+        self.write_log(file_path);
+        Ok(())
+    }
+
+    // Moves the DONE items completed more than `days` days ago, with their subtasks, to the
+    // end of `<file>.archive` and saves the list. The archive is synced before the list
+    // lets go of them: a crash in between leaves them in both files, never in neither.
+    // Nothing moves while the file waits for a migration, it couldn't be saved.
+    pub fn auto_archive(&mut self, file_path: &str, days: usize) {
+        if days == 0 || self.read_only || self.needs_migration() {
+            return;
+        }
+        let today = Local::now().date_naive();
+        let old: Vec<usize> = self
+            .dones
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| {
+                item.parent.is_none() && (today - item.date.date_naive()).num_days() > days as i64
+            })
+            .map(|(idx, _)| idx)
+            .collect();
+        if old.is_empty() {
+            return;
+        }
+
+        let mut lines = String::new();
+        for &idx in old.iter() {
+            let subtree = self.dones.children_cnt(idx) + 1;
+            for (item, level) in self.dones.iter_from(idx, false).take(subtree) {
+                lines.push_str(&serialize_item(item, Panel::Done, level, &self.indent));
+                lines.push('\n');
+            }
+        }
+        let path = format!("{file_path}.archive");
+        if let Err(err) = append_archive(&path, &lines) {
+            self.message = format!("Could not archive the old DONE items to '{path}': {err}");
+            return;
+        }
+
+        let mut ids = Vec::new();
+        // Going backwards keeps the indices of the remaining ones valid.
+        for &idx in old.iter().rev() {
+            self.dones.cur = idx;
+            ids.extend(self.dones.subtree_ids());
+            let _ = self.dones.delete();
+        }
+        self.dones.cur = 0;
+        self.clear_blocked_refs(&ids);
+
+        let archived = format!("Archived {} item(s) to '{path}'.", old.len());
+        self.message = match self.save(file_path) {
+            Ok(()) => archived,
+            Err(err) => format!("{archived} Could not save '{file_path}': {err}"),
+        };
+    }
+
+    // The list in the file format, as save() writes it.
+    pub fn contents(&self) -> String {
+        if self.format == Format::Org {
+            return self.contents_org();
+        }
+        let mut contents = String::new();
+        for (todo, level) in self.todos.iter(false) {
+            contents.push_str(&"\n".repeat(todo.gap));
+            contents.push_str(&serialize_item(todo, Panel::Todo, level, &self.indent));
+            contents.push('\n');
+        }
+        contents.push_str(&"\n".repeat(self.todos.tail_gap));
+        contents.push_str(SEP);
+        contents.push('\n');
+        for (done, level) in self.dones.iter(false) {
+            contents.push_str(&"\n".repeat(done.gap));
+            contents.push_str(&serialize_item(done, Panel::Done, level, &self.indent));
+            contents.push('\n');
+        }
+        contents.push_str(&"\n".repeat(self.dones.tail_gap));
+        if self.keep_format {
+            self.downgrade(contents)
+        } else {
+            contents
+        }
+    }
+
+    // Puts back the old lines of a declined migration in place of their upgraded version.
+    // The items changed or added since are written in the current format.
+    fn downgrade(&self, contents: String) -> String {
+        let mut old_lines: Vec<(&str, &str)> = self
+            .migration
+            .iter()
+            .map(|(_, old, new)| (new.as_str(), old.as_str()))
+            .collect();
+        let mut downgraded = String::new();
+        for line in contents.lines() {
+            match old_lines.iter().position(|(new, _)| *new == line) {
+                Some(pos) => downgraded.push_str(old_lines.remove(pos).1),
+                None => downgraded.push_str(line),
+            }
+            downgraded.push('\n');
+        }
+        downgraded
+    }
+
+    // The top items of both panels in the order they are saved to an Org-mode file: as
+    // they were read, each panel in its own order. An item that wasn't read from the file
+    // follows the one above it in its panel.
+    fn org_roots(&self) -> Vec<(Panel, usize)> {
+        let roots = |panel: Panel, list: &List| {
+            let mut order = 0;
+            list.list
+                .iter()
+                .enumerate()
+                .filter(|(_, item)| item.is_root())
+                .map(|(idx, item)| {
+                    order = item.org.as_ref().map_or(order, |org| org.order);
+                    (order, panel, idx)
+                })
+                .collect::<Vec<_>>()
+        };
+        let (todos, dones) = (
+            roots(Panel::Todo, &self.todos),
+            roots(Panel::Done, &self.dones),
+        );
+        let (mut todos, mut dones) = (todos.iter().peekable(), dones.iter().peekable());
+        let mut merged = Vec::new();
+        loop {
+            let next = match (todos.peek(), dones.peek()) {
+                (Some(todo), Some(done)) if todo.0 <= done.0 => todos.next(),
+                (Some(_), Some(_)) => dones.next(),
+                (Some(_), None) => todos.next(),
+                (None, Some(_)) => dones.next(),
+                (None, None) => break,
+            };
+            merged.extend(next.map(|&(_, panel, idx)| (panel, idx)));
+        }
+        merged
+    }
+
+    // The list as an Org-mode file, see parse_org(). The headings are written in the order
+    // of org_roots(), each at the level it was read at while that still nests it the same way.
+    fn contents_org(&self) -> String {
+        let mut contents = String::new();
+        for line in self.preamble.iter() {
+            contents.push_str(line);
+            contents.push('\n');
+        }
+        // The headings written so far as parse_org() stacks them when reading them back.
+        let mut stack: Vec<(usize, Panel, usize)> = Vec::new();
+        for (panel, root) in self.org_roots() {
+            let list = match panel {
+                Panel::Todo => &self.todos,
+                Panel::Done => &self.dones,
+            };
+            let subtree = root..root + list.children_cnt(root) + 1;
+            for (idx, item) in list.list.iter().enumerate().take(subtree.end).skip(root) {
+                let parent = item.parent.map(|parent| (panel, parent));
+                let nests = |stars: usize| {
+                    stack
+                        .iter()
+                        .rev()
+                        .find(|&&(above, _, _)| above < stars)
+                        .map(|&(_, panel, idx)| (panel, idx))
+                        == parent
+                };
+                let stars = match item.org.as_ref().map(|org| org.stars) {
+                    Some(stars) if nests(stars) => stars,
+                    _ => match parent {
+                        None => stack.first().map_or(1, |&(stars, _, _)| stars),
+                        Some(parent) => stack
+                            .iter()
+                            .find(|&&(_, panel, idx)| (panel, idx) == parent)
+                            .map_or(1, |&(stars, _, _)| stars + 1),
+                    },
+                };
+                let done = panel == Panel::Done || !item.is_active();
+                contents.push_str(&serialize_org(item, done, stars));
+
+                stack.retain(|&(above, _, _)| above < stars);
+                stack.push((stars, panel, idx));
+                for line in item.org.iter().flat_map(|org| org.body.iter()) {
+                    if let Some(heading) = org::heading(line) {
+                        stack.retain(|&(above, _, _)| above < heading.stars);
+                    }
+                }
+            }
+        }
+        contents
+    }
+
+    // What the file holds now if it was changed since it was loaded or last saved.
+    pub fn external_change(&self, file_path: &str) -> Option<String> {
+        let current = fs::read_to_string(file_path).ok()?;
+        (self.on_disk.as_ref() != Some(&current)).then_some(current)
+    }
+
+    // Lines of a snapshot as the checkbox prefix and the text of each item. With `shown`
+    // only the items on screen: those the filter shows, without hidden subtasks.
+    fn snapshot_lines(&self, kind: Snapshot, shown: bool) -> Vec<(String, &str)> {
+        let mut lines = Vec::new();
+        let skip_children = shown && self.hide_subs;
+
+        if kind != Snapshot::Dones {
+            for (todo, level) in (ListIter {
+                obj: &self.todos,
+                cur: 0,
+                skip_children,
+            }) {
+                if shown && !self.is_shown(todo, Panel::Todo) {
+                    continue;
+                }
+                let indent = " ".repeat(level * INDENT_SIZE);
+                let mark = if todo.is_active() { " " } else { "x" };
+                lines.push((format!("{indent}- [{mark}] "), todo.text.as_str()));
+            }
+        }
+
+        if kind != Snapshot::Todos {
+            for (done, level) in (ListIter {
+                obj: &self.dones,
+                cur: 0,
+                skip_children,
+            }) {
+                if shown && !self.is_shown(done, Panel::Done) {
+                    continue;
+                }
+                let indent = " ".repeat(level * INDENT_SIZE);
+                lines.push((format!("{indent}- [x] "), done.text.as_str()));
+            }
+        }
+
+        lines
+    }
+
+    pub fn snapshot(&self, kind: Snapshot) -> String {
+        self.snapshot_lines(kind, false)
+            .into_iter()
+            .map(|(prefix, text)| format!("{prefix}{text}\n"))
+            .collect()
+    }
+
+    // Like snapshot(), but of the items on screen only, with the number of lines.
+    #[cfg(feature = "clipboard")]
+    pub fn snapshot_shown(&self, kind: Snapshot) -> (String, usize) {
+        let lines = self.snapshot_lines(kind, true);
+        let cnt = lines.len();
+        let text = lines
+            .into_iter()
+            .map(|(prefix, text)| format!("{prefix}{text}\n"))
+            .collect();
+        (text, cnt)
+    }
+
+    // The DONE items (with their subtasks) in the given order, the file order without one.
+    // Ties keep the file order (the reverse one for `recent`, items are completed in
+    // the file order), `limit` is applied after sorting.
+    pub fn list_dones(&self, sort: Option<Sort>, reverse: bool, limit: Option<usize>) -> String {
+        let mut roots = self.dones.sorted_roots(sort, Panel::Done);
+        if reverse {
+            roots.reverse();
+        }
+
+        let mut out = String::new();
+        for root in roots.into_iter().take(limit.unwrap_or(usize::MAX)) {
+            let end = root + self.dones.children_cnt(root) + 1;
+            for (done, level) in (ListIter {
+                obj: &self.dones,
+                cur: root,
+                skip_children: false,
+            })
+            .take(end - root)
+            {
+                let indent = " ".repeat(level * INDENT_SIZE);
+                out.push_str(&format!("{indent}- [x] {}\n", done.text));
+            }
+        }
+        out
+    }
+
+    // Like snapshot(), but long items are hard-wrapped to `width` columns.
+    pub fn export_plain(&self, width: usize) -> String {
+        self.snapshot_lines(Snapshot::All, false)
+            .into_iter()
+            .map(|(prefix, text)| {
+                // Continuation lines are indented to line up under the text.
+                let hang = display_width(&prefix);
+                let indent = " ".repeat(hang);
+                wrap(text, width.saturating_sub(hang))
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, line)| format!("{}{line}\n", if i == 0 { &prefix } else { &indent }))
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    // The inverse of import_todo_txt(), one line per item:
+    //   open TODO items become `[(A)] [creation date] text`, the priority taken from a `pri:A` token,
+    //   marked TODO items become `x [creation date] text` (todo.txt has no completion date for them),
+    //   DONE items become `x <completion date> [creation date] text`, keeping `pri:A` in the text.
+    // Subtasks are written as items of their own, todo.txt has no hierarchy.
+    pub fn to_todo_txt(&self) -> String {
+        static RE_PRI: OnceLock<Regex> = OnceLock::new();
+        let re_pri = RE_PRI.get_or_init(|| Regex::new(r"\s*\bpri:([A-Z])\b").unwrap());
+
+        let mut out = String::new();
+        let items = self
+            .todos
+            .list
+            .iter()
+            .map(|item| (Panel::Todo, item))
+            .chain(self.dones.list.iter().map(|item| (Panel::Done, item)));
+
+        for (panel, item) in items {
+            let mut text = item.text.clone();
+            if let Some(id) = item.id {
+                text.push_str(&format!(" id:{id}"));
+            }
+            if let Some(blocker) = item.blocked_by {
+                text.push_str(&format!(" blocked:{blocker}"));
+            }
+            let created = item
+                .created
+                .map(|date| format!("{} ", date.format("%Y-%m-%d")))
+                .unwrap_or_default();
+
+            let line = match panel {
+                Panel::Todo if item.is_active() => {
+                    let priority = re_pri
+                        .captures(&text)
+                        .map(|caps| format!("({}) ", &caps[1]))
+                        .unwrap_or_default();
+                    let text = re_pri.replace(&text, "").trim().to_string();
+                    format!("{priority}{created}{text}")
+                }
+                Panel::Todo => format!("x {created}{text}"),
+                Panel::Done => format!("x {} {created}{text}", item.date.format("%Y-%m-%d")),
+            };
+            out.push_str(&line);
+            out.push('\n');
+        }
+
+        out
+    }
+
+    pub fn toggle_panel(&mut self) {
+        assert!(!self.is_in_edit(), "Can't toggle panel while in edit mode.");
+
+        self.set_panel(self.panel.togle());
+    }
+
+    // The selection and the picks are indices into the current panel, so they never span
+    // both panels: whatever moves to the other panel (TAB, undo, a jump) drops them. A
+    // batch transfer then always goes one way, to the opposite panel.
+    fn set_panel(&mut self, panel: Panel) {
+        if panel != self.panel {
+            self.anchor = None;
+            self.picked.clear();
+        }
+        self.panel = panel;
+    }
+
+    pub fn toggle_subtasks(&mut self) {
+        assert!(
+            !self.is_in_edit(),
+            "Can't toggle subtasks while in edit mode."
+        );
+
+        self.hide_subs = !self.hide_subs;
+
+        if self.hide_subs {
+            if let Some(cur_todo) = self.todos.get_cur_item() {
+                if !cur_todo.is_root() {
+                    self.todos.up(!self.hide_subs);
+                }
+            }
+            if let Some(cur_done) = self.dones.get_cur_item() {
+                if !cur_done.is_root() {
+                    self.dones.up(!self.hide_subs);
+                }
+            }
+        }
+    }
+
+    // The indices of the items of the panel shown under the filter and with the subtasks
+    // hidden or not. Navigation and the batch operations work on these, saving always
+    // writes the whole list.
+    fn view(&self, panel: Panel) -> Vec<usize> {
+        let list = match panel {
+            Panel::Todo => &self.todos,
+            Panel::Done => &self.dones,
+        };
+        list.list
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| (!self.hide_subs || item.is_root()) && self.is_shown(item, panel))
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    // The shown items of the visual selection.
+    fn selection(&self) -> Vec<usize> {
+        let list = match self.panel {
+            Panel::Todo => &self.todos,
+            Panel::Done => &self.dones,
+        };
+        match self.anchor.and_then(|anchor| list.range(anchor)) {
+            Some((lo, hi)) => self
+                .view(self.panel)
+                .into_iter()
+                .filter(|i| (lo..=hi).contains(i))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Like selection(), ending it.
+    fn take_selection(&mut self) -> Vec<usize> {
+        let selection = self.selection();
+        self.anchor = None;
+        selection
+    }
+
+    fn go_to(&mut self, pick: impl FnOnce(&[usize], usize) -> Option<usize>) {
+        let view = self.view(self.panel);
+        let list = match self.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        if let Some(idx) = pick(&view, list.cur) {
+            list.cur = idx;
+        }
+    }
+
+    pub fn go_up(&mut self) {
+        assert!(!self.is_in_edit(), "Can't go up while in edit mode.");
+        self.go_to(|view, cur| view.iter().rev().find(|&&i| i < cur).copied());
+    }
+
+    pub fn go_down(&mut self) {
+        assert!(!self.is_in_edit(), "Can't go down while in edit mode.");
+        self.go_to(|view, cur| view.iter().find(|&&i| i > cur).copied());
+    }
+
+    pub fn go_top(&mut self) {
+        assert!(!self.is_in_edit(), "Can't go top while in edit mode.");
+        self.go_to(|view, _| view.first().copied());
+    }
+
+    pub fn go_half(&mut self) {
+        assert!(!self.is_in_edit(), "Can't go half while in edit mode.");
+        self.go_to(|view, _| view.get(view.len() / 2).copied());
+    }
+
+    pub fn go_bottom(&mut self) {
+        assert!(!self.is_in_edit(), "Can't go bottom while in edit mode.");
+        self.go_to(|view, _| view.last().copied());
+    }
+
+    fn cur_text(&self) -> String {
+        let item = match self.panel {
+            Panel::Todo => self.todos.get_cur_item(),
+            Panel::Done => self.dones.get_cur_item(),
+        };
+        item.map(|item| item.text.clone()).unwrap_or_default()
+    }
+
+    // The item being edited stays shown until the edit is done, even if the filter
+    // doesn't match it, e.g. a new item.
+    fn is_shown(&self, item: &Item, panel: Panel) -> bool {
+        self.filter.as_ref().is_none_or(|f| f.matches(item, panel))
+            || self.editing(panel).is_some_and(|idx| {
+                let list = match panel {
+                    Panel::Todo => &self.todos,
+                    Panel::Done => &self.dones,
+                };
+                list.list.get(idx).is_some_and(|i| std::ptr::eq(i, item))
+            })
+    }
+
+    // The index of the item being edited in `panel`.
+    fn editing(&self, panel: Panel) -> Option<usize> {
+        let cur = match panel {
+            Panel::Todo => self.todos.cur,
+            Panel::Done => self.dones.cur,
+        };
+        (self.is_in_edit() && self.panel == panel).then_some(cur)
+    }
+
+    pub fn is_cur_shown(&self) -> bool {
+        let item = match self.panel {
+            Panel::Todo => self.todos.get_cur_item(),
+            Panel::Done => self.dones.get_cur_item(),
+        };
+        item.is_none_or(|item| self.is_shown(item, self.panel))
+    }
+
+    pub fn get_filter(&self) -> Option<&Filter> {
+        self.filter.as_ref()
+    }
+
+    pub fn get_filtered_n(&self) -> (usize, usize) {
+        let count = |list: &List, panel| {
+            list.iter(true)
+                .filter(|(item, _)| self.is_shown(item, panel))
+                .count()
+        };
+        (
+            count(&self.todos, Panel::Todo),
+            count(&self.dones, Panel::Done),
+        )
+    }
+
+    pub fn toggle_today(&mut self) {
+        if self.filter == Some(Filter::Today) {
+            self.filter = None;
+            self.message.push_str("Filter cleared.");
+        } else {
+            self.filter = Some(Filter::Today);
+            self.settle(Panel::Todo, true);
+            self.settle(Panel::Done, true);
+            self.message
+                .push_str("Showing items added or completed today.");
+        }
+    }
+
+    // Applying the active tag filter again clears it.
+    pub fn filter_tag(&mut self, tag: &str) {
+        let filter = Filter::Tag(tag.to_string());
+        if self.filter.as_ref() == Some(&filter) {
+            self.filter = None;
+            self.message.push_str("Filter cleared.");
+        } else {
+            self.filter = Some(filter);
+            self.settle(Panel::Todo, true);
+            self.settle(Panel::Done, true);
+            self.message
+                .push_str(&format!("Showing items tagged '{tag}'."));
+        }
+    }
+
+    // The state of the app kept between sessions in `<file>.state`: the active filter and
+    // the sort of each panel, a `key = value` line each. Without any the file is removed.
+    pub fn save_state(&self, file_path: &str) -> io::Result<()> {
+        let path = format!("{file_path}.state");
+        let mut state = String::new();
+        if let Some(filter) = &self.filter {
+            state.push_str(&format!("filter = {}\n", filter.to_state()));
+        }
+        if let Some(sort) = self.todo_sort {
+            state.push_str(&format!("sort = todo {}\n", sort.name()));
+        }
+        if let Some(sort) = self.done_sort {
+            state.push_str(&format!("sort = done {}\n", sort.name()));
+        }
+        if !state.is_empty() {
+            return fs::write(&path, state);
+        }
+        match fs::remove_file(&path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    // Puts back the filter of the last session, even if it shows nothing now, and sorts
+    // the panels again, the items added since go in their place.
+    pub fn load_state(&mut self, file_path: &str) {
+        let Ok(state) = fs::read_to_string(format!("{file_path}.state")) else {
+            return;
+        };
+        let mut sorted = Vec::new();
+        for line in state.lines() {
+            if let Some(value) = line.strip_prefix("filter = ") {
+                match Filter::from_state(value) {
+                    Some(filter) => {
+                        self.message = format!(
+                            "Filter '{}' of the last session restored, :filter clear drops it.",
+                            filter.name()
+                        );
+                        self.filter = Some(filter);
+                        self.settle(Panel::Todo, true);
+                        self.settle(Panel::Done, true);
+                    }
+                    None => self.message = format!("Ignored the saved filter '{value}'."),
+                }
+            } else if let Some(value) = line.strip_prefix("sort = ") {
+                let sort = value.split_once(' ').and_then(|(panel, sort)| {
+                    let panel = match panel {
+                        "todo" => Panel::Todo,
+                        "done" => Panel::Done,
+                        _ => return None,
+                    };
+                    Some((panel, Sort::from_str(sort)?))
+                });
+                match sort {
+                    Some((panel, sort)) => {
+                        self.keep_sort(panel, Some(sort));
+                        self.sort_list(panel, sort);
+                        sorted.push(format!("{} by {}", panel.name(), sort.name()));
+                    }
+                    None => self.message = format!("Ignored the saved sort '{value}'."),
+                }
+            }
+        }
+        if !sorted.is_empty() {
+            if self.filter.is_some() {
+                self.message.push(' ');
+            } else {
+                self.message.clear();
+            }
+            self.message.push_str(&format!(
+                "Sorted {} as in the last session, :sort off stops it.",
+                sorted.join(" and ")
+            ));
+        }
+    }
+
+    // Sorts the panel under the cursor and keeps it in that order for the file: the next
+    // time it's opened the panel is sorted again. `None` stops keeping an order, the items
+    // stay where they are.
+    pub fn sort_panel(&mut self, sort: Option<Sort>) {
+        assert!(!self.is_in_edit(), "Can't sort while in edit mode.");
+
+        let panel = self.panel;
+        let name = panel.name();
+        match (sort, self.keep_sort(panel, sort)) {
+            (None, Some(_)) => self
+                .message
+                .push_str(&format!("The {name} panel is no longer kept sorted.")),
+            (None, None) => self
+                .message
+                .push_str(&format!("The {name} panel isn't kept sorted.")),
+            (Some(sort), _) => {
+                let by = sort.name();
+                if self.sort_list(panel, sort) {
+                    self.message
+                        .push_str(&format!("Sorted the {name} panel by {by}."));
+                } else {
+                    self.message
+                        .push_str(&format!("The {name} panel is already sorted by {by}."));
+                }
+            }
+        }
+    }
+
+    // Sets the order the panel is kept in, giving the one it replaces.
+    fn keep_sort(&mut self, panel: Panel, sort: Option<Sort>) -> Option<Sort> {
+        let kept = match panel {
+            Panel::Todo => &mut self.todo_sort,
+            Panel::Done => &mut self.done_sort,
+        };
+        std::mem::replace(kept, sort)
+    }
+
+    // One step to undo, false when the items already were in that order.
+    fn sort_list(&mut self, panel: Panel, sort: Sort) -> bool {
+        let list = match panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        list.record_state();
+        if list.sort(sort, panel) {
+            self.push_operation(Action::Sort, panel);
+            true
+        } else {
+            list.revert_state().unwrap();
+            false
+        }
+    }
+
+    // Applying the active preset again clears it.
+    pub fn filter_preset(&mut self, name: &str, query: &Query) {
+        if matches!(&self.filter, Some(Filter::Preset(active, _)) if active == name) {
+            self.clear_filter();
+        } else {
+            self.filter = Some(Filter::Preset(name.to_string(), query.clone()));
+            self.settle(Panel::Todo, true);
+            self.settle(Panel::Done, true);
+            self.message
+                .push_str(&format!("Showing the '{name}' filter."));
+        }
+    }
+
+    // The badge of the header while a filter hides items: its name with the number of
+    // items shown out of all of them, e.g. `FILTER: +work | 5/20`.
+    pub fn active_filter_summary(&self) -> Option<String> {
+        let filter = self.filter.as_ref()?;
+        let (todos, dones) = self.get_filtered_n();
+        let all = self.get_todos_n(true) + self.get_dones_n(true);
+        Some(format!(
+            "FILTER: {} | {}/{all}",
+            filter.name(),
+            todos + dones
+        ))
+    }
+
+    // Everything that hides items at once: the filter and the hiding of subtasks.
+    pub fn clear_filters(&mut self) {
+        assert!(
+            !self.is_in_edit(),
+            "Can't clear filters while in edit mode."
+        );
+
+        let cleared = self.filter.take().is_some() | std::mem::take(&mut self.hide_subs);
+        self.message.push_str(if cleared {
+            "Filters cleared, every item is shown."
+        } else {
+            "No filter to clear."
+        });
+    }
+
+    pub fn clear_filter(&mut self) {
+        if self.filter.take().is_some() {
+            self.message.push_str("Filter cleared.");
+        } else {
+            self.message.push_str("No filter to clear.");
+        }
+    }
+
+    // Every tag with the number of open and done items carrying it, sorted by name.
+    // Extracted on first use after a change to the lists.
+    pub fn tags(&mut self) -> &[(String, usize, usize)] {
+        let (todos, dones) = (&self.todos, &self.dones);
+        self.tags.get_or_insert_with(|| {
+            let mut tags: Vec<(String, usize, usize)> = Vec::new();
+            let items = todos
+                .list
+                .iter()
+                .map(|item| (item, item.is_active()))
+                .chain(dones.list.iter().map(|item| (item, false)));
+            for (item, open) in items {
+                for tag in tags_of(&item.text) {
+                    let idx = match tags.iter().position(|(t, _, _)| t == tag) {
+                        Some(idx) => idx,
+                        None => {
+                            tags.push((tag.to_string(), 0, 0));
+                            tags.len() - 1
+                        }
+                    };
+                    if open {
+                        tags[idx].1 += 1;
+                    } else {
+                        tags[idx].2 += 1;
+                    }
+                }
+            }
+            tags.sort_by(|a, b| a.0.cmp(&b.0));
+            tags
+        })
+    }
+
+    pub fn remove_tag(&mut self, tag: &str) {
+        let cnt = self
+            .todos
+            .list
+            .iter()
+            .chain(self.dones.list.iter())
+            .filter(|item| tags_of(&item.text).any(|t| t == tag))
+            .count();
+        if cnt == 0 {
+            self.message
+                .push_str(&format!("No item is tagged '{tag}'."));
+            return;
+        }
+        self.message
+            .push_str(&format!("Remove '{tag}' from {cnt} item(s)? (y/n)"));
+        self.confirm = Some(Confirm::RemoveTag(tag.to_string()));
+    }
+
+    pub fn rename_tag(&mut self, from: &str, to: &str) {
+        if !is_tag(from) || !is_tag(to) {
+            self.message
+                .push_str("Tags are single words starting with '+' or '@'.");
+            return;
+        }
+        let cnt = self.retag(from, Some(to));
+        self.message
+            .push_str(&format!("Renamed '{from}' to '{to}' in {cnt} item(s)."));
+    }
+
+    // Replaces the tag in the text of every item, or drops it without `to`.
+    fn retag(&mut self, from: &str, to: Option<&str>) -> usize {
+        self.todos.record_state();
+        self.dones.record_state();
+
+        let mut cnt = 0;
+        for item in self.todos.list.iter_mut().chain(self.dones.list.iter_mut()) {
+            if !tags_of(&item.text).any(|t| t == from) {
+                continue;
+            }
+            item.text = item
+                .text
+                .split_whitespace()
+                .filter_map(|word| if word == from { to } else { Some(word) })
+                .collect::<Vec<&str>>()
+                .join(" ");
+            cnt += 1;
+        }
+
+        if cnt > 0 {
+            self.push_operation(Action::Retag, self.panel);
+        } else {
+            self.todos.revert_state().unwrap();
+            self.dones.revert_state().unwrap();
+        }
+        cnt
+    }
+
+    // Moves the cursor off an item hidden by the filter, in the given direction first.
+    fn settle(&mut self, panel: Panel, down: bool) {
+        if self.filter.is_none() {
+            return;
+        }
+        let view = self.view(panel);
+        let list = match panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        if view.contains(&list.cur) {
+            return;
+        }
+
+        let next = view.iter().find(|&&i| i > list.cur).copied();
+        let prev = view.iter().rev().find(|&&i| i < list.cur).copied();
+        let to = if down { next.or(prev) } else { prev.or(next) };
+        if let Some(to) = to {
+            list.cur = to;
+        }
+    }
+
+    pub fn drag_up(&mut self) {
+        assert!(!self.is_in_edit(), "Can't drag up while in edit mode.");
+        match self.panel {
+            Panel::Todo => {
+                self.todos.record_state();
+                match self.todos.drag(true, self.drag_wrap) {
+                    Ok(()) => self.push_operation(Action::DragUp, Panel::Todo),
+                    Err(err) => {
+                        self.message.push_str(err);
+                        self.todos.revert_state().unwrap();
+                    }
+                };
+            }
+            Panel::Done => {
+                self.dones.record_state();
+                match self.dones.drag(true, self.drag_wrap) {
+                    Ok(()) => self.push_operation(Action::DragUp, Panel::Done),
+                    Err(err) => {
+                        self.message.push_str(err);
+                        self.dones.revert_state().unwrap();
                     }
+                };
+            }
+        }
+    }
+
+    pub fn drag_down(&mut self) {
+        assert!(!self.is_in_edit(), "Can't drag down while in edit mode.");
+
+        match self.panel {
+            Panel::Todo => {
+                self.todos.record_state();
+                match self.todos.drag(false, self.drag_wrap) {
+                    Ok(()) => self.push_operation(Action::DragDown, Panel::Todo),
                     Err(err) => {
                         self.message.push_str(err);
                         self.todos.revert_state().unwrap();
                     }
                 };
             }
-            Panel::Done => self
-                .message
-                .push_str("Can't mark done item, try transfering it first."),
+            Panel::Done => {
+                self.dones.record_state();
+                match self.dones.drag(false, self.drag_wrap) {
+                    Ok(()) => self.push_operation(Action::DragDown, Panel::Done),
+                    Err(err) => {
+                        self.message.push_str(err);
+                        self.dones.revert_state().unwrap();
+                    }
+                };
+            }
+        }
+    }
+
+    pub fn indent_current(&mut self) {
+        assert!(!self.is_in_edit(), "Can't indent while in edit mode.");
+        self.nest(Action::Indent, List::indent);
+    }
+
+    pub fn outdent_current(&mut self) {
+        assert!(!self.is_in_edit(), "Can't outdent while in edit mode.");
+        self.nest(Action::Outdent, List::outdent);
+    }
+
+    // Changes the parent of the item under the cursor. Not while subtasks are hidden, the
+    // item would disappear under another one.
+    fn nest(&mut self, action: Action, change: fn(&mut List) -> Result<(), &'static str>) {
+        if self.hide_subs {
+            self.message
+                .push_str("Can't change the nesting while subtasks are hidden.");
+            return;
+        }
+        let list = match self.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        list.record_state();
+        match change(list) {
+            Ok(()) => self.push_operation(action, self.panel),
+            Err(err) => {
+                list.revert_state().unwrap();
+                self.message.push_str(err);
+            }
+        }
+    }
+
+    pub fn mark_item(&mut self) {
+        assert!(!self.is_in_edit(), "Can't mark item while in edit mode");
+
+        match self.panel {
+            Panel::Todo => {
+                if let Some(item) = self.todos.get_cur_item() {
+                    if item.is_active() && !item.has_children() {
+                        if let Some(blocker) = self.get_blocker(item) {
+                            self.message.push_str(&format!(
+                                "Item is blocked by '{}'. Mark it anyway? (y/n)",
+                                blocker.text
+                            ));
+                            self.confirm = Some(Confirm::MarkBlocked);
+                            return;
+                        }
+                    }
+                }
+                self.mark_todo();
+            }
+            Panel::Done => self
+                .message
+                .push_str("Can't mark done item, try transfering it first."),
+        };
+    }
+
+    // Marks every shown active TODO item in one step, items hidden by the filter stay as
+    // they are. Blocked items and items with active subtasks are skipped.
+    pub fn mark_all_shown(&mut self) {
+        assert!(!self.is_in_edit(), "Can't mark items while in edit mode");
+
+        let view = self.view(Panel::Todo);
+        let cur = self.todos.cur;
+        self.todos.record_state();
+
+        let (mut cnt, mut skipped, mut first) = (0, 0, cur);
+        // Subtasks come after their parent, going backwards marks them first.
+        for &i in view.iter().rev() {
+            let item = &self.todos.list[i];
+            if !item.is_active() {
+                continue;
+            }
+            if !item.has_children() && self.is_blocked(item) {
+                skipped += 1;
+                continue;
+            }
+            self.todos.cur = i;
+            match self.todos.mark() {
+                Ok(()) => {
+                    cnt += 1;
+                    first = i;
+                }
+                Err(_) => skipped += 1,
+            }
+        }
+        self.todos.cur = cur;
+
+        if cnt > 0 {
+            self.push_batch_operation(Action::Mark, Panel::Todo, first);
+            self.message.push_str(&format!("Marked {cnt} item(s)."));
+            if skipped > 0 {
+                self.message.push_str(&format!(
+                    " Skipped {skipped} blocked or unfinished item(s)."
+                ));
+            }
+            self.unblock_finished();
+        } else {
+            self.todos.revert_state().unwrap();
+            self.message.push_str("Nothing to mark.");
+        }
+    }
+
+    fn mark_todo(&mut self) {
+        self.todos.record_state();
+        match self.todos.mark() {
+            Ok(()) => {
+                self.push_operation(Action::Mark, Panel::Todo);
+                self.unblock_finished();
+            }
+            Err(err) => {
+                self.message.push_str(err);
+                self.todos.revert_state().unwrap();
+            }
+        };
+    }
+
+    pub fn confirm(&mut self, yes: bool) {
+        assert!(
+            self.is_confirming(),
+            "confirm() called without a pending confirmation"
+        );
+
+        self.clear_message();
+        match (self.confirm.take().unwrap(), yes) {
+            (Confirm::MarkBlocked, true) => self.mark_todo(),
+            (Confirm::Migrate, true) => {
+                self.confirm_migration();
+                self.message.push_str("The file will be upgraded on save.");
+            }
+            (Confirm::RemoveTag(tag), true) => {
+                let cnt = self.retag(&tag, None);
+                if self.filter == Some(Filter::Tag(tag.clone())) {
+                    self.filter = None;
+                }
+                self.message
+                    .push_str(&format!("Removed '{tag}' from {cnt} item(s)."));
+            }
+            (Confirm::Migrate, false) => {
+                self.keep_format = true;
+                self.message
+                    .push_str("The lines in the old format will be saved as they were.");
+            }
+            (_, false) => self.message.push_str("Cancelled."),
+        }
+    }
+
+    pub fn block_item(&mut self) {
+        assert!(!self.is_in_edit(), "Can't block item while in edit mode");
+
+        if self.panel == Panel::Done {
+            self.message.push_str("Only TODO items can be blocked.");
+            return;
+        }
+
+        let cur = match self.todos.get_cur_item() {
+            Some(item) => item.clone(),
+            None => {
+                self.message.push_str("Can't block item. List is empty.");
+                return;
+            }
+        };
+
+        match self.blocking.take() {
+            None if cur.blocked_by.is_some() => {
+                self.todos.record_state();
+                self.todos.get_cur_item_mut().unwrap().blocked_by = None;
+                self.push_operation(Action::Block, Panel::Todo);
+                self.message.push_str("Item unblocked.");
+            }
+            None => {
+                self.message
+                    .push_str("Select the blocking item and press 'b' again, ESC to cancel.");
+                self.blocking = Some(cur);
+            }
+            Some(blocked) if blocked == cur => self.message.push_str("Cancelled."),
+            Some(blocked) => {
+                let blocked_idx = match self.todos.list.iter().position(|item| *item == blocked) {
+                    Some(idx) => idx,
+                    None => {
+                        self.message.push_str("Blocked item no longer exists.");
+                        return;
+                    }
+                };
+
+                // A chain that comes back to an id already seen without passing the
+                // blocked item is a circle of its own, it can't lead to it.
+                let mut seen = Vec::new();
+                let mut chain = cur.blocked_by;
+                while let Some(id) = chain.filter(|id| !seen.contains(id)) {
+                    if blocked.id == Some(id) {
+                        self.message
+                            .push_str("Can't block item. This would create a cycle.");
+                        return;
+                    }
+                    seen.push(id);
+                    chain = self.find_by_id(id).and_then(|item| item.blocked_by);
+                }
+
+                let id = cur.id.unwrap_or_else(|| self.next_id());
+                self.todos.record_state();
+                self.todos.get_cur_item_mut().unwrap().id = Some(id);
+                self.todos.list[blocked_idx].blocked_by = Some(id);
+                self.push_operation(Action::Block, Panel::Todo);
+                self.message.push_str(&format!(
+                    "'{}' is now blocked by '{}'.",
+                    blocked.text, cur.text
+                ));
+            }
+        }
+    }
+
+    pub fn add_todo(&mut self, text: &str, position: InsertPosition) -> Result<(), String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Err("TODO item can't be empty.".to_string());
+        }
+        let len = text.chars().count();
+        if len > self.max_len {
+            return Err(format!(
+                "Item is too long ({len}/{} characters).",
+                self.max_len
+            ));
+        }
+
+        self.room(Panel::Todo, 1)?;
+
+        // Without a cursor only the ends of the list make sense.
+        let position = match position {
+            InsertPosition::Top => InsertPosition::Top,
+            _ => InsertPosition::Bottom,
+        };
+
+        self.todos.record_state();
+        self.todos.insert(position)?;
+        self.todos.get_cur_item_mut().unwrap().text = text.to_string();
+        self.push_operation(Action::Insert, Panel::Todo);
+        self.log_event("ADD", text);
+        Ok(())
+    }
+
+    pub fn find_todo(&mut self, query: &str) -> bool {
+        let roots = self
+            .todos
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_root());
+
+        let found = match query.parse::<usize>() {
+            Ok(n) => roots.map(|(i, _)| i).nth(n.wrapping_sub(1)),
+            Err(_) => {
+                let query = query.to_lowercase();
+                roots
+                    .filter(|(_, item)| item.text.to_lowercase().contains(&query))
+                    .map(|(i, _)| i)
+                    .next()
+            }
+        };
+
+        match found {
+            Some(i) => {
+                self.set_panel(Panel::Todo);
+                self.todos.cur = i;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn complete_todo(&mut self, transfer: bool) -> Result<String, &'static str> {
+        self.todos.record_state();
+        self.dones.record_state();
+
+        let text = self.todos.get_cur_item().map(|item| item.text.clone());
+        let result = match self.todos.get_cur_item() {
+            Some(item) if item.is_active() => self.todos.mark(),
+            _ => Ok(()),
+        }
+        .and_then(|()| {
+            if transfer {
+                self.todos.transfer(&mut self.dones)
+            } else {
+                Ok(())
+            }
+        });
+
+        match result {
+            Ok(()) => {
+                let text = text.unwrap_or_default();
+                self.push_operation(Action::Transfer, Panel::Todo);
+                self.log_event("DONE", &text);
+                Ok(text)
+            }
+            Err(err) => {
+                self.todos.revert_state().unwrap();
+                self.dones.revert_state().unwrap();
+                Err(err)
+            }
+        }
+    }
+
+    // Appends the items of a todo.txt file, open ones to the TODO list and completed
+    // ones to the DONE list. Returns how many went to each, nothing is added when they
+    // don't all fit in max_items.
+    pub fn import_todo_txt(&mut self, content: &str) -> Result<(usize, usize), String> {
+        let (mut todos, mut dones) = (Vec::new(), Vec::new());
+        for (panel, item) in content.lines().filter_map(parse_todo_txt) {
+            match panel {
+                Panel::Todo => todos.push(item),
+                Panel::Done => dones.push(item),
+            }
+        }
+        self.append_imported(todos, dones)
+    }
+
+    // Appends the tasks of a Taskwarrior export, the completed ones to 'Done'. A task
+    // already in the list, by its `tw:` token or its text, is skipped and counted. Gives
+    // the number of todos and dones added and of the tasks skipped, nothing is added when
+    // they don't all fit in max_items.
+    pub fn import_taskwarrior(&mut self, tasks: &[Task]) -> Result<(usize, usize, usize), String> {
+        let (mut todos, mut dones, mut skipped): (Vec<Item>, Vec<Item>, usize) =
+            (Vec::new(), Vec::new(), 0);
+        for task in tasks.iter().filter(|task| task.is_kept()) {
+            let text = task.text();
+            let token = task.uuid.as_ref().map(|uuid| format!("tw:{uuid}"));
+            let present = self
+                .todos
+                .list
+                .iter()
+                .chain(self.dones.list.iter())
+                .chain(todos.iter())
+                .chain(dones.iter())
+                .any(|item| {
+                    item.text == text
+                        || item.text == task.description
+                        || token
+                            .as_ref()
+                            .is_some_and(|token| item.text.split_whitespace().any(|w| w == token))
+                });
+            if present {
+                skipped += 1;
+                continue;
+            }
+
+            let (date, act_cnt) = if task.is_done() {
+                (task.end.unwrap_or_else(Local::now), 0)
+            } else {
+                (Local::now(), 1)
+            };
+            let mut item = Item::new(text, date, None, act_cnt).with_tokens();
+            item.created = task.entry.map(|entry| entry.date_naive());
+            if task.is_done() {
+                dones.push(item);
+            } else {
+                todos.push(item);
+            }
+        }
+        let (todos_n, dones_n) = self.append_imported(todos, dones)?;
+        Ok((todos_n, dones_n, skipped))
+    }
+
+    fn append_imported(
+        &mut self,
+        mut todos: Vec<Item>,
+        mut dones: Vec<Item>,
+    ) -> Result<(usize, usize), String> {
+        self.room(Panel::Todo, todos.len())?;
+        self.room(Panel::Done, dones.len())?;
+        let counts = (todos.len(), dones.len());
+        self.todos.list.append(&mut todos);
+        self.dones.list.append(&mut dones);
+        if counts != (0, 0) {
+            self.dirty = true;
+            self.tags = None;
+        }
+        Ok(counts)
+    }
+
+    pub fn cancel_block(&mut self) {
+        if self.blocking.take().is_some() {
+            self.message.push_str("Cancelled.");
+        }
+    }
+
+    pub fn swap(&mut self, i: usize, j: usize) {
+        assert!(!self.is_in_edit(), "Can't swap items while in edit mode");
+
+        let list = match self.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        list.record_state();
+        match list.swap(i, j) {
+            Ok(()) => {
+                self.push_operation(Action::Swap, self.panel);
+                self.message.push_str("Items swapped.");
+            }
+            Err(err) => {
+                list.revert_state().unwrap();
+                self.message.push_str(err);
+            }
+        }
+    }
+
+    pub fn swap_item(&mut self) {
+        let cur = match self.panel {
+            Panel::Todo => self.todos.get_cur_item(),
+            Panel::Done => self.dones.get_cur_item(),
+        };
+        let cur = match cur {
+            Some(item) => item.clone(),
+            None => {
+                self.message.push_str("Can't swap. List is empty.");
+                return;
+            }
+        };
+
+        match self.swapping.take() {
+            None => {
+                self.message
+                    .push_str("Select the item to swap with and press 's' again, ESC to cancel.");
+                self.swapping = Some((self.panel, cur));
+            }
+            Some((_, marked)) if marked == cur => self.message.push_str("Cancelled."),
+            Some((panel, _)) if panel != self.panel => self
+                .message
+                .push_str("Items can only be swapped within a panel."),
+            Some((panel, marked)) => {
+                let list = match panel {
+                    Panel::Todo => &self.todos,
+                    Panel::Done => &self.dones,
+                };
+                match list.list.iter().position(|item| *item == marked) {
+                    Some(idx) => self.swap(idx, list.cur),
+                    None => self.message.push_str("Marked item no longer exists."),
+                }
+            }
+        }
+    }
+
+    pub fn cancel_swap(&mut self) {
+        if self.swapping.take().is_some() {
+            self.message.push_str("Cancelled.");
+        }
+    }
+
+    pub fn is_flashing(&self, item: &Item) -> bool {
+        match self.flash {
+            Some((Panel::Todo, idx)) => self
+                .todos
+                .list
+                .get(idx)
+                .is_some_and(|i| std::ptr::eq(i, item)),
+            Some((Panel::Done, idx)) => self
+                .dones
+                .list
+                .get(idx)
+                .is_some_and(|i| std::ptr::eq(i, item)),
+            None => false,
+        }
+    }
+
+    pub fn clear_flash(&mut self) {
+        self.flash = None;
+    }
+
+    pub fn start_selection(&mut self) {
+        let cur = match self.panel {
+            Panel::Todo => self.todos.cur,
+            Panel::Done => self.dones.cur,
+        };
+        self.anchor = Some(cur);
+    }
+
+    pub fn cancel_selection(&mut self) {
+        self.anchor = None;
+    }
+
+    // Selects every shown item of the panel, with the cursor on the first one, for the
+    // batch operations of the visual mode. False when nothing is shown.
+    pub fn select_all(&mut self) -> bool {
+        let view = self.view(self.panel);
+        let (Some(&first), Some(&last)) = (view.first(), view.last()) else {
+            self.message.push_str("Nothing to select.");
+            return false;
+        };
+        match self.panel {
+            Panel::Todo => self.todos.cur = first,
+            Panel::Done => self.dones.cur = first,
+        }
+        self.anchor = Some(last);
+        self.message
+            .push_str(&format!("{} item(s) selected.", view.len()));
+        true
+    }
+
+    // Ends the visual selection and drops the picks.
+    pub fn clear_selection(&mut self) {
+        let picked = self.picked.len();
+        self.anchor = None;
+        self.picked.clear();
+        self.message.push_str(&match picked {
+            0 => "Cleared the selection.".to_string(),
+            _ => format!("Cleared the selection and {picked} pick(s)."),
+        });
+    }
+
+    pub fn is_selected(&self, item: &Item) -> bool {
+        let list = match self.panel {
+            Panel::Todo => &self.todos,
+            Panel::Done => &self.dones,
+        };
+        match self.anchor.and_then(|anchor| list.range(anchor)) {
+            Some((lo, hi)) => list.list[lo..=hi].iter().any(|i| std::ptr::eq(i, item)),
+            None => false,
+        }
+    }
+
+    // Picked items are root items of the current panel, kept until the lists change.
+    pub fn pick_item(&mut self) {
+        let list = match self.panel {
+            Panel::Todo => &self.todos,
+            Panel::Done => &self.dones,
+        };
+        let Some(mut root) = list.get_cur_item().map(|_| list.cur) else {
+            self.message.push_str("Can't pick. List is empty.");
+            return;
+        };
+        while let Some(parent) = list.list[root].parent {
+            root = parent;
+        }
+
+        match self.picked.iter().position(|&i| i == root) {
+            Some(pos) => drop(self.picked.remove(pos)),
+            None => self.picked.push(root),
+        }
+        self.message.push_str(&format!(
+            "{} item(s) picked, press 'p' to move them below the cursor.",
+            self.picked.len()
+        ));
+    }
+
+    pub fn pick_selection(&mut self) {
+        let selection = self.take_selection();
+        let list = match self.panel {
+            Panel::Todo => &self.todos,
+            Panel::Done => &self.dones,
+        };
+        for i in selection.into_iter().filter(|&i| list.list[i].is_root()) {
+            if !self.picked.contains(&i) {
+                self.picked.push(i);
+            }
+        }
+        self.message.push_str(&format!(
+            "{} item(s) picked, press 'p' to move them below the cursor.",
+            self.picked.len()
+        ));
+    }
+
+    // Picks the shown root items of the panel that aren't picked and unpicks the ones
+    // that are. Picks hidden by the filter are kept.
+    pub fn invert_selection(&mut self) {
+        let list = match self.panel {
+            Panel::Todo => &self.todos,
+            Panel::Done => &self.dones,
+        };
+        let shown: Vec<usize> = self
+            .view(self.panel)
+            .into_iter()
+            .filter(|&i| list.list[i].is_root())
+            .collect();
+        if shown.is_empty() {
+            self.message.push_str("Nothing to pick.");
+            return;
+        }
+        let mut picked: Vec<usize> = self
+            .picked
+            .iter()
+            .copied()
+            .filter(|i| !shown.contains(i))
+            .collect();
+        picked.extend(shown.into_iter().filter(|i| !self.picked.contains(i)));
+        self.picked = picked;
+        self.message.push_str(&format!(
+            "{} item(s) picked, press 'p' to move them below the cursor.",
+            self.picked.len()
+        ));
+    }
+
+    // Appends the token to the text of every selected item (or the current one), or removes
+    // it with `remove`. The words are rejoined by single spaces either way.
+    pub fn tag_selection(&mut self, token: &str, remove: bool) {
+        assert!(!self.is_in_edit(), "Can't tag items while in edit mode");
+
+        let panel = self.panel;
+        let targets = match self.anchor {
+            Some(_) => self.take_selection(),
+            None if self.is_cur_shown() => match panel {
+                Panel::Todo => self.todos.get_cur_item().map(|_| self.todos.cur),
+                Panel::Done => self.dones.get_cur_item().map(|_| self.dones.cur),
+            }
+            .into_iter()
+            .collect(),
+            None => Vec::new(),
+        };
+        if targets.is_empty() {
+            self.message.push_str("Nothing to tag.");
+            return;
+        }
+        let list = match panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+
+        list.record_state();
+        let (mut cnt, mut first) = (0, None);
+        for &i in targets.iter() {
+            let item = &mut list.list[i];
+            let has = item.text.split_whitespace().any(|word| word == token);
+            if has != remove {
+                continue;
+            }
+            first.get_or_insert(i);
+            let mut words = item
+                .text
+                .split_whitespace()
+                .filter(|&word| word != token)
+                .collect::<Vec<&str>>();
+            if !remove {
+                words.push(token);
+            }
+            item.text = words.join(" ");
+            cnt += 1;
+        }
+        let skipped = targets.len() - cnt;
+
+        if let Some(first) = first {
+            let action = if remove { Action::Untag } else { Action::Tag };
+            self.push_batch_operation(action, panel, first);
+        } else {
+            list.revert_state().unwrap();
+        }
+        self.message.push_str(&match (remove, skipped) {
+            (false, 0) => format!("Tagged {cnt} item(s) with '{token}'."),
+            (false, _) => format!("Tagged {cnt} item(s) with '{token}', {skipped} already had it."),
+            (true, 0) => format!("Removed '{token}' from {cnt} item(s)."),
+            (true, _) => format!("Removed '{token}' from {cnt} item(s), {skipped} didn't have it."),
+        });
+    }
+
+    // Changes the case of the current item's words, leaving the tags and `key:value`
+    // tokens as they are.
+    pub fn change_case(&mut self, upper: bool) {
+        assert!(!self.is_in_edit(), "Can't change case while in edit mode");
+
+        if !self.is_cur_shown() {
+            self.message.push_str("No item is shown by the filter.");
+            return;
+        }
+        let panel = self.panel;
+        let list = match panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        let Some(item) = list.get_cur_item() else {
+            return;
+        };
+        let text = item
+            .text
+            .split(' ')
+            .map(|word| {
+                if is_tag(word) || word.contains(':') {
+                    word.to_string()
+                } else if upper {
+                    word.to_uppercase()
+                } else {
+                    word.to_lowercase()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(" ");
+        if text == item.text {
+            self.message.push_str("Nothing to change.");
+            return;
+        }
+
+        list.record_state();
+        list.get_cur_item_mut().unwrap().text = text;
+        self.push_operation(Action::Case, panel);
+        self.message.push_str(if upper {
+            "Changed the item to upper case."
+        } else {
+            "Changed the item to lower case."
+        });
+    }
+
+    // The text of the current item, if the filter shows it.
+    pub fn get_cur_text(&self) -> Option<&String> {
+        if !self.is_cur_shown() {
+            return None;
+        }
+        match self.panel {
+            Panel::Todo => self.todos.get_cur_item(),
+            Panel::Done => self.dones.get_cur_item(),
+        }
+        .map(|item| &item.text)
+    }
+
+    // Groups of TODO root items with the same text under the policy, in the list order.
+    fn find_duplicates(&self, policy: DuplicatePolicy) -> Vec<Vec<usize>> {
+        let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+        for (idx, item) in self.todos.list.iter().enumerate() {
+            if !item.is_root() {
+                continue;
+            }
+            let key = duplicate_key(&item.text, policy);
+            match groups.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, group)) => group.push(idx),
+                None => groups.push((key, vec![idx])),
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(_, group)| group)
+            .filter(|group| group.len() > 1)
+            .collect()
+    }
+
+    // Picks the later copies of duplicated TODO items, so they can be moved next to the first one.
+    pub fn pick_duplicates(&mut self, policy: DuplicatePolicy) {
+        if self.panel != Panel::Todo {
+            self.message
+                .push_str("Duplicates are only looked for in the TODO panel.");
+            return;
+        }
+
+        let groups = self.find_duplicates(policy);
+        self.picked = groups
+            .iter()
+            .flat_map(|group| group[1..].iter().copied())
+            .collect();
+        if groups.is_empty() {
+            self.message.push_str("No duplicates found.");
+        } else {
+            self.message.push_str(&format!(
+                "Picked {} duplicate(s) of {} item(s).",
+                self.picked.len(),
+                groups.len()
+            ));
+        }
+    }
+
+    // Starts a `:dedupe` run over the TODO root items, which are the same ignoring case and
+    // spacing, or up to `distance` edits apart.
+    pub fn start_dedupe(&mut self, distance: usize) -> bool {
+        assert!(!self.is_in_edit(), "Can't dedupe while in edit mode");
+
+        let keys = self
+            .todos
+            .list
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| item.is_root())
+            .map(|(idx, item)| {
+                let words = item.text.split_whitespace().collect::<Vec<&str>>();
+                (idx, words.join(" ").to_lowercase())
+            })
+            .collect();
+        let pairs = duplicate_pairs(keys, distance);
+        if pairs.is_empty() {
+            self.message.push_str("No duplicates found.");
+            return false;
+        }
+        self.dedupe = Some(Dedupe {
+            pairs,
+            cur: 0,
+            removed: Vec::new(),
+            texts: Vec::new(),
+        });
+        true
+    }
+
+    // The texts of the pair to resolve, with its number and the number of pairs.
+    pub fn dedupe_pair(&self) -> Option<(&str, &str, usize, usize)> {
+        let dedupe = self.dedupe.as_ref()?;
+        let &(first, second) = dedupe.pairs.get(dedupe.cur)?;
+        Some((
+            dedupe.text(&self.todos, first),
+            dedupe.text(&self.todos, second),
+            dedupe.cur + 1,
+            dedupe.pairs.len(),
+        ))
+    }
+
+    // Merging keeps the first item, with the tags and `key:value` tokens of the second one
+    // it doesn't have added to its text.
+    pub fn resolve_duplicate(&mut self, resolve: Resolve) {
+        let Some(dedupe) = self.dedupe.as_mut() else {
+            return;
+        };
+        let Some(&(first, second)) = dedupe.pairs.get(dedupe.cur) else {
+            return;
+        };
+        match resolve {
+            Resolve::KeepFirst => dedupe.removed.push((second, first)),
+            Resolve::KeepSecond => dedupe.removed.push((first, second)),
+            Resolve::KeepBoth => {}
+            Resolve::Merge => {
+                let kept = dedupe.text(&self.todos, first).to_string();
+                let has = |word: &str| match word.split_once(':') {
+                    Some((key, _)) if !is_tag(word) => kept
+                        .split_whitespace()
+                        .any(|w| w.split_once(':').is_some_and(|(k, _)| k == key)),
+                    _ => kept.split_whitespace().any(|w| w == word),
+                };
+                let mut extra: Vec<&str> = Vec::new();
+                for word in dedupe.text(&self.todos, second).split_whitespace() {
+                    if (is_tag(word) || word.contains(':')) && !has(word) && !extra.contains(&word)
+                    {
+                        extra.push(word);
+                    }
+                }
+                let extra = extra.join(" ");
+                let text = if extra.is_empty() {
+                    kept
+                } else {
+                    format!("{} {extra}", kept.trim_end())
+                };
+                dedupe.texts.retain(|(idx, _)| *idx != first);
+                dedupe.texts.push((first, text));
+                dedupe.removed.push((second, first));
+            }
+        }
+        dedupe.next();
+        if dedupe.cur >= dedupe.pairs.len() {
+            self.finish_dedupe();
+        }
+    }
+
+    pub fn is_deduping(&self) -> bool {
+        self.dedupe.is_some()
+    }
+
+    // Ends the run, early too: the items removed and the texts merged so far are one
+    // change to undo.
+    pub fn finish_dedupe(&mut self) {
+        let Some(mut dedupe) = self.dedupe.take() else {
+            return;
+        };
+        if dedupe.removed.is_empty() && dedupe.texts.is_empty() {
+            self.message.push_str("No duplicates removed.");
+            return;
+        }
+
+        self.todos.record_state();
+        for (idx, text) in dedupe.texts.drain(..) {
+            self.todos.list[idx].text = text;
+        }
+        let mut removed: Vec<usize> = dedupe.removed.iter().map(|&(idx, _)| idx).collect();
+        removed.sort_unstable();
+        let mut ids = Vec::new();
+        // Going backwards keeps the indices of the remaining ones valid.
+        for &idx in removed.iter().rev() {
+            self.todos.cur = idx;
+            ids.extend(self.todos.subtree_ids());
+            let text = self.todos.list[idx].text.clone();
+            if self.todos.delete().is_ok() {
+                self.log_event("DELETE", &text);
+            }
+        }
+        let first = removed.first().copied().unwrap_or(0);
+        self.todos.cur = min(first, self.todos.list.len().saturating_sub(1));
+        self.push_batch_operation(Action::Dedupe, Panel::Todo, self.todos.cur);
+        self.message
+            .push_str(&format!("Removed {} duplicate(s).", removed.len()));
+        let cleared = self.clear_blocked_refs(&ids);
+        if cleared > 0 {
+            self.message
+                .push_str(&format!(" Cleared {cleared} blocked-by reference(s)."));
+        }
+    }
+
+    pub fn cancel_picks(&mut self) {
+        if !self.picked.is_empty() {
+            self.picked.clear();
+            self.message.push_str("Cancelled.");
+        }
+    }
+
+    pub fn is_picked(&self, item: &Item) -> bool {
+        let list = match self.panel {
+            Panel::Todo => &self.todos,
+            Panel::Done => &self.dones,
+        };
+        self.picked
+            .iter()
+            .any(|&i| list.list.get(i).is_some_and(|i| std::ptr::eq(i, item)))
+    }
+
+    pub fn gather_selected_to_cursor(&mut self) {
+        assert!(!self.is_in_edit(), "Can't gather items while in edit mode");
+
+        if self.picked.is_empty() {
+            self.message
+                .push_str("Nothing to gather. Pick items with 'm' first.");
+            return;
+        }
+        let mut picked = std::mem::take(&mut self.picked);
+        picked.sort();
+
+        let list = match self.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        list.record_state();
+        match list.gather(&picked) {
+            Ok(()) => {
+                self.push_operation(Action::Gather, self.panel);
+                self.message
+                    .push_str(&format!("Moved {} item(s).", picked.len()));
+            }
+            Err(err) => {
+                list.revert_state().unwrap();
+                self.message.push_str(err);
+            }
+        }
+    }
+
+    // False when the other panel has no room for the selected items, which stay selected.
+    pub fn transfer_selection(&mut self) -> bool {
+        assert!(
+            !self.is_in_edit(),
+            "Can't transfer items while in edit mode"
+        );
+
+        let from = match self.panel {
+            Panel::Todo => &self.todos,
+            Panel::Done => &self.dones,
+        };
+        let roots = self
+            .selection()
+            .into_iter()
+            .filter(|&i| from.list[i].is_root())
+            .collect::<Vec<usize>>();
+        let moving = roots
+            .iter()
+            .filter(|&&i| !from.list[i].is_active())
+            .map(|&i| from.children_cnt(i) + 1)
+            .sum();
+        if self.is_full(self.panel.togle(), moving) {
+            return false;
+        }
+        self.anchor = None;
+        let (from, to) = match self.panel {
+            Panel::Todo => (&mut self.todos, &mut self.dones),
+            Panel::Done => (&mut self.dones, &mut self.todos),
+        };
+
+        from.record_state();
+        to.record_state();
+
+        let (mut moved, mut skipped, mut removed) = (0, 0, 0);
+        let mut done = Vec::new();
+        let mut first = None;
+        for root in roots {
+            from.cur = root - removed;
+            let cnt = from.children_cnt(from.cur) + 1;
+            let text = from.list[from.cur].text.clone();
+            match from.transfer(to) {
+                Ok(()) => {
+                    first.get_or_insert(root);
+                    moved += 1;
+                    removed += cnt;
+                    done.push(text);
+                }
+                Err(_) => skipped += 1,
+            }
+        }
+
+        if let Some(first) = first {
+            self.push_batch_operation(Action::Transfer, self.panel, first);
+            if self.panel == Panel::Todo {
+                for text in done {
+                    self.log_event("DONE", &text);
+                }
+            }
+            self.message
+                .push_str(&format!("Transferred {moved} item(s)."));
+            if skipped > 0 {
+                self.message
+                    .push_str(&format!(" Skipped {skipped} active item(s)."));
+            }
+        } else {
+            self.todos.revert_state().unwrap();
+            self.dones.revert_state().unwrap();
+            self.message.push_str("Nothing to transfer.");
+        }
+        true
+    }
+
+    pub fn delete_selection(&mut self) {
+        assert!(!self.is_in_edit(), "Can't delete items while in edit mode");
+
+        let panel = self.panel;
+        let selection = self.take_selection();
+        let list = match panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
         };
+        // Only TODO subtasks and DONE roots can be deleted, same as for a single item.
+        let targets = selection
+            .into_iter()
+            .filter(|&i| list.list[i].is_root() == (panel == Panel::Done))
+            .collect::<Vec<usize>>();
+
+        list.record_state();
+
+        let mut ids = Vec::new();
+        let mut deleted = Vec::new();
+        let (mut cnt, mut first) = (0, 0);
+        // Going backwards keeps the indices of the remaining targets valid.
+        for &target in targets.iter().rev() {
+            if target >= list.list.len() || list.list[target].is_root() != (panel == Panel::Done) {
+                continue;
+            }
+            list.cur = target;
+            ids.extend(list.subtree_ids());
+            let text = list.list[target].text.clone();
+            if list.delete().is_ok() {
+                cnt += 1;
+                first = target;
+                deleted.push(text);
+            }
+        }
+
+        if cnt > 0 {
+            self.push_batch_operation(Action::Delete, panel, first);
+            for text in deleted.iter().rev() {
+                self.log_event("DELETE", text);
+            }
+            self.message.push_str(&format!("Deleted {cnt} item(s)."));
+            let cleared = self.clear_blocked_refs(&ids);
+            if cleared > 0 {
+                self.message
+                    .push_str(&format!(" Cleared {cleared} blocked-by reference(s)."));
+            }
+        } else {
+            list.revert_state().unwrap();
+            self.message.push_str(match panel {
+                Panel::Todo => "Nothing to delete. Only TODO subtasks can be deleted.",
+                Panel::Done => "Nothing to delete.",
+            });
+        }
     }
 
     pub fn transfer_item(&mut self) {
         assert!(!self.is_in_edit(), "Can't transfer item while in edit mode");
 
+        let moving = match self.panel {
+            Panel::Todo => self.todos.children_cnt(self.todos.cur) + 1,
+            Panel::Done => self.dones.children_cnt(self.dones.cur) + 1,
+        };
+        if self.is_full(self.panel.togle(), moving) {
+            return;
+        }
         self.todos.record_state();
         self.dones.record_state();
 
+        let moved_to = match self.panel {
+            Panel::Todo => self.dones.list.len(),
+            Panel::Done => self.todos.list.len(),
+        };
+        let text = self.cur_text();
         let result = match self.panel {
             Panel::Todo => self.todos.transfer(&mut self.dones),
             Panel::Done => self.dones.transfer(&mut self.todos),
         };
+        if result.is_ok() {
+            self.flash = Some((self.panel.togle(), moved_to));
+        }
         match result {
             Ok(()) => match self.panel {
                 Panel::Todo => {
-                    self.operation_stack
-                        .push(Operation::new(Action::Transfer, Panel::Todo));
+                    self.push_operation(Action::Transfer, Panel::Todo);
+                    self.log_event("DONE", &text);
                     self.message.push_str("Done! Great job!");
+                    self.unblock_finished();
                 }
                 Panel::Done => {
-                    self.operation_stack
-                        .push(Operation::new(Action::Transfer, Panel::Done));
+                    self.push_operation(Action::Transfer, Panel::Done);
                     self.message.push_str("Not done yet? Keep going!")
                 }
             },
@@ -998,6 +4888,12 @@ impl TodoApp {
     pub fn delete_item(&mut self) {
         assert!(!self.is_in_edit(), "Can't delete item while in edit mode");
 
+        let ids = match self.panel {
+            Panel::Todo => self.todos.subtree_ids(),
+            Panel::Done => self.dones.subtree_ids(),
+        };
+        let text = self.cur_text();
+
         match self.panel {
             Panel::Todo => {
                 if self.todos.is_at_sub() {
@@ -1005,8 +4901,8 @@ impl TodoApp {
                     match self.todos.delete() {
                         Ok(()) => {
                             self.message.push_str("A TODO subtask deleted.");
-                            self.operation_stack
-                                .push(Operation::new(Action::Delete, Panel::Todo));
+                            self.push_operation(Action::Delete, Panel::Todo);
+                            self.log_event("DELETE", &text);
                         }
                         Err(err) => {
                             self.message.push_str(err);
@@ -1024,8 +4920,8 @@ impl TodoApp {
                     match self.dones.delete() {
                         Ok(()) => {
                             self.message.push_str("A DONE item deleted.");
-                            self.operation_stack
-                                .push(Operation::new(Action::Delete, Panel::Done));
+                            self.push_operation(Action::Delete, Panel::Done);
+                            self.log_event("DELETE", &text);
                         }
                         Err(err) => {
                             self.message.push_str(err);
@@ -1038,6 +4934,14 @@ impl TodoApp {
                 }
             }
         }
+
+        if self.is_deleted(&ids) {
+            let cnt = self.clear_blocked_refs(&ids);
+            if cnt > 0 {
+                self.message
+                    .push_str(&format!(" Cleared {cnt} blocked-by reference(s)."));
+            }
+        }
     }
 
     pub fn undo(&mut self) {
@@ -1047,45 +4951,129 @@ impl TodoApp {
         match op {
             Some(op) => {
                 match op.action {
-                    Action::Transfer => {
-                        self.todos.revert_state().unwrap();
-                        self.dones.revert_state().unwrap();
+                    Action::Transfer | Action::Retag => {
+                        self.todos.undo_state();
+                        self.dones.undo_state();
                     }
                     _ => match op.panel {
-                        Panel::Todo => {
-                            self.todos.revert_state().unwrap();
-                        }
-                        Panel::Done => {
-                            self.dones.revert_state().unwrap();
-                        }
+                        Panel::Todo => self.todos.undo_state(),
+                        Panel::Done => self.dones.undo_state(),
                     },
                 }
-                self.panel = op.panel;
-                self.message.push_str(&format!("Undo: {}", op.action));
+                self.set_panel(op.panel);
+                self.dirty = true;
+                self.changes += 1;
+                self.tags = None;
+                self.picked.clear();
+                let at = self.move_to_op(op.panel, op.undo_at, true);
+                self.message.push_str(&format!("Undo: {}{at}", op.action));
+                self.redo_stack.push(op);
             }
             None => self.message.push_str("Nothing to undo."),
         }
     }
 
-    pub fn insert_item(&mut self) -> Option<usize> {
+    // Undoes `n` actions at once, all of them (back to the list as loaded) without `n`.
+    // The lists are only drawn again once it's done, the message tells the last one.
+    pub fn undo_many(&mut self, n: Option<usize>) {
+        let mut undone = 0;
+        while n.is_none_or(|n| undone < n) && !self.operation_stack.is_empty() {
+            self.clear_message();
+            self.undo();
+            undone += 1;
+        }
+        match undone {
+            0 => self.message.push_str("Nothing to undo."),
+            1 => {}
+            _ => {
+                let last = self.message.trim_start_matches("Undo: ").to_string();
+                self.message = format!("Undo x{undone}, the last: {last}");
+            }
+        }
+    }
+
+    pub fn redo(&mut self) {
+        assert!(!self.is_in_edit(), "Can't redo while in edit mode");
+
+        match self.redo_stack.pop() {
+            Some(op) => {
+                match op.action {
+                    Action::Transfer | Action::Retag => {
+                        self.todos.redo_state();
+                        self.dones.redo_state();
+                    }
+                    _ => match op.panel {
+                        Panel::Todo => self.todos.redo_state(),
+                        Panel::Done => self.dones.redo_state(),
+                    },
+                }
+                self.set_panel(op.panel);
+                self.dirty = true;
+                self.changes += 1;
+                self.tags = None;
+                self.picked.clear();
+                // A deleted or transferred item isn't there any more to be named.
+                let named = !matches!(op.action, Action::Delete | Action::Transfer);
+                let at = self.move_to_op(op.panel, op.redo_at, named);
+                self.message.push_str(&format!("Redo: {}{at}", op.action));
+                self.operation_stack.push(op);
+            }
+            None => self.message.push_str("Nothing to redo."),
+        }
+    }
+
+    // Puts the cursor of the panel on the item an undo or redo affected and describes
+    // where it is, e.g. ` 'pay rent' at TODO #14`.
+    fn move_to_op(&mut self, panel: Panel, at: usize, named: bool) -> String {
+        let (list, name) = match panel {
+            Panel::Todo => (&mut self.todos, "TODO"),
+            Panel::Done => (&mut self.dones, "DONE"),
+        };
+        if list.list.is_empty() {
+            return String::new();
+        }
+        list.cur = at.min(list.list.len() - 1);
+        if named {
+            format!(
+                " '{}' at {name} #{}",
+                list.list[list.cur].text,
+                list.cur + 1
+            )
+        } else {
+            format!(" at {name} #{}", list.cur + 1)
+        }
+    }
+
+    pub fn undo_depth(&self) -> usize {
+        self.operation_stack
+            .iter()
+            .filter(|op| op.action != Action::InEdit)
+            .count()
+    }
+
+    pub fn redo_depth(&self) -> usize {
+        self.redo_stack.len()
+    }
+
+    pub fn insert_item(&mut self, position: InsertPosition) -> Option<usize> {
         assert!(
             !self.is_in_edit(),
             "insert_item() called in already running edit mode."
         );
 
+        if self.panel == Panel::Todo && self.is_full(Panel::Todo, 1) {
+            return None;
+        }
         let mut editing_cursor = None;
 
         match self.panel {
             Panel::Todo => {
                 self.todos.record_state();
-                match self.todos.insert() {
+                match self.todos.insert(position) {
                     Ok(()) => {
-                        self.operation_stack
-                            .push(Operation::new(Action::Insert, Panel::Todo));
+                        self.push_edit_operation(Action::Insert, Panel::Todo);
                         editing_cursor = Some(0);
 
-                        self.operation_stack
-                            .push(Operation::new(Action::InEdit, self.panel));
                         self.message.push_str("What needs to be done?");
                     }
                     Err(err) => {
@@ -1108,19 +5096,21 @@ impl TodoApp {
             "append_item() called in already running edit mode."
         );
 
+        if self.panel == Panel::Todo && self.is_full(Panel::Todo, 1) {
+            return None;
+        }
         let mut editing_cursor = None;
 
         match self.panel {
             Panel::Todo => {
+                // The new subtask would be hidden under its parent while typed.
+                self.hide_subs = false;
                 self.todos.record_state();
                 match self.todos.append() {
                     Ok(()) => {
-                        self.operation_stack
-                            .push(Operation::new(Action::Append, Panel::Todo));
+                        self.push_edit_operation(Action::Append, Panel::Todo);
                         editing_cursor = Some(0);
 
-                        self.operation_stack
-                            .push(Operation::new(Action::InEdit, self.panel));
                         self.message
                             .push_str("What needs to be done for the this TODO?");
                     }
@@ -1136,38 +5126,81 @@ impl TodoApp {
         editing_cursor
     }
 
-    pub fn edit_item(&mut self) -> Option<usize> {
+    pub fn edit_item(&mut self, start: EditStart) -> Option<usize> {
         assert!(
             !self.is_in_edit(),
             "edit_item() called in already running edit mode."
         );
 
         let editing_cursor = match self.panel {
-            Panel::Todo => self.todos.get_cur_item().map_or(0, |item| item.text.len()),
-            Panel::Done => self.dones.get_cur_item().map_or(0, |item| item.text.len()),
+            Panel::Todo => self
+                .todos
+                .get_cur_item()
+                .map_or(0, |item| item.text.chars().count()),
+            Panel::Done => self
+                .dones
+                .get_cur_item()
+                .map_or(0, |item| item.text.chars().count()),
         };
 
         if editing_cursor > 0 {
             match self.panel {
                 Panel::Todo => {
                     self.todos.record_state();
-                    self.operation_stack
-                        .push(Operation::new(Action::Edit, Panel::Todo));
+                    self.push_edit_operation(Action::Edit, Panel::Todo);
                 }
                 Panel::Done => {
                     self.dones.record_state();
-                    self.operation_stack
-                        .push(Operation::new(Action::Edit, Panel::Done));
+                    self.push_edit_operation(Action::Edit, Panel::Done);
+                }
+            };
+            self.replacing = start == EditStart::Replace;
+            if self.replacing {
+                self.message
+                    .push_str("Editing current item, typing replaces it.");
+            } else {
+                self.message.push_str("Editing current item.");
+            }
+
+            Some(editing_cursor)
+        } else {
+            self.message.push_str("Nothing to edit.");
+            None
+        }
+    }
+
+    pub fn edit_date(&mut self) -> Option<usize> {
+        assert!(
+            !self.is_in_edit(),
+            "edit_date() called in already running edit mode."
+        );
+
+        match self.panel {
+            Panel::Todo => {
+                self.message
+                    .push_str("Only DONE items have a completion date.");
+                None
+            }
+            Panel::Done => match self.dones.get_cur_item() {
+                Some(item) => {
+                    let date = item.date.format(&self.date_format).to_string();
+                    let editing_cursor = date.chars().count();
+
+                    self.dones.record_state();
+                    self.push_edit_operation(Action::EditDate, Panel::Done);
+                    self.date_input = Some(date);
+                    self.message.push_str(&format!(
+                        "When was it done? ({}, today, yesterday, N days ago)",
+                        date_hint(&self.date_format)
+                    ));
+
+                    Some(editing_cursor)
                 }
-            };
-            self.operation_stack
-                .push(Operation::new(Action::InEdit, self.panel));
-            self.message.push_str("Editing current item.");
-
-            Some(editing_cursor)
-        } else {
-            self.message.push_str("Nothing to edit.");
-            None
+                None => {
+                    self.message.push_str("Nothing to edit.");
+                    None
+                }
+            },
         }
     }
 
@@ -1177,6 +5210,29 @@ impl TodoApp {
             "edit_item_with() called without a matching edit_item() or insert_item()"
         );
 
+        if let Some(date) = self.date_input.as_mut() {
+            edit_text(date, cur, key);
+            return;
+        }
+
+        // The first key typed (or deleting) replaces the whole text, moving keeps it.
+        if std::mem::take(&mut self.replacing)
+            && (key_char(key).is_some()
+                || matches!(key, constants::KEY_BACKSPACE | 127 | constants::KEY_DC))
+        {
+            let item = match self.panel {
+                Panel::Todo => self.todos.get_cur_item_mut(),
+                Panel::Done => self.dones.get_cur_item_mut(),
+            };
+            if let Some(item) = item {
+                item.text.clear();
+            }
+            *cur = 0;
+            if key_char(key).is_none() {
+                return;
+            }
+        }
+
         match self.panel {
             Panel::Todo => self.todos.edit(cur, key),
             Panel::Done => self.dones.edit(cur, key),
@@ -1188,13 +5244,55 @@ impl TodoApp {
             self.is_in_edit() && self.operation_stack.len() >= 2,
             "finish_edit() called without a matching edit_item() or insert_item()"
         );
+        self.tags = None;
+        self.replacing = false;
 
         self.clear_message();
 
+        if let Some(input) = self.date_input.as_ref() {
+            match parse_date(input, &self.date_format) {
+                Some(date) => {
+                    if let Some(item) = self.dones.get_cur_item_mut() {
+                        item.date = date;
+                    }
+                    self.date_input = None;
+                }
+                None => {
+                    self.message.push_str("Invalid date.");
+                    return false;
+                }
+            }
+
+            self.close_edit();
+            return true;
+        }
+
+        if let Some(len) = self.get_edit_len() {
+            if len > self.max_len {
+                self.message.push_str(&format!(
+                    "Item is too long ({len}/{} characters).",
+                    self.max_len
+                ));
+                return false;
+            }
+        }
+
+        let item = match self.panel {
+            Panel::Todo => self.todos.get_cur_item(),
+            Panel::Done => self.dones.get_cur_item(),
+        };
+        if let Some(bad) = item.and_then(|item| item.bad_estimate()) {
+            self.message =
+                format!("Invalid estimate '{bad}', expected e.g. est:30m, est:2h or est:1h30m.");
+            return false;
+        }
+
         match self.panel {
             Panel::Todo => {
                 if let Some(item) = self.todos.get_cur_item() {
-                    if item.text.is_empty() {
+                    // Whitespace alone is as empty as no text: a new item is dropped, an
+                    // existing one is kept in edit until it has some text.
+                    if item.text.trim().is_empty() {
                         let act = self
                             .operation_stack
                             .get(self.operation_stack.len() - 2)
@@ -1204,6 +5302,8 @@ impl TodoApp {
                             Action::Insert | Action::Append => {
                                 self.todos.revert_state().unwrap();
                                 self.operation_stack.pop();
+                                self.operation_stack.pop();
+                                return true;
                             }
                             Action::Edit => {
                                 self.message.push_str("TODO item can't be empty.");
@@ -1211,13 +5311,27 @@ impl TodoApp {
                             }
                             _ => unreachable!(),
                         }
+                    } else {
+                        self.todos.get_cur_item_mut().unwrap().trim_text();
+
+                        let act = self.operation_stack[self.operation_stack.len() - 2].action;
+                        if matches!(act, Action::Insert | Action::Append) {
+                            let item = self.todos.get_cur_item_mut().unwrap();
+                            if let Some((text, checked)) = strip_checkbox(&item.text) {
+                                item.text = text.to_string();
+                                if checked {
+                                    self.todos.mark().unwrap();
+                                }
+                            }
+                            let text = self.cur_text();
+                            self.log_event("ADD", &text);
+                        }
                     }
-                    self.todos.get_cur_item_mut().unwrap().trim_text();
                 }
             }
             Panel::Done => {
                 if let Some(cur_done) = self.dones.get_cur_item_mut() {
-                    if cur_done.text.is_empty() {
+                    if cur_done.text.trim().is_empty() {
                         self.message.push_str("DONE item can't be empty.");
                         return false;
                     }
@@ -1226,10 +5340,109 @@ impl TodoApp {
             }
         }
 
-        self.operation_stack.pop();
+        self.close_edit();
         true
     }
 
+    // An edit that left the item as it was isn't a change: it's dropped, so it's not a
+    // step to undo and what was undone before can still be redone.
+    fn close_edit(&mut self) {
+        self.operation_stack.pop();
+        let op = self.operation_stack.last().unwrap();
+        let list = match op.panel {
+            Panel::Todo => &mut self.todos,
+            Panel::Done => &mut self.dones,
+        };
+        let before = list
+            .state_stack
+            .last()
+            .and_then(|(items, _)| items.get(list.cur));
+        if matches!(op.action, Action::Insert | Action::Append) || before != list.get_cur_item() {
+            self.changed();
+        } else {
+            list.revert_state().unwrap();
+            self.operation_stack.pop();
+        }
+    }
+
+    pub fn cancel_edit(&mut self) {
+        assert!(
+            self.is_in_edit() && self.operation_stack.len() >= 2,
+            "cancel_edit() called without a matching edit_item() or insert_item()"
+        );
+
+        self.replacing = false;
+        self.clear_message();
+        self.operation_stack.pop();
+
+        let op = self.operation_stack.pop().unwrap();
+        match op.panel {
+            Panel::Todo => self.todos.revert_state().unwrap(),
+            Panel::Done => self.dones.revert_state().unwrap(),
+        }
+        self.date_input = None;
+        self.message.push_str(&format!("{} cancelled.", op.action));
+    }
+
+    // The cursor before the change (from the state recorded for it) is where undo goes,
+    // the one after it where redo goes.
+    fn push_operation(&mut self, action: Action, panel: Panel) {
+        let list = match panel {
+            Panel::Todo => &self.todos,
+            Panel::Done => &self.dones,
+        };
+        let undo_at = list.state_stack.last().map_or(list.cur, |(_, cur)| *cur);
+        self.push_operation_at(action, panel, undo_at, list.cur);
+    }
+
+    // For a batch, both go to the first item it changed.
+    fn push_batch_operation(&mut self, action: Action, panel: Panel, first: usize) {
+        self.push_operation_at(action, panel, first, first);
+    }
+
+    fn push_operation_at(&mut self, action: Action, panel: Panel, undo_at: usize, redo_at: usize) {
+        self.changed();
+        self.operation_stack
+            .push(Operation::new(action, panel, undo_at, redo_at));
+    }
+
+    // The operation of an edit is pushed when it starts, but only counts as a change once
+    // it's finished, see close_edit(). Moving around, switching panels or filtering in the
+    // meantime (or instead) doesn't touch the undo and redo history either.
+    fn push_edit_operation(&mut self, action: Action, panel: Panel) {
+        let list = match panel {
+            Panel::Todo => &self.todos,
+            Panel::Done => &self.dones,
+        };
+        let undo_at = list.state_stack.last().map_or(list.cur, |(_, cur)| *cur);
+        let cur = list.cur;
+        // The picks are indices, an inserted item would shift them.
+        self.picked.clear();
+        self.operation_stack
+            .push(Operation::new(action, panel, undo_at, cur));
+        self.operation_stack
+            .push(Operation::new(Action::InEdit, panel, cur, cur));
+    }
+
+    fn changed(&mut self) {
+        self.dirty = true;
+        self.changes += 1;
+        self.tags = None;
+        self.picked.clear();
+        // A new change can't be followed by the ones undone before it.
+        self.redo_stack.clear();
+        self.todos.redo_stack.clear();
+        self.dones.redo_stack.clear();
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn unsaved_changes(&self) -> usize {
+        self.changes
+    }
+
     fn is_in_edit(&self) -> bool {
         if let Some(op) = self.operation_stack.last() {
             op.action == Action::InEdit
@@ -1238,3 +5451,1175 @@ impl TodoApp {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(content: &str) -> TodoApp {
+        let mut app = TodoApp::new();
+        app.parse_contents("test", content).unwrap();
+        app
+    }
+
+    fn blockers(app: &TodoApp) -> Vec<Option<usize>> {
+        app.todos.list.iter().map(|item| item.blocked_by).collect()
+    }
+
+    #[test]
+    fn load_breaks_blocking_cycles() {
+        let app = load("TODO(*): x id:1 blocked:2\nTODO(*): y id:2 blocked:1\n<--->\n");
+        assert_eq!(blockers(&app), [None, Some(1)]);
+        assert!(app.message.contains("Broke 1 blocking cycle(s)."));
+        assert!(app.dirty);
+    }
+
+    #[test]
+    fn load_drops_duplicate_ids() {
+        let app = load("TODO(*): x id:1\nTODO(*): y id:1\nTODO(*): z blocked:1\n<--->\n");
+        let ids: Vec<Option<usize>> = app.todos.list.iter().map(|item| item.id).collect();
+        assert_eq!(ids, [Some(1), None, None]);
+        assert_eq!(blockers(&app), [None, None, Some(1)]);
+        assert!(app.message.contains("Dropped 1 duplicate id(s)."));
+    }
+
+    #[test]
+    fn block_item_ends_on_an_existing_cycle() {
+        let mut app = load("TODO(*): x id:1 blocked:2\nTODO(*): y id:2\nTODO(*): z\n<--->\n");
+        // A cycle the load didn't see, as a bad edit of the list could leave one.
+        app.todos.list[1].blocked_by = Some(1);
+        app.todos.cur = 2;
+        app.block_item();
+        app.todos.cur = 0;
+        app.block_item();
+        assert_eq!(blockers(&app), [Some(2), Some(1), Some(1)]);
+    }
+
+    #[test]
+    fn block_item_refuses_a_cycle() {
+        let mut app = load("TODO(*): x id:1 blocked:2\nTODO(*): y id:2\n<--->\n");
+        app.todos.cur = 1;
+        app.block_item();
+        app.todos.cur = 0;
+        app.block_item();
+        assert_eq!(blockers(&app), [Some(2), None]);
+        assert!(app.message.contains("would create a cycle"));
+    }
+
+    // The texts of the TODO panel, the new item has none yet.
+    fn todo_texts(app: &TodoApp) -> Vec<&str> {
+        app.todos
+            .list
+            .iter()
+            .map(|item| item.text.as_str())
+            .collect()
+    }
+
+    const NESTED: &str = "TODO(*): a\nTODO(*): b\n  TODO(*): b1\nTODO(*): c\n<--->\n";
+
+    #[test]
+    fn insert_item_at_each_position() {
+        let cases = [
+            (InsertPosition::Top, vec!["", "a", "b", "b1", "c"], 0),
+            (InsertPosition::Bottom, vec!["a", "b", "b1", "c", ""], 4),
+            (
+                InsertPosition::AboveCursor,
+                vec!["a", "", "b", "b1", "c"],
+                1,
+            ),
+            (
+                InsertPosition::BelowCursor,
+                vec!["a", "b", "b1", "", "c"],
+                3,
+            ),
+        ];
+        for (position, texts, cur) in cases {
+            let mut app = load(NESTED);
+            app.todos.cur = 1;
+            assert_eq!(app.insert_item(position), Some(0), "{position:?}");
+            assert_eq!(todo_texts(&app), texts, "{position:?}");
+            assert_eq!(app.todos.cur, cur, "{position:?}");
+            assert_eq!(app.todos.list[cur].parent, None, "{position:?}");
+        }
+    }
+
+    #[test]
+    fn insert_item_below_a_subtask_goes_after_its_parent() {
+        let mut app = load(NESTED);
+        app.todos.cur = 2;
+        app.insert_item(InsertPosition::BelowCursor);
+        assert_eq!(todo_texts(&app), ["a", "b", "b1", "", "c"]);
+
+        let mut app = load(NESTED);
+        app.todos.cur = 2;
+        assert_eq!(app.insert_item(InsertPosition::AboveCursor), None);
+        assert_eq!(todo_texts(&app), ["a", "b", "b1", "c"]);
+        assert!(app.message.contains("Current item is a subtask."));
+    }
+
+    #[test]
+    fn insert_item_into_an_empty_list() {
+        for position in [
+            InsertPosition::Top,
+            InsertPosition::Bottom,
+            InsertPosition::AboveCursor,
+            InsertPosition::BelowCursor,
+        ] {
+            let mut app = load("<--->\n");
+            assert_eq!(app.insert_item(position), Some(0), "{position:?}");
+            assert_eq!(todo_texts(&app), [""], "{position:?}");
+            assert_eq!(app.todos.cur, 0, "{position:?}");
+        }
+    }
+
+    #[test]
+    fn add_todo_goes_to_the_top_or_the_bottom() {
+        let cases = [
+            (InsertPosition::Top, 0),
+            (InsertPosition::Bottom, 4),
+            (InsertPosition::AboveCursor, 4),
+            (InsertPosition::BelowCursor, 4),
+        ];
+        for (position, at) in cases {
+            let mut app = load(NESTED);
+            app.todos.cur = 1;
+            app.add_todo("new", position).unwrap();
+            assert_eq!(app.todos.list[at].text, "new", "{position:?}");
+            assert_eq!(app.todos.cur, at, "{position:?}");
+        }
+
+        let mut app = load("<--->\n");
+        app.add_todo("new", InsertPosition::AboveCursor).unwrap();
+        assert_eq!(todo_texts(&app), ["new"]);
+    }
+
+    // 30 TODO items t0..t29 and 30 DONE items d0..d29.
+    fn long_lists() -> TodoApp {
+        let mut content = String::new();
+        for i in 0..30 {
+            content.push_str(&format!("TODO(*): t{i}\n"));
+        }
+        content.push_str("<--->\n");
+        for i in 0..30 {
+            content.push_str(&format!("DONE(2026-01-02 10:00 +0000): d{i}\n"));
+        }
+        load(&content)
+    }
+
+    fn scroll(app: &mut TodoApp) {
+        app.scroll_todos(10, 0);
+        app.scroll_dones(10, 0);
+    }
+
+    fn places(app: &TodoApp) -> [(&str, usize); 2] {
+        [
+            (
+                app.todos.list[app.todos.cur].text.as_str(),
+                app.todos.scroll,
+            ),
+            (
+                app.dones.list[app.dones.cur].text.as_str(),
+                app.dones.scroll,
+            ),
+        ]
+    }
+
+    #[test]
+    fn toggle_keeps_each_panel_in_place() {
+        let mut app = long_lists();
+        app.go_bottom();
+        scroll(&mut app);
+        app.toggle_panel();
+        for _ in 0..12 {
+            app.go_down();
+        }
+        scroll(&mut app);
+        assert_eq!(places(&app), [("t29", 20), ("d12", 3)]);
+
+        app.toggle_panel();
+        scroll(&mut app);
+        assert_eq!(places(&app), [("t29", 20), ("d12", 3)]);
+        app.toggle_panel();
+        scroll(&mut app);
+        assert_eq!(places(&app), [("t29", 20), ("d12", 3)]);
+    }
+
+    #[test]
+    fn transfer_keeps_the_other_panel_in_place() {
+        let mut app = long_lists();
+        app.toggle_panel();
+        app.go_bottom();
+        scroll(&mut app);
+        app.toggle_panel();
+        for _ in 0..5 {
+            app.go_down();
+        }
+
+        // Each transfer adds an item to the DONE panel, below its cursor.
+        for _ in 0..2 {
+            app.mark_item();
+            app.transfer_item();
+        }
+        scroll(&mut app);
+        assert_eq!(app.dones.list.len(), 32);
+        assert_eq!(places(&app), [("t7", 0), ("d29", 20)]);
+
+        app.toggle_panel();
+        scroll(&mut app);
+        assert_eq!(places(&app), [("t7", 0), ("d29", 20)]);
+    }
+
+    #[test]
+    fn shrinking_panel_clamps_its_place() {
+        let mut app = long_lists();
+        app.go_bottom();
+        scroll(&mut app);
+        app.toggle_panel();
+        app.go_bottom();
+        scroll(&mut app);
+
+        // Back to TODO from the bottom of DONE, the cursor and the scroll follow the end.
+        for _ in 0..5 {
+            app.transfer_item();
+        }
+        scroll(&mut app);
+        assert_eq!(app.dones.list.len(), 25);
+        assert_eq!(places(&app), [("t29", 20), ("d24", 15)]);
+
+        app.toggle_panel();
+        app.go_bottom();
+        scroll(&mut app);
+        assert_eq!(places(&app), [("d25", 25), ("d24", 15)]);
+
+        app.toggle_panel();
+        scroll(&mut app);
+        assert_eq!(places(&app), [("d25", 25), ("d24", 15)]);
+    }
+
+    #[test]
+    fn parse_line_todo() {
+        let item = parse_line("TODO(*): buy milk ", Panel::Todo).unwrap();
+        assert_eq!((item.text.as_str(), item.is_active()), ("buy milk", true));
+
+        let item = parse_line("    TODO(): sent", Panel::Todo).unwrap();
+        assert_eq!((item.text.as_str(), item.is_active()), ("sent", false));
+        assert_eq!(item.parent, None);
+
+        let item = parse_line("TODO(*):", Panel::Todo).unwrap();
+        assert_eq!(item.text, "");
+    }
+
+    #[test]
+    fn parse_line_tokens() {
+        let item = parse_line(
+            "TODO(*): call id:3 Bob blocked:1 created:2026-03-04 due:2026-04-01",
+            Panel::Todo,
+        )
+        .unwrap();
+        assert_eq!(item.text, "call Bob due:2026-04-01");
+        assert_eq!((item.id, item.blocked_by), (Some(3), Some(1)));
+        assert_eq!(item.created, NaiveDate::from_ymd_opt(2026, 3, 4));
+        assert_eq!(item.get_due(), NaiveDate::from_ymd_opt(2026, 4, 1));
+
+        // A token that isn't a number or a date is text.
+        let item = parse_line("TODO(*): see id:x created:soon", Panel::Todo).unwrap();
+        assert_eq!(item.text, "see id:x created:soon");
+        assert_eq!((item.id, item.created), (None, None));
+    }
+
+    #[test]
+    fn parse_line_done() {
+        let item = parse_line("DONE(2026-01-02 10:30 +0000): filed", Panel::Done).unwrap();
+        assert_eq!(item.text, "filed");
+        assert_eq!(
+            item.date.naive_utc(),
+            NaiveDate::from_ymd_opt(2026, 1, 2)
+                .unwrap()
+                .and_hms_opt(10, 30, 0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_line_errors() {
+        let err = |line, panel| parse_line(line, panel).err();
+        assert_eq!(
+            err("DONE(2026-01-02 10:30 +0000): x", Panel::Todo),
+            Some("invalid format for a TODO item")
+        );
+        assert_eq!(
+            err("TODO(x): x", Panel::Todo),
+            Some("invalid format for a TODO item")
+        );
+        assert_eq!(
+            err("TODO(*):x", Panel::Todo),
+            Some("invalid format for a TODO item")
+        );
+        assert_eq!(
+            err("TODO(*): x", Panel::Done),
+            Some("invalid format for a DONE item")
+        );
+        assert_eq!(
+            err("DONE(yesterday): x", Panel::Done),
+            Some("invalid date format for a DONE item")
+        );
+    }
+
+    #[test]
+    fn serialize_item_round_trip() {
+        // Dates are written in the local offset, whatever the offset of the file was.
+        let done = format!("DONE({}): filed id:2", Local::now().format(DATE_FMT));
+        let lines = [
+            ("TODO(*): buy milk", Panel::Todo),
+            (
+                "TODO(): call Bob id:3 blocked:1 created:2026-03-04",
+                Panel::Todo,
+            ),
+            (done.as_str(), Panel::Done),
+        ];
+        for (line, panel) in lines {
+            let item = parse_line(line, panel).unwrap();
+            assert_eq!(serialize_item(&item, panel, 0, "  "), line);
+        }
+
+        let item = parse_line("TODO(*): sub", Panel::Todo).unwrap();
+        assert_eq!(
+            serialize_item(&item, Panel::Todo, 2, "  "),
+            "    TODO(*): sub"
+        );
+        assert_eq!(
+            serialize_item(&item, Panel::Todo, 1, "\t"),
+            "\tTODO(*): sub"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn write_atomic_replaces_the_file() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = std::env::temp_dir().join(format!("todors-atomic-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("list.todo");
+        let link = dir.join("link.todo");
+        fs::write(&path, "old\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink(&path, &link).unwrap();
+
+        write_atomic(path.to_str().unwrap(), "new\n").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new\n");
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // Through the link the file it points to is replaced, the link stays.
+        write_atomic(link.to_str().unwrap(), "linked\n").unwrap();
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "linked\n");
+
+        // A file that doesn't exist yet is created, nothing else is left behind.
+        write_atomic(dir.join("fresh.todo").to_str().unwrap(), "fresh\n").unwrap();
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 3);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // The same tree with each indentation, down to a sub-subtask. The dates are in the
+    // local offset, the one they are written back in.
+    fn nested(indent: &str) -> String {
+        format!(
+            "TODO(*): a\n{i}TODO(*): a1\n{i}{i}TODO(): a11\n{i}TODO(*): a2\nTODO(*): b\n<--->\n\
+             DONE({date}): c\n{i}DONE({date}): c1\n",
+            i = indent,
+            date = Local::now().format(DATE_FMT)
+        )
+    }
+
+    #[test]
+    fn indentation_round_trip() {
+        for indent in ["  ", "    ", "\t"] {
+            let content = nested(indent);
+            let app = load(&content);
+            assert_eq!(app.todos.list[2].parent, Some(1), "{indent:?}");
+            assert_eq!(app.dones.list[1].parent, Some(0), "{indent:?}");
+            assert_eq!(app.contents(), content, "{indent:?}");
+        }
+    }
+
+    #[test]
+    fn indentation_mixed_in_a_file_is_an_error() {
+        let content = "TODO(*): a\n  TODO(*): a1\nTODO(*): b\n\tTODO(*): b1\n<--->\n";
+        assert_eq!(
+            check_contents(content),
+            Err("4: invalid indentation".to_string())
+        );
+    }
+
+    fn todo_txt(line: &str) -> (Panel, Item) {
+        parse_todo_txt(line).unwrap()
+    }
+
+    #[test]
+    fn todo_txt_open_items() {
+        let (panel, item) = todo_txt("(A) 2026-03-01 Call Mom +Family @phone due:2026-03-05");
+        assert_eq!(panel, Panel::Todo);
+        assert!(item.is_active());
+        assert_eq!(item.text, "Call Mom +Family @phone due:2026-03-05 pri:A");
+        assert_eq!(item.created, NaiveDate::from_ymd_opt(2026, 3, 1));
+        assert_eq!(item.get_due(), NaiveDate::from_ymd_opt(2026, 3, 5));
+
+        let (_, item) = todo_txt("Post letter");
+        assert_eq!((item.text.as_str(), item.created), ("Post letter", None));
+
+        // A priority not at the start, or in lower case, is text.
+        let (_, item) = todo_txt("Ask (A) about it");
+        assert_eq!(item.text, "Ask (A) about it");
+        let (_, item) = todo_txt("(a) lower");
+        assert_eq!(item.text, "(a) lower");
+
+        assert!(parse_todo_txt("   ").is_none());
+        assert!(parse_todo_txt("(B) 2026-03-01").is_none());
+    }
+
+    #[test]
+    fn todo_txt_completed_items() {
+        let (panel, item) = todo_txt("x 2026-03-02 2026-03-01 Pay rent +Home");
+        assert_eq!(panel, Panel::Done);
+        assert_eq!(
+            item.date.date_naive(),
+            NaiveDate::from_ymd_opt(2026, 3, 2).unwrap()
+        );
+        assert_eq!(item.created, NaiveDate::from_ymd_opt(2026, 3, 1));
+        assert_eq!(item.text, "Pay rent +Home");
+
+        let (panel, item) = todo_txt("x (C) 2026-03-02 Filed");
+        assert_eq!(panel, Panel::Done);
+        assert_eq!(item.text, "Filed pri:C");
+
+        // Without a completion date the item stays a finished TODO, with no date made up.
+        let (panel, item) = todo_txt("x Water plants");
+        assert_eq!(panel, Panel::Todo);
+        assert!(!item.is_active());
+        assert_eq!((item.text.as_str(), item.created), ("Water plants", None));
+
+        // Not a completion marker without the space.
+        let (panel, item) = todo_txt("xylophone lesson");
+        assert_eq!(
+            (panel, item.text.as_str()),
+            (Panel::Todo, "xylophone lesson")
+        );
+    }
+
+    #[test]
+    fn todo_txt_round_trip() {
+        let lines = "(A) 2026-03-01 Call Mom +Family @phone\n\
+                     Post letter due:2026-03-05\n\
+                     x Water plants\n\
+                     x 2026-03-01 Lawn\n\
+                     x 2026-03-02 2026-03-01 Pay rent +Home\n";
+        let mut app = load("<--->\n");
+        assert_eq!(app.import_todo_txt(lines), Ok((3, 2)));
+        assert_eq!(app.to_todo_txt(), lines);
+    }
+
+    // Dones with ties in both the dates and the texts, in the file order a..f.
+    const DONES: &str = "<--->\n\
+        DONE(2026-01-03 09:00 +0000): a beta\n\
+        DONE(2026-01-01 09:00 +0000): b Alpha\n\
+        \x20 DONE(2026-01-01 09:00 +0000): b sub\n\
+        DONE(2026-01-03 09:00 +0000): c gamma\n\
+        DONE(2026-01-02 09:00 +0000): d alpha\n\
+        DONE(2026-01-01 09:00 +0000): e Beta\n\
+        DONE(2026-01-03 09:00 +0000): f delta\n";
+
+    fn dones(sort: Option<Sort>, reverse: bool, limit: Option<usize>) -> Vec<String> {
+        let list = load(DONES).list_dones(sort, reverse, limit);
+        list.lines()
+            .map(|line| line.trim_start().trim_start_matches("- [x] ").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn list_dones_sorted_with_ties() {
+        assert_eq!(
+            dones(None, false, None),
+            ["a beta", "b Alpha", "b sub", "c gamma", "d alpha", "e Beta", "f delta"]
+        );
+        // Ties keep the file order.
+        assert_eq!(
+            dones(Some(Sort::Date), false, None),
+            ["b Alpha", "b sub", "e Beta", "d alpha", "a beta", "c gamma", "f delta"]
+        );
+        // The latest first, ties the other way round: the last in the file was done last.
+        assert_eq!(
+            dones(Some(Sort::Recent), false, None),
+            ["f delta", "c gamma", "a beta", "d alpha", "e Beta", "b Alpha", "b sub"]
+        );
+        assert_eq!(
+            dones(Some(Sort::Alpha), false, None),
+            ["a beta", "b Alpha", "b sub", "c gamma", "d alpha", "e Beta", "f delta"]
+        );
+    }
+
+    #[test]
+    fn list_dones_reverse_and_limit() {
+        assert_eq!(
+            dones(Some(Sort::Date), true, None),
+            ["f delta", "c gamma", "a beta", "d alpha", "e Beta", "b Alpha", "b sub"]
+        );
+        // The limit counts items with their subtasks, after sorting.
+        assert_eq!(
+            dones(Some(Sort::Recent), false, Some(3)),
+            ["f delta", "c gamma", "a beta"]
+        );
+        assert_eq!(
+            dones(Some(Sort::Date), false, Some(1)),
+            ["b Alpha", "b sub"]
+        );
+        assert_eq!(dones(None, false, Some(0)), Vec::<String>::new());
+    }
+
+    #[test]
+    fn sort_compare_ties_are_equal() {
+        let app = load(DONES);
+        let (a, c) = (&app.dones.list[0], &app.dones.list[3]);
+        assert_eq!(Sort::Date.compare(a, c), Ordering::Equal);
+        assert_eq!(Sort::Recent.compare(a, c), Ordering::Equal);
+        let (b, d) = (&app.dones.list[1], &app.dones.list[4]);
+        assert_eq!(Sort::Date.compare(b, d), Ordering::Less);
+        assert_eq!(Sort::Recent.compare(b, d), Ordering::Greater);
+
+        // Case doesn't count.
+        let upper = Item::new("Beta".to_string(), Local::now(), None, 0);
+        let lower = Item::new("beta".to_string(), Local::now(), None, 0);
+        assert_eq!(Sort::Alpha.compare(&upper, &lower), Ordering::Equal);
+        let alpha = Item::new("alpha".to_string(), Local::now(), None, 0);
+        assert_eq!(Sort::Alpha.compare(&upper, &alpha), Ordering::Greater);
+    }
+
+    const BACKLOG: &str = "TODO(*): none created:2026-01-02\n\
+        TODO(*): low pri:C due:2026-03-01 created:2026-01-03\n\
+        \x20 TODO(*): sub pri:A\n\
+        TODO(*): top pri:A\n\
+        TODO(*): mid pri:B due:2026-02-01 created:2026-01-01\n\
+        <--->\n";
+
+    #[test]
+    fn sort_keeps_the_subtasks_under_their_item() {
+        let mut app = load(BACKLOG);
+        app.go_bottom();
+        app.clear_message();
+        app.sort_panel(Some(Sort::Priority));
+        assert_eq!(
+            todo_texts(&app),
+            [
+                "top pri:A",
+                "mid pri:B due:2026-02-01",
+                "low pri:C due:2026-03-01",
+                "sub pri:A",
+                "none"
+            ]
+        );
+        // The cursor stays on its item.
+        assert_eq!(cur_text(&app), "mid pri:B due:2026-02-01");
+        assert_eq!(app.message, "Sorted the TODO panel by pri.");
+
+        // Without a due date or a `created:` token last.
+        for (sort, first) in [
+            (Sort::Due, ["mid", "low", "none", "top"]),
+            (Sort::Date, ["mid", "none", "low", "top"]),
+            (Sort::Recent, ["low", "none", "mid", "top"]),
+            (Sort::Alpha, ["low", "mid", "none", "top"]),
+        ] {
+            let mut app = load(BACKLOG);
+            app.sort_panel(Some(sort));
+            let roots: Vec<&str> = app
+                .todos
+                .list
+                .iter()
+                .filter(|item| item.is_root())
+                .map(|item| item.text.split(' ').next().unwrap())
+                .collect();
+            assert_eq!(roots, first, "{sort:?}");
+        }
+    }
+
+    #[test]
+    fn sort_is_one_step_to_undo() {
+        let mut app = load(BACKLOG);
+        let loaded = todo_texts(&app).join("|");
+        app.sort_panel(Some(Sort::Alpha));
+        assert!(app.dirty);
+        app.undo();
+        assert_eq!(todo_texts(&app).join("|"), loaded);
+
+        // Sorting what already is in order changes nothing, the order is still kept.
+        app.redo();
+        app.clear_message();
+        let depth = app.undo_depth();
+        app.sort_panel(Some(Sort::Alpha));
+        assert_eq!(app.message, "The TODO panel is already sorted by alpha.");
+        assert_eq!(app.undo_depth(), depth);
+        assert_eq!(app.todo_sort, Some(Sort::Alpha));
+
+        app.clear_message();
+        app.sort_panel(None);
+        assert_eq!(app.message, "The TODO panel is no longer kept sorted.");
+        assert_eq!(app.todo_sort, None);
+        assert_eq!(app.done_sort, None);
+    }
+
+    const DUPLICATES: &str = "TODO(*): Buy milk\n\
+        TODO(*): buy milk\n\
+        TODO(*): Buy  milk +shop\n\
+        \x20 TODO(*): Buy milk\n\
+        TODO(*): Buy milk due:2026-05-01\n\
+        TODO(*): Buy milk\n\
+        TODO(*): Call Bob\n\
+        <--->\n";
+
+    #[test]
+    fn duplicate_key_of_each_policy() {
+        let text = "Buy  Milk +shop @town due:2026-05-01";
+        assert_eq!(duplicate_key(text, DuplicatePolicy::Exact), text);
+        assert_eq!(
+            duplicate_key(text, DuplicatePolicy::IgnoreCase),
+            "buy  milk +shop @town due:2026-05-01"
+        );
+        assert_eq!(duplicate_key(text, DuplicatePolicy::IgnoreTags), "buy milk");
+        // A lone `+` or `@` isn't a tag.
+        assert_eq!(duplicate_key("a + b", DuplicatePolicy::IgnoreTags), "a + b");
+    }
+
+    #[test]
+    fn find_duplicates_with_each_policy() {
+        let app = load(DUPLICATES);
+        // Subtasks are never duplicates, the one at 3 is left out.
+        assert_eq!(app.find_duplicates(DuplicatePolicy::Exact), [vec![0, 5]]);
+        assert_eq!(
+            app.find_duplicates(DuplicatePolicy::IgnoreCase),
+            [vec![0, 1, 5]]
+        );
+        assert_eq!(
+            app.find_duplicates(DuplicatePolicy::IgnoreTags),
+            [vec![0, 1, 2, 4, 5]]
+        );
+
+        let app = load("TODO(*): a\nTODO(*): b\n<--->\n");
+        assert!(app.find_duplicates(DuplicatePolicy::IgnoreTags).is_empty());
+    }
+
+    #[test]
+    fn pick_duplicates_picks_the_later_copies() {
+        let mut app = load(DUPLICATES);
+        app.pick_duplicates(DuplicatePolicy::IgnoreCase);
+        assert_eq!(app.picked, [1, 5]);
+        assert!(app.message.contains("Picked 2 duplicate(s) of 1 item(s)."));
+
+        let mut app = load(DUPLICATES);
+        app.toggle_panel();
+        app.pick_duplicates(DuplicatePolicy::Exact);
+        assert!(app.picked.is_empty());
+        assert!(app.message.contains("only looked for in the TODO panel"));
+    }
+
+    fn edit(text: &str, cur: usize, keys: &[i32]) -> (String, usize) {
+        let (mut text, mut cur) = (text.to_string(), cur);
+        for &key in keys {
+            edit_text(&mut text, &mut cur, key);
+        }
+        (text, cur)
+    }
+
+    #[test]
+    fn edit_text_counts_characters() {
+        use constants::{KEY_BACKSPACE, KEY_DC, KEY_END, KEY_HOME, KEY_LEFT, KEY_RIGHT};
+
+        // The cursor is past the é, not inside it.
+        assert_eq!(edit("café", 4, &[KEY_BACKSPACE]), ("caf".to_string(), 3));
+        assert_eq!(
+            edit("café", 4, &[KEY_LEFT, 'x' as i32]),
+            ("cafxé".to_string(), 4)
+        );
+        assert_eq!(edit("café", 4, &[KEY_LEFT, KEY_DC]), ("caf".to_string(), 3));
+        assert_eq!(
+            edit("café", 4, &[KEY_RIGHT, '!' as i32]),
+            ("café!".to_string(), 5)
+        );
+        assert_eq!(
+            edit(
+                "日本語",
+                3,
+                &[KEY_HOME, KEY_RIGHT, KEY_DC, KEY_END, 'x' as i32]
+            ),
+            ("日語x".to_string(), 3)
+        );
+        // A cursor past the end is moved back to it.
+        assert_eq!(edit("é", 9, &[KEY_BACKSPACE]), (String::new(), 0));
+    }
+
+    #[test]
+    fn edit_text_types_wide_characters() {
+        use super::super::backend::char_key;
+
+        let keys: Vec<i32> = "é日🎉".chars().map(char_key).collect();
+        let typed = edit("a", 1, &keys);
+        if cfg!(feature = "wide") {
+            assert_eq!(typed, ("aé日🎉".to_string(), 4));
+        } else {
+            assert_eq!(typed, ("a".to_string(), 1));
+        }
+        // Control characters and the ncurses key codes are never text.
+        assert_eq!(
+            edit("a", 1, &[9, 27, constants::KEY_UP]),
+            ("a".to_string(), 1)
+        );
+    }
+
+    #[test]
+    fn strip_checkbox_of_markdown_lines() {
+        assert_eq!(strip_checkbox("- [ ] open"), Some(("open", false)));
+        assert_eq!(strip_checkbox("- [x] done"), Some(("done", true)));
+        assert_eq!(strip_checkbox("* [X] done"), Some(("done", true)));
+        assert_eq!(
+            strip_checkbox("+ [ ]  two spaces"),
+            Some(("two spaces", false))
+        );
+        assert_eq!(
+            strip_checkbox("[x] no list marker"),
+            Some(("no list marker", true))
+        );
+
+        for text in [
+            "plain",
+            "[x]",
+            "- [x] ",
+            "- [?] odd",
+            "-- [x] x",
+            "a - [x] b",
+        ] {
+            assert_eq!(strip_checkbox(text), None, "{text}");
+        }
+    }
+
+    const TAGGED: &str = "TODO(*): a +work\nTODO(*): b\nTODO(*): c +work\nTODO(*): d\n\
+                          TODO(*): e +work\nTODO(*): f\n<--->\n";
+
+    fn cur_text(app: &TodoApp) -> &str {
+        app.todos.list[app.todos.cur].text.as_str()
+    }
+
+    #[test]
+    fn navigation_under_a_filter_skips_hidden_items() {
+        let mut app = load(TAGGED);
+        app.go_bottom();
+        assert_eq!(cur_text(&app), "f");
+
+        // The cursor settles on a matching item, then moves between matching ones only.
+        app.filter_tag("+work");
+        assert_eq!(cur_text(&app), "e +work");
+        app.go_top();
+        assert_eq!(cur_text(&app), "a +work");
+        app.go_down();
+        assert_eq!(cur_text(&app), "c +work");
+        app.go_down();
+        app.go_down();
+        assert_eq!(cur_text(&app), "e +work");
+        app.go_up();
+        assert_eq!(cur_text(&app), "c +work");
+        app.go_bottom();
+        assert_eq!(cur_text(&app), "e +work");
+        app.go_half();
+        assert_eq!(cur_text(&app), "c +work");
+
+        // Nothing matching leaves the cursor where it is.
+        app.filter_tag("+work");
+        app.filter_tag("+none");
+        let cur = app.todos.cur;
+        for go in [
+            TodoApp::go_top,
+            TodoApp::go_bottom,
+            TodoApp::go_up,
+            TodoApp::go_down,
+        ] {
+            go(&mut app);
+            assert_eq!(app.todos.cur, cur);
+        }
+    }
+
+    #[test]
+    fn batch_operations_under_a_filter_leave_hidden_items() {
+        let mut app = load(TAGGED);
+        app.filter_tag("+work");
+        assert_eq!(app.get_filtered_n(), (3, 0));
+        assert_eq!(
+            app.active_filter_summary().as_deref(),
+            Some("FILTER: +work | 3/6")
+        );
+
+        app.mark_all_shown();
+        assert!(app.message.contains("Marked 3 item(s)."));
+        let done: Vec<bool> = app
+            .todos
+            .list
+            .iter()
+            .map(|item| !item.is_active())
+            .collect();
+        assert_eq!(done, [true, false, true, false, true, false]);
+
+        // A selection from the first to the last shown item takes the shown ones only.
+        let mut app = load(TAGGED);
+        app.filter_tag("+work");
+        app.go_top();
+        app.start_selection();
+        app.go_bottom();
+        app.tag_selection("@now", false);
+        assert_eq!(
+            todo_texts(&app),
+            [
+                "a +work @now",
+                "b",
+                "c +work @now",
+                "d",
+                "e +work @now",
+                "f"
+            ]
+        );
+
+        // Only finished items transfer, the hidden ones stay although they are finished too.
+        app.mark_all_shown();
+        app.filter_tag("+work");
+        app.go_bottom();
+        app.mark_item();
+        app.filter_tag("+work");
+        app.go_top();
+        app.start_selection();
+        app.go_bottom();
+        app.transfer_selection();
+        assert_eq!(todo_texts(&app), ["b", "d", "f"]);
+        assert_eq!(app.dones.list.len(), 3);
+
+        // Saving writes the hidden items too.
+        let contents = app.contents();
+        assert!(
+            contents.starts_with("TODO(*): b\nTODO(*): d\nTODO(): f\n<--->\n"),
+            "{contents}"
+        );
+        assert_eq!(contents.matches("DONE(").count(), 3);
+    }
+
+    #[test]
+    fn delete_under_a_filter_leaves_hidden_items() {
+        let mut app = load(&format!(
+            "<--->\nDONE({date}): a +work\nDONE({date}): b\nDONE({date}): c +work\n",
+            date = Local::now().format(DATE_FMT)
+        ));
+        app.toggle_panel();
+        app.filter_tag("+work");
+        assert_eq!(app.get_filtered_n(), (0, 2));
+        app.go_top();
+        app.start_selection();
+        app.go_bottom();
+        app.delete_selection();
+        let texts: Vec<&str> = app
+            .dones
+            .list
+            .iter()
+            .map(|item| item.text.as_str())
+            .collect();
+        assert_eq!(texts, ["b"]);
+    }
+
+    fn picked(app: &TodoApp) -> Vec<usize> {
+        let mut picked = app.picked.clone();
+        picked.sort();
+        picked
+    }
+
+    #[test]
+    fn invert_selection_toggles_every_shown_root() {
+        // The roots of NESTED are a, b and c, the subtask b1 is never picked on its own.
+        let mut app = load(NESTED);
+        app.pick_item();
+        app.invert_selection();
+        assert_eq!(picked(&app), [1, 3]);
+        assert!(app
+            .message
+            .ends_with("2 item(s) picked, press 'p' to move them below the cursor."));
+        app.invert_selection();
+        assert_eq!(picked(&app), [0]);
+
+        // A pick hidden by the filter is kept as it is.
+        let mut app = load(TAGGED);
+        app.go_down();
+        app.pick_item();
+        app.filter_tag("+work");
+        app.invert_selection();
+        assert_eq!(picked(&app), [0, 1, 2, 4]);
+        app.invert_selection();
+        assert_eq!(picked(&app), [1]);
+
+        let mut app = load("<--->\n");
+        app.invert_selection();
+        assert!(app.picked.is_empty());
+        assert!(app.message.ends_with("Nothing to pick."));
+    }
+
+    #[test]
+    fn drag_at_the_ends_of_the_siblings() {
+        // Stopping at the ends, the list is left as it was.
+        let mut app = load(NESTED);
+        app.drag_up();
+        assert_eq!(todo_texts(&app), ["a", "b", "b1", "c"]);
+        assert!(!app.message.is_empty());
+        app.go_bottom();
+        app.drag_down();
+        assert_eq!(todo_texts(&app), ["a", "b", "b1", "c"]);
+
+        // Wrapping around, an item goes to the other end with its subtasks.
+        let mut app = load(NESTED);
+        app.set_drag_wrap(true);
+        app.drag_up();
+        assert_eq!(todo_texts(&app), ["b", "b1", "c", "a"]);
+        assert_eq!(cur_text(&app), "a");
+        app.drag_down();
+        assert_eq!(todo_texts(&app), ["a", "b", "b1", "c"]);
+        app.go_down();
+        app.drag_up();
+        assert_eq!(todo_texts(&app), ["b", "b1", "a", "c"]);
+        app.go_bottom();
+        app.drag_down();
+        assert_eq!(todo_texts(&app), ["c", "b", "b1", "a"]);
+        assert_eq!(app.todos.list[2].parent, Some(1));
+
+        // A subtask wraps within its parent, an only child has nowhere to go.
+        let mut app =
+            load("TODO(*): a\n  TODO(*): a1\n  TODO(*): a2\nTODO(*): b\n  TODO(*): b1\n<--->\n");
+        app.set_drag_wrap(true);
+        app.go_down();
+        app.drag_up();
+        assert_eq!(todo_texts(&app), ["a", "a2", "a1", "b", "b1"]);
+        app.drag_down();
+        assert_eq!(todo_texts(&app), ["a", "a1", "a2", "b", "b1"]);
+        app.go_bottom();
+        app.drag_down();
+        assert_eq!(todo_texts(&app), ["a", "a1", "a2", "b", "b1"]);
+        assert!(!app.message.is_empty());
+    }
+
+    const FINISHED: &str = "TODO(): a\n  TODO(): a1\nTODO(): b\nTODO(): c\n<--->\n\
+                            DONE(2026-01-02 10:00 +0000): x\n";
+
+    fn done_texts(app: &TodoApp) -> Vec<&str> {
+        app.dones
+            .list
+            .iter()
+            .map(|item| item.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn max_items_counts_the_subtasks_moved() {
+        let mut app = load(FINISHED);
+        app.set_max_items(2);
+        app.transfer_item();
+        assert!(app.message.contains("no room for 2 more items (1 items"));
+        assert_eq!(done_texts(&app), ["x"]);
+
+        app.message.clear();
+        app.set_max_items(3);
+        app.transfer_item();
+        assert_eq!(done_texts(&app), ["x", "a", "a1"]);
+        app.message.clear();
+        app.transfer_item();
+        assert!(app
+            .message
+            .contains("The DONE panel is full (3 items, max_items is 3)."));
+        assert_eq!(todo_texts(&app), ["b", "c"]);
+    }
+
+    #[test]
+    fn refused_transfer_keeps_the_selection() {
+        let mut app = load(FINISHED);
+        app.set_max_items(4);
+        assert!(app.select_all());
+        assert!(!app.transfer_selection());
+        assert!(app.anchor.is_some());
+        assert_eq!(done_texts(&app), ["x"]);
+
+        // Active items stay where they are, so they don't count.
+        app.set_max_items(5);
+        app.todos.list[3].act_cnt = 1;
+        assert!(app.transfer_selection());
+        assert!(app.anchor.is_none());
+        assert_eq!(done_texts(&app), ["x", "a", "a1", "b"]);
+        assert_eq!(todo_texts(&app), ["c"]);
+    }
+
+    #[test]
+    fn max_items_caps_adding_and_importing() {
+        let mut app = load(FINISHED);
+        app.set_max_items(5);
+        assert!(app.add_todo("d", InsertPosition::Bottom).is_ok());
+        let err = app.add_todo("e", InsertPosition::Bottom).unwrap_err();
+        assert!(err.starts_with("The TODO panel is full (5 items"), "{err}");
+
+        // An import is taken whole or not at all.
+        let mut app = load(FINISHED);
+        app.set_max_items(5);
+        let dones = "x 2026-03-02 one\nx 2026-03-02 two\nx 2026-03-02 three\n";
+        assert_eq!(app.import_todo_txt(dones), Ok((0, 3)));
+        let err = app
+            .import_todo_txt("new\nx 2026-03-02 four\nx 2026-03-02 five\n")
+            .unwrap_err();
+        assert!(
+            err.starts_with("The DONE panel has no room for 2 more items (4 items"),
+            "{err}"
+        );
+        let err = app.import_todo_txt("new\nnewer\n").unwrap_err();
+        assert!(
+            err.starts_with("The TODO panel has no room for 2 more items"),
+            "{err}"
+        );
+        assert_eq!(app.import_todo_txt("new\n"), Ok((1, 0)));
+        let err = app.import_todo_txt("newer\n").unwrap_err();
+        assert!(err.starts_with("The TODO panel is full (5 items"), "{err}");
+        assert_eq!(todo_texts(&app), ["a", "a1", "b", "c", "new"]);
+        assert_eq!(done_texts(&app).len(), 4);
+    }
+
+    #[test]
+    fn undo_many_steps_back_at_once() {
+        let mut app = load(NESTED);
+        let mut states = vec![app.contents()];
+        app.add_todo("d", InsertPosition::Bottom).unwrap();
+        states.push(app.contents());
+        app.go_top();
+        app.mark_item();
+        states.push(app.contents());
+        app.go_down();
+        app.go_down();
+        app.delete_item();
+        states.push(app.contents());
+        app.go_bottom();
+        app.mark_item();
+        states.push(app.contents());
+        assert_eq!(app.undo_depth(), 4);
+        assert!(states.windows(2).all(|pair| pair[0] != pair[1]));
+
+        app.clear_message();
+        app.undo_many(Some(2));
+        assert_eq!(app.contents(), states[2]);
+        assert_eq!(app.undo_depth(), 2);
+        assert!(
+            app.message.starts_with("Undo x2, the last: "),
+            "{}",
+            app.message
+        );
+
+        // One is the plain undo, all goes back to the list as loaded.
+        app.clear_message();
+        app.undo_many(Some(1));
+        assert_eq!(app.contents(), states[1]);
+        assert!(app.message.starts_with("Undo: "), "{}", app.message);
+        app.clear_message();
+        app.undo_many(None);
+        assert_eq!(app.contents(), states[0]);
+        assert_eq!(app.undo_depth(), 0);
+        app.clear_message();
+        app.undo_many(None);
+        assert_eq!(app.message, "Nothing to undo.");
+
+        // Every step can be redone.
+        for state in &states[1..] {
+            app.redo();
+            assert_eq!(&app.contents(), state);
+        }
+    }
+
+    #[test]
+    fn redo_survives_what_changes_nothing() {
+        let mut app = load(NESTED);
+        app.mark_item();
+        app.undo();
+        assert_eq!(app.redo_depth(), 1);
+
+        // Moving around and switching panels.
+        app.go_bottom();
+        app.toggle_panel();
+        app.toggle_panel();
+        app.go_top();
+        assert_eq!(app.redo_depth(), 1);
+
+        // Edits cancelled or left as they were.
+        let mut cur = app.edit_item(EditStart::End).unwrap();
+        app.edit_item_with(&mut cur, 'x' as i32);
+        app.cancel_edit();
+        app.edit_item(EditStart::End).unwrap();
+        assert!(app.finish_edit());
+        let mut cur = app.edit_item(EditStart::End).unwrap();
+        app.edit_item_with(&mut cur, 'x' as i32);
+        app.edit_item_with(&mut cur, constants::KEY_BACKSPACE);
+        assert!(app.finish_edit());
+        app.insert_item(InsertPosition::Bottom).unwrap();
+        app.cancel_edit();
+        assert_eq!(app.redo_depth(), 1);
+        assert_eq!(app.undo_depth(), 0);
+        assert_eq!(todo_texts(&app), ["a", "b", "b1", "c"]);
+
+        app.redo();
+        assert!(!app.todos.list[0].is_active());
+        app.undo();
+
+        // A real change ends it.
+        let mut cur = app.edit_item(EditStart::End).unwrap();
+        app.edit_item_with(&mut cur, 'x' as i32);
+        assert!(app.finish_edit());
+        assert_eq!(app.redo_depth(), 0);
+        assert_eq!(app.undo_depth(), 1);
+        assert_eq!(todo_texts(&app), ["ax", "b", "b1", "c"]);
+    }
+
+    #[test]
+    fn selection_never_spans_both_panels() {
+        let mut app = load(FINISHED);
+        app.start_selection();
+        app.go_down();
+        app.toggle_panel();
+        assert!(app.anchor.is_none());
+
+        // An undo going back to the TODO panel drops a selection made in DONE.
+        app.toggle_panel();
+        app.go_bottom();
+        app.transfer_item();
+        app.toggle_panel();
+        assert!(app.select_all());
+        app.undo();
+        assert_eq!(app.panel, Panel::Todo);
+        assert!(app.anchor.is_none());
+        app.clear_message();
+        assert!(app.transfer_selection());
+        assert_eq!(app.message, "Nothing to transfer.");
+        assert_eq!(todo_texts(&app), ["a", "a1", "b", "c"]);
+        assert_eq!(done_texts(&app), ["x"]);
+
+        // So do the lookup of the CLI and the picks.
+        app.toggle_panel();
+        app.pick_item();
+        assert!(app.find_todo("b"));
+        assert_eq!(app.panel, Panel::Todo);
+        assert!(app.picked.is_empty());
+
+        // A selection goes one way and comes back in one undo.
+        app.toggle_panel();
+        assert!(app.select_all());
+        assert!(app.transfer_selection());
+        assert_eq!(todo_texts(&app), ["a", "a1", "b", "c", "x"]);
+        assert!(app.dones.list.is_empty());
+        app.undo();
+        assert_eq!(todo_texts(&app), ["a", "a1", "b", "c"]);
+        assert_eq!(done_texts(&app), ["x"]);
+    }
+}
@@ -5,7 +5,13 @@ use std::rc::Rc;
 
 use ncurses::*;
 
-use super::utils::truncate;
+use super::backend::Backend;
+use super::config::CursorStyle;
+use super::text::{display_width, pad_to_width, sanitize_controls, skip_width, truncate_to_width};
+use crate::{
+    ADDED_PAIR, AGE_PAIR, BLOCKED_PAIR, HELP_PAIR, HIGHLIGHT_PAIR, REMOVED_PAIR, SELECTED_PAIR,
+    UI_PAIR, UNSELECTED_PAIR,
+};
 
 type LayoutRef = Rc<RefCell<Box<Layout>>>;
 
@@ -14,6 +20,52 @@ pub enum LayoutKind {
     Horz,
 }
 
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Style {
+    Selected,
+    Unselected,
+    Highlight,
+    Ui,
+    Help,
+    Blocked,
+    Added,
+    Removed,
+    Valid,
+    Invalid,
+    // A step of the age gradient, from 0 for the freshest.
+    Age(usize),
+}
+
+impl Style {
+    // Terminals without colors get the closest attribute instead of a color pair.
+    fn attr(self, colors: bool) -> attr_t {
+        match (self, colors) {
+            (Style::Selected, true) => COLOR_PAIR(SELECTED_PAIR),
+            (Style::Unselected, true) => COLOR_PAIR(UNSELECTED_PAIR),
+            (Style::Highlight, true) => COLOR_PAIR(HIGHLIGHT_PAIR),
+            (Style::Ui, true) => COLOR_PAIR(UI_PAIR),
+            (Style::Help, true) => COLOR_PAIR(HELP_PAIR),
+            (Style::Blocked, true) => COLOR_PAIR(BLOCKED_PAIR),
+            (Style::Added, true) => COLOR_PAIR(ADDED_PAIR),
+            (Style::Removed, true) => COLOR_PAIR(REMOVED_PAIR),
+            (Style::Valid, true) => COLOR_PAIR(ADDED_PAIR),
+            (Style::Invalid, true) => COLOR_PAIR(REMOVED_PAIR),
+            (Style::Age(step), true) => COLOR_PAIR(AGE_PAIR + step as i16),
+            (Style::Selected, false) => A_REVERSE(),
+            (Style::Unselected, false) => A_NORMAL(),
+            (Style::Highlight, false) => A_UNDERLINE(),
+            (Style::Ui, false) => A_BOLD(),
+            (Style::Help, false) => A_NORMAL(),
+            (Style::Blocked, false) => A_DIM(),
+            (Style::Added, false) => A_BOLD(),
+            (Style::Removed, false) => A_DIM(),
+            (Style::Valid, false) => A_NORMAL(),
+            (Style::Invalid, false) => A_UNDERLINE(),
+            (Style::Age(_), false) => A_NORMAL(),
+        }
+    }
+}
+
 #[derive(Default, Clone, Copy, Debug)]
 pub struct Vec2 {
     pub x: i32,
@@ -84,6 +136,8 @@ struct Layout {
     pos: Vec2,
     size: Vec2,
     max_size: Vec2,
+    border: bool,
+    parts: i32,
     children: Vec<LayoutRef>,
 }
 
@@ -94,10 +148,24 @@ impl Layout {
             pos,
             max_size,
             size: Vec2::default(),
+            border: false,
+            parts: 0,
             children: Vec::new(),
         }
     }
 
+    fn inset(&mut self) {
+        self.border = true;
+        self.pos = self.pos + Vec2::new(1, 1);
+        self.max_size = self.max_size - Vec2::new(2, 2);
+    }
+
+    fn outset(&mut self) {
+        self.pos = self.pos - Vec2::new(1, 1);
+        self.size = self.size + Vec2::new(2, 2);
+        self.max_size = self.max_size + Vec2::new(2, 2);
+    }
+
     fn available_pos(&self) -> Vec2 {
         let child_size = self.available_size().0;
 
@@ -111,7 +179,8 @@ impl Layout {
     }
 
     fn available_size(&self) -> (Vec2, Vec2) {
-        let div = self.children.len() as i32 + 1;
+        // With a known number of parts the first children don't take up the space of the later ones.
+        let div = max(self.children.len() as i32 + 1, self.parts);
         match self.kind {
             LayoutKind::Horz => self.max_size.div_rem(Vec2::new(div, 1)),
             LayoutKind::Vert => self.max_size.div_rem(Vec2::new(1, div)),
@@ -160,11 +229,41 @@ impl Layout {
 
 pub struct UI {
     stack: Vec<LayoutRef>,
+    backend: Box<dyn Backend>,
+    ellipsis: bool,
+    colors: bool,
+    // The character of hl().
+    rule: char,
+    // Where the terminal's cursor is left once the frame is drawn.
+    cursor: Option<(Vec2, CURSOR_VISIBILITY)>,
 }
 
 impl UI {
-    pub fn new() -> Self {
-        Self { stack: Vec::new() }
+    pub fn new(backend: Box<dyn Backend>) -> Self {
+        Self {
+            stack: Vec::new(),
+            backend,
+            ellipsis: false,
+            colors: true,
+            rule: '‾',
+            cursor: None,
+        }
+    }
+
+    pub fn set_ellipsis(&mut self, ellipsis: bool) {
+        self.ellipsis = ellipsis;
+    }
+
+    pub fn set_colors(&mut self, colors: bool) {
+        self.colors = colors;
+    }
+
+    pub fn set_rule(&mut self, rule: char) {
+        self.rule = rule;
+    }
+
+    pub fn backend(&mut self) -> &mut dyn Backend {
+        self.backend.as_mut()
     }
 
     pub fn begin(&mut self, pos: Vec2, kind: LayoutKind, max_size: Vec2) {
@@ -172,6 +271,7 @@ impl UI {
 
         let root = Box::new(Layout::new(kind, pos, max_size));
         self.stack.push(Rc::new(RefCell::new(root)));
+        self.cursor = None;
     }
 
     pub fn begin_layout(&mut self, kind: LayoutKind) {
@@ -189,6 +289,54 @@ impl UI {
         self.stack.push(Rc::new(RefCell::new(child)));
     }
 
+    pub fn begin_split_layout(&mut self, kind: LayoutKind, parts: i32) {
+        self.begin_layout(kind);
+        self.stack
+            .last()
+            .expect("Can't create a layout outside of UI::begin() and UI::end()")
+            .borrow_mut()
+            .parts = parts;
+    }
+
+    pub fn begin_bordered_layout(&mut self, kind: LayoutKind) {
+        self.begin_layout(kind);
+        self.stack
+            .last()
+            .expect("Can't create a layout outside of UI::begin() and UI::end()")
+            .borrow_mut()
+            .inset();
+    }
+
+    pub fn border(&mut self, pos: Vec2, size: Vec2) {
+        if size.x < 2 || size.y < 2 {
+            return;
+        }
+
+        let line = "─".repeat(size.x as usize - 2);
+        let bottom = pos.y + size.y - 1;
+
+        self.backend.put(pos, &format!("┌{line}┐"));
+        for y in pos.y + 1..bottom {
+            self.backend.put(Vec2::new(pos.x, y), "│");
+            self.backend.put(Vec2::new(pos.x + size.x - 1, y), "│");
+        }
+        self.backend
+            .put(Vec2::new(pos.x, bottom), &format!("└{line}┘"));
+    }
+
+    // Rows left until the bottom of the screen (or of the bordered layout) below the last widget.
+    pub fn remaining_height(&self) -> i32 {
+        let root = self
+            .stack
+            .first()
+            .expect("Tried to measure outside of UI::begin() and UI::end()")
+            .borrow();
+        let layout = self.stack.last().unwrap().borrow();
+        let border = if layout.border { 1 } else { 0 };
+
+        (root.pos.y + root.max_size.y - layout.available_pos().y - border).max(0)
+    }
+
     pub fn br(&mut self) {
         let layout = self
             .stack
@@ -204,64 +352,149 @@ impl UI {
             .last()
             .expect("Tried to render horizontal line outside of any layout");
 
-        let text = "‾".repeat(layout.borrow().max_size.x as usize); //‾
+        let text = self
+            .rule
+            .to_string()
+            .repeat(layout.borrow().max_size.x as usize);
         self.label(&text);
     }
 
+    // Leaves the (hidden) terminal cursor at the start of the next widget, e.g. the
+    // selected row, once the frame is drawn.
+    pub fn cursor_here(&mut self) {
+        let layout = self
+            .stack
+            .last()
+            .expect("Tried to place the cursor outside of any layout");
+        let pos = layout.borrow().available_pos();
+        self.cursor = Some((pos, CURSOR_VISIBILITY::CURSOR_INVISIBLE));
+    }
+
     pub fn label(&mut self, text: &str) {
+        self.label_split(text, "");
+    }
+
+    // A label with `right` at the right edge of the layout, `left` is cut short to leave
+    // it room. A `right` that doesn't fit with a column to spare is left out.
+    pub fn label_split(&mut self, left: &str, right: &str) {
         let layout = self
             .stack
             .last()
             .expect("Tried to render label outside of any layout");
         let pos = layout.borrow().available_pos();
 
-        let space_fill =
-            " ".repeat((layout.borrow().max_size.x as usize).saturating_sub(text.len()));
-        let text = truncate(text, layout.borrow().max_size.x as usize);
+        let width = layout.borrow().max_size.x as usize;
+        let right = sanitize_controls(right);
+        let right_width = display_width(&right);
+        let (text, len) = if right.is_empty() || right_width >= width {
+            let text = truncate_to_width(&sanitize_controls(left), width, self.ellipsis);
+            let len = display_width(&text);
+            (pad_to_width(&text, width), len)
+        } else {
+            let room = width - right_width - 1;
+            let text = truncate_to_width(&sanitize_controls(left), room, self.ellipsis);
+            (format!("{} {right}", pad_to_width(&text, room)), width)
+        };
 
-        mv(pos.y, pos.x);
-        addstr(&format!("{text}{space_fill}"));
+        self.backend.put(pos, &text);
 
-        layout
-            .borrow_mut()
-            .add_widget(Vec2::new(text.len() as i32, 1));
+        layout.borrow_mut().add_widget(Vec2::new(len as i32, 1));
     }
 
-    pub fn label_styled(&mut self, text: &str, color_pair: i16, style: Option<u32>) {
+    pub fn label_styled(&mut self, text: &str, color: Style, style: Option<u32>) {
+        self.label_split_styled(text, "", color, style);
+    }
+
+    pub fn label_split_styled(
+        &mut self,
+        left: &str,
+        right: &str,
+        color: Style,
+        style: Option<u32>,
+    ) {
+        let color = color.attr(self.colors);
         if let Some(s) = style {
-            attr_on(s);
+            self.backend.attr_on(s);
         }
-        attr_on(COLOR_PAIR(color_pair));
-        self.label(text);
-        attr_off(COLOR_PAIR(color_pair));
+        self.backend.attr_on(color);
+        self.label_split(left, right);
+        self.backend.attr_off(color);
         if let Some(s) = style {
-            attr_off(s);
+            self.backend.attr_off(s);
         }
     }
 
-    pub fn edit_label(&mut self, text: &String, cur: usize, prefix: String) {
+    pub fn edit_label(
+        &mut self,
+        text: &str,
+        cur: usize,
+        prefix: String,
+        cursor_style: CursorStyle,
+        cursor_blink: bool,
+    ) {
+        self.edit_label_styled(text, cur, prefix, None, cursor_style, cursor_blink);
+    }
+
+    // An edit label with the prefix drawn in `prefix_style`, e.g. a prompt showing
+    // whether the input so far is valid.
+    pub fn edit_label_styled(
+        &mut self,
+        text: &str,
+        cur: usize,
+        prefix: String,
+        prefix_style: Option<Style>,
+        cursor_style: CursorStyle,
+        cursor_blink: bool,
+    ) {
         let layout = self
             .stack
             .last_mut()
             .expect("Tried to render edit mode outside of any layout");
         let pos = layout.borrow().available_pos();
-        let space_fill =
-            " ".repeat((layout.borrow().max_size.x as usize).saturating_sub(text.len()));
+        let width = layout.borrow().max_size.x as usize;
+
+        // Text that doesn't fit is scrolled horizontally to keep the cursor in view.
+        let text = sanitize_controls(text);
+        let prefix_width = display_width(&prefix);
+        let avail = width.saturating_sub(prefix_width).max(1);
+        let cur_col = display_width(&text.chars().take(cur).collect::<String>());
+        let (rest, start) = skip_width(&text, (cur_col + 1).saturating_sub(avail));
+        let visible = truncate_to_width(rest, avail, false);
 
         // Buffer
         {
-            mv(pos.y, pos.x);
-            addstr(&format!("{prefix}{text}{space_fill}"));
+            let attr = prefix_style.map(|style| style.attr(self.colors));
+            if let Some(attr) = attr {
+                self.backend.attr_on(attr);
+            }
+            self.backend.put(pos, &prefix);
+            if let Some(attr) = attr {
+                self.backend.attr_off(attr);
+            }
+            self.backend.put(
+                pos + Vec2::new(prefix_width as i32, 0),
+                &pad_to_width(&visible, avail),
+            );
             layout
                 .borrow_mut()
-                .add_widget(Vec2::new(text.len() as i32, 1));
+                .add_widget(Vec2::new(display_width(&visible) as i32, 1));
         }
-        // Cursor
+        // Cursor, the terminal's own one or drawn over the character under it.
         {
-            mv(pos.y, pos.x + cur as i32 + prefix.len() as i32);
-            attr_on(A_REVERSE());
-            addstr(text.get(cur..=cur).unwrap_or(" "));
-            attr_off(A_REVERSE());
+            let pos = pos + Vec2::new((cur_col - start + prefix_width) as i32, 0);
+            match cursor_attr(cursor_style, cursor_blink) {
+                None if cursor_blink => {
+                    self.cursor = Some((pos, CURSOR_VISIBILITY::CURSOR_VERY_VISIBLE))
+                }
+                None => self.cursor = Some((pos, CURSOR_VISIBILITY::CURSOR_VISIBLE)),
+                Some(attr) => {
+                    let under = text.chars().nth(cur).unwrap_or(' ');
+                    self.backend.attr_on(attr);
+                    self.backend.put(pos, &under.to_string());
+                    self.backend.attr_off(attr);
+                    self.cursor = Some((pos, CURSOR_VISIBILITY::CURSOR_INVISIBLE));
+                }
+            }
         }
     }
 
@@ -270,6 +503,9 @@ impl UI {
             .stack
             .pop()
             .expect("Can't end a non-existing layout. Was there UI::begin_layout()?");
+        if child.borrow().border {
+            child.borrow_mut().outset();
+        }
         let size_diff = self
             .stack
             .last()
@@ -282,16 +518,87 @@ impl UI {
                 let pos = child.borrow().available_pos();
                 let space_fill = " ".repeat(child.borrow().max_size.x as usize);
                 for i in 0..y {
-                    mv(pos.y + i, pos.x);
-                    addstr(&space_fill.to_string());
+                    self.backend
+                        .put(pos + Vec2::new(0, i), &space_fill.to_string());
                 }
             }
         }
     }
 
     pub fn end(&mut self) {
-        self.stack
+        let root = self
+            .stack
             .pop()
             .expect("Can't end a non-existing UI. Was there UI::begin()?");
+        // Borders are drawn last, once the sizes of all layouts are settled
+        self.draw_borders(&root);
+
+        let (pos, visibility) = self
+            .cursor
+            .take()
+            .unwrap_or((Vec2::default(), CURSOR_VISIBILITY::CURSOR_INVISIBLE));
+        self.backend.set_cursor(pos, visibility);
+    }
+
+    fn draw_borders(&mut self, layout: &LayoutRef) {
+        let layout = layout.borrow();
+        if layout.border {
+            self.border(layout.pos, Vec2::new(layout.max_size.x, layout.size.y));
+        }
+        for child in &layout.children {
+            self.draw_borders(child);
+        }
+    }
+}
+
+// The attribute a drawn cursor is shown with, None for the terminal's own cursor.
+fn cursor_attr(style: CursorStyle, blink: bool) -> Option<attr_t> {
+    let attr = match style {
+        CursorStyle::Terminal => return None,
+        CursorStyle::Reverse => A_REVERSE(),
+        CursorStyle::Underline => A_UNDERLINE(),
+        CursorStyle::Block => A_STANDOUT() | A_BOLD(),
+    };
+
+    if blink {
+        Some(attr | A_BLINK())
+    } else {
+        Some(attr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn styles_map_to_pairs_with_colors() {
+        assert_eq!(Style::Selected.attr(true), COLOR_PAIR(SELECTED_PAIR));
+        assert_eq!(Style::Unselected.attr(true), COLOR_PAIR(UNSELECTED_PAIR));
+        assert_eq!(Style::Highlight.attr(true), COLOR_PAIR(HIGHLIGHT_PAIR));
+        assert_eq!(Style::Ui.attr(true), COLOR_PAIR(UI_PAIR));
+        assert_eq!(Style::Help.attr(true), COLOR_PAIR(HELP_PAIR));
+        assert_eq!(Style::Blocked.attr(true), COLOR_PAIR(BLOCKED_PAIR));
+        assert_eq!(Style::Valid.attr(true), Style::Added.attr(true));
+        assert_eq!(Style::Invalid.attr(true), Style::Removed.attr(true));
+        assert_eq!(Style::Age(2).attr(true), COLOR_PAIR(AGE_PAIR + 2));
+    }
+
+    #[test]
+    fn styles_map_to_attributes_without_colors() {
+        assert_eq!(Style::Selected.attr(false), A_REVERSE());
+        assert_eq!(Style::Unselected.attr(false), A_NORMAL());
+        assert_eq!(Style::Highlight.attr(false), A_UNDERLINE());
+        assert_eq!(Style::Ui.attr(false), A_BOLD());
+        assert_eq!(Style::Help.attr(false), A_NORMAL());
+        assert_eq!(Style::Blocked.attr(false), A_DIM());
+        assert_eq!(Style::Added.attr(false), A_BOLD());
+        assert_eq!(Style::Removed.attr(false), A_DIM());
+        assert_eq!(Style::Invalid.attr(false), A_UNDERLINE());
+        assert_eq!(Style::Age(2).attr(false), A_NORMAL());
+
+        // Without a color pair the selected item still differs from the others.
+        assert_ne!(Style::Selected.attr(false), Style::Unselected.attr(false));
+        assert_ne!(Style::Selected.attr(false), Style::Highlight.attr(false));
     }
 }
@@ -1,11 +1,19 @@
-use std::env::args;
-use std::process::exit;
+use std::env::{self, args};
+use std::fs::{self, read_dir};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command as Process, Stdio};
 use std::sync::atomic::{AtomicBool, Ordering};
 
+use chrono::Duration;
 use ncurses::*;
 
+use super::color::{self, Rgb};
+use super::org::Format;
+use super::todo::{Snapshot, Sort};
 use crate::{
-    FILE_PATH, FPS, HELP, HELP_PAIR, HIGHLIGHT_PAIR, SELECTED_PAIR, UI_PAIR, UNSELECTED_PAIR, USAGE,
+    ADDED_PAIR, AGE_PAIR, AGE_STEPS, BLOCKED_PAIR, FILE_PATH, FPS, HELP_PAIR, HIGHLIGHT_PAIR,
+    REMOVED_PAIR, SELECTED_PAIR, UI_PAIR, UNSELECTED_PAIR,
 };
 
 static CTRLC: AtomicBool = AtomicBool::new(false);
@@ -14,10 +22,13 @@ extern "C" fn callback(_signum: i32) {
     CTRLC.store(true, Ordering::Relaxed);
 }
 
+// SIGTERM and SIGHUP quit the same way as CTRL+c, so the list is saved and the lock released.
 pub fn sig_handler_init() {
-    unsafe {
-        if libc::signal(libc::SIGINT, callback as libc::sighandler_t) == libc::SIG_ERR {
-            unreachable!()
+    for signum in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        unsafe {
+            if libc::signal(signum, callback as *const () as libc::sighandler_t) == libc::SIG_ERR {
+                unreachable!()
+            }
         }
     }
 }
@@ -26,7 +37,8 @@ pub fn ctrlc_poll() -> bool {
     CTRLC.swap(false, Ordering::Relaxed)
 }
 
-pub fn ncurses_init() {
+// Returns whether the terminal can show the color pairs used by the UI.
+pub fn ncurses_init(age: Option<(Rgb, Rgb)>) -> bool {
     setlocale(LcCategory::all, "");
     // Init ncurses
     initscr();
@@ -39,42 +51,582 @@ pub fn ncurses_init() {
     timeout(1000 / FPS);
     set_escdelay(0);
     // Set colors
-    use_default_colors();
+    if !has_colors() {
+        return false;
+    }
     start_color();
+    if COLOR_PAIRS() <= REMOVED_PAIR as i32 {
+        return false;
+    }
+    use_default_colors();
     init_pair(HIGHLIGHT_PAIR, COLOR_BLACK, COLOR_GREEN);
     init_pair(SELECTED_PAIR, COLOR_BLACK, COLOR_CYAN);
     init_pair(UNSELECTED_PAIR, COLOR_BLACK, COLOR_WHITE);
     init_pair(UI_PAIR, COLOR_WHITE, COLOR_BLACK);
     init_pair(HELP_PAIR, COLOR_WHITE, COLOR_BLACK);
+    init_pair(BLOCKED_PAIR, COLOR_WHITE, -1);
+    init_pair(ADDED_PAIR, COLOR_GREEN, -1);
+    init_pair(REMOVED_PAIR, COLOR_RED, -1);
+    // With fewer than 256 colors the steps between are too far off, so the range is
+    // split in two: the fresh color first, then the stale one.
+    if let Some((fresh, stale)) =
+        age.filter(|_| COLOR_PAIRS() >= (AGE_PAIR as usize + AGE_STEPS) as i32)
+    {
+        let steps = color::gradient(fresh, stale, AGE_STEPS);
+        for (i, &rgb) in steps.iter().enumerate() {
+            let rgb = if COLORS() >= 256 {
+                rgb
+            } else if i < AGE_STEPS / 2 {
+                fresh
+            } else {
+                stale
+            };
+            init_pair(AGE_PAIR + i as i16, color::nearest(rgb, COLORS()), -1);
+        }
+    }
+    true
+}
+
+// Exit codes of the commands and of the app itself, listed in the help message.
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ExitCode {
+    Success = 0,
+    NotFound = 1,
+    Usage = 2,
+    Io = 3,
+    Check = 4,
+    Parse = 5,
+    Locked = 6,
+}
+
+pub enum Command {
+    Add(String),
+    Done(String),
+    List,
+    ListDones {
+        sort: Option<Sort>,
+        reverse: bool,
+        limit: Option<usize>,
+    },
+    Migrate,
+    Import(String),
+    // The file of a `task export`, None for stdin.
+    ImportTaskwarrior(Option<String>),
+    ExportTxt,
+    ExportPlain,
+    Stats,
+    Doctor,
+    Config(ConfigCommand),
+}
+
+#[derive(Clone, Copy)]
+pub enum ConfigCommand {
+    Init,
+    Path,
+    Check,
 }
 
-pub fn get_args() -> String {
+impl Command {
+    // Whether the command saves the file, and so needs to lock it.
+    pub fn writes(&self) -> bool {
+        matches!(
+            self,
+            Command::Add(_)
+                | Command::Done(_)
+                | Command::Migrate
+                | Command::Import(_)
+                | Command::ImportTaskwarrior(_)
+        )
+    }
+}
+
+pub enum ArgsError {
+    Help,
+    Version,
+    Usage(String),
+}
+
+pub struct Args {
+    pub file_paths: Vec<String>,
+    pub print_on_exit: Option<Snapshot>,
+    pub script: Option<String>,
+    pub command: Option<Command>,
+    pub quiet: bool,
+    pub migrate: bool,
+    pub read_only: bool,
+    pub no_state: bool,
+    pub no_auto_archive: bool,
+    // The format of the files given, instead of the one of their extension.
+    pub format: Option<Format>,
+    // The items of `--add`, in order.
+    pub adds: Vec<String>,
+    pub dry_run: bool,
+    pub log: bool,
+    pub width: Option<usize>,
+}
+
+pub fn get_args() -> Result<Args, ArgsError> {
     let mut args = args().skip(1);
+    let mut parsed = Args {
+        file_paths: Vec::new(),
+        print_on_exit: None,
+        script: None,
+        command: None,
+        quiet: false,
+        migrate: false,
+        read_only: false,
+        no_state: false,
+        no_auto_archive: false,
+        format: None,
+        adds: Vec::new(),
+        dry_run: false,
+        log: false,
+        width: None,
+    };
+    let (mut done, mut sort, mut reverse, mut limit) = (false, None, false, None);
 
-    match args.next() {
-        Some(arg) => match arg.as_str() {
-            "-f" | "--file" => args.next().unwrap_or_else(|| {
-                eprintln!("[ERROR]: No file given for '{arg}'.");
-                eprintln!("{USAGE}");
-                exit(1);
-            }),
-            "-h" | "--help" => {
-                println!("{HELP}\n{USAGE}");
-                exit(0);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" | "--file" => {
+                let path = args
+                    .next()
+                    .ok_or(ArgsError::Usage(format!("No file given for '{arg}'.")))?;
+                parsed.file_paths.push(path);
+            }
+            "-h" | "--help" => return Err(ArgsError::Help),
+            "-v" | "--version" => return Err(ArgsError::Version),
+            "-q" | "--quiet" => parsed.quiet = true,
+            "--migrate" => parsed.migrate = true,
+            "--read-only" => parsed.read_only = true,
+            "--no-state" => parsed.no_state = true,
+            "--no-auto-archive" => parsed.no_auto_archive = true,
+            "--format" => {
+                let format = args
+                    .next()
+                    .ok_or(ArgsError::Usage(format!("No format given for '{arg}'.")))?;
+                parsed.format = Some(Format::from_str(&format).ok_or(ArgsError::Usage(
+                    format!("Unknown file format: '{format}', expected todo|org."),
+                ))?);
+            }
+            "--add" => {
+                let text = args
+                    .next()
+                    .filter(|text| !text.trim().is_empty())
+                    .ok_or(ArgsError::Usage(format!("No text given for '{arg}'.")))?;
+                parsed.adds.push(text);
+            }
+            "--export-txt" if parsed.command.is_none() => parsed.command = Some(Command::ExportTxt),
+            "--dry-run" => parsed.dry_run = true,
+            "--log" => parsed.log = true,
+            "--done" => done = true,
+            "--reverse" => reverse = true,
+            "--sort" => {
+                let kind = args
+                    .next()
+                    .ok_or(ArgsError::Usage(format!("No order given for '{arg}'.")))?;
+                sort = Some(Sort::from_str(&kind).ok_or(ArgsError::Usage(format!(
+                    "Unknown sort order: '{kind}', expected date|alpha|recent|pri|due."
+                )))?);
+            }
+            "--limit" => {
+                let n = args
+                    .next()
+                    .ok_or(ArgsError::Usage(format!("No number given for '{arg}'.")))?;
+                limit = match n.parse() {
+                    Ok(n) => Some(n),
+                    _ => return Err(ArgsError::Usage(format!("Invalid limit: '{n}'."))),
+                };
+            }
+            "--keys" => {
+                parsed.script = Some(
+                    args.next()
+                        .ok_or(ArgsError::Usage(format!("No script given for '{arg}'.")))?,
+                )
+            }
+            "--width" => {
+                let width = args
+                    .next()
+                    .ok_or(ArgsError::Usage(format!("No width given for '{arg}'.")))?;
+                parsed.width = match width.parse() {
+                    Ok(width) if width > 0 => Some(width),
+                    _ => return Err(ArgsError::Usage(format!("Invalid width: '{width}'."))),
+                };
             }
-            _ => {
-                eprintln!("[ERROR]: Unknown argument: '{arg}'.");
-                eprintln!("{USAGE}");
-                exit(1);
+            "--print-on-exit" => parsed.print_on_exit = Some(Snapshot::Todos),
+            "add" | "done" | "list" | "migrate" | "import" | "export" | "stats" | "doctor"
+            | "config"
+                if parsed.command.is_none() =>
+            {
+                parsed.command = Some(match arg.as_str() {
+                    "add" => Command::Add(args.by_ref().collect::<Vec<String>>().join(" ")),
+                    "done" => Command::Done(
+                        args.next()
+                            .ok_or(ArgsError::Usage("No item given for 'done'.".to_string()))?,
+                    ),
+                    "migrate" => Command::Migrate,
+                    "stats" => Command::Stats,
+                    "doctor" => Command::Doctor,
+                    "config" => Command::Config(match args.next().as_deref() {
+                        Some("init") => ConfigCommand::Init,
+                        Some("path") => ConfigCommand::Path,
+                        Some("check") => ConfigCommand::Check,
+                        _ => {
+                            return Err(ArgsError::Usage(
+                                "'config' needs a subcommand: 'init', 'path' or 'check'."
+                                    .to_string(),
+                            ))
+                        }
+                    }),
+                    "export" => match args.next().as_deref() {
+                        Some("--plain") => Command::ExportPlain,
+                        _ => {
+                            return Err(ArgsError::Usage(
+                                "'export' needs a format: '--plain'.".to_string(),
+                            ))
+                        }
+                    },
+                    "import" => match args.next() {
+                        Some(flag) if flag == "--taskwarrior" => {
+                            Command::ImportTaskwarrior(args.next().filter(|path| path != "-"))
+                        }
+                        Some(path) => Command::Import(path),
+                        None => {
+                            return Err(ArgsError::Usage("No file given for 'import'.".to_string()))
+                        }
+                    },
+                    _ => Command::List,
+                })
             }
-        },
-        None => FILE_PATH.to_string(),
+            _ => match arg.strip_prefix("--print-on-exit=") {
+                Some(kind) => {
+                    parsed.print_on_exit = Some(Snapshot::from_str(kind).ok_or(
+                        ArgsError::Usage(format!("Unknown snapshot kind: '{kind}'.")),
+                    )?)
+                }
+                None => return Err(ArgsError::Usage(format!("Unknown argument: '{arg}'."))),
+            },
+        }
+    }
+
+    if let Some(Command::Add(text)) = &parsed.command {
+        if text.trim().is_empty() {
+            return Err(ArgsError::Usage("No text given for 'add'.".to_string()));
+        }
+    }
+
+    if !parsed.adds.is_empty() && parsed.command.is_some() {
+        return Err(ArgsError::Usage(
+            "'--add' opens the app, use 'add' with a command.".to_string(),
+        ));
+    }
+
+    if parsed.dry_run && !matches!(parsed.command, Some(Command::Migrate)) {
+        return Err(ArgsError::Usage(
+            "'--dry-run' only applies to 'migrate'.".to_string(),
+        ));
+    }
+
+    if done || sort.is_some() || reverse || limit.is_some() {
+        if !matches!(parsed.command, Some(Command::List)) {
+            return Err(ArgsError::Usage(
+                "'--done', '--sort', '--reverse' and '--limit' only apply to 'list'.".to_string(),
+            ));
+        }
+        if !done {
+            return Err(ArgsError::Usage(
+                "'--sort', '--reverse' and '--limit' need '--done'.".to_string(),
+            ));
+        }
+        parsed.command = Some(Command::ListDones {
+            sort,
+            reverse,
+            limit,
+        });
+    }
+
+    if parsed.log && !matches!(parsed.command, Some(Command::Stats)) {
+        return Err(ArgsError::Usage(
+            "'--log' only applies to 'stats'.".to_string(),
+        ));
+    }
+
+    if parsed.width.is_some() && !matches!(parsed.command, Some(Command::ExportPlain)) {
+        return Err(ArgsError::Usage(
+            "'--width' only applies to 'export --plain'.".to_string(),
+        ));
     }
+
+    if parsed.file_paths.is_empty() {
+        parsed.file_paths.push(FILE_PATH.to_string());
+    }
+
+    Ok(parsed)
 }
 
-pub fn truncate(s: &str, max_chars: usize) -> &str {
-    match s.char_indices().nth(max_chars) {
-        None => s,
-        Some((idx, _)) => &s[..idx],
+// The files in a directory a todo list can be kept in (`.list` as the default `TODO.list`,
+// `.todo`, `.md`, `.txt`, `.org` or no extension), sorted, the hidden ones left out.
+pub fn todo_files(dir: &str) -> Result<Vec<String>, String> {
+    let entries =
+        read_dir(dir).map_err(|err| format!("Could not read directory '{dir}': {err}"))?;
+    let mut paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file())
+        .filter(|path| {
+            !path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with('.'))
+        })
+        .filter(|path| {
+            path.extension().is_none_or(|ext| {
+                ["list", "todo", "md", "txt", "org"]
+                    .iter()
+                    .any(|e| ext == *e)
+            })
+        })
+        .filter_map(|path| path.to_str().map(String::from))
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+// The file of a directory picked last, kept in `<dir>/.todors.state` by name.
+pub fn last_picked(dir: &str) -> Option<String> {
+    let state = fs::read_to_string(Path::new(dir).join(".todors.state")).ok()?;
+    state
+        .lines()
+        .find_map(|line| line.strip_prefix("file = "))
+        .map(String::from)
+}
+
+pub fn save_picked(dir: &str, file_path: &str) -> io::Result<()> {
+    let name = Path::new(file_path)
+        .file_name()
+        .map_or(file_path.into(), |name| name.to_string_lossy());
+    fs::write(
+        Path::new(dir).join(".todors.state"),
+        format!("file = {name}\n"),
+    )
+}
+
+// Checks that the file can be saved: its directory exists, or with `create` the nearest
+// existing ancestor is a writable directory the missing ones can be created in.
+pub fn check_dir(file_path: &str, create: bool) -> Result<(), String> {
+    let dir = match Path::new(file_path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => return Ok(()),
+    };
+    if dir.is_dir() {
+        return Ok(());
+    }
+    if !create {
+        return Err(format!(
+            "Directory '{}' does not exist. Set 'create_dirs = true' to create it on save.",
+            dir.display()
+        ));
     }
+
+    let ancestor = dir
+        .ancestors()
+        .find(|path| path.as_os_str().is_empty() || path.exists())
+        .unwrap();
+    let ancestor = if ancestor.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        ancestor
+    };
+    let writable = ancestor.is_dir()
+        && ancestor.to_str().is_some_and(|path| {
+            let path = std::ffi::CString::new(path).unwrap_or_default();
+            unsafe { libc::access(path.as_ptr(), libc::W_OK | libc::X_OK) == 0 }
+        });
+    if !writable {
+        return Err(format!(
+            "Directory '{}' can't be created in '{}'.",
+            dir.display(),
+            ancestor.display()
+        ));
+    }
+    Ok(())
+}
+
+// Compact form of a duration, e.g. "6h", "1h30m" or "45m".
+pub fn format_duration(duration: Duration) -> String {
+    let (hours, minutes) = (duration.num_hours(), duration.num_minutes() % 60);
+    match (hours, minutes) {
+        (0, m) => format!("{m}m"),
+        (h, 0) => format!("{h}h"),
+        (h, m) => format!("{h}h{m}m"),
+    }
+}
+
+// Whether the app can be drawn: both stdin and stdout are a terminal.
+pub fn interactive() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) != 0 && libc::isatty(libc::STDOUT_FILENO) != 0 }
+}
+
+// Width of the terminal stdout is attached to, if it is one.
+pub fn terminal_width() -> Option<usize> {
+    unsafe {
+        if libc::isatty(libc::STDOUT_FILENO) == 0 {
+            return None;
+        }
+        let mut size: libc::winsize = std::mem::zeroed();
+        if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) != 0 || size.ws_col == 0 {
+            return None;
+        }
+        Some(size.ws_col as usize)
+    }
+}
+
+// Prints `text` through $PAGER (`less -FRX` when unset) if stdout is a terminal, directly
+// otherwise or when the pager can't be started. Quitting the pager (or the reader of a
+// pipe) early is not an error.
+pub fn page(text: &str) {
+    let is_tty = unsafe { libc::isatty(libc::STDOUT_FILENO) } != 0;
+    let pager = env::var("PAGER").unwrap_or_else(|_| "less -FRX".to_string());
+    let mut words = pager.split_whitespace();
+
+    if let (true, Some(program)) = (is_tty, words.next()) {
+        if let Ok(mut child) = Process::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            let _ = child.wait();
+            return;
+        }
+    }
+
+    let mut stdout = io::stdout().lock();
+    match stdout
+        .write_all(text.as_bytes())
+        .and_then(|()| stdout.flush())
+    {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+            eprintln!("[ERROR]: Could not write the output: {err}");
+        }
+        _ => {}
+    }
+}
+
+pub enum LockError {
+    Locked(i32),
+    Io(io::Error),
+}
+
+// A `<file>.lock` sidecar holding the pid of the instance that has the file open, removed
+// when dropped. A lock left by a process that no longer runs is taken over. The missing
+// directories of the file are created for the lock, check_dir() tells beforehand whether
+// they may be.
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    pub fn acquire(file_path: &str) -> Result<Self, LockError> {
+        let path = PathBuf::from(format!("{file_path}.lock"));
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(LockError::Io)?;
+        }
+
+        // The pid is written to a file of this instance first and linked in place, so the
+        // lock never shows up empty to another instance checking whether it's stale.
+        let own = PathBuf::from(format!("{file_path}.lock.{}", std::process::id()));
+        fs::write(&own, format!("{}\n", std::process::id())).map_err(LockError::Io)?;
+        let result = Self::link(&own, &path);
+        let _ = fs::remove_file(&own);
+        result.map(|()| Self { path })
+    }
+
+    fn link(own: &Path, path: &Path) -> Result<(), LockError> {
+        for _ in 0..3 {
+            match fs::hard_link(own, path) {
+                Ok(()) => return Ok(()),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    let stale = fs::read_to_string(path).unwrap_or_default();
+                    if let Some(pid) = running_pid(&stale) {
+                        return Err(LockError::Locked(pid));
+                    }
+                    Self::take_over(path, &stale)?;
+                }
+                // Without hard links the pid is written once the lock is created.
+                Err(_) => {
+                    let mut file = fs::OpenOptions::new()
+                        .write(true)
+                        .create_new(true)
+                        .open(path)
+                        .map_err(LockError::Io)?;
+                    return file
+                        .write_all(format!("{}\n", std::process::id()).as_bytes())
+                        .map_err(LockError::Io);
+                }
+            }
+        }
+        Err(LockError::Io(io::Error::other(
+            "the lock keeps being taken",
+        )))
+    }
+
+    // Moves a stale lock out of the way. Renaming is atomic, so of the instances that found
+    // the same stale lock only one moves it. Another one may by then move the lock the first
+    // one linked in its place: it sees that what it moved isn't what it read and puts it back.
+    fn take_over(path: &Path, stale: &str) -> Result<(), LockError> {
+        let aside = PathBuf::from(format!("{}.{}.stale", path.display(), std::process::id()));
+        match fs::rename(path, &aside) {
+            Ok(()) => {
+                if fs::read_to_string(&aside).unwrap_or_default() != stale {
+                    let _ = fs::hard_link(&aside, path);
+                }
+                let _ = fs::remove_file(&aside);
+                Ok(())
+            }
+            // Moved by another instance first.
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(LockError::Io(err)),
+        }
+    }
+
+    // The pid of the running instance holding the lock of the file, if any.
+    pub fn holder(file_path: &str) -> Option<i32> {
+        running_pid(&fs::read_to_string(format!("{file_path}.lock")).ok()?)
+    }
+}
+
+fn running_pid(lock: &str) -> Option<i32> {
+    lock.trim()
+        .parse::<i32>()
+        .ok()
+        .filter(|&pid| pid > 0 && is_running(pid))
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Whether the locale the terminal is set up with (LC_ALL, LC_CTYPE or LANG, the first one
+// set) uses UTF-8, which the borders, markers and arrows need.
+pub fn utf8_locale() -> bool {
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()))
+        .is_some_and(|locale| {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        })
+}
+
+// Whether UTF-8 can be drawn: the locale uses it and ncursesw is linked.
+pub fn utf8_output() -> bool {
+    cfg!(feature = "wide") && utf8_locale()
+}
+
+fn is_running(pid: i32) -> bool {
+    let alive = unsafe { libc::kill(pid, 0) } == 0;
+    alive || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
 }
@@ -0,0 +1,89 @@
+// Auto-archive on startup: which DONE items are old enough to leave for `<file>.archive`.
+mod common;
+
+use chrono::{Duration, Local};
+use common::{stderr, Scratch};
+
+fn done(days_ago: i64, text: &str) -> String {
+    let date = Local::now() - Duration::days(days_ago);
+    format!("DONE({}): {text}\n", date.format("%Y-%m-%d %H:%M %z"))
+}
+
+#[test]
+fn boundary_day() {
+    let scratch = Scratch::new("archive-boundary");
+    scratch.config("auto_archive_days = 30\n");
+    let kept = format!("{}{}", done(29, "recent"), done(30, "thirty"));
+    let old = format!("{}  {}", done(31, "old"), done(31, "old sub").trim_start());
+    scratch.write(
+        "list.todo",
+        &format!("TODO(*): open\n<--->\n{kept}{old}{}", done(400, "older")),
+    );
+
+    // 30 days ago is still kept, a day more goes with its subtasks. The archive is a list
+    // of its own.
+    let frame = scratch.keys("list.todo", "", &[]);
+    assert!(
+        frame.message().starts_with("Archived 2 item(s) to '"),
+        "{}",
+        frame.message()
+    );
+    assert_eq!(
+        scratch.read("list.todo"),
+        format!("TODO(*): open\n<--->\n{kept}")
+    );
+    assert_eq!(
+        scratch.read("list.todo.archive"),
+        format!("<--->\n{old}{}", done(400, "older"))
+    );
+
+    // Nothing is left to move the next time, 0 and the flag turn it off.
+    scratch.keys("list.todo", "", &[]);
+    assert_eq!(
+        scratch.read("list.todo.archive").matches("older").count(),
+        1
+    );
+    scratch.config("auto_archive_days = 0\n");
+    let list = format!("<--->\n{}", done(100, "old"));
+    scratch.write("other.todo", &list);
+    scratch.keys("other.todo", "", &[]);
+    scratch.config("auto_archive_days = 1\n");
+    scratch.keys("other.todo", "", &["--no-auto-archive"]);
+    assert_eq!(scratch.read("other.todo"), list);
+    assert!(!scratch.dir.join("other.todo.archive").exists());
+}
+
+#[test]
+fn unparseable_dates_are_never_archived() {
+    let scratch = Scratch::new("archive-unparseable");
+    scratch.config("auto_archive_days = 1\n");
+
+    for date in ["garbage", "2020-13-45 10:00 +0000", ""] {
+        let list = format!("<--->\nDONE({date}): x\n{}", done(100, "old"));
+        scratch.write("list.todo", &list);
+        let frame = scratch.keys("list.todo", "", &[]);
+        assert_eq!(frame.code, Some(5), "{date}");
+        assert!(
+            frame.stderr.contains("list.todo:2: invalid date format"),
+            "{}",
+            frame.stderr
+        );
+        assert_eq!(scratch.read("list.todo"), list);
+        assert!(!scratch.dir.join("list.todo.archive").exists());
+    }
+
+    // A date of an older version is upgraded by the migration first, it doesn't move
+    // before the file is migrated.
+    let list = format!("<--->\nDONE(2020-01-02): legacy\n{}", done(100, "old"));
+    scratch.write("list.todo", &list);
+    let output = scratch.run(&[
+        "-q",
+        "--no-state",
+        "-f",
+        &scratch.path("list.todo"),
+        "--keys",
+        "n",
+    ]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(!scratch.dir.join("list.todo.archive").exists());
+}
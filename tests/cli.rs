@@ -0,0 +1,172 @@
+// The commands run without the UI, pinned on the exit codes listed in `--help` and on what
+// reaches stdout and stderr.
+mod common;
+
+use common::{stderr, stdout, Scratch};
+
+const LIST: &str = "TODO(*): one\nTODO(*): two\n<--->\n";
+
+fn code(output: &std::process::Output) -> Option<i32> {
+    output.status.code()
+}
+
+#[test]
+fn success_is_0() {
+    let scratch = Scratch::new("cli-success");
+    let path = scratch.write("list.todo", LIST);
+
+    let output = scratch.run(&["-f", &path, "add", "three"]);
+    assert_eq!(code(&output), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "[INFO]: Added 'three'.\n");
+
+    let output = scratch.run(&["-f", &path, "done", "two"]);
+    assert_eq!(code(&output), Some(0), "{}", stderr(&output));
+
+    let output = scratch.run(&["-f", &path, "list"]);
+    assert_eq!(code(&output), Some(0), "{}", stderr(&output));
+    assert_eq!(stdout(&output), "- [ ] one\n- [ ] three\n");
+}
+
+#[test]
+fn not_found_or_empty_is_1() {
+    let scratch = Scratch::new("cli-not-found");
+    let path = scratch.write("list.todo", LIST);
+
+    let output = scratch.run(&["-f", &path, "done", "nothing like it"]);
+    assert_eq!(code(&output), Some(1));
+    assert!(stderr(&output).contains("No TODO item matching"));
+    assert_eq!(scratch.read("list.todo"), LIST);
+
+    let output = scratch.run(&["-f", &path, "done", "3"]);
+    assert_eq!(code(&output), Some(1));
+
+    let empty = scratch.write("empty.todo", "<--->\n");
+    let output = scratch.run(&["-f", &empty, "list"]);
+    assert_eq!(code(&output), Some(1));
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn usage_error_is_2() {
+    let scratch = Scratch::new("cli-usage");
+    let path = scratch.write("list.todo", LIST);
+
+    for args in [
+        &["bogus"][..],
+        &["add"],
+        &["done"],
+        &["--format", "xml", "list"],
+    ] {
+        let output = scratch.run(&[&["-f", path.as_str()][..], args].concat());
+        assert_eq!(code(&output), Some(2), "{args:?}");
+        assert!(stderr(&output).contains("[ERROR]"), "{args:?}");
+        assert_eq!(stdout(&output), "", "{args:?}");
+    }
+    assert_eq!(scratch.read("list.todo"), LIST);
+}
+
+#[test]
+fn io_error_is_3() {
+    let scratch = Scratch::new("cli-io");
+    let path = scratch.write("list.todo", LIST);
+
+    // A path through a regular file can't be created.
+    let output = scratch.run(&["-f", &format!("{path}/inner.todo"), "add", "x"]);
+    assert_eq!(code(&output), Some(3));
+    assert!(stderr(&output).contains("[ERROR]"));
+}
+
+#[test]
+fn parse_error_is_5() {
+    let scratch = Scratch::new("cli-parse");
+    let path = scratch.write("list.todo", "garbage\n");
+
+    let output = scratch.run(&["-f", &path, "list"]);
+    assert_eq!(code(&output), Some(5));
+    assert!(stderr(&output).contains("invalid format"));
+    assert_eq!(scratch.read("list.todo"), "garbage\n");
+}
+
+#[test]
+fn quiet_keeps_only_the_errors() {
+    let scratch = Scratch::new("cli-quiet");
+    let path = scratch.write("list.todo", LIST);
+
+    let output = scratch.run(&["-q", "-f", &path, "add", "three"]);
+    assert_eq!(code(&output), Some(0));
+    assert_eq!(stdout(&output), "");
+    assert_eq!(stderr(&output), "");
+
+    let output = scratch.run(&["--quiet", "-f", &path, "done", "missing"]);
+    assert_eq!(code(&output), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(stderr(&output).contains("No TODO item matching 'missing'."));
+
+    // The list is what was asked for, not a message.
+    let output = scratch.run(&["-q", "-f", &path, "list"]);
+    assert_eq!(stdout(&output), "- [ ] one\n- [ ] two\n- [ ] three\n");
+}
+
+#[test]
+fn todo_txt_import_export_keeps_the_dates() {
+    let scratch = Scratch::new("cli-todo-txt");
+    let path = scratch.write("list.todo", "<--->\n");
+    let lines = "(A) Call Mom +Family @phone\nx Water plants\nx 2026-03-02 2026-03-01 Pay rent\n";
+    let txt = scratch.write("todo.txt", lines);
+
+    let output = scratch.run(&["-q", "-f", &path, "import", &txt]);
+    assert_eq!(code(&output), Some(0), "{}", stderr(&output));
+    let output = scratch.run(&["-f", &path, "--export-txt"]);
+    assert_eq!(stdout(&output), lines);
+}
+
+#[test]
+fn bad_content_format_falls_back() {
+    let scratch = Scratch::new("cli-content-format");
+    scratch.write("list.todo", LIST);
+    scratch.config("hint_bar = false\ncontent_format = {bogus} left\n");
+
+    // The app starts with the default summary, the line is named in a warning.
+    let frame = scratch.keys("list.todo", "", &[]);
+    assert_eq!(frame.code, Some(0), "{}", frame.stderr);
+    let config = scratch.dir.join("config");
+    assert_eq!(
+        frame.stderr,
+        format!(
+            "[WARNING]: {}:2: invalid content_format '{{bogus}} left', unknown field '{{bogus}}', expected {{todos}}|{{dones}}|{{total}}|{{percent}}, using the default\n",
+            config.display()
+        )
+    );
+    assert!(
+        frame.has("[CONTENT]: (2)todos and (0)dones"),
+        "{}",
+        frame.text
+    );
+
+    let output = scratch.run(&["config", "check"]);
+    assert_eq!(code(&output), Some(0));
+    assert!(stderr(&output).contains(":2: invalid content_format"));
+    assert!(stdout(&output).contains("content_format = ({todos})todos and ({dones})dones\n"));
+}
+
+#[test]
+fn autosave_defaults_to_30s() {
+    let scratch = Scratch::new("cli-autosave");
+    scratch.config("hint_bar = false\n");
+
+    let output = scratch.run(&["config", "check"]);
+    assert_eq!(code(&output), Some(0), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("autosave = 30s\n"),
+        "{}",
+        stdout(&output)
+    );
+
+    scratch.config("autosave = off\n");
+    let output = scratch.run(&["config", "check"]);
+    assert!(
+        stdout(&output).contains("autosave = off\n"),
+        "{}",
+        stdout(&output)
+    );
+}
@@ -0,0 +1,117 @@
+// Runs the todors binary on files in a scratch directory of its own. `--keys` feeds the
+// keys through the Grid backend and prints the last frame, which is what the tests read
+// along with the files. The config comes from the scratch directory, never the user's.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static SCRATCH_N: AtomicUsize = AtomicUsize::new(0);
+
+pub struct Scratch {
+    pub dir: PathBuf,
+}
+
+impl Scratch {
+    pub fn new(name: &str) -> Self {
+        let n = SCRATCH_N.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("todors-{name}-{}-{n}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("config"), "").unwrap();
+        Self { dir }
+    }
+
+    pub fn path(&self, file: &str) -> String {
+        self.dir.join(file).to_str().unwrap().to_string()
+    }
+
+    pub fn write(&self, file: &str, contents: &str) -> String {
+        let path = self.path(file);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    pub fn read(&self, file: &str) -> String {
+        fs::read_to_string(self.path(file)).unwrap()
+    }
+
+    pub fn config(&self, contents: &str) {
+        fs::write(self.dir.join("config"), contents).unwrap();
+    }
+
+    // The binary with the scratch config, run in the scratch directory.
+    pub fn command(&self) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_todors"));
+        command
+            .current_dir(&self.dir)
+            .env("TODORS_CONFIG", self.dir.join("config"))
+            .env("HOME", &self.dir)
+            .env_remove("XDG_CONFIG_HOME")
+            .env("PAGER", "cat")
+            .stdin(Stdio::null());
+        command
+    }
+
+    pub fn run(&self, args: &[&str]) -> Output {
+        self.command().args(args).output().unwrap()
+    }
+
+    // Feeds `keys` to the app on `file` and gives the last frame, quietly and without
+    // the state file.
+    pub fn keys(&self, file: &str, keys: &str, args: &[&str]) -> Frame {
+        self.session(file, keys, &[&["--no-state"][..], args].concat())
+    }
+
+    // Like keys(), but the state of the last session is restored and kept.
+    pub fn session(&self, file: &str, keys: &str, args: &[&str]) -> Frame {
+        let mut command = self.command();
+        command.args(["-q", "-f", &self.path(file), "--keys", keys]);
+        let output = command.args(args).output().unwrap();
+        Frame {
+            text: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+            code: output.status.code(),
+        }
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+pub struct Frame {
+    pub text: String,
+    pub stderr: String,
+    pub code: Option<i32>,
+}
+
+impl Frame {
+    pub fn lines(&self) -> Vec<&str> {
+        self.text.lines().collect()
+    }
+
+    // The text after `[MESSAGE]: ` on the second line, up to the file name.
+    pub fn message(&self) -> String {
+        let line = self.lines().get(1).copied().unwrap_or("");
+        let message = line.strip_prefix("[MESSAGE]: ").unwrap_or(line);
+        message.split("[FILE]").next().unwrap().trim().to_string()
+    }
+
+    pub fn has(&self, text: &str) -> bool {
+        self.text.contains(text)
+    }
+}
+
+// Output as text, for the CLI commands.
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+pub fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
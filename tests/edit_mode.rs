@@ -0,0 +1,149 @@
+// In edit mode only Enter and Esc leave the editor, every other key is editing input
+// even when Normal mode binds it to an action.
+mod common;
+
+use common::Scratch;
+
+const LIST: &str = "TODO(*): first\nTODO(*): second\n<--->\n";
+
+fn first_line(scratch: &Scratch) -> String {
+    scratch
+        .read("list.todo")
+        .lines()
+        .next()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn action_keys_are_typed() {
+    let scratch = Scratch::new("edit-action-keys");
+    scratch.write("list.todo", LIST);
+
+    // d deletes, q quits, u undoes, J drags and : starts a command in Normal mode.
+    scratch.keys("list.todo", "r dquJ:<Enter>", &[]);
+    let saved = scratch.read("list.todo");
+    assert_eq!(saved, "TODO(*): first dquJ:\nTODO(*): second\n<--->\n");
+}
+
+#[test]
+fn enter_saves_without_transferring() {
+    let scratch = Scratch::new("edit-enter");
+    scratch.write("list.todo", LIST);
+
+    scratch.keys("list.todo", " r!<Enter>", &[]);
+    assert_eq!(
+        scratch.read("list.todo"),
+        "TODO(): first!\nTODO(*): second\n<--->\n"
+    );
+}
+
+#[test]
+fn esc_cancels() {
+    let scratch = Scratch::new("edit-esc");
+    scratch.write("list.todo", LIST);
+
+    scratch.keys("list.todo", "rxyz<Esc>", &[]);
+    assert_eq!(scratch.read("list.todo"), LIST);
+
+    // A new item cancelled before it got any text is dropped.
+    scratch.keys("list.todo", "inew<Esc>", &[]);
+    assert_eq!(scratch.read("list.todo"), LIST);
+}
+
+#[test]
+fn tab_is_ignored() {
+    let scratch = Scratch::new("edit-tab");
+    scratch.write("list.todo", LIST);
+
+    // Tab would switch panels in Normal mode, and a tab in the text would break the file.
+    let frame = scratch.keys("list.todo", "r<Tab>x<Enter>", &[]);
+    assert_eq!(first_line(&scratch), "TODO(*): firstx");
+    assert!(frame.has("[TODO]"));
+}
+
+#[test]
+fn arrows_move_within_the_text() {
+    let scratch = Scratch::new("edit-arrows");
+    scratch.write("list.todo", LIST);
+
+    // Left and right move the cursor, up and down have nowhere to go in one line.
+    scratch.keys("list.todo", "r<Left><Left>X<Up><Down>Y<Enter>", &[]);
+    assert_eq!(first_line(&scratch), "TODO(*): firXYst");
+
+    scratch.keys("list.todo", "r<Home>^<End>$<Enter>", &[]);
+    assert_eq!(first_line(&scratch), "TODO(*): ^firXYst$");
+}
+
+#[test]
+fn sigint_mid_edit() {
+    let scratch = Scratch::new("edit-sigint");
+
+    // A started edit is finished, the keys after the signal are never read.
+    scratch.write("list.todo", LIST);
+    scratch.keys("list.todo", "r more<C-c>ignored", &[]);
+    assert_eq!(
+        scratch.read("list.todo"),
+        "TODO(*): first more\nTODO(*): second\n<--->\n"
+    );
+
+    // A new item is kept with what was typed of it, or dropped when nothing was.
+    scratch.keys("list.todo", "Gihalf typed<C-c>", &[]);
+    let saved = scratch.read("list.todo");
+    assert!(saved.contains("\nTODO(*): half typed created:"), "{saved}");
+    scratch.write("list.todo", LIST);
+    scratch.keys("list.todo", "i<C-c>", &[]);
+    assert_eq!(scratch.read("list.todo"), LIST);
+
+    // An edit that emptied the item gives the old text back.
+    scratch.keys("list.todo", "r<BS><BS><BS><BS><BS><C-c>", &[]);
+    assert_eq!(scratch.read("list.todo"), LIST);
+}
+
+#[test]
+fn sigint_reports_the_save() {
+    let scratch = Scratch::new("edit-sigint-status");
+    let path = scratch.write("list.todo", LIST);
+
+    let output = scratch
+        .command()
+        .args(["--no-state", "-f", &path, "--keys", "r!<C-c>"])
+        .output()
+        .unwrap();
+    let stdout = common::stdout(&output);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(
+        stdout.contains(&format!("[INFO]: Saved (2)todos and (0)dones to '{path}'.")),
+        "{stdout}"
+    );
+    assert_eq!(first_line(&scratch), "TODO(*): first!");
+}
+
+#[cfg(feature = "wide")]
+#[test]
+fn multibyte_text() {
+    let scratch = Scratch::new("edit-multibyte");
+    scratch.write("list.todo", "TODO(*): café\nTODO(*): 日本\n<--->\n");
+
+    // The cursor moves over whole characters, wide ones are typed as they are.
+    scratch.keys("list.todo", "r<BS>e<Left><Left>ß<End> 🎉<Enter>", &[]);
+    assert_eq!(first_line(&scratch), "TODO(*): caßfe 🎉");
+
+    let frame = scratch.keys("list.todo", "jr<Home><Del>東京<Right>", &[]);
+    assert!(frame.has("東京本"), "{}", frame.text);
+}
+
+#[test]
+fn ascii_edit_of_multibyte_text() {
+    let scratch = Scratch::new("edit-multibyte-ascii");
+    scratch.write("list.todo", "TODO(*): café\nTODO(*): 日本\n<--->\n");
+
+    // Also without ncursesw, the cursor steps over whole characters.
+    scratch.keys("list.todo", "r<BS>e<Left><Left><Del>x<Enter>", &[]);
+    assert_eq!(first_line(&scratch), "TODO(*): caxe");
+    scratch.keys("list.todo", "jr<Home><Right>-<Enter>", &[]);
+    assert_eq!(
+        scratch.read("list.todo"),
+        "TODO(*): caxe\nTODO(*): 日-本\n<--->\n"
+    );
+}
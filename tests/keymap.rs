@@ -0,0 +1,74 @@
+// The chords of the config: bound, rebound and unbound, and the lines that are refused.
+mod common;
+
+use common::{stderr, stdout, Scratch};
+
+const LIST: &str = "TODO(*): first\nTODO(*): second\nTODO(*): third\n<--->\n";
+
+#[test]
+fn chords_from_the_config() {
+    let scratch = Scratch::new("keymap-chords");
+    scratch.write("list.todo", LIST);
+    scratch.config("\"z z\" = go_bottom\n\"g e\" = \"go_top\"\n\"g g\" = none\n");
+
+    // z z is a new chord, g e goes the other way now and g g does nothing.
+    scratch.keys("list.todo", "zzr!<Enter>", &[]);
+    assert!(scratch.read("list.todo").contains("TODO(*): third!\n"));
+    scratch.keys("list.todo", "Gger?<Enter>", &[]);
+    assert!(scratch.read("list.todo").starts_with("TODO(*): first?\n"));
+    scratch.keys("list.todo", "Gggr#<Enter>", &[]);
+    assert!(scratch.read("list.todo").contains("TODO(*): third!#\n"));
+
+    let output = scratch.run(&["config", "check"]);
+    let settings = stdout(&output);
+    for line in [
+        "\"g e\" = go_top\n",
+        "\"z z\" = go_bottom\n",
+        "\"g u\" = lowercase\n",
+    ] {
+        assert!(settings.contains(line), "{settings}");
+    }
+    assert!(!settings.contains("\"g g\""), "{settings}");
+}
+
+#[test]
+fn invalid_chords_are_refused() {
+    let scratch = Scratch::new("keymap-invalid");
+    let path = scratch.write("list.todo", LIST);
+
+    for (line, error) in [
+        (
+            "\"g g\" = nowhere",
+            "unknown action 'nowhere' for the chord 'g g'",
+        ),
+        ("\"gg\" = go_top", "invalid chord 'gg'"),
+        ("\"g g g\" = go_top", "invalid chord 'g g g'"),
+        ("\"d d\" = go_top", "'d' is bound to delete on its own"),
+    ] {
+        scratch.config(&format!("{line}\n"));
+        let output = scratch.run(&["-f", &path, "list"]);
+        assert_eq!(output.status.code(), Some(5), "{line}");
+        assert!(
+            stderr(&output).contains(error),
+            "{line}: {}",
+            stderr(&output)
+        );
+    }
+}
+
+#[test]
+fn template_lists_the_default_chords() {
+    let scratch = Scratch::new("keymap-template");
+    std::fs::remove_file(scratch.dir.join("config")).unwrap();
+
+    let output = scratch.run(&["config", "init"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let template = scratch.read("config");
+    assert!(template.contains("\n# \"g g\" = go_top\n"), "{template}");
+
+    // Uncommented, the chords read back.
+    let uncommented = template.replace("# \"", "\"");
+    scratch.config(&uncommented);
+    let output = scratch.run(&["config", "check"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+}
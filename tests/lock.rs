@@ -0,0 +1,126 @@
+// The `<file>.lock` sidecar: held by a running instance, taken over when stale, and never
+// left behind, not even when the file doesn't load.
+mod common;
+
+use std::fs;
+
+use common::{stderr, Scratch};
+
+const LIST: &str = "TODO(*): one\n<--->\n";
+
+fn names(scratch: &Scratch) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(&scratch.dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[test]
+fn running_holder_keeps_the_lock() {
+    let scratch = Scratch::new("lock-held");
+    let path = scratch.write("list.todo", LIST);
+    // The test itself is the running instance holding it.
+    scratch.write("list.todo.lock", &format!("{}\n", std::process::id()));
+
+    let output = scratch.run(&["-f", &path, "add", "two"]);
+    assert_eq!(output.status.code(), Some(6));
+    assert!(
+        stderr(&output).contains("--read-only"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(scratch.read("list.todo"), LIST);
+    assert_eq!(
+        scratch.read("list.todo.lock"),
+        format!("{}\n", std::process::id())
+    );
+}
+
+#[test]
+fn stale_lock_is_taken_over() {
+    let scratch = Scratch::new("lock-stale");
+    let path = scratch.write("list.todo", LIST);
+
+    // A pid that can't be running, and an unreadable lock.
+    for stale in ["2147483647\n", "garbage"] {
+        scratch.write("list.todo.lock", stale);
+        let output = scratch.run(&["-q", "-f", &path, "add", "two"]);
+        assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+        assert_eq!(names(&scratch), ["config", "list.todo"]);
+    }
+    assert_eq!(scratch.read("list.todo").matches("two").count(), 2);
+}
+
+#[test]
+fn parse_error_releases_the_lock() {
+    let scratch = Scratch::new("lock-parse-error");
+    let good = scratch.write("good.todo", LIST);
+    let bad = scratch.write("list.todo", "TODO(*): one\n<--->\n<--->\n");
+
+    // The lock of the file opened before the bad one goes too.
+    let output = scratch.run(&["-f", &good, "-f", &bad, "--keys", "q"]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(
+        stderr(&output).contains("list.todo:3: invalid separator"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(names(&scratch), ["config", "good.todo", "list.todo"]);
+}
+
+#[test]
+fn open_of_a_bad_file_is_a_message() {
+    let scratch = Scratch::new("lock-open-error");
+    scratch.write("list.todo", LIST);
+    let bad = scratch.write("bad.todo", "TODO(*): one\n<--->\n<--->\n");
+
+    let frame = scratch.keys("list.todo", &format!(":open {bad}<Enter>"), &[]);
+    assert_eq!(frame.code, Some(0), "{}", frame.stderr);
+    assert!(
+        frame.message().starts_with("Can't open '"),
+        "{}",
+        frame.message()
+    );
+    assert_eq!(names(&scratch), ["bad.todo", "config", "list.todo"]);
+}
+
+#[test]
+fn stale_lock_has_one_taker() {
+    let scratch = Scratch::new("lock-stale-race");
+    let path = scratch.write("list.todo", "<--->\n");
+    scratch.write("list.todo.lock", "2147483647\n");
+
+    // Instances that saw the same stale lock: the ones let in hold it one at a time, so
+    // none of their adds is lost, the others are told it's locked.
+    let children: Vec<_> = (0..8)
+        .map(|i| {
+            scratch
+                .command()
+                .args(["-q", "-f", &path, "add", &format!("item-{i}")])
+                .spawn()
+                .unwrap()
+        })
+        .collect();
+    let codes: Vec<Option<i32>> = children
+        .into_iter()
+        .map(|child| child.wait_with_output().unwrap().status.code())
+        .collect();
+    assert!(
+        codes.iter().all(|&code| matches!(code, Some(0 | 6))),
+        "{codes:?}"
+    );
+    let added = codes.iter().filter(|&&code| code == Some(0)).count();
+    assert!(added > 0);
+    assert_eq!(scratch.read("list.todo").matches("item-").count(), added);
+
+    // At most a lock left stale by one of them, never a moved or half-taken one.
+    let names = names(&scratch);
+    assert!(
+        names
+            .iter()
+            .all(|name| ["config", "list.todo", "list.todo.lock"].contains(&name.as_str())),
+        "{names:?}"
+    );
+}
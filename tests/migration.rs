@@ -0,0 +1,91 @@
+// Files in an old format: the question asked on load, and what each answer saves.
+mod common;
+
+use std::fs;
+
+use common::Scratch;
+
+const OLD: &str = "TODO( ): old\nTODO(*): a\n<--->\nDONE(2026-01-02): d\n";
+
+#[test]
+fn declined_keeps_the_old_lines() {
+    let scratch = Scratch::new("migrate-declined");
+    scratch.write("list.todo", OLD);
+
+    // The changes made after declining are saved, the old lines are written as they were.
+    scratch.keys("list.todo", "nJ", &[]);
+    assert_eq!(
+        scratch.read("list.todo"),
+        "TODO(*): a\nTODO( ): old\n<--->\nDONE(2026-01-02): d\n"
+    );
+    assert!(!scratch.dir.join("list.todo.pre-migrate").exists());
+
+    // Esc declines too. An edited line is written in the current format.
+    scratch.write("list.todo", OLD);
+    scratch.keys("list.todo", "<Esc>r!<Enter>", &[]);
+    assert_eq!(
+        scratch.read("list.todo"),
+        "TODO(): old!\nTODO(*): a\n<--->\nDONE(2026-01-02): d\n"
+    );
+}
+
+#[test]
+fn accepted_upgrades_the_file() {
+    let scratch = Scratch::new("migrate-accepted");
+    scratch.write("list.todo", OLD);
+
+    scratch.keys("list.todo", "y", &[]);
+    let saved = scratch.read("list.todo");
+    assert!(saved.starts_with("TODO(): old\nTODO(*): a\n<--->\nDONE(2026-01-02 00:00 "));
+    assert_eq!(scratch.read("list.todo.pre-migrate"), OLD);
+}
+
+#[test]
+fn other_keys_wait_for_an_answer() {
+    let scratch = Scratch::new("migrate-other-keys");
+    scratch.write("list.todo", OLD);
+
+    // Neither d nor J does anything while the question is asked, nor does q quit.
+    let frame = scratch.keys("list.todo", "dJq", &[]);
+    assert!(
+        frame.message().starts_with("Loaded '"),
+        "{}",
+        frame.message()
+    );
+    assert!(frame.has("y:yes  n:no"));
+    assert_eq!(scratch.read("list.todo"), OLD);
+
+    scratch.keys("list.todo", "dJqnJ", &[]);
+    assert_eq!(
+        scratch.read("list.todo"),
+        "TODO(*): a\nTODO( ): old\n<--->\nDONE(2026-01-02): d\n"
+    );
+}
+
+#[test]
+fn save_leaves_no_temporary_file() {
+    let scratch = Scratch::new("migrate-atomic");
+    scratch.write("list.todo", "TODO(*): a\nTODO(*): b\n<--->\n");
+
+    scratch.keys("list.todo", "J", &[]);
+    assert_eq!(scratch.read("list.todo"), "TODO(*): b\nTODO(*): a\n<--->\n");
+    let mut names: Vec<String> = fs::read_dir(&scratch.dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    names.sort();
+    assert_eq!(names, ["config", "list.todo"]);
+}
+
+#[test]
+fn unanswered_still_prints_on_exit() {
+    let scratch = Scratch::new("migrate-print");
+    let path = scratch.write("list.todo", OLD);
+
+    // Quitting before the question is answered saves nothing, the list is printed anyway.
+    let output = scratch.run(&["-f", &path, "--keys", "", "--print-on-exit"]);
+    let stdout = common::stdout(&output);
+    assert!(stdout.contains("- [x] old\n- [ ] a\n"), "{stdout}");
+    assert!(stdout.contains("it uses an old format"), "{stdout}");
+    assert_eq!(scratch.read("list.todo"), OLD);
+}
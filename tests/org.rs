@@ -0,0 +1,81 @@
+// Org-mode files are written back as they were read, the parts the app doesn't change too.
+mod common;
+
+use common::{stderr, Scratch};
+
+const HOME: &str = "\
+#+TITLE: Home
+#+STARTUP: overview
+
+* DONE Renew passport
+  CLOSED: [2026-09-01 Tue 10:00]
+  Took the photos at the mall.
+* TODO Fix the bike :errand:
+  DEADLINE: <2026-10-20 Tue>
+** TODO Buy a tube
+** DONE Find the pump
+   CLOSED: [2026-10-01 Thu 18:30]
+* Notes
+Some free text under a plain heading.
+* DONE Call the bank
+  CLOSED: [2026-10-02 Fri 09:15]
+* TODO Plan the trip
+  SCHEDULED: <2026-11-01 Sun>
+  - flights
+  - hotel
+";
+
+#[test]
+fn unchanged_file_is_saved_as_it_was() {
+    let scratch = Scratch::new("org-round-trip");
+    let path = scratch.write("home.org", HOME);
+
+    // A DONE heading first stays first.
+    scratch.keys("home.org", "q", &[]);
+    assert_eq!(scratch.read("home.org"), HOME);
+    let output = scratch.run(&["-q", "-f", &path, "add", "Water the plants"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    let saved = scratch.read("home.org");
+    assert!(saved.starts_with(HOME), "{saved}");
+    assert!(
+        saved[HOME.len()..].starts_with("* TODO Water the plants"),
+        "{saved}"
+    );
+}
+
+#[test]
+fn changed_items_keep_their_place() {
+    let scratch = Scratch::new("org-in-place");
+    scratch.write("home.org", HOME);
+
+    // Done and moved to the DONE panel, the heading is still where it was.
+    scratch.keys("home.org", "G <Enter>", &[]);
+    let saved = scratch.read("home.org");
+    let before = HOME.split_once("* TODO Plan the trip\n").unwrap().0;
+    assert!(saved.starts_with(before), "{saved}");
+    let rest = &saved[before.len()..];
+    assert!(
+        rest.starts_with("* DONE Plan the trip\n  CLOSED: ["),
+        "{saved}"
+    );
+    assert!(
+        rest.ends_with("] SCHEDULED: <2026-11-01 Sun>\n  - flights\n  - hotel\n"),
+        "{saved}"
+    );
+
+    // Back in the TODO panel it's at the end of the open ones, and where it was after an
+    // undo.
+    scratch.write("home.org", HOME);
+    scratch.keys("home.org", "<Tab><Enter>", &[]);
+    let saved = scratch.read("home.org");
+    let renew = "* DONE Renew passport\n  CLOSED: [2026-09-01 Tue 10:00]\n";
+    assert_eq!(saved.lines().count(), HOME.lines().count());
+    assert!(!saved.contains(renew), "{saved}");
+    assert!(
+        saved.contains("  - hotel\n* DONE Renew passport\n  CLOSED: ["),
+        "{saved}"
+    );
+    scratch.write("home.org", HOME);
+    scratch.keys("home.org", "<Tab><Enter>u", &[]);
+    assert_eq!(scratch.read("home.org"), HOME);
+}
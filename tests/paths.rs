@@ -0,0 +1,83 @@
+// Files in directories that don't exist yet, created or refused depending on `create_dirs`,
+// and the files a directory given to `-f` offers.
+mod common;
+
+use common::{stderr, Scratch};
+
+#[test]
+fn nested_missing_directories_are_created() {
+    let scratch = Scratch::new("paths-created");
+    let path = scratch.path("a/b/c/list.todo");
+
+    let output = scratch.run(&["-q", "-f", &path, "add", "first"]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(scratch
+        .read("a/b/c/list.todo")
+        .starts_with("TODO(*): first"));
+    assert!(!scratch.dir.join("a/b/c/list.todo.lock").exists());
+
+    // The same from the app, saved on exit.
+    scratch.keys("x/y/list.todo", "inew<Enter>", &[]);
+    assert!(scratch.read("x/y/list.todo").starts_with("TODO(*): new"));
+}
+
+#[test]
+fn nested_missing_directories_are_refused() {
+    let scratch = Scratch::new("paths-refused");
+    scratch.config("create_dirs = false\n");
+    let path = scratch.path("a/b/list.todo");
+
+    let output = scratch.run(&["-f", &path, "add", "first"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("does not exist. Set 'create_dirs = true'"));
+    assert!(!scratch.dir.join("a").exists());
+
+    let frame = scratch.keys("a/b/list.todo", "inew<Enter>", &[]);
+    assert_eq!(frame.code, Some(3));
+    assert!(!scratch.dir.join("a").exists());
+}
+
+#[test]
+fn directories_that_cant_be_created() {
+    let scratch = Scratch::new("paths-blocked");
+    scratch.write("file", "");
+    let path = scratch.path("file/a/list.todo");
+
+    let output = scratch.run(&["-f", &path, "add", "first"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(stderr(&output).contains("can't be created in"));
+}
+
+#[test]
+fn reading_creates_nothing() {
+    let scratch = Scratch::new("paths-read");
+    let path = scratch.path("a/b/list.todo");
+
+    let output = scratch.run(&["-f", &path, "list"]);
+    assert_eq!(output.status.code(), Some(1));
+    scratch.keys("a/b/list.todo", "inew<Enter>", &["--read-only"]);
+    assert!(!scratch.dir.join("a").exists());
+}
+
+#[test]
+fn directory_lists_the_todo_files() {
+    let scratch = Scratch::new("paths-directory");
+    scratch.write("TODO.list", "TODO(*): default\n<--->\n");
+    scratch.write("b.todo", "<--->\n");
+    scratch.write("notes", "<--->\n");
+    scratch.write("data.json", "{}");
+    scratch.write(".hidden.todo", "<--->\n");
+
+    // The default file name is among them, the scratch config too as it has no extension.
+    let frame = scratch.keys("", "", &[]);
+    let lines = frame.lines();
+    let top = lines.iter().position(|l| l.starts_with("----")).unwrap() + 1;
+    assert_eq!(
+        lines[top..top + 5],
+        ["TODO.list", "b.todo", "config", "notes", ""],
+        "{}",
+        frame.text
+    );
+    let frame = scratch.keys("", "<Enter>", &[]);
+    assert!(frame.has("[ ] default"), "{}", frame.text);
+}
@@ -0,0 +1,150 @@
+// What the items look like on the 80x24 Grid and in the plain export.
+mod common;
+
+use common::{stdout, Scratch};
+
+#[test]
+fn long_word_is_cut_at_the_panel() {
+    let scratch = Scratch::new("render-long-word");
+    let word = "x".repeat(500);
+    scratch.write(
+        "list.todo",
+        &format!("TODO(*): {word}\nTODO(*): short\n<--->\n"),
+    );
+
+    // The TODO panel is 40 columns of the 80, with 4 of them taken by the checkbox.
+    let frame = scratch.keys("list.todo", "", &[]);
+    let line = frame
+        .lines()
+        .into_iter()
+        .find(|l| l.starts_with("[ ] x"))
+        .unwrap();
+    assert_eq!(line.trim_end(), format!("[ ] {}…", "x".repeat(35)));
+    assert!(frame.lines().iter().all(|l| l.chars().count() <= 80));
+
+    scratch.config("ellipsis = false\n");
+    let frame = scratch.keys("list.todo", "", &[]);
+    assert!(frame.has(&format!("[ ] {}\n", "x".repeat(36))));
+    assert!(frame.has("[ ] short"));
+
+    // Saved as it was, the cut is only on the screen.
+    assert!(scratch.read("list.todo").contains(&word));
+}
+
+#[test]
+fn long_word_is_broken_in_the_plain_export() {
+    let scratch = Scratch::new("render-long-word-export");
+    let word = "x".repeat(500);
+    let path = scratch.write("list.todo", &format!("TODO(*): {word}\n<--->\n"));
+
+    let output = scratch.run(&["-f", &path, "export", "--plain", "--width", "30"]);
+    let text = stdout(&output);
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], format!("- [ ] {}", "x".repeat(24)));
+    assert!(lines[1..].iter().all(|l| l.starts_with("      x")));
+    assert!(lines.iter().all(|l| l.chars().count() <= 30));
+    assert_eq!(text.matches('x').count(), 500);
+}
+
+#[test]
+fn plain_export_of_mixed_width_text() {
+    let scratch = Scratch::new("render-mixed-export");
+    let path = scratch.write(
+        "list.todo",
+        "TODO(*): 買い物リストを作ってスーパーで牛乳と卵とパンを買う before the weekend party starts\n\
+         TODO(*): résumé naïve café déjà vu — update the CV with the 2026 projects and send it ✅ today\n\
+         \x20 TODO(*): sub 日本語のテキスト wraps here too\n\
+         <--->\n\
+         DONE(2026-01-02 10:30 +0000): done ☕ coffee with 🎉 emoji party planning committee meeting notes\n",
+    );
+
+    // Wide characters take two columns: the first line is 6 + 12 * 2 = 30 of them.
+    let output = scratch.run(&["-f", &path, "export", "--plain", "--width", "30"]);
+    assert_eq!(
+        stdout(&output),
+        "\
+- [ ] 買い物リストを作ってスー
+      パーで牛乳と卵とパンを買
+      う before the weekend
+      party starts
+- [ ] résumé naïve café déjà
+      vu — update the CV with
+      the 2026 projects and
+      send it ✅ today
+    - [ ] sub 日本語のテキスト
+          wraps here too
+- [x] done ☕ coffee with 🎉
+      emoji party planning
+      committee meeting notes
+"
+    );
+}
+
+#[test]
+fn wide_characters_on_the_grid() {
+    let scratch = Scratch::new("render-wide");
+    scratch.write(
+        "list.todo",
+        "TODO(*): 日本語のテキスト\nTODO(*): 買い物リストを作ってスーパーで牛乳と卵とパンを買う\n\
+         TODO(*): café ☕ 🎉\n<--->\nDONE(2026-01-02 10:30 +0000): 한국어 완료\n",
+    );
+
+    // Two columns each, and a cut one still ends in the ellipsis inside its panel.
+    let frame = scratch.keys("list.todo", "", &[]);
+    let lines = frame.lines();
+    let row = |start: &str| {
+        lines
+            .iter()
+            .find(|l| l.starts_with(start))
+            .unwrap_or_else(|| panic!("{}", frame.text))
+            .to_string()
+    };
+    // The DONE panel still starts at column 40.
+    assert_eq!(
+        row("[ ] 日本"),
+        format!(
+            "[ ] 日本語のテキスト{}[X](26-01-02) 한국어 완료",
+            " ".repeat(20)
+        )
+    );
+    // 4 + 17 * 2 + 1 columns, one more wide character wouldn't leave room for the ellipsis.
+    assert_eq!(
+        row("[ ] 買い").trim_end(),
+        "[ ] 買い物リストを作ってスーパーで牛乳…"
+    );
+    assert!(frame.has("[ ] café ☕ 🎉"), "{}", frame.text);
+}
+
+#[test]
+fn f12_dumps_the_frame() {
+    let scratch = Scratch::new("render-dump");
+    let path = scratch.write("list.todo", "TODO(*): one 日本\n<--->\n");
+
+    let output = scratch
+        .command()
+        .env("TMPDIR", &scratch.dir)
+        .args(["-q", "--no-state", "-f", &path, "--keys", "<Idle><F12>"])
+        .output()
+        .unwrap();
+    let frame = stdout(&output);
+    let dump = std::fs::read_dir(&scratch.dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with("todors-frame-") && name.ends_with(".txt")
+        })
+        .unwrap_or_else(|| panic!("{frame}"));
+    assert!(frame.contains("Frame written to '"), "{frame}");
+
+    // A header line, then the frame as it was drawn before the key, without attributes.
+    let dump = std::fs::read_to_string(dump).unwrap();
+    let (header, rows) = dump.split_once('\n').unwrap();
+    assert_eq!(
+        header,
+        format!("todors {} 80x24", env!("CARGO_PKG_VERSION"))
+    );
+    assert_eq!(rows.lines().count(), 24);
+    assert!(rows.contains("\n[ ] one 日本\n"), "{rows}");
+    assert!(rows.contains("\n[MESSAGE]: Loaded '"), "{rows}");
+}
@@ -0,0 +1,474 @@
+// Whole key sequences through the app loop, checked on the saved file and the last frame.
+mod common;
+
+use common::Scratch;
+
+const LIST: &str = "TODO(*): first\nTODO(*): second\nTODO(*): third\n<--->\n";
+
+fn todos(contents: &str) -> Vec<&str> {
+    contents
+        .lines()
+        .take_while(|line| *line != "<--->")
+        .collect()
+}
+
+#[test]
+fn add_complete_undo() {
+    let scratch = Scratch::new("add-complete-undo");
+    scratch.write("list.todo", LIST);
+
+    scratch.keys("list.todo", "ggifourth<Enter> ", &[]);
+    let saved = scratch.read("list.todo");
+    assert!(saved.starts_with("TODO(): fourth created:"), "{saved}");
+
+    scratch.write("list.todo", LIST);
+    scratch.keys("list.todo", "ggifourth<Enter> u", &[]);
+    let saved = scratch.read("list.todo");
+    assert!(saved.starts_with("TODO(*): fourth created:"), "{saved}");
+    assert_eq!(todos(&saved).len(), 4);
+}
+
+#[test]
+fn edit_with_cancel() {
+    let scratch = Scratch::new("edit-cancel");
+    scratch.write("list.todo", LIST);
+
+    let frame = scratch.keys("list.todo", "r changed<Esc>", &[]);
+    assert_eq!(scratch.read("list.todo"), LIST);
+    assert!(frame.has("[ ] first"));
+    assert!(!frame.has("changed"));
+
+    scratch.keys("list.todo", "r changed<Enter>", &[]);
+    assert_eq!(
+        todos(&scratch.read("list.todo"))[0],
+        "TODO(*): first changed"
+    );
+}
+
+#[test]
+fn drag_reordering() {
+    let scratch = Scratch::new("drag");
+    scratch.write("list.todo", LIST);
+
+    scratch.keys("list.todo", "JJ", &[]);
+    assert_eq!(
+        todos(&scratch.read("list.todo")),
+        ["TODO(*): second", "TODO(*): third", "TODO(*): first"]
+    );
+
+    scratch.keys("list.todo", "GK", &[]);
+    assert_eq!(
+        todos(&scratch.read("list.todo")),
+        ["TODO(*): second", "TODO(*): first", "TODO(*): third"]
+    );
+}
+
+#[test]
+fn panel_toggle() {
+    let scratch = Scratch::new("panel-toggle");
+    scratch.write(
+        "list.todo",
+        "TODO(*): open\n<--->\nDONE(2026-01-02 10:00 +0000): closed\n",
+    );
+
+    // The keys act on the panel the cursor is in, d deletes the DONE item.
+    let frame = scratch.keys("list.todo", "<Tab>d", &[]);
+    let saved = scratch.read("list.todo");
+    assert_eq!(saved, "TODO(*): open\n<--->\n");
+    assert!(frame.has("[ ] open"));
+
+    // Back in the TODO panel, the cursor is where it was.
+    scratch.write(
+        "list.todo",
+        "TODO(*): open\nTODO(*): other\n<--->\nDONE(2026-01-02 10:00 +0000): closed\n",
+    );
+    scratch.keys("list.todo", "j<Tab><Tab> <Enter>", &[]);
+    let saved = scratch.read("list.todo");
+    assert!(saved.starts_with("TODO(*): open\n<--->\n"), "{saved}");
+    assert!(saved.contains("): other\n"), "{saved}");
+}
+
+#[test]
+fn quit_with_unsaved_changes_saves_them() {
+    let scratch = Scratch::new("quit-unsaved");
+    scratch.write("list.todo", LIST);
+
+    let frame = scratch.keys("list.todo", "Jq", &[]);
+    assert!(frame.code == Some(0), "{}", frame.stderr);
+    let saved = scratch.read("list.todo");
+    assert_eq!(
+        todos(&saved),
+        ["TODO(*): second", "TODO(*): first", "TODO(*): third"]
+    );
+
+    // Read-only files are left as they were.
+    scratch.keys("list.todo", "Jq", &["--read-only"]);
+    assert_eq!(scratch.read("list.todo"), saved);
+}
+
+#[test]
+fn new_subtask_uses_the_file_indentation() {
+    let scratch = Scratch::new("indentation");
+    for indent in ["  ", "    ", "\t"] {
+        scratch.write(
+            "list.todo",
+            &format!("TODO(*): a\n{indent}TODO(*): a1\nTODO(*): b\n<--->\n"),
+        );
+        scratch.keys("list.todo", "Gasub<Enter>", &[]);
+        let saved = scratch.read("list.todo");
+        let expected = format!("TODO(*): a\n{indent}TODO(*): a1\nTODO(*): b\n{indent}TODO(*): sub");
+        assert!(saved.starts_with(&expected), "{saved:?}");
+    }
+}
+
+#[test]
+fn pasted_checkbox_is_stripped() {
+    let scratch = Scratch::new("paste-checkbox");
+    scratch.write("list.todo", "<--->\n");
+
+    // A checked box inserts the item checked, an empty one unchecked.
+    scratch.keys(
+        "list.todo",
+        "i* [X] star<Enter>i- [ ] copied open<Enter>i- [x] copied done<Enter>",
+        &[],
+    );
+    let saved = scratch.read("list.todo");
+    let items = todos(&saved);
+    assert!(
+        items[0].starts_with("TODO(): copied done created:"),
+        "{saved}"
+    );
+    assert!(
+        items[1].starts_with("TODO(*): copied open created:"),
+        "{saved}"
+    );
+    assert!(items[2].starts_with("TODO(): star created:"), "{saved}");
+
+    // Other brackets stay part of the text.
+    scratch.write("list.todo", "<--->\n");
+    scratch.keys(
+        "list.todo",
+        "i-- [x] x<Enter>i- [?] odd<Enter>i[x]nospace<Enter>",
+        &[],
+    );
+    let saved = scratch.read("list.todo");
+    let items = todos(&saved);
+    assert!(
+        items[0].starts_with("TODO(*): [x]nospace created:"),
+        "{saved}"
+    );
+    assert!(
+        items[1].starts_with("TODO(*): - [?] odd created:"),
+        "{saved}"
+    );
+    assert!(
+        items[2].starts_with("TODO(*): -- [x] x created:"),
+        "{saved}"
+    );
+}
+
+const GROUPS: &str = "TODO(*): a1\nTODO(*): a2\n\nTODO(*): b1\n  TODO(*): b1x\n\n\nTODO(*): c1\n\n\
+                      <--->\n\nDONE(2026-01-02 10:00 +0000): d1\n\nDONE(2026-01-02 10:00 +0000): d2\n\n";
+
+#[test]
+fn blank_lines_separate_groups() {
+    let scratch = Scratch::new("groups");
+    scratch.write("list.todo", GROUPS);
+
+    // A group starts with an empty row, but not at the top of the panel.
+    let frame = scratch.keys("list.todo", "", &[]);
+    let lines = frame.lines();
+    let top = lines.iter().position(|l| l.starts_with("[ ] a1")).unwrap();
+    let rows: Vec<&str> = lines[top..top + 7]
+        .iter()
+        .map(|l| l.get(..40).unwrap_or(l).trim_end())
+        .collect();
+    assert_eq!(
+        rows,
+        [
+            "[ ] a1",
+            "[ ] a2",
+            "",
+            "[ ] b1                             (0/1)",
+            "    [ ] b1x",
+            "",
+            "[ ] c1"
+        ]
+    );
+
+    // The cursor skips the separators, and they are saved back as they were.
+    scratch.keys("list.todo", "jjr!<Enter>", &[]);
+    assert_eq!(scratch.read("list.todo"), GROUPS.replace("b1\n", "b1!\n"));
+
+    // A group keeps its separator when its first item goes.
+    scratch.write("list.todo", GROUPS);
+    scratch.keys("list.todo", "<Tab>d", &[]);
+    assert!(
+        scratch
+            .read("list.todo")
+            .ends_with("<--->\n\nDONE(2026-01-02 10:00 +0000): d2\n\n"),
+        "{}",
+        scratch.read("list.todo")
+    );
+
+    // Dragging an item takes its separator along.
+    scratch.write("list.todo", GROUPS);
+    scratch.keys("list.todo", "jjK", &[]);
+    assert!(
+        scratch.read("list.todo").starts_with(
+            "TODO(*): a1\n\nTODO(*): b1\n  TODO(*): b1x\nTODO(*): a2\n\n\nTODO(*): c1\n"
+        ),
+        "{}",
+        scratch.read("list.todo")
+    );
+}
+
+#[test]
+fn new_item_is_scrolled_into_view() {
+    let scratch = Scratch::new("insert-scroll");
+    scratch.config("insert_position = bottom\n");
+    let list = |tail: &str| {
+        let items: String = (0..60)
+            .map(|i| format!("TODO(*): item {i}{}\n", [" +work", ""][i % 2]))
+            .collect();
+        format!("{items}{tail}<--->\n")
+    };
+
+    // 60 items don't fit the 24 rows, the new one at the bottom is shown while typed.
+    scratch.write("list.todo", &list(""));
+    let frame = scratch.keys("list.todo", "inew one", &[]);
+    assert!(frame.has("[ ] new one\n"), "{}", frame.text);
+    assert!(!frame.has("item 0 "));
+
+    // Also when the filter doesn't match it yet.
+    let frame = scratch.keys("list.todo", ":tags<Enter><Enter>inew two", &[]);
+    assert!(frame.has("[FILTER: +work"), "{}", frame.text);
+    assert!(frame.has("[ ] new two\n"), "{}", frame.text);
+
+    // A subtask appended with the subtasks hidden shows them.
+    scratch.write("list.todo", &list("  TODO(*): sub\n"));
+    let frame = scratch.keys("list.todo", "tGanew sub", &[]);
+    assert!(
+        frame.has("    [ ] sub\n    [ ] new sub\n"),
+        "{}",
+        frame.text
+    );
+}
+
+#[test]
+fn filter_presets_of_the_config() {
+    let scratch = Scratch::new("filter-presets");
+    scratch.config("filter = work: +work and not @someday\nfilter = urgent: pri:A\n");
+    scratch.write(
+        "list.todo",
+        "TODO(*): a +work @someday\nTODO(*): b +work\nTODO(*): c pri:A\n<--->\n",
+    );
+
+    // F goes through the presets in their order, then back to no filter.
+    let frame = scratch.keys("list.todo", "F", &[]);
+    assert!(frame.has("[FILTER: work | 1/3]"), "{}", frame.text);
+    assert!(frame.has("[ ] b +work") && !frame.has("[ ] a") && !frame.has("[ ] c"));
+    let frame = scratch.keys("list.todo", "FF", &[]);
+    assert!(frame.has("[FILTER: urgent | 1/3]"), "{}", frame.text);
+    let frame = scratch.keys("list.todo", "FFF", &[]);
+    assert!(!frame.has("[FILTER:") && frame.has("[ ] a +work @someday"));
+
+    let frame = scratch.keys("list.todo", ":filter urgent<Enter>", &[]);
+    assert!(frame.has("[ ] c pri:A") && !frame.has("[ ] b"));
+    let frame = scratch.keys("list.todo", ":filter nope<Enter>", &[]);
+    assert!(frame.message().starts_with("Unknown filter: 'nope'."));
+
+    // An invalid preset is refused with the column of the error.
+    scratch.config("filter = bad: +a +b\n");
+    let output = scratch.run(&["config", "check"]);
+    assert!(
+        common::stderr(&output).contains("invalid filter 'bad': column 4"),
+        "{}",
+        common::stderr(&output)
+    );
+}
+
+#[test]
+fn select_all_then_act() {
+    let scratch = Scratch::new("select-all");
+    let dones = "DONE(2026-01-02 10:00 +0000): x\nDONE(2026-01-02 10:00 +0000): y\n";
+    scratch.write("list.todo", &format!("TODO(*): open\n<--->\n{dones}"));
+
+    // CTRL+a selects every shown item of the panel, d deletes them all.
+    let frame = scratch.keys("list.todo", "<Tab><C-a>", &[]);
+    assert!(
+        frame.message().starts_with("2 item(s) selected."),
+        "{}",
+        frame.message()
+    );
+    scratch.keys("list.todo", "<Tab><C-a>d", &[]);
+    assert_eq!(scratch.read("list.todo"), "TODO(*): open\n<--->\n");
+
+    // With nothing to select it stays in Normal mode, d is then the usual delete.
+    let frame = scratch.keys("list.todo", "<Tab><C-a>", &[]);
+    assert!(
+        frame.message().starts_with("Nothing to select."),
+        "{}",
+        frame.message()
+    );
+    assert!(!frame.has("-- VISUAL --"));
+
+    // CTRL+x ends the selection.
+    scratch.write("list.todo", &format!("TODO(*): open\n<--->\n{dones}"));
+    let frame = scratch.keys("list.todo", "<Tab><C-a><C-x>d", &[]);
+    assert!(!frame.has("-- VISUAL --"));
+    assert_eq!(
+        scratch.read("list.todo"),
+        "TODO(*): open\n<--->\nDONE(2026-01-02 10:00 +0000): y\n"
+    );
+}
+
+#[test]
+fn max_items_refuses_what_would_go_past_it() {
+    let scratch = Scratch::new("max-items");
+    scratch.config("max_items = 3\n");
+    let list = "TODO(): a\nTODO(): b\n<--->\nDONE(2026-01-02 10:00 +0000): x\nDONE(2026-01-02 10:00 +0000): y\n";
+    let path = scratch.write("list.todo", list);
+
+    // Both don't fit, the selection stays for another try. One does.
+    let frame = scratch.keys("list.todo", "<C-a><Enter>", &[]);
+    assert!(
+        frame.message().starts_with("The DONE panel has no room"),
+        "{}",
+        frame.message()
+    );
+    assert!(
+        frame.has("enter:transfer  m:pick  esc:cancel"),
+        "{}",
+        frame.text
+    );
+    assert_eq!(scratch.read("list.todo"), list);
+    scratch.keys("list.todo", "j<Enter>", &[]);
+    assert!(scratch.read("list.todo").starts_with("TODO(): a\n<--->\n"));
+
+    // From the command line too, nothing is added.
+    let output = scratch.run(&[
+        "-f", &path, "--add", "c", "--add", "d", "--add", "e", "--keys", "",
+    ]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(common::stderr(&output).contains("The TODO panel is full (3 items"));
+    assert!(!std::path::Path::new(&format!("{path}.lock")).exists());
+    scratch.run(&["-f", &path, "add", "b"]);
+    let output = scratch.run(&["-f", &path, "add", "c"]);
+    assert_eq!(output.status.code(), Some(0), "{}", common::stderr(&output));
+    let output = scratch.run(&["-f", &path, "add", "d"]);
+    assert_eq!(output.status.code(), Some(2));
+    let saved = scratch.read("list.todo");
+    assert_eq!(todos(&saved).len(), 3, "{saved}");
+    assert!(!saved.contains("TODO(*): d"), "{saved}");
+}
+
+#[test]
+fn undo_several_from_the_command_line() {
+    let scratch = Scratch::new("undo-many");
+    scratch.write("list.todo", LIST);
+    let edits = "r1<Enter>jr2<Enter>jr3<Enter>";
+
+    let frame = scratch.keys("list.todo", &format!("{edits}:undo 2<Enter>"), &[]);
+    assert!(
+        frame.message().starts_with("Undo x2, the last: "),
+        "{}",
+        frame.message()
+    );
+    assert_eq!(
+        todos(&scratch.read("list.todo")),
+        ["TODO(*): first1", "TODO(*): second", "TODO(*): third"]
+    );
+
+    // More than there is stops at the list as loaded, like all.
+    for undo in [":undo 9<Enter>", ":undo all<Enter>"] {
+        scratch.write("list.todo", LIST);
+        scratch.keys("list.todo", &format!("{edits}{undo}"), &[]);
+        assert_eq!(scratch.read("list.todo"), LIST);
+    }
+
+    for bad in ["0", "-1", "x", "all 2"] {
+        let frame = scratch.keys("list.todo", &format!("r!<Enter>:undo {bad}<Enter>"), &[]);
+        assert_eq!(frame.message(), "Usage: undo [<n>|all].", "{bad}");
+    }
+}
+
+#[test]
+fn help_and_diff_screen_keys() {
+    let scratch = Scratch::new("screen-keys");
+    scratch.write("list.todo", LIST);
+
+    // Space goes back from the help, q quits from it.
+    let frame = scratch.keys("list.todo", "?<Space>", &[]);
+    assert!(frame.has("[TODO]"), "{}", frame.text);
+    let frame = scratch.keys("list.todo", "?q", &[]);
+    assert_eq!(frame.code, Some(0), "{}", frame.stderr);
+
+    // The hook changes the file behind the session, so q stops at the diff.
+    scratch.config("hook_add = sed -i '1i TODO(*): outside' list.todo\n");
+    let frame = scratch.keys("list.todo", "inew<Enter>q", &[]);
+    assert!(frame.has("WAS CHANGED ON DISK"), "{}", frame.text);
+
+    scratch.write("list.todo", LIST);
+    scratch.keys("list.todo", "inew<Enter>qo", &[]);
+    let saved = scratch.read("list.todo");
+    assert!(
+        saved.contains("TODO(*): new") && !saved.contains("outside"),
+        "{saved}"
+    );
+
+    scratch.write("list.todo", LIST);
+    scratch.keys("list.todo", "inew<Enter>qr", &[]);
+    let saved = scratch.read("list.todo");
+    assert!(
+        saved.contains("TODO(*): new") && saved.contains("outside"),
+        "{saved}"
+    );
+
+    scratch.write("list.todo", LIST);
+    scratch.keys("list.todo", "inew<Enter>qsother.todo<Enter>", &[]);
+    assert!(scratch.read("other.todo").contains("TODO(*): new"));
+}
+
+#[test]
+fn print_defaults_to_the_todos() {
+    let scratch = Scratch::new("print-default");
+    let path = scratch.write(
+        "list.todo",
+        "TODO(*): a\n<--->\nDONE(2026-01-02 10:00 +0000): x\n",
+    );
+
+    // `:print` and `--print-on-exit` without a kind print the same.
+    for args in [
+        vec!["--keys", ":print<Enter>"],
+        vec!["--keys", "", "--print-on-exit"],
+    ] {
+        let output = scratch
+            .command()
+            .args([&["-q", "--no-state", "-f", &path][..], &args].concat())
+            .output()
+            .unwrap();
+        let stdout = common::stdout(&output);
+        assert!(stdout.ends_with("- [ ] a\n"), "{args:?}: {stdout}");
+        assert!(!stdout.contains("- [x] x"), "{args:?}: {stdout}");
+    }
+}
+
+#[test]
+fn read_only_still_prints_on_exit() {
+    let scratch = Scratch::new("print-read-only");
+    let path = scratch.write("list.todo", LIST);
+
+    let output = scratch.run(&[
+        "-f",
+        &path,
+        "--keys",
+        "dd",
+        "--print-on-exit",
+        "--read-only",
+    ]);
+    let stdout = common::stdout(&output);
+    assert!(stdout.contains("- [ ] second\n- [ ] third\n"), "{stdout}");
+    assert!(stdout.contains("it is opened read-only"), "{stdout}");
+    assert_eq!(scratch.read("list.todo"), LIST);
+}
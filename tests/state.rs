@@ -0,0 +1,105 @@
+// The `<file>.state` sidecar: what a session leaves for the next one on the same file.
+mod common;
+
+use common::Scratch;
+
+const LIST: &str = "TODO(*): a +work\nTODO(*): b +home\nTODO(*): c\n<--->\n";
+
+#[test]
+fn each_file_restores_its_own_state() {
+    let scratch = Scratch::new("state-per-file");
+    scratch.config("filter = work: +work\nfilter = home: +home\n");
+    scratch.write("a.todo", LIST);
+    scratch.write("b.todo", LIST);
+
+    // F steps through the presets: work on one file, home on the other.
+    scratch.session("a.todo", "F", &[]);
+    scratch.session("b.todo", "FF", &[]);
+    assert_eq!(
+        scratch.read("a.todo.state"),
+        "filter = preset work: +work\n"
+    );
+    assert_eq!(
+        scratch.read("b.todo.state"),
+        "filter = preset home: +home\n"
+    );
+
+    let frame = scratch.session("a.todo", "", &[]);
+    assert!(frame.has("[FILTER: work"), "{}", frame.text);
+    assert!(frame.has("[ ] a +work") && !frame.has("[ ] b"));
+    let frame = scratch.session("b.todo", "", &[]);
+    assert!(frame.has("[FILTER: home"), "{}", frame.text);
+    assert!(frame.has("[ ] b +home") && !frame.has("[ ] a"));
+
+    // Clearing it on one file leaves the other one's.
+    scratch.session("a.todo", ":filter clear<Enter>", &[]);
+    assert!(!std::path::Path::new(&scratch.path("a.todo.state")).exists());
+    let frame = scratch.session("b.todo", "", &[]);
+    assert!(frame.has("[FILTER: home"), "{}", frame.text);
+
+    // --no-state neither restores nor touches it.
+    let frame = scratch.keys("b.todo", "F", &[]);
+    assert!(frame.has("[FILTER: work"), "{}", frame.text);
+    assert_eq!(
+        scratch.read("b.todo.state"),
+        "filter = preset home: +home\n"
+    );
+}
+
+#[test]
+fn each_file_restores_its_own_sort() {
+    let scratch = Scratch::new("state-sort");
+    let archive = scratch.write(
+        "archive.todo",
+        "TODO(*): open\n<--->\nDONE(2026-01-05 10:00 +0000): y\nDONE(2026-01-04 10:00 +0000): x\n",
+    );
+    let backlog = scratch.write(
+        "backlog.todo",
+        "TODO(*): low pri:C\nTODO(*): none\nTODO(*): top pri:A\n  TODO(*): sub\n<--->\n",
+    );
+
+    // An archive by date, a backlog by priority.
+    scratch.session("archive.todo", "<Tab>:sort date<Enter>", &[]);
+    scratch.session("backlog.todo", ":sort pri<Enter>", &[]);
+    assert_eq!(scratch.read("archive.todo.state"), "sort = done date\n");
+    assert_eq!(scratch.read("backlog.todo.state"), "sort = todo pri\n");
+    assert!(scratch
+        .read("archive.todo")
+        .ends_with("DONE(2026-01-04 10:00 +0000): x\nDONE(2026-01-05 10:00 +0000): y\n"));
+    assert!(scratch
+        .read("backlog.todo")
+        .starts_with("TODO(*): top pri:A\n  TODO(*): sub\nTODO(*): low pri:C\nTODO(*): none\n"));
+
+    // Items added since go in their place when each file is opened again, by its own order.
+    scratch.run(&["-q", "-f", &archive, "done", "open"]);
+    scratch.run(&["-q", "-f", &backlog, "add", "mid pri:B"]);
+    let frame = scratch.session("archive.todo", "", &[]);
+    assert!(
+        frame.message().starts_with("Sorted DONE by date as in th"),
+        "{}",
+        frame.message()
+    );
+    let saved = scratch.read("archive.todo");
+    assert!(
+        saved.starts_with(
+            "<--->\nDONE(2026-01-04 10:00 +0000): x\nDONE(2026-01-05 10:00 +0000): y\nDONE("
+        ),
+        "{saved}"
+    );
+    assert!(saved.ends_with("): open\n"), "{saved}");
+    scratch.session("backlog.todo", "", &[]);
+    let saved = scratch.read("backlog.todo");
+    assert!(
+        saved.starts_with("TODO(*): top pri:A\n  TODO(*): sub\nTODO(*): mid pri:B created:"),
+        "{saved}"
+    );
+    assert!(
+        saved.ends_with("\nTODO(*): low pri:C\nTODO(*): none\n<--->\n"),
+        "{saved}"
+    );
+
+    // Stopping it on one file leaves the other one's.
+    scratch.session("backlog.todo", ":sort off<Enter>", &[]);
+    assert!(!std::path::Path::new(&scratch.path("backlog.todo.state")).exists());
+    assert_eq!(scratch.read("archive.todo.state"), "sort = done date\n");
+}
@@ -0,0 +1,129 @@
+// `import --taskwarrior`: a `task export` moved into the list once, and again without
+// doubling what's already there.
+mod common;
+
+use std::io::Write;
+use std::process::Stdio;
+
+use common::{stderr, stdout, Scratch};
+
+const EXPORT: &str = r#"[
+{"id":1,"description":"Fix the bike","entry":"20261001T080000Z","modified":"20261001T080000Z","project":"home","status":"pending","tags":["errand"],"uuid":"5b2ce1a8-6f1c-4bb2-9a4a-8d7c0c0d6e11","due":"20261020T120000Z","priority":"H","urgency":9.2},
+{"id":2,"description":"Book \"the\" flights \u2708\ufe0f","entry":"20261002T080000Z","status":"waiting","uuid":"5b2ce1a8-0000-4bb2-9a4a-8d7c0c0d6e12","wait":"20261101T000000Z","urgency":1},
+{"id":0,"description":"Call the bank","end":"20261002T091500Z","entry":"20260930T080000Z","status":"completed","uuid":"9d1f3a77-2c4e-4e0b-8f55-0a1b2c3d4e5f"},
+{"id":0,"description":"Old idea","status":"deleted","uuid":"0e0e0e0e-2c4e-4e0b-8f55-0a1b2c3d4e5f"},
+{"id":0,"description":"Water plants","recur":"weekly","status":"recurring","uuid":"1f1f1f1f-2c4e-4e0b-8f55-0a1b2c3d4e5f"}
+]
+"#;
+
+#[test]
+fn export_becomes_todos_and_dones() {
+    let scratch = Scratch::new("taskwarrior-import");
+    let path = scratch.write("list.todo", "TODO(*): mine\n<--->\n");
+    let export = scratch.write("export.json", EXPORT);
+
+    let output = scratch.run(&["-f", &path, "import", "--taskwarrior", &export]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "[INFO]: Imported (2)todos and (1)dones from '{export}', skipped (0) already in the list.\n"
+        )
+    );
+    // Both UUIDs start with the same 8 digits, the whole ones tell them apart.
+    let saved = scratch.read("list.todo");
+    let lines: Vec<&str> = saved.lines().collect();
+    assert_eq!(lines[0], "TODO(*): mine");
+    assert_eq!(
+        lines[1],
+        "TODO(*): Fix the bike +home @errand pri:A due:2026-10-20 tw:5b2ce1a8-6f1c-4bb2-9a4a-8d7c0c0d6e11 created:2026-10-01"
+    );
+    assert_eq!(
+        lines[2],
+        "TODO(*): Book \"the\" flights ✈\u{fe0f} wait:2026-11-01 tw:5b2ce1a8-0000-4bb2-9a4a-8d7c0c0d6e12 created:2026-10-02"
+    );
+    assert_eq!(lines[3], "<--->");
+    assert!(
+        lines[4].starts_with("DONE(2026-10-02 ")
+            && lines[4].ends_with(
+                "Call the bank tw:9d1f3a77-2c4e-4e0b-8f55-0a1b2c3d4e5f created:2026-09-30"
+            ),
+        "{saved}"
+    );
+    assert_eq!(lines.len(), 5, "{saved}");
+}
+
+#[test]
+fn present_tasks_are_skipped() {
+    let scratch = Scratch::new("taskwarrior-again");
+    let path = scratch.write("list.todo", "TODO(*): mine\n<--->\n");
+    let export = scratch.write("export.json", EXPORT);
+
+    scratch.run(&["-q", "-f", &path, "import", "--taskwarrior", &export]);
+    let first = scratch.read("list.todo");
+
+    // Found by the uuid even with the text edited, or by the text without a uuid.
+    let edited = first.replace("Fix the bike", "Fix the bike tonight");
+    scratch.write("list.todo", &edited);
+    let more = EXPORT.replace("]\n", ",\n{\"description\":\"mine\"}\n]\n");
+    let export = scratch.write("export.json", &more);
+    let output = scratch.run(&["-f", &path, "import", "--taskwarrior", &export]);
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "[INFO]: Imported (0)todos and (0)dones from '{export}', skipped (4) already in the list.\n"
+        )
+    );
+    assert_eq!(scratch.read("list.todo"), edited);
+}
+
+#[test]
+fn line_per_task_from_stdin() {
+    let scratch = Scratch::new("taskwarrior-stdin");
+    let path = scratch.write("list.todo", "<--->\n");
+    let lines =
+        "{\"description\":\"one\",\"uuid\":\"11111111-aaaa\"},\n{\"description\":\"two\"}\n";
+
+    let mut child = scratch
+        .command()
+        .args(["-f", &path, "import", "--taskwarrior"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(lines.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{}", stderr(&output));
+    assert!(
+        stdout(&output).contains("from 'stdin'"),
+        "{}",
+        stdout(&output)
+    );
+    let saved = scratch.read("list.todo");
+    assert_eq!(
+        saved,
+        "TODO(*): one tw:11111111-aaaa\nTODO(*): two\n<--->\n"
+    );
+}
+
+#[test]
+fn bad_export_is_a_parse_error() {
+    let scratch = Scratch::new("taskwarrior-bad");
+    let path = scratch.write("list.todo", "<--->\n");
+    let export = scratch.write("export.json", "[{\"description\":\"\\u+041\"}]\n");
+
+    let output = scratch.run(&["-f", &path, "import", "--taskwarrior", &export]);
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(
+        stderr(&output),
+        format!("[ERROR]: Could not read the tasks of '{export}': line 1: invalid \\u escape\n")
+    );
+    assert_eq!(scratch.read("list.todo"), "<--->\n");
+}